@@ -0,0 +1,1595 @@
+//! Assembler for AArch64.
+//!
+//! Accepts the syntax the decoder prints, e.g. `ldr x0, [sp, #0x10]` or `b.ne #0x4010`,
+//! including aliases such as `mov`, `cmp` and `lsl`. General purpose, load and store, branch,
+//! system and scalar floating point instructions are supported, SIMD instructions aren't.
+
+use crate::parse::Parser;
+use crate::{fits_signed, fits_unsigned, offset, Error};
+use debugvault::Index;
+
+/// `nop`, used to pad instructions.
+pub const NOP: [u8; 4] = 0xd503201f_u32.to_le_bytes();
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    X,
+    W,
+    Sp,
+    Wsp,
+    /// Scalar floating point and SIMD registers by their size.
+    B,
+    H,
+    S,
+    D,
+    Q,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Register {
+    kind: Kind,
+    num: u32,
+}
+
+impl Register {
+    /// General purpose register where 31 is the zero register, and whether it's 64-bit.
+    fn zr(self) -> Option<(u32, bool)> {
+        match self.kind {
+            Kind::X => Some((self.num, true)),
+            Kind::W => Some((self.num, false)),
+            _ => None,
+        }
+    }
+
+    /// General purpose register where 31 is the stack pointer, and whether it's 64-bit.
+    fn sp(self) -> Option<(u32, bool)> {
+        match self.kind {
+            Kind::X if self.num != 31 => Some((self.num, true)),
+            Kind::W if self.num != 31 => Some((self.num, false)),
+            Kind::Sp => Some((31, true)),
+            Kind::Wsp => Some((31, false)),
+            _ => None,
+        }
+    }
+
+    fn is_sp(self) -> bool {
+        matches!(self.kind, Kind::Sp | Kind::Wsp)
+    }
+
+    /// Floating point register and its type: `0` for single, `1` for double and `3` for half
+    /// precision.
+    fn fp(self) -> Option<(u32, u32)> {
+        match self.kind {
+            Kind::S => Some((self.num, 0b00)),
+            Kind::D => Some((self.num, 0b01)),
+            Kind::H => Some((self.num, 0b11)),
+            _ => None,
+        }
+    }
+
+    /// Log2 of the size in bytes of a floating point or SIMD register.
+    fn vector_size(self) -> Option<u32> {
+        match self.kind {
+            Kind::B => Some(0),
+            Kind::H => Some(1),
+            Kind::S => Some(2),
+            Kind::D => Some(3),
+            Kind::Q => Some(4),
+            _ => None,
+        }
+    }
+}
+
+fn register(name: &str) -> Option<Register> {
+    let numbered = |prefix: &str, kind, count: u32| {
+        let num: u32 = name.strip_prefix(prefix)?.parse().ok()?;
+        (num < count).then_some(Register { kind, num })
+    };
+
+    match name {
+        "xzr" => Some(Register {
+            kind: Kind::X,
+            num: 31,
+        }),
+        "wzr" => Some(Register {
+            kind: Kind::W,
+            num: 31,
+        }),
+        "sp" => Some(Register {
+            kind: Kind::Sp,
+            num: 31,
+        }),
+        "wsp" => Some(Register {
+            kind: Kind::Wsp,
+            num: 31,
+        }),
+        _ => numbered("x", Kind::X, 31)
+            .or_else(|| numbered("w", Kind::W, 31))
+            .or_else(|| numbered("b", Kind::B, 32))
+            .or_else(|| numbered("h", Kind::H, 32))
+            .or_else(|| numbered("s", Kind::S, 32))
+            .or_else(|| numbered("d", Kind::D, 32))
+            .or_else(|| numbered("q", Kind::Q, 32)),
+    }
+}
+
+/// Shifts by their encoding followed by extends, which are encoded as their index minus 4.
+const MODIFIERS: [&str; 12] = [
+    "lsl", "lsr", "asr", "ror", "uxtb", "uxth", "uxtw", "uxtx", "sxtb", "sxth", "sxtw", "sxtx",
+];
+
+const LSL: usize = 0;
+const ROR: usize = 3;
+const EXTEND: usize = 4;
+
+const CONDITIONS: [&str; 16] = [
+    "eq", "ne", "hs", "lo", "mi", "pl", "vs", "vc", "hi", "ls", "ge", "lt", "gt", "le", "al", "nv",
+];
+
+const BARRIERS: [&str; 16] = [
+    "#0x0", "oshld", "oshst", "osh", "#0x4", "nshld", "nshst", "nsh", "#0x8", "ishld", "ishst",
+    "ish", "#0xc", "ld", "st", "sy",
+];
+
+/// System registers with a name and their `o0:op1:CRn:CRm:op2` encoding.
+const SYSTEM_REGISTERS: &[(&str, u32)] = &[
+    ("midr_el1", 0x4000),
+    ("ctr_el0", 0x5801),
+    ("dczid_el0", 0x5807),
+    ("nzcv", 0x5a10),
+    ("fpcr", 0x5a20),
+    ("fpsr", 0x5a21),
+    ("tpidr_el0", 0x5e82),
+    ("tpidrro_el0", 0x5e83),
+    ("cntfrq_el0", 0x5f00),
+    ("cntvct_el0", 0x5f02),
+];
+
+/// Hints with their own mnemonic.
+const HINTS: &[(&str, u32)] = &[
+    ("nop", 0),
+    ("yield", 1),
+    ("wfe", 2),
+    ("wfi", 3),
+    ("sev", 4),
+    ("sevl", 5),
+    ("esb", 0x10),
+    ("csdb", 0x14),
+    ("paciasp", 0x19),
+    ("autiasp", 0x1d),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Offset {
+    None,
+    Imm(i64),
+    /// Index register and how it's extended or shifted.
+    Reg(Register, Option<(usize, Option<i64>)>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Memory {
+    base: u32,
+    offset: Offset,
+    /// Whether the address is written back to the base register, `[base, #imm]!`.
+    writeback: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Reg(Register),
+    Imm(i64),
+    Float(f64),
+    /// Shift or extend by its index in [`MODIFIERS`], with an optional amount.
+    Modifier(usize, Option<i64>),
+    Mem(Memory),
+    /// Names of conditions, barriers, system registers and such.
+    Name(String),
+}
+
+/// Reads a memory operand, `[base]`, `[base, #imm]` or `[base, index, extend #amount]`.
+fn memory(p: &mut Parser) -> Result<Memory, Error> {
+    p.expect('[', "'['")?;
+    let base = match p.try_word(register).and_then(Register::sp) {
+        Some((base, true)) => base,
+        _ => return Err(p.expected("base register")),
+    };
+
+    let mut offset = Offset::None;
+    if p.eat(',') {
+        if p.eat('#') {
+            offset = Offset::Imm(p.value()?);
+        } else {
+            let index = p.try_word(register).ok_or_else(|| p.expected("offset"))?;
+            let mut modifier = None;
+            if p.eat(',') {
+                let name = p.word().ok_or_else(|| p.expected("extend"))?;
+                let n = MODIFIERS
+                    .iter()
+                    .position(|&modifier| modifier == name)
+                    .ok_or_else(|| p.expected("extend"))?;
+                let amount = if p.eat('#') { Some(p.number()?) } else { None };
+                modifier = Some((n, amount));
+            }
+            offset = Offset::Reg(index, modifier);
+        }
+    }
+    p.expect(']', "']'")?;
+
+    Ok(Memory {
+        base,
+        offset,
+        writeback: p.eat('!'),
+    })
+}
+
+fn operand(p: &mut Parser) -> Result<Operand, Error> {
+    match p.peek() {
+        Some('[') => memory(p).map(Operand::Mem),
+        Some('#') => {
+            p.eat('#');
+            if p.at_float() {
+                p.float().map(Operand::Float)
+            } else {
+                p.value().map(Operand::Imm)
+            }
+        }
+        Some('<') => p.value().map(Operand::Imm),
+        _ => {
+            let word = p.word().ok_or_else(|| p.expected("operand"))?;
+            if let Some(reg) = register(&word) {
+                return Ok(Operand::Reg(reg));
+            }
+
+            if let Some(n) = MODIFIERS.iter().position(|&modifier| modifier == word) {
+                let amount = if p.eat('#') { Some(p.number()?) } else { None };
+                return Ok(Operand::Modifier(n, amount));
+            }
+
+            Ok(Operand::Name(word))
+        }
+    }
+}
+
+/// Assemble a single instruction at `addr`.
+pub fn assemble(src: &str, addr: usize, symbols: &Index) -> Result<Vec<u8>, Error> {
+    let mut p = Parser::new(src, symbols);
+    let mnemonic = p.mnemonic()?;
+
+    let mut ops = Vec::new();
+    if p.peek().is_some() {
+        loop {
+            ops.push(operand(&mut p)?);
+            if !p.eat(',') {
+                break;
+            }
+        }
+    }
+    p.end()?;
+
+    encode(&mnemonic, &ops, addr).map(|word| word.to_le_bytes().to_vec())
+}
+
+fn sf(is_64: bool) -> u32 {
+    (is_64 as u32) << 31
+}
+
+fn condition(name: &str) -> Option<u32> {
+    match name {
+        "cs" => Some(2),
+        "cc" => Some(3),
+        _ => CONDITIONS.iter().position(|&cond| cond == name).map(|cond| cond as u32),
+    }
+}
+
+/// Condition of an alias that's shown inverted, such as `cset`.
+fn inverted_condition(op: &Operand) -> Result<u32, Error> {
+    match op {
+        Operand::Name(name) => match condition(name) {
+            Some(cond) if cond < 14 => Ok(cond ^ 1),
+            _ => Err(Error::InvalidOperands),
+        },
+        _ => Err(Error::InvalidOperands),
+    }
+}
+
+fn zr_register(is_64: bool) -> Operand {
+    Operand::Reg(Register {
+        kind: if is_64 { Kind::X } else { Kind::W },
+        num: 31,
+    })
+}
+
+/// General purpose registers with 31 being the zero register, which all have the same size.
+fn same_size<const N: usize>(ops: &[&Operand; N]) -> Result<([u32; N], bool), Error> {
+    let mut nums = [0; N];
+    let mut size = None;
+    for (idx, op) in ops.iter().enumerate() {
+        let Operand::Reg(reg) = op else {
+            return Err(Error::InvalidOperands);
+        };
+        let (num, is_64) = reg.zr().ok_or(Error::InvalidOperands)?;
+        if size.is_some_and(|size| size != is_64) {
+            return Err(Error::InvalidOperands);
+        }
+        nums[idx] = num;
+        size = Some(is_64);
+    }
+    Ok((nums, size.unwrap_or(true)))
+}
+
+/// Immediate between `0` and `max`.
+fn unsigned(value: i64, max: i64) -> Result<u32, Error> {
+    match (0..=max).contains(&value) {
+        true => Ok(value as u32),
+        false => Err(Error::OutOfRange(value)),
+    }
+}
+
+/// Encodes `value` as a logical immediate, returning the `N:immr:imms` fields.
+fn bitmask(value: i64, is_64: bool) -> Option<u32> {
+    let mut value = value as u64;
+    if !is_64 {
+        if !fits_unsigned(value as i64, 32) && !fits_signed(value as i64, 32) {
+            return None;
+        }
+        value = (value & 0xffff_ffff) | value << 32;
+    }
+
+    if value == 0 || value == u64::MAX {
+        return None;
+    }
+
+    // the smallest element that's repeated to make up the value
+    let mut size = 64;
+    while size > 2 {
+        let half = size / 2;
+        let mask = (1u64 << half) - 1;
+        if value & mask != (value >> half) & mask {
+            break;
+        }
+        size = half;
+    }
+
+    let mask = if size == 64 {
+        u64::MAX
+    } else {
+        (1u64 << size) - 1
+    };
+    let elem = value & mask;
+    let ones = elem.count_ones();
+    let run = (1u64 << ones) - 1;
+
+    // the element has to be a rotated run of ones
+    let rotate = |rotation: u32| match rotation {
+        0 => run,
+        _ => ((run >> rotation) | (run << (size - rotation))) & mask,
+    };
+    let immr = (0..size).find(|&rotation| rotate(rotation) == elem)?;
+    let imms = ((!(size - 1) << 1) & 0x3f) | (ones - 1);
+    let n = (size == 64) as u32;
+
+    Some(n << 12 | immr << 6 | imms)
+}
+
+/// Encodes a floating point value that can be expressed in 8 bits.
+fn fp_immediate(value: f64) -> Option<u32> {
+    (0..256).find(|&imm8: &u32| {
+        let sign = if imm8 & 0x80 != 0 { -1.0 } else { 1.0 };
+        let exp = (imm8 >> 4) & 0b11;
+        let exp = if imm8 & 0x40 != 0 {
+            exp as i32 - 3
+        } else {
+            exp as i32 + 1
+        };
+        let frac = (16 + (imm8 & 0xf)) as f64 / 16.0;
+        sign * frac * 2f64.powi(exp) == value
+    })
+}
+
+fn encode(mnemonic: &str, ops: &[Operand], addr: usize) -> Result<u32, Error> {
+    use Operand::*;
+
+    if let Some(&(_, imm)) = HINTS.iter().find(|(name, _)| *name == mnemonic) {
+        return match ops {
+            [] => Ok(0xd503201f | imm << 5),
+            _ => Err(Error::InvalidOperands),
+        };
+    }
+
+    if let Some(cond) = mnemonic.strip_prefix("b.").and_then(condition) {
+        return match ops {
+            [Imm(target)] => {
+                let offset = offset(*target, addr, 19, 4)? >> 2;
+                Ok(0x54000000 | (offset as u32 & 0x7ffff) << 5 | cond)
+            }
+            _ => Err(Error::InvalidOperands),
+        };
+    }
+
+    if let Some(word) = load_store(mnemonic, ops, addr)? {
+        return Ok(word);
+    }
+
+    if let Some(word) = atomic(mnemonic, ops)? {
+        return Ok(word);
+    }
+
+    if mnemonic.starts_with('f') || mnemonic.ends_with("cvtf") {
+        return fp(mnemonic, ops);
+    }
+
+    match (mnemonic, ops) {
+        ("add" | "adds" | "sub" | "subs", [dst, src, rest @ ..]) => {
+            let sub = mnemonic.starts_with("sub");
+            add_sub(sub, mnemonic.ends_with('s'), dst, src, rest)
+        }
+        ("cmp" | "cmn", [src @ Reg(reg), rest @ ..]) => {
+            let is_64 = matches!(reg.kind, Kind::X | Kind::Sp);
+            add_sub(mnemonic == "cmp", true, &zr_register(is_64), src, rest)
+        }
+        ("neg" | "negs", [dst @ Reg(reg), rest @ ..]) => {
+            let is_64 = reg.kind == Kind::X;
+            add_sub(true, mnemonic == "negs", dst, &zr_register(is_64), rest)
+        }
+        ("mov", [Reg(dst), Reg(src)]) if dst.is_sp() || src.is_sp() => {
+            add_sub(false, false, &ops[0], &ops[1], &[Imm(0)])
+        }
+        ("mov", [dst @ Reg(reg), src @ Reg(_)]) => {
+            let is_64 = reg.kind == Kind::X;
+            logical(
+                1,
+                false,
+                dst,
+                &zr_register(is_64),
+                std::slice::from_ref(src),
+            )
+        }
+        ("mov", [Reg(dst), Imm(value)]) => mov(*dst, *value),
+        ("movz" | "movn" | "movk", [Reg(dst), Imm(imm), shift @ ..]) => {
+            let (d, is_64) = dst.zr().ok_or(Error::InvalidOperands)?;
+            let hw = match shift {
+                [] => 0,
+                [Modifier(LSL, Some(amount))] if amount % 16 == 0 => (amount / 16) as u32,
+                _ => return Err(Error::InvalidOperands),
+            };
+            if hw >= if is_64 { 4 } else { 2 } {
+                return Err(Error::InvalidOperands);
+            }
+
+            let opc = match mnemonic {
+                "movn" => 0b00,
+                "movz" => 0b10,
+                _ => 0b11,
+            };
+            let imm = unsigned(*imm, 0xffff)?;
+            Ok(sf(is_64) | opc << 29 | 0x12800000 | hw << 21 | imm << 5 | d)
+        }
+        (
+            "and" | "orr" | "eor" | "ands" | "bic" | "orn" | "eon" | "bics",
+            [dst, src, rest @ ..],
+        ) => {
+            let opc = match mnemonic {
+                "and" | "bic" => 0,
+                "orr" | "orn" => 1,
+                "eor" | "eon" => 2,
+                _ => 3,
+            };
+            let invert = matches!(mnemonic, "bic" | "orn" | "eon" | "bics");
+            logical(opc, invert, dst, src, rest)
+        }
+        ("tst", [src @ Reg(reg), rest @ ..]) => {
+            let is_64 = reg.kind == Kind::X;
+            logical(3, false, &zr_register(is_64), src, rest)
+        }
+        ("mvn", [dst @ Reg(reg), rest @ ..]) => {
+            let is_64 = reg.kind == Kind::X;
+            logical(1, true, dst, &zr_register(is_64), rest)
+        }
+        ("asr" | "lsl" | "lsr" | "ror", [dst, src, rm @ Reg(_)])
+        | ("asrv" | "lslv" | "lsrv" | "rorv" | "udiv" | "sdiv", [dst, src, rm]) => {
+            let opcode = match mnemonic {
+                "udiv" => 0b0010,
+                "sdiv" => 0b0011,
+                "lsl" | "lslv" => 0b1000,
+                "lsr" | "lsrv" => 0b1001,
+                "asr" | "asrv" => 0b1010,
+                _ => 0b1011,
+            };
+            let ([d, n, m], is_64) = same_size(&[dst, src, rm])?;
+            Ok(sf(is_64) | 0x1ac00000 | m << 16 | opcode << 10 | n << 5 | d)
+        }
+        (
+            "crc32b" | "crc32h" | "crc32w" | "crc32x" | "crc32cb" | "crc32ch" | "crc32cw"
+            | "crc32cx",
+            [Reg(dst), Reg(src), Reg(rm)],
+        ) => {
+            let size = match mnemonic.as_bytes()[mnemonic.len() - 1] {
+                b'b' => 0,
+                b'h' => 1,
+                b'w' => 2,
+                _ => 3,
+            };
+            let c = (mnemonic.len() == 7) as u32;
+            let (d, dst_64) = dst.zr().ok_or(Error::InvalidOperands)?;
+            let (n, src_64) = src.zr().ok_or(Error::InvalidOperands)?;
+            let (m, rm_64) = rm.zr().ok_or(Error::InvalidOperands)?;
+            if dst_64 || src_64 || rm_64 != (size == 3) {
+                return Err(Error::InvalidOperands);
+            }
+            let opcode = 0b010000 | c << 2 | size;
+            Ok(sf(size == 3) | 0x1ac00000 | m << 16 | opcode << 10 | n << 5 | d)
+        }
+        ("asr" | "lsl" | "lsr", [Reg(dst), Reg(src), Imm(shift)]) => {
+            let (_, is_64) = dst.zr().ok_or(Error::InvalidOperands)?;
+            let bits = if is_64 { 64 } else { 32 };
+            let shift = unsigned(*shift, bits - 1)? as i64;
+            let (opc, immr, imms) = match mnemonic {
+                "lsl" => (0b10, (bits - shift) % bits, bits - 1 - shift),
+                "lsr" => (0b10, shift, bits - 1),
+                _ => (0b00, shift, bits - 1),
+            };
+            bitfield(opc, *dst, *src, immr, imms)
+        }
+        ("ror", [dst, src, Imm(shift)]) => extract(dst, src, src, *shift),
+        ("extr", [dst, src, rm, Imm(lsb)]) => extract(dst, src, rm, *lsb),
+        ("sbfm" | "bfm" | "ubfm", [Reg(dst), Reg(src), Imm(immr), Imm(imms)]) => {
+            let opc = match mnemonic {
+                "sbfm" => 0b00,
+                "bfm" => 0b01,
+                _ => 0b10,
+            };
+            bitfield(opc, *dst, *src, *immr, *imms)
+        }
+        ("sbfx" | "ubfx" | "bfxil", [Reg(dst), Reg(src), Imm(lsb), Imm(width)]) => {
+            let opc = match mnemonic {
+                "sbfx" => 0b00,
+                "bfxil" => 0b01,
+                _ => 0b10,
+            };
+            if *width < 1 {
+                return Err(Error::OutOfRange(*width));
+            }
+            bitfield(opc, *dst, *src, *lsb, lsb + width - 1)
+        }
+        ("sbfiz" | "ubfiz" | "bfi", [Reg(dst), Reg(src), Imm(lsb), Imm(width)]) => {
+            let opc = match mnemonic {
+                "sbfiz" => 0b00,
+                "bfi" => 0b01,
+                _ => 0b10,
+            };
+            insert(opc, *dst, *src, *lsb, *width)
+        }
+        ("bfc", [Reg(dst), Imm(lsb), Imm(width)]) => {
+            let zr = Register { num: 31, ..*dst };
+            insert(0b01, *dst, zr, *lsb, *width)
+        }
+        ("sxtb" | "sxth" | "sxtw" | "uxtb" | "uxth", [Reg(dst), Reg(src)]) => {
+            if src.kind != Kind::W || (mnemonic.starts_with('u') && dst.kind != Kind::W) {
+                return Err(Error::InvalidOperands);
+            }
+            let (opc, imms) = match mnemonic {
+                "sxtb" => (0b00, 7),
+                "sxth" => (0b00, 15),
+                "sxtw" if dst.kind == Kind::X => (0b00, 31),
+                "uxtb" => (0b10, 7),
+                "uxth" => (0b10, 15),
+                _ => return Err(Error::InvalidOperands),
+            };
+            let src = Register {
+                kind: dst.kind,
+                ..*src
+            };
+            bitfield(opc, *dst, src, 0, imms)
+        }
+        ("rbit" | "rev16" | "rev32" | "rev" | "clz" | "cls", [dst, src]) => {
+            let ([d, n], is_64) = same_size(&[dst, src])?;
+            let opcode = match mnemonic {
+                "rbit" => 0,
+                "rev16" => 1,
+                "rev32" if is_64 => 2,
+                "rev" => 2 + is_64 as u32,
+                "clz" => 4,
+                "cls" => 5,
+                _ => return Err(Error::InvalidOperands),
+            };
+            Ok(sf(is_64) | 0x5ac00000 | opcode << 10 | n << 5 | d)
+        }
+        ("madd" | "msub", [dst, src, rm, ra]) => {
+            let ([d, n, m, a], is_64) = same_size(&[dst, src, rm, ra])?;
+            let o0 = (mnemonic == "msub") as u32;
+            Ok(sf(is_64) | 0x1b000000 | m << 16 | o0 << 15 | a << 10 | n << 5 | d)
+        }
+        ("mul" | "mneg", [dst, src, rm]) => {
+            let ([d, n, m], is_64) = same_size(&[dst, src, rm])?;
+            let o0 = (mnemonic == "mneg") as u32;
+            Ok(sf(is_64) | 0x1b000000 | m << 16 | o0 << 15 | 31 << 10 | n << 5 | d)
+        }
+        (
+            "smaddl" | "smsubl" | "umaddl" | "umsubl" | "smull" | "smnegl" | "umull" | "umnegl",
+            _,
+        ) => {
+            let (dst, src, rm, ra) = match (mnemonic, ops) {
+                ("smaddl" | "smsubl" | "umaddl" | "umsubl", [dst, src, rm, ra]) => {
+                    (dst, src, rm, ra.clone())
+                }
+                ("smull" | "smnegl" | "umull" | "umnegl", [dst, src, rm]) => {
+                    (dst, src, rm, zr_register(true))
+                }
+                _ => return Err(Error::InvalidOperands),
+            };
+            let ([d, a], true) = same_size(&[dst, &ra])? else {
+                return Err(Error::InvalidOperands);
+            };
+            let ([n, m], false) = same_size(&[src, rm])? else {
+                return Err(Error::InvalidOperands);
+            };
+            let u = mnemonic.starts_with('u') as u32;
+            let o0 = matches!(mnemonic, "smsubl" | "umsubl" | "smnegl" | "umnegl") as u32;
+            Ok(0x9b200000 | u << 23 | m << 16 | o0 << 15 | a << 10 | n << 5 | d)
+        }
+        ("smulh" | "umulh", [dst, src, rm]) => {
+            let ([d, n, m], true) = same_size(&[dst, src, rm])? else {
+                return Err(Error::InvalidOperands);
+            };
+            let u = (mnemonic == "umulh") as u32;
+            Ok(0x9b407c00 | u << 23 | m << 16 | n << 5 | d)
+        }
+        ("adc" | "adcs" | "sbc" | "sbcs", [dst, src, rm]) => {
+            let ([d, n, m], is_64) = same_size(&[dst, src, rm])?;
+            let op = mnemonic.starts_with("sbc") as u32;
+            let s = mnemonic.ends_with('s') as u32;
+            Ok(sf(is_64) | op << 30 | s << 29 | 0x1a000000 | m << 16 | n << 5 | d)
+        }
+        ("ngc" | "ngcs", [dst, rm]) => {
+            let ([d, m], is_64) = same_size(&[dst, rm])?;
+            let s = (mnemonic == "ngcs") as u32;
+            Ok(sf(is_64) | 1 << 30 | s << 29 | 0x1a000000 | m << 16 | 31 << 5 | d)
+        }
+        ("csel" | "csinc" | "csinv" | "csneg", [dst, src, rm, Name(cond)]) => {
+            let cond = condition(cond).ok_or(Error::InvalidOperands)?;
+            let (op, o2) = match mnemonic {
+                "csel" => (0, 0),
+                "csinc" => (0, 1),
+                "csinv" => (1, 0),
+                _ => (1, 1),
+            };
+            conditional_select(op, o2, dst, src, rm, cond)
+        }
+        ("cset" | "csetm", [dst @ Reg(reg), cond]) => {
+            let zr = zr_register(reg.kind == Kind::X);
+            let op = (mnemonic == "csetm") as u32;
+            conditional_select(op, op ^ 1, dst, &zr, &zr, inverted_condition(cond)?)
+        }
+        ("cinc" | "cinv" | "cneg", [dst, src, cond]) => {
+            let (op, o2) = match mnemonic {
+                "cinc" => (0, 1),
+                "cinv" => (1, 0),
+                _ => (1, 1),
+            };
+            conditional_select(op, o2, dst, src, src, inverted_condition(cond)?)
+        }
+        ("ccmp" | "ccmn", [src, rm, Imm(nzcv), Name(cond)]) => {
+            let cond = condition(cond).ok_or(Error::InvalidOperands)?;
+            let nzcv = unsigned(*nzcv, 15)?;
+            let op = (mnemonic == "ccmp") as u32;
+            let (n, is_64, m) = match rm {
+                Imm(imm) => {
+                    let ([n], is_64) = same_size(&[src])?;
+                    (n, is_64, unsigned(*imm, 31)? << 16 | 1 << 11)
+                }
+                _ => {
+                    let ([n, m], is_64) = same_size(&[src, rm])?;
+                    (n, is_64, m << 16)
+                }
+            };
+            Ok(sf(is_64) | op << 30 | 0x3a400000 | m | cond << 12 | n << 5 | nzcv)
+        }
+        ("adr", [Reg(dst), Imm(target)]) => {
+            let (d, true) = dst.zr().ok_or(Error::InvalidOperands)? else {
+                return Err(Error::InvalidOperands);
+            };
+            let offset = offset(*target, addr, 21, 1)? as u32;
+            Ok(0x10000000 | (offset & 0b11) << 29 | (offset >> 2 & 0x7ffff) << 5 | d)
+        }
+        ("adrp", [Reg(dst), Imm(target)]) => {
+            let (d, true) = dst.zr().ok_or(Error::InvalidOperands)? else {
+                return Err(Error::InvalidOperands);
+            };
+            let pages = (target >> 12).wrapping_sub(addr as i64 >> 12);
+            if !fits_signed(pages, 21) {
+                return Err(Error::OutOfRange(*target));
+            }
+            let pages = pages as u32;
+            Ok(0x90000000 | (pages & 0b11) << 29 | (pages >> 2 & 0x7ffff) << 5 | d)
+        }
+        ("b" | "bl", [Imm(target)]) => {
+            let offset = offset(*target, addr, 26, 4)? >> 2;
+            let op = if mnemonic == "bl" {
+                0x94000000
+            } else {
+                0x14000000
+            };
+            Ok(op | offset as u32 & 0x3ffffff)
+        }
+        ("cbz" | "cbnz", [Reg(reg), Imm(target)]) => {
+            let (t, is_64) = reg.zr().ok_or(Error::InvalidOperands)?;
+            let offset = offset(*target, addr, 19, 4)? >> 2;
+            let nz = (mnemonic == "cbnz") as u32;
+            Ok(sf(is_64) | 0x34000000 | nz << 24 | (offset as u32 & 0x7ffff) << 5 | t)
+        }
+        ("tbz" | "tbnz", [Reg(reg), Imm(bit), Imm(target)]) => {
+            let (t, is_64) = reg.zr().ok_or(Error::InvalidOperands)?;
+            let bit = unsigned(*bit, if is_64 { 63 } else { 31 })?;
+            let offset = offset(*target, addr, 14, 4)? >> 2;
+            let nz = (mnemonic == "tbnz") as u32;
+            Ok((bit >> 5) << 31
+                | 0x36000000
+                | nz << 24
+                | (bit & 0x1f) << 19
+                | (offset as u32 & 0x3fff) << 5
+                | t)
+        }
+        ("br" | "blr" | "ret", _) => {
+            let n = match ops {
+                [] if mnemonic == "ret" => 30,
+                [Reg(Register { kind: Kind::X, num })] => *num,
+                _ => return Err(Error::InvalidOperands),
+            };
+            let opc = match mnemonic {
+                "br" => 0,
+                "blr" => 1,
+                _ => 2,
+            };
+            Ok(0xd61f0000 | opc << 21 | n << 5)
+        }
+        ("svc" | "hvc" | "smc" | "brk" | "hlt", [Imm(imm)]) => {
+            let imm = unsigned(*imm, 0xffff)?;
+            let op = match mnemonic {
+                "svc" => 0xd4000001,
+                "hvc" => 0xd4000002,
+                "smc" => 0xd4000003,
+                "brk" => 0xd4200000,
+                _ => 0xd4400000,
+            };
+            Ok(op | imm << 5)
+        }
+        ("hint", [Imm(imm)]) => Ok(0xd503201f | unsigned(*imm, 0x7f)? << 5),
+        ("psb" | "tsb", [Name(name)]) if name == "csync" => Ok(if mnemonic == "psb" {
+            0xd503223f
+        } else {
+            0xd503225f
+        }),
+        ("dmb" | "dsb" | "isb" | "clrex", _) => {
+            let crm = match ops {
+                [] if matches!(mnemonic, "isb" | "clrex") => 15,
+                [Imm(imm)] => unsigned(*imm, 15)?,
+                [Name(name)] if mnemonic != "isb" => BARRIERS
+                    .iter()
+                    .position(|barrier| barrier == name)
+                    .ok_or(Error::InvalidOperands)?
+                    as u32,
+                _ => return Err(Error::InvalidOperands),
+            };
+            let op2 = match mnemonic {
+                "clrex" => 0b010,
+                "dsb" => 0b100,
+                "dmb" => 0b101,
+                _ => 0b110,
+            };
+            Ok(0xd503301f | crm << 8 | op2 << 5)
+        }
+        ("mrs", [Reg(reg), Name(name)]) => {
+            let (t, true) = reg.zr().ok_or(Error::InvalidOperands)? else {
+                return Err(Error::InvalidOperands);
+            };
+            Ok(0xd5300000 | system_register(name)? << 5 | t)
+        }
+        ("msr", [Name(name), Reg(reg)]) => {
+            let (t, true) = reg.zr().ok_or(Error::InvalidOperands)? else {
+                return Err(Error::InvalidOperands);
+            };
+            Ok(0xd5100000 | system_register(name)? << 5 | t)
+        }
+        _ => Err(Error::UnknownInstruction(mnemonic.to_string())),
+    }
+}
+
+/// Encoding of a system register, either by its name or as `s<op0>_<op1>_c<n>_c<m>_<op2>`.
+fn system_register(name: &str) -> Result<u32, Error> {
+    if let Some(&(_, reg)) = SYSTEM_REGISTERS.iter().find(|(reg, _)| *reg == name) {
+        return Ok(reg);
+    }
+
+    let fields: Vec<&str> = name.split('_').collect();
+    let field = |idx: usize, prefix: &str, max: u32| {
+        let value: u32 = fields.get(idx)?.strip_prefix(prefix)?.parse().ok()?;
+        (value <= max).then_some(value)
+    };
+
+    match (
+        field(0, "s", 3),
+        field(1, "", 7),
+        field(2, "c", 15),
+        field(3, "c", 15),
+        field(4, "", 7),
+    ) {
+        (Some(op0 @ 2..=3), Some(op1), Some(crn), Some(crm), Some(op2)) if fields.len() == 5 => {
+            Ok((op0 - 2) << 14 | op1 << 11 | crn << 7 | crm << 3 | op2)
+        }
+        _ => Err(Error::InvalidRegister(name.to_string())),
+    }
+}
+
+fn add_sub(
+    sub: bool,
+    flags: bool,
+    dst: &Operand,
+    src: &Operand,
+    rest: &[Operand],
+) -> Result<u32, Error> {
+    use Operand::*;
+
+    let (Reg(dst), Reg(src)) = (dst, src) else {
+        return Err(Error::InvalidOperands);
+    };
+    let op = (sub as u32) << 30 | (flags as u32) << 29;
+
+    // the destination is the stack pointer unless flags are set
+    let dst_sp = |dst: Register| match flags {
+        true => dst.zr(),
+        false => dst.sp(),
+    };
+
+    match rest {
+        [Imm(imm), shift @ ..] => {
+            let (d, is_64) = dst_sp(*dst).ok_or(Error::InvalidOperands)?;
+            let (n, src_64) = src.sp().ok_or(Error::InvalidOperands)?;
+            if is_64 != src_64 {
+                return Err(Error::InvalidOperands);
+            }
+
+            let (imm, sh) = match shift {
+                [] | [Modifier(LSL, Some(0))] if fits_unsigned(*imm, 12) => (*imm, 0),
+                [] if imm & 0xfff == 0 && fits_unsigned(*imm, 24) => (imm >> 12, 1),
+                [Modifier(LSL, Some(12))] if fits_unsigned(*imm, 12) => (*imm, 1),
+                [] | [Modifier(LSL, Some(0 | 12))] => return Err(Error::OutOfRange(*imm)),
+                _ => return Err(Error::InvalidOperands),
+            };
+
+            Ok(sf(is_64) | op | 0x11000000 | sh << 22 | (imm as u32) << 10 | n << 5 | d)
+        }
+        [Reg(rm), modifier @ ..] => {
+            let modifier = match modifier {
+                [] => None,
+                [Modifier(n, amount)] => Some((*n, *amount)),
+                _ => return Err(Error::InvalidOperands),
+            };
+
+            let is_64 = matches!(dst.kind, Kind::X | Kind::Sp);
+            let extended = dst.is_sp()
+                || src.is_sp()
+                || (is_64 && rm.kind == Kind::W)
+                || modifier.is_some_and(|(n, _)| n >= EXTEND);
+
+            if !extended {
+                let ([d, n, m], is_64) = same_size(&[&Reg(*dst), &Reg(*src), &Reg(*rm)])?;
+                let (shift, amount) = shift(modifier, is_64, false)?;
+                return Ok(sf(is_64)
+                    | op
+                    | 0x0b000000
+                    | shift << 22
+                    | m << 16
+                    | amount << 10
+                    | n << 5
+                    | d);
+            }
+
+            let (d, is_64) = dst_sp(*dst).ok_or(Error::InvalidOperands)?;
+            let (n, src_64) = src.sp().ok_or(Error::InvalidOperands)?;
+            let (m, rm_64) = rm.zr().ok_or(Error::InvalidOperands)?;
+            if is_64 != src_64 {
+                return Err(Error::InvalidOperands);
+            }
+
+            let (option, amount) = match modifier {
+                // `lsl` is `uxtx` for 64-bit registers and `uxtw` for 32-bit ones
+                None | Some((LSL, _)) => (
+                    if rm_64 { 0b011 } else { 0b010 },
+                    modifier.and_then(|m| m.1),
+                ),
+                Some((n, amount)) if n >= EXTEND => ((n - EXTEND) as u32, amount),
+                Some(_) => return Err(Error::InvalidOperands),
+            };
+            if rm_64 != (option & 0b011 == 0b011) {
+                return Err(Error::InvalidOperands);
+            }
+            let amount = unsigned(amount.unwrap_or(0), 4)?;
+
+            Ok(sf(is_64) | op | 0x0b200000 | m << 16 | option << 13 | amount << 10 | n << 5 | d)
+        }
+        _ => Err(Error::InvalidOperands),
+    }
+}
+
+/// Type and amount of a shifted register operand.
+fn shift(
+    modifier: Option<(usize, Option<i64>)>,
+    is_64: bool,
+    ror: bool,
+) -> Result<(u32, u32), Error> {
+    match modifier {
+        None => Ok((0, 0)),
+        Some((shift, Some(amount))) if shift < ROR || (shift == ROR && ror) => {
+            Ok((shift as u32, unsigned(amount, if is_64 { 63 } else { 31 })?))
+        }
+        Some(_) => Err(Error::InvalidOperands),
+    }
+}
+
+fn logical(
+    opc: u32,
+    invert: bool,
+    dst: &Operand,
+    src: &Operand,
+    rest: &[Operand],
+) -> Result<u32, Error> {
+    use Operand::*;
+
+    match rest {
+        [Imm(imm)] if !invert => {
+            let (Reg(dst), Reg(src)) = (dst, src) else {
+                return Err(Error::InvalidOperands);
+            };
+
+            // the destination is the stack pointer unless flags are set
+            let dst = match opc {
+                3 => dst.zr(),
+                _ => dst.sp(),
+            };
+            let (d, is_64) = dst.ok_or(Error::InvalidOperands)?;
+            let (n, src_64) = src.zr().ok_or(Error::InvalidOperands)?;
+            if is_64 != src_64 {
+                return Err(Error::InvalidOperands);
+            }
+
+            let imm = bitmask(*imm, is_64).ok_or(Error::OutOfRange(*imm))?;
+            Ok(sf(is_64) | opc << 29 | 0x12000000 | imm << 10 | n << 5 | d)
+        }
+        [rm @ Reg(_), modifier @ ..] => {
+            let ([d, n, m], is_64) = same_size(&[dst, src, rm])?;
+            let modifier = match modifier {
+                [] => None,
+                [Modifier(n, amount)] => Some((*n, *amount)),
+                _ => return Err(Error::InvalidOperands),
+            };
+            let (shift, amount) = shift(modifier, is_64, true)?;
+
+            Ok(sf(is_64)
+                | opc << 29
+                | 0x0a000000
+                | shift << 22
+                | (invert as u32) << 21
+                | m << 16
+                | amount << 10
+                | n << 5
+                | d)
+        }
+        _ => Err(Error::InvalidOperands),
+    }
+}
+
+/// `mov` of an immediate, which is encoded using `movz`, `movn` or `orr`.
+fn mov(dst: Register, value: i64) -> Result<u32, Error> {
+    let (d, is_64) = dst.zr().ok_or(Error::InvalidOperands)?;
+    let (value, mask) = if is_64 {
+        (value as u64, u64::MAX)
+    } else if fits_unsigned(value, 32) || fits_signed(value, 32) {
+        (value as u64 & 0xffff_ffff, 0xffff_ffff)
+    } else {
+        return Err(Error::OutOfRange(value));
+    };
+
+    let chunks = if is_64 { 4 } else { 2 };
+    let single_chunk = |value: u64| {
+        (0..chunks).find_map(|hw| {
+            let chunk = value >> (hw * 16) & 0xffff;
+            (value & !(0xffff << (hw * 16)) == 0).then_some((hw as u32, chunk as u32))
+        })
+    };
+
+    if let Some((hw, imm)) = single_chunk(value) {
+        return Ok(sf(is_64) | 0x52800000 | hw << 21 | imm << 5 | d);
+    }
+
+    if let Some((hw, imm)) = single_chunk(!value & mask) {
+        return Ok(sf(is_64) | 0x12800000 | hw << 21 | imm << 5 | d);
+    }
+
+    match bitmask(value as i64, is_64) {
+        Some(imm) if d != 31 => Ok(sf(is_64) | 0x32000000 | imm << 10 | 31 << 5 | d),
+        _ => Err(Error::OutOfRange(value as i64)),
+    }
+}
+
+fn bitfield(opc: u32, dst: Register, src: Register, immr: i64, imms: i64) -> Result<u32, Error> {
+    let ([d, n], is_64) = same_size(&[&Operand::Reg(dst), &Operand::Reg(src)])?;
+    let max = if is_64 { 63 } else { 31 };
+    let immr = unsigned(immr, max)?;
+    let imms = unsigned(imms, max)?;
+    let n_bit = is_64 as u32;
+
+    Ok(sf(is_64) | opc << 29 | 0x13000000 | n_bit << 22 | immr << 16 | imms << 10 | n << 5 | d)
+}
+
+/// `sbfiz`, `ubfiz` and `bfi`, which insert `width` bits at `lsb`.
+fn insert(opc: u32, dst: Register, src: Register, lsb: i64, width: i64) -> Result<u32, Error> {
+    let bits = if dst.kind == Kind::X { 64 } else { 32 };
+    if !(0..bits).contains(&lsb) {
+        return Err(Error::OutOfRange(lsb));
+    }
+    if width < 1 || lsb + width > bits {
+        return Err(Error::OutOfRange(width));
+    }
+    bitfield(opc, dst, src, (bits - lsb) % bits, width - 1)
+}
+
+fn extract(dst: &Operand, src: &Operand, rm: &Operand, lsb: i64) -> Result<u32, Error> {
+    let ([d, n, m], is_64) = same_size(&[dst, src, rm])?;
+    let lsb = unsigned(lsb, if is_64 { 63 } else { 31 })?;
+    let n_bit = is_64 as u32;
+    Ok(sf(is_64) | 0x13800000 | n_bit << 22 | m << 16 | lsb << 10 | n << 5 | d)
+}
+
+fn conditional_select(
+    op: u32,
+    o2: u32,
+    dst: &Operand,
+    src: &Operand,
+    rm: &Operand,
+    cond: u32,
+) -> Result<u32, Error> {
+    let ([d, n, m], is_64) = same_size(&[dst, src, rm])?;
+    Ok(sf(is_64) | op << 30 | 0x1a800000 | m << 16 | cond << 12 | o2 << 10 | n << 5 | d)
+}
+
+/// Loads and stores of a single register or a pair of registers.
+fn load_store(mnemonic: &str, ops: &[Operand], addr: usize) -> Result<Option<u32>, Error> {
+    use Operand::*;
+
+    // size of the access and whether it sign-extends
+    let (name, unscaled) = match mnemonic {
+        "ldur" | "stur" | "ldurb" | "sturb" | "ldurh" | "sturh" | "ldursb" | "ldursh"
+        | "ldursw" | "prfum" => (mnemonic.replacen("ur", "r", 1), true),
+        _ => (mnemonic.to_string(), false),
+    };
+
+    let (load, size, signed) = match name.as_str() {
+        "ldr" | "str" => (name == "ldr", None, false),
+        "ldrb" | "strb" => (name == "ldrb", Some(0), false),
+        "ldrh" | "strh" => (name == "ldrh", Some(1), false),
+        "ldrsb" => (true, Some(0), true),
+        "ldrsh" => (true, Some(1), true),
+        "ldrsw" => (true, Some(2), true),
+        "prfm" => return prefetch(ops, unscaled, addr).map(Some),
+        "ldp" | "stp" | "ldnp" | "stnp" | "ldpsw" => return pair(mnemonic, ops).map(Some),
+        "ldxr" | "ldaxr" | "stxr" | "stlxr" | "ldar" | "stlr" | "ldxrb" | "ldaxrb" | "stxrb"
+        | "stlxrb" | "ldarb" | "stlrb" | "ldxrh" | "ldaxrh" | "stxrh" | "stlxrh" | "ldarh"
+        | "stlrh" | "ldxp" | "ldaxp" | "stxp" | "stlxp" => {
+            return exclusive(mnemonic, ops).map(Some)
+        }
+        _ => return Ok(None),
+    };
+
+    let (rt, mem) = match ops {
+        [Reg(rt), rest @ ..] => (*rt, rest),
+        _ => return Err(Error::InvalidOperands),
+    };
+
+    // the size of the register determines the size of the access of `ldr` and `str`
+    let (t, size, v, opc) = match (size, rt.vector_size()) {
+        (None, Some(4)) => (rt.num, 0, 1, 0b10 | load as u32),
+        (None, Some(size)) => (rt.num, size, 1, load as u32),
+        (None, None) => {
+            let (t, is_64) = rt.zr().ok_or(Error::InvalidOperands)?;
+            (t, 2 + is_64 as u32, 0, load as u32)
+        }
+        (Some(size), None) => {
+            let (t, is_64) = rt.zr().ok_or(Error::InvalidOperands)?;
+            let opc = match signed {
+                true if size == 2 && !is_64 => return Err(Error::InvalidOperands),
+                true => 0b10 | !is_64 as u32,
+                false if is_64 => return Err(Error::InvalidOperands),
+                false => load as u32,
+            };
+            (t, size, 0, opc)
+        }
+        (Some(_), Some(_)) => return Err(Error::InvalidOperands),
+    };
+    let scale = if v == 1 && opc & 0b10 != 0 && !signed {
+        4
+    } else {
+        size
+    };
+
+    let base = size << 30 | 0b111 << 27 | v << 26 | opc << 22 | t;
+    match mem {
+        // literals are only loaded into registers of at least 32 bits
+        [Imm(target)] if load && !unscaled => {
+            let opc = match (scale, signed) {
+                (2, true) => 0b10,
+                (2, false) => 0b00,
+                (3, false) => 0b01,
+                (4, false) => 0b10,
+                _ => return Err(Error::InvalidOperands),
+            };
+            let offset = offset(*target, addr, 19, 4)? >> 2;
+            Ok(Some(
+                opc << 30 | 0b011 << 27 | v << 26 | (offset as u32 & 0x7ffff) << 5 | t,
+            ))
+        }
+        [Mem(mem)] => Ok(Some(base | address(mem, scale, unscaled)?)),
+        [Mem(mem), Imm(imm)] if mem.offset == Offset::None && !unscaled => {
+            Ok(Some(base | indexed(mem.base, *imm, 0b01)?))
+        }
+        _ => Err(Error::InvalidOperands),
+    }
+}
+
+/// Pre- or post-indexed, or unscaled addressing with a 9-bit offset.
+fn indexed(base: u32, imm: i64, idx: u32) -> Result<u32, Error> {
+    if !fits_signed(imm, 9) {
+        return Err(Error::OutOfRange(imm));
+    }
+    Ok((imm as u32 & 0x1ff) << 12 | idx << 10 | base << 5)
+}
+
+/// Fields of a memory operand of a single register load or store, of which the access is
+/// `1 << scale` bytes.
+fn address(mem: &Memory, scale: u32, unscaled: bool) -> Result<u32, Error> {
+    let imm = match mem.offset {
+        Offset::None => 0,
+        Offset::Imm(imm) => imm,
+        Offset::Reg(index, modifier) if !unscaled && !mem.writeback => {
+            let (m, is_64) = index.zr().ok_or(Error::InvalidOperands)?;
+            let (option, amount) = match modifier {
+                None => (0b011, None),
+                Some((LSL, amount)) if is_64 => (0b011, amount),
+                Some((n, amount)) if n >= EXTEND => ((n - EXTEND) as u32, amount),
+                Some(_) => return Err(Error::InvalidOperands),
+            };
+            if option & 0b010 == 0 || is_64 != (option & 0b001 != 0) {
+                return Err(Error::InvalidOperands);
+            }
+
+            // byte accesses can only be shifted by 0, which is shown when it's used
+            let s = match amount {
+                None => 0,
+                Some(0) if scale != 0 => 0,
+                Some(amount) if amount == scale as i64 => 1,
+                Some(_) => return Err(Error::InvalidOperands),
+            };
+
+            return Ok(1 << 21 | m << 16 | option << 13 | s << 12 | 0b10 << 10 | mem.base << 5);
+        }
+        Offset::Reg(..) => return Err(Error::InvalidOperands),
+    };
+
+    if mem.writeback {
+        if unscaled {
+            return Err(Error::InvalidOperands);
+        }
+        return indexed(mem.base, imm, 0b11);
+    }
+
+    let size = 1i64 << scale;
+    if !unscaled && imm % size == 0 && (0..4096).contains(&(imm / size)) {
+        return Ok(1 << 24 | ((imm / size) as u32) << 10 | mem.base << 5);
+    }
+
+    indexed(mem.base, imm, 0b00)
+}
+
+fn prefetch(ops: &[Operand], unscaled: bool, addr: usize) -> Result<u32, Error> {
+    use Operand::*;
+
+    let (op, mem) = match ops {
+        [op, mem @ ..] => (op, mem),
+        [] => return Err(Error::InvalidOperands),
+    };
+
+    let op = match op {
+        Imm(op) => unsigned(*op, 31)?,
+        // operations without a name are shown as a plain number
+        Name(name) if name.starts_with("0x") => {
+            let op = u32::from_str_radix(&name[2..], 16).map_err(|_| Error::InvalidOperands)?;
+            unsigned(op as i64, 31)?
+        }
+        Name(name) => {
+            let kind = ["pld", "pli", "pst"].iter().position(|kind| name.starts_with(kind));
+            let rest = name.get(3..).unwrap_or_default();
+            let target = ["l1", "l2", "l3"].iter().position(|target| rest.starts_with(target));
+            let policy = ["keep", "strm"].iter().position(|policy| rest.get(2..) == Some(policy));
+            match (kind, target, policy) {
+                (Some(kind), Some(target), Some(policy)) => {
+                    (kind << 3 | target << 1 | policy) as u32
+                }
+                _ => return Err(Error::InvalidOperands),
+            }
+        }
+        _ => return Err(Error::InvalidOperands),
+    };
+
+    match mem {
+        [Imm(target)] if !unscaled => {
+            let offset = offset(*target, addr, 19, 4)? >> 2;
+            Ok(0xd8000000 | (offset as u32 & 0x7ffff) << 5 | op)
+        }
+        [Mem(mem)] if !mem.writeback => Ok(0xf8800000 | address(mem, 3, unscaled)? | op),
+        _ => Err(Error::InvalidOperands),
+    }
+}
+
+fn pair(mnemonic: &str, ops: &[Operand]) -> Result<u32, Error> {
+    use Operand::*;
+
+    let (rt, rt2, mem, post) = match ops {
+        [Reg(rt), Reg(rt2), Mem(mem)] => (rt, rt2, mem, None),
+        [Reg(rt), Reg(rt2), Mem(mem), Imm(imm)] if mem.offset == Offset::None && !mem.writeback => {
+            (rt, rt2, mem, Some(*imm))
+        }
+        _ => return Err(Error::InvalidOperands),
+    };
+    if rt.kind != rt2.kind {
+        return Err(Error::InvalidOperands);
+    }
+
+    let load = mnemonic.starts_with("ld") as u32;
+    let (opc, v, scale) = match (rt.kind, mnemonic) {
+        (Kind::W, "ldpsw") => return Err(Error::InvalidOperands),
+        (Kind::X, "ldpsw") => (0b01, 0, 2),
+        (Kind::W, _) => (0b00, 0, 2),
+        (Kind::X, _) => (0b10, 0, 3),
+        (Kind::S, _) => (0b00, 1, 2),
+        (Kind::D, _) => (0b01, 1, 3),
+        (Kind::Q, _) => (0b10, 1, 4),
+        _ => return Err(Error::InvalidOperands),
+    };
+
+    let non_temporal = mnemonic.ends_with("np");
+    let (idx, imm) = match (post, &mem.offset) {
+        (Some(imm), _) if !non_temporal => (0b01, imm),
+        (None, Offset::None) if !mem.writeback => (if non_temporal { 0b00 } else { 0b10 }, 0),
+        (None, Offset::Imm(imm)) if !mem.writeback => {
+            (if non_temporal { 0b00 } else { 0b10 }, *imm)
+        }
+        (None, Offset::Imm(imm)) if !non_temporal => (0b11, *imm),
+        _ => return Err(Error::InvalidOperands),
+    };
+
+    let size = 1i64 << scale;
+    if imm % size != 0 {
+        return Err(Error::Misaligned(imm));
+    }
+    if !fits_signed(imm / size, 7) {
+        return Err(Error::OutOfRange(imm));
+    }
+    let imm = (imm / size) as u32 & 0x7f;
+
+    Ok(opc << 30
+        | 0b101 << 27
+        | v << 26
+        | idx << 23
+        | load << 22
+        | imm << 15
+        | rt2.num << 10
+        | mem.base << 5
+        | rt.num)
+}
+
+/// Exclusive and acquire-release loads and stores.
+fn exclusive(mnemonic: &str, ops: &[Operand]) -> Result<u32, Error> {
+    use Operand::*;
+
+    let (name, size) = match mnemonic.as_bytes()[mnemonic.len() - 1] {
+        b'b' => (&mnemonic[..mnemonic.len() - 1], Some(0)),
+        b'h' => (&mnemonic[..mnemonic.len() - 1], Some(1)),
+        _ => (mnemonic, None),
+    };
+
+    // exclusive stores write whether they succeeded to a status register
+    let (status, ops) = match ops {
+        [Reg(status), ops @ ..] if name.starts_with("st") && name.contains('x') => {
+            let (status, false) = status.zr().ok_or(Error::InvalidOperands)? else {
+                return Err(Error::InvalidOperands);
+            };
+            (status, ops)
+        }
+        _ => (31, ops),
+    };
+
+    let pair = name.ends_with('p');
+    let (rt, rt2, mem) = match ops {
+        [rt, Mem(mem)] if !pair => (rt, None, mem),
+        [rt, rt2, Mem(mem)] if pair => (rt, Some(rt2), mem),
+        _ => return Err(Error::InvalidOperands),
+    };
+    if mem.offset != Offset::None && mem.offset != Offset::Imm(0) || mem.writeback {
+        return Err(Error::InvalidOperands);
+    }
+
+    let ([t, t2], is_64) = same_size(&[rt, rt2.unwrap_or(rt)])?;
+    let size = match size {
+        Some(_) if is_64 => return Err(Error::InvalidOperands),
+        Some(size) => size,
+        None => 2 + is_64 as u32,
+    };
+    let t2 = if pair { t2 } else { 31 };
+
+    let (o2, l, o0) = match name {
+        "stxr" | "stxp" => (0, 0, 0),
+        "stlxr" | "stlxp" => (0, 0, 1),
+        "ldxr" | "ldxp" => (0, 1, 0),
+        "ldaxr" | "ldaxp" => (0, 1, 1),
+        "stlr" => (1, 0, 1),
+        _ => (1, 1, 1),
+    };
+
+    Ok(size << 30
+        | 0x08000000
+        | o2 << 23
+        | l << 22
+        | (pair as u32) << 21
+        | status << 16
+        | o0 << 15
+        | t2 << 10
+        | mem.base << 5
+        | t)
+}
+
+/// Atomic memory operations from the large system extensions.
+fn atomic(mnemonic: &str, ops: &[Operand]) -> Result<Option<u32>, Error> {
+    use Operand::*;
+
+    const OPERATIONS: [&str; 8] = ["add", "clr", "eor", "set", "smax", "smin", "umax", "umin"];
+
+    let (name, size) = match mnemonic.as_bytes()[mnemonic.len() - 1] {
+        b'b' => (&mnemonic[..mnemonic.len() - 1], Some(0)),
+        b'h' => (&mnemonic[..mnemonic.len() - 1], Some(1)),
+        _ => (mnemonic, None),
+    };
+
+    // acquire and release semantics come after the operation
+    let (name, a, r) = if let Some(name) = name.strip_suffix("al") {
+        (name, 1, 1)
+    } else if let Some(name) = name.strip_suffix('a') {
+        (name, 1, 0)
+    } else if let Some(name) = name.strip_suffix('l') {
+        (name, 0, 1)
+    } else {
+        (name, 0, 0)
+    };
+
+    let (kind, op) = if let Some(op) = name.strip_prefix("ld") {
+        match OPERATIONS.iter().position(|&operation| operation == op) {
+            Some(op) => ("ld", op as u32),
+            None => return Ok(None),
+        }
+    } else if let Some(op) = name.strip_prefix("st") {
+        match OPERATIONS.iter().position(|&operation| operation == op) {
+            // stores only have a release version
+            Some(op) if a == 0 => ("st", op as u32),
+            _ => return Ok(None),
+        }
+    } else if name == "swp" || name == "cas" {
+        (name, 0)
+    } else {
+        return Ok(None);
+    };
+
+    let (rs, rt, mem) = match (kind, ops) {
+        // the loaded value is discarded
+        ("st", [Reg(rs), Mem(mem)]) => (*rs, Register { num: 31, ..*rs }, mem),
+        ("ld" | "swp" | "cas", [Reg(rs), Reg(rt), Mem(mem)]) => (*rs, *rt, mem),
+        _ => return Err(Error::InvalidOperands),
+    };
+    if mem.offset != Offset::None && mem.offset != Offset::Imm(0) || mem.writeback {
+        return Err(Error::InvalidOperands);
+    }
+    let ([s, t], is_64) = same_size(&[&Reg(rs), &Reg(rt)])?;
+    let size = match size {
+        Some(_) if is_64 => return Err(Error::InvalidOperands),
+        Some(size) => size,
+        None => 2 + is_64 as u32,
+    };
+
+    let base = size << 30 | s << 16 | mem.base << 5 | t;
+    Ok(Some(match kind {
+        "cas" => base | 0x08a07c00 | a << 22 | r << 15,
+        "swp" => base | 0x38208000 | a << 23 | r << 22,
+        _ => base | 0x38200000 | a << 23 | r << 22 | op << 12,
+    }))
+}
+
+/// Scalar floating point instructions.
+fn fp(mnemonic: &str, ops: &[Operand]) -> Result<u32, Error> {
+    use Operand::*;
+
+    let fp_regs = |ops: &[&Operand]| -> Result<(Vec<u32>, u32), Error> {
+        let mut nums = Vec::with_capacity(ops.len());
+        let mut ftype = None;
+        for op in ops {
+            let Reg(reg) = op else {
+                return Err(Error::InvalidOperands);
+            };
+            let (num, ty) = reg.fp().ok_or(Error::InvalidOperands)?;
+            if ftype.is_some_and(|ftype| ftype != ty) {
+                return Err(Error::InvalidOperands);
+            }
+            nums.push(num);
+            ftype = Some(ty);
+        }
+        Ok((nums, ftype.unwrap_or(0)))
+    };
+
+    const TWO_SOURCE: [&str; 9] = [
+        "fmul", "fdiv", "fadd", "fsub", "fmax", "fmin", "fmaxnm", "fminnm", "fnmul",
+    ];
+    const ONE_SOURCE: [&str; 16] = [
+        "fmov", "fabs", "fneg", "fsqrt", "", "", "", "", "frintn", "frintp", "frintm", "frintz",
+        "frinta", "", "frintx", "frinti",
+    ];
+    const CONVERSIONS: &[(&str, u32, u32)] = &[
+        ("fcvtns", 0b00, 0b000),
+        ("fcvtnu", 0b00, 0b001),
+        ("scvtf", 0b00, 0b010),
+        ("ucvtf", 0b00, 0b011),
+        ("fcvtas", 0b00, 0b100),
+        ("fcvtau", 0b00, 0b101),
+        ("fcvtps", 0b01, 0b000),
+        ("fcvtpu", 0b01, 0b001),
+        ("fcvtms", 0b10, 0b000),
+        ("fcvtmu", 0b10, 0b001),
+        ("fcvtzs", 0b11, 0b000),
+        ("fcvtzu", 0b11, 0b001),
+    ];
+
+    if let Some(opcode) = TWO_SOURCE.iter().position(|&name| name == mnemonic) {
+        let [dst, src, rm] = ops else {
+            return Err(Error::InvalidOperands);
+        };
+        let (regs, ftype) = fp_regs(&[dst, src, rm])?;
+        let opcode = opcode as u32;
+        return Ok(0x1e200800
+            | ftype << 22
+            | regs[2] << 16
+            | opcode << 12
+            | regs[1] << 5
+            | regs[0]);
+    }
+
+    if let Some(opcode) = ONE_SOURCE.iter().position(|&name| name == mnemonic) {
+        if let [dst, src] = ops {
+            if let Ok((regs, ftype)) = fp_regs(&[dst, src]) {
+                let opcode = opcode as u32;
+                return Ok(0x1e204000 | ftype << 22 | opcode << 15 | regs[1] << 5 | regs[0]);
+            }
+        }
+    }
+
+    if let Some(&(_, rmode, opcode)) = CONVERSIONS.iter().find(|(name, ..)| *name == mnemonic) {
+        let (dst, src, fbits) = match ops {
+            [Reg(dst), Reg(src)] => (dst, src, None),
+            [Reg(dst), Reg(src), Imm(fbits)]
+                if matches!((rmode, opcode >> 1), (0b11, 0b00) | (0b00, 0b01)) =>
+            {
+                (dst, src, Some(*fbits))
+            }
+            _ => return Err(Error::InvalidOperands),
+        };
+
+        // converting to floating point has the registers the other way around
+        let (gpr, fpr) = if opcode & 0b110 == 0b010 {
+            (src, dst)
+        } else {
+            (dst, src)
+        };
+        let (gpr, is_64) = gpr.zr().ok_or(Error::InvalidOperands)?;
+        let (fpr, ftype) = fpr.fp().ok_or(Error::InvalidOperands)?;
+        let (d, n) = if opcode & 0b110 == 0b010 {
+            (fpr, gpr)
+        } else {
+            (gpr, fpr)
+        };
+
+        // fixed point conversions have the number of fractional bits encoded as `64 - fbits`
+        let (fixed, scale) = match fbits {
+            None => (1 << 21, 0),
+            Some(fbits) if (1..=if is_64 { 64 } else { 32 }).contains(&fbits) => {
+                (0, 64 - fbits as u32)
+            }
+            Some(fbits) => return Err(Error::OutOfRange(fbits)),
+        };
+
+        return Ok(sf(is_64)
+            | 0x1e000000
+            | fixed
+            | ftype << 22
+            | rmode << 19
+            | opcode << 16
+            | scale << 10
+            | n << 5
+            | d);
+    }
+
+    match (mnemonic, ops) {
+        ("fmov", [Reg(dst), Float(value)]) => {
+            let (d, ftype) = dst.fp().ok_or(Error::InvalidOperands)?;
+            let imm = fp_immediate(*value).ok_or(Error::InvalidOperands)?;
+            Ok(0x1e201000 | ftype << 22 | imm << 13 | d)
+        }
+        ("fmov", [Reg(dst), Reg(src)]) => {
+            // moves between general purpose and floating point registers of the same size
+            let (d, n, opcode, reg, fpr) = match (dst.zr(), src.zr()) {
+                (Some((d, _)), None) => (d, src.num, 0b110, *dst, *src),
+                (None, Some((n, _))) => (dst.num, n, 0b111, *src, *dst),
+                _ => return Err(Error::InvalidOperands),
+            };
+            let (_, ftype) = fpr.fp().ok_or(Error::InvalidOperands)?;
+            let is_64 = reg.kind == Kind::X;
+            match (is_64, ftype) {
+                (true, 0b01) | (false, 0b00) | (_, 0b11) => {}
+                _ => return Err(Error::InvalidOperands),
+            }
+
+            Ok(sf(is_64) | 0x1e200000 | ftype << 22 | opcode << 16 | n << 5 | d)
+        }
+        ("fcmp" | "fcmpe", [src, rm]) => {
+            let e = ((mnemonic == "fcmpe") as u32) << 4;
+            match rm {
+                Float(value) if *value == 0.0 => {
+                    let (regs, ftype) = fp_regs(&[src])?;
+                    Ok(0x1e202008 | ftype << 22 | regs[0] << 5 | e)
+                }
+                _ => {
+                    let (regs, ftype) = fp_regs(&[src, rm])?;
+                    Ok(0x1e202000 | ftype << 22 | regs[1] << 16 | regs[0] << 5 | e)
+                }
+            }
+        }
+        ("fccmp" | "fccmpe", [src, rm, Imm(nzcv), Name(cond)]) => {
+            let cond = condition(cond).ok_or(Error::InvalidOperands)?;
+            let nzcv = unsigned(*nzcv, 15)?;
+            let e = ((mnemonic == "fccmpe") as u32) << 4;
+            let (regs, ftype) = fp_regs(&[src, rm])?;
+            Ok(0x1e200400 | ftype << 22 | regs[1] << 16 | cond << 12 | regs[0] << 5 | e | nzcv)
+        }
+        ("fcsel", [dst, src, rm, Name(cond)]) => {
+            let cond = condition(cond).ok_or(Error::InvalidOperands)?;
+            let (regs, ftype) = fp_regs(&[dst, src, rm])?;
+            Ok(0x1e200c00 | ftype << 22 | regs[2] << 16 | cond << 12 | regs[1] << 5 | regs[0])
+        }
+        ("fmadd" | "fmsub" | "fnmadd" | "fnmsub", [dst, src, rm, ra]) => {
+            let (regs, ftype) = fp_regs(&[dst, src, rm, ra])?;
+            let o1 = mnemonic.starts_with("fn") as u32;
+            let o0 = mnemonic.ends_with("sub") as u32;
+            Ok(0x1f000000
+                | ftype << 22
+                | o1 << 21
+                | regs[2] << 16
+                | o0 << 15
+                | regs[3] << 10
+                | regs[1] << 5
+                | regs[0])
+        }
+        ("fcvt", [Reg(dst), Reg(src)]) => {
+            let (d, to) = dst.fp().ok_or(Error::InvalidOperands)?;
+            let (n, from) = src.fp().ok_or(Error::InvalidOperands)?;
+            if to == from {
+                return Err(Error::InvalidOperands);
+            }
+            Ok(0x1e224000 | from << 22 | to << 15 | n << 5 | d)
+        }
+        _ => Err(Error::UnknownInstruction(mnemonic.to_string())),
+    }
+}
@@ -0,0 +1,91 @@
+//! Assemblers for patching instructions by their mnemonic.
+//!
+//! Each architecture accepts the syntax its decoder prints, so an instruction from the listing
+//! can be edited and assembled again. Symbols are written like they're shown, as `<name>`, and
+//! are looked up in the [`Index`](debugvault::Index). Branch targets are absolute addresses,
+//! the instruction's own address is used to turn them into offsets.
+
+mod parse;
+mod tests;
+
+pub mod aarch64;
+pub mod riscv;
+pub mod x86_64;
+
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// There's no instruction to assemble.
+    Empty,
+    /// The instruction isn't known or isn't supported.
+    UnknownInstruction(String),
+    /// The operands don't match any form of the instruction.
+    InvalidOperands,
+    /// A register that can't be used where it was given.
+    InvalidRegister(String),
+    /// An immediate, offset or branch target that doesn't fit in the instruction.
+    OutOfRange(i64),
+    /// An offset or branch target that isn't aligned the way the instruction needs.
+    Misaligned(i64),
+    /// A symbol that isn't in the index.
+    UnknownSymbol(String),
+    /// The text couldn't be parsed, expected something else at a byte offset.
+    Expected(&'static str, usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => f.write_str("Nothing to assemble"),
+            Self::UnknownInstruction(name) => {
+                f.write_fmt(format_args!("Unknown instruction '{name}'"))
+            }
+            Self::InvalidOperands => f.write_str("Invalid operands for instruction"),
+            Self::InvalidRegister(name) => {
+                f.write_fmt(format_args!("Register '{name}' can't be used here"))
+            }
+            Self::OutOfRange(value) => f.write_fmt(format_args!("{} is out of range", hex(*value))),
+            Self::Misaligned(value) => f.write_fmt(format_args!("{} isn't aligned", hex(*value))),
+            Self::UnknownSymbol(name) => f.write_fmt(format_args!("Unknown symbol '{name}'")),
+            Self::Expected(what, offset) => {
+                f.write_fmt(format_args!("Expected {what} at position {offset}"))
+            }
+        }
+    }
+}
+
+fn hex(value: i64) -> String {
+    if value < 0 {
+        format!("-{:#x}", value.unsigned_abs())
+    } else {
+        format!("{value:#x}")
+    }
+}
+
+/// Whether `value` fits in a two's complement integer of `bits`.
+fn fits_signed(value: i64, bits: u32) -> bool {
+    let min = -1i64 << (bits - 1);
+    let max = (1i64 << (bits - 1)) - 1;
+    (min..=max).contains(&value)
+}
+
+/// Whether `value` fits in an unsigned integer of `bits`.
+fn fits_unsigned(value: i64, bits: u32) -> bool {
+    bits >= 64 || (0..1i64 << bits).contains(&value)
+}
+
+/// Offset from `addr` to `target`, which has to be a multiple of `align` and fit in a signed
+/// integer of `bits` after being scaled down by `align`.
+fn offset(target: i64, addr: usize, bits: u32, align: i64) -> Result<i64, Error> {
+    let offset = target.wrapping_sub(addr as i64);
+    if offset % align != 0 {
+        return Err(Error::Misaligned(target));
+    }
+
+    if !fits_signed(offset / align, bits) {
+        return Err(Error::OutOfRange(target));
+    }
+
+    Ok(offset)
+}
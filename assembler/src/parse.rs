@@ -0,0 +1,240 @@
+//! Operands shared by the assemblers.
+//!
+//! ```text
+//! <value>  = <term> {<ws> ('+' | '-') <ws> <term>}
+//! <term>   = <number> | <symbol>
+//!
+//! <number> = ['-'] <hex> | ['-'] <integer>
+//! <hex>     = '0' ('x' | 'X') {'A'..'F' | 'a'..'f' | '0'..'9'}+
+//! <integer> = {'0'..'9'}+
+//!
+//! <symbol> = '<' {<characters>}+ '>' # if known in index
+//! ```
+
+use crate::Error;
+use debugvault::Index;
+
+/// Cursor over an instruction's text.
+pub(crate) struct Parser<'src> {
+    /// Reference to input string.
+    src: &'src str,
+
+    /// Byte offset into input string.
+    offset: usize,
+
+    /// Symbols that `<name>` can refer to.
+    symbols: &'src Index,
+}
+
+impl<'src> Parser<'src> {
+    pub fn new(src: &'src str, symbols: &'src Index) -> Self {
+        Self {
+            src,
+            offset: 0,
+            symbols,
+        }
+    }
+
+    /// Where we are in the string.
+    fn src(&self) -> &'src str {
+        &self.src[self.offset..]
+    }
+
+    /// Error for when the next token isn't `what`.
+    pub fn expected(&self, what: &'static str) -> Error {
+        Error::Expected(what, self.offset)
+    }
+
+    /// Increments the stream whilst any whitespace is encountered.
+    pub fn ws(&mut self) {
+        let trimmed = self.src().trim_start();
+        self.offset = self.src.len() - trimmed.len();
+    }
+
+    /// Return the next character in the stream, skipping whitespace.
+    pub fn peek(&mut self) -> Option<char> {
+        self.ws();
+        self.src().chars().next()
+    }
+
+    /// Conditionally increments the stream if the next character matches `chr`.
+    pub fn eat(&mut self, chr: char) -> bool {
+        if self.peek() == Some(chr) {
+            self.offset += chr.len_utf8();
+            return true;
+        }
+
+        false
+    }
+
+    /// Increments the stream past `chr` or fails.
+    pub fn expect(&mut self, chr: char, what: &'static str) -> Result<(), Error> {
+        match self.eat(chr) {
+            true => Ok(()),
+            false => Err(self.expected(what)),
+        }
+    }
+
+    /// Reads a word made of alphanumerics, '_' and '.', without incrementing the stream.
+    fn peek_word(&mut self) -> &'src str {
+        self.ws();
+        let src = self.src();
+        let len = src
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+            .unwrap_or(src.len());
+        &src[..len]
+    }
+
+    /// Reads a lowercased word, incrementing the stream past it.
+    pub fn word(&mut self) -> Option<String> {
+        let word = self.peek_word();
+        if word.is_empty() {
+            return None;
+        }
+
+        self.offset += word.len();
+        Some(word.to_ascii_lowercase())
+    }
+
+    /// Conditionally increments the stream if the next word is `keyword`, ignoring case.
+    pub fn eat_word(&mut self, keyword: &str) -> bool {
+        if self.peek_word().eq_ignore_ascii_case(keyword) {
+            self.offset += keyword.len();
+            return true;
+        }
+
+        false
+    }
+
+    /// Reads a word if it's accepted by `f`, otherwise leaves the stream as is.
+    pub fn try_word<T>(&mut self, f: impl FnOnce(&str) -> Option<T>) -> Option<T> {
+        let word = self.peek_word();
+        if word.is_empty() {
+            return None;
+        }
+
+        let value = f(&word.to_ascii_lowercase())?;
+        self.offset += word.len();
+        Some(value)
+    }
+
+    /// Reads the mnemonic.
+    pub fn mnemonic(&mut self) -> Result<String, Error> {
+        self.word().ok_or(Error::Empty)
+    }
+
+    /// Checks that all of the input was consumed.
+    pub fn end(&mut self) -> Result<(), Error> {
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(self.expected("end of instruction")),
+        }
+    }
+
+    /// Reads a hexadecimal or decimal number. Numbers up to [`u64::MAX`] are accepted and
+    /// wrap around.
+    pub fn number(&mut self) -> Result<i64, Error> {
+        let negative = self.eat('-');
+        self.ws();
+
+        let src = self.src();
+        let (digits, radix, prefix) = match src.get(..2) {
+            Some("0x" | "0X") => (&src[2..], 16, 2),
+            _ => (src, 10, 0),
+        };
+
+        let len = digits.find(|c: char| !c.is_digit(radix)).unwrap_or(digits.len());
+        if len == 0 {
+            return Err(self.expected("number"));
+        }
+
+        let value = u64::from_str_radix(&digits[..len], radix)
+            .map_err(|_| Error::OutOfRange(i64::MAX))? as i64;
+
+        self.offset += prefix + len;
+        Ok(if negative {
+            value.wrapping_neg()
+        } else {
+            value
+        })
+    }
+
+    /// Whether the next token is a floating point number rather than an integer.
+    pub fn at_float(&mut self) -> bool {
+        self.ws();
+        let src = self.src();
+        let len = src
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '.' || c == '-'))
+            .unwrap_or(src.len());
+        let token = &src[..len];
+        token.contains('.') && !token.contains(['x', 'X'])
+    }
+
+    /// Reads a floating point number such as `1.5` or `-0.25`.
+    pub fn float(&mut self) -> Result<f64, Error> {
+        self.ws();
+        let src = self.src();
+        let len = src
+            .char_indices()
+            .find(|&(idx, c)| !(c.is_ascii_digit() || c == '.' || (idx == 0 && c == '-')))
+            .map_or(src.len(), |(idx, _)| idx);
+
+        let value = src[..len].parse().map_err(|_| self.expected("floating point number"))?;
+        self.offset += len;
+        Ok(value)
+    }
+
+    /// Reads the address of a symbol written as `<name>`.
+    pub fn symbol(&mut self) -> Result<i64, Error> {
+        self.expect('<', "symbol")?;
+
+        // symbols can contain generics, which have their own angle brackets
+        let src = self.src();
+        let mut depth = 1;
+        let len = src
+            .char_indices()
+            .find(|&(_, c)| {
+                match c {
+                    '<' => depth += 1,
+                    '>' => depth -= 1,
+                    _ => {}
+                }
+                depth == 0
+            })
+            .map(|(idx, _)| idx)
+            .ok_or_else(|| self.expected("'>'"))?;
+
+        let name = &src[..len];
+        self.offset += len + 1;
+
+        match self.symbols.get_func_by_name(name) {
+            Some(addr) => Ok(addr as i64),
+            None => Err(Error::UnknownSymbol(name.to_string())),
+        }
+    }
+
+    /// Reads a number or symbol.
+    pub fn term(&mut self) -> Result<i64, Error> {
+        match self.peek() {
+            Some('<') => self.symbol(),
+            _ => self.number(),
+        }
+    }
+
+    /// Reads terms added to or subtracted from each other, such as `<main> + 0x10`.
+    pub fn value(&mut self) -> Result<i64, Error> {
+        let mut value = self.term()?;
+
+        loop {
+            if self.eat('+') {
+                value = value.wrapping_add(self.term()?);
+            } else if self.eat('-') {
+                value = value.wrapping_sub(self.term()?);
+            } else {
+                break;
+            }
+        }
+
+        Ok(value)
+    }
+}
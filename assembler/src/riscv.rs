@@ -0,0 +1,1039 @@
+//! Assembler for RV64GC.
+//!
+//! Accepts the usual syntax such as `ld a0, 8(sp)` or `beq a0, a1, 0x1040`, as well as the
+//! forms the decoder prints, such as `ld a0, sp, 8` or `addi a0, 1` where the destination is
+//! also the first source. Instructions are never compressed implicitly, the `c.` mnemonics
+//! assemble to 2-byte instructions.
+
+use crate::parse::Parser;
+use crate::{fits_signed, fits_unsigned, offset, Error};
+use debugvault::Index;
+
+/// `nop`, used to pad instructions.
+pub const NOP: [u8; 4] = 0x00000013_u32.to_le_bytes();
+
+/// `c.nop`, used to pad when there are only 2 bytes left.
+pub const C_NOP: [u8; 2] = 0x0001_u16.to_le_bytes();
+
+#[rustfmt::skip]
+const REGISTERS: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp",
+    "t0", "t1", "t2",
+    "s0", "s1",
+    "a0", "a1", "a2", "a3", "a4", "a5", "a6", "a7",
+    "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11",
+    "t3", "t4", "t5", "t6",
+];
+
+#[rustfmt::skip]
+const FP_REGISTERS: [&str; 32] = [
+    "ft0", "ft1", "ft2", "ft3", "ft4", "ft5", "ft6", "ft7",
+    "fs0", "fs1",
+    "fa0", "fa1", "fa2", "fa3", "fa4", "fa5", "fa6", "fa7",
+    "fs2", "fs3", "fs4", "fs5", "fs6", "fs7", "fs8", "fs9", "fs10", "fs11",
+    "ft8", "ft9", "ft10", "ft11",
+];
+
+/// Register-register instructions with their `funct7`, `funct3` and opcode.
+#[rustfmt::skip]
+const R_TYPE: &[(&str, u32, u32, u32)] = &[
+    ("add", 0x00, 0, 0x33), ("sub", 0x20, 0, 0x33), ("sll", 0x00, 1, 0x33),
+    ("slt", 0x00, 2, 0x33), ("sltu", 0x00, 3, 0x33), ("xor", 0x00, 4, 0x33),
+    ("srl", 0x00, 5, 0x33), ("sra", 0x20, 5, 0x33), ("or", 0x00, 6, 0x33),
+    ("and", 0x00, 7, 0x33),
+    ("addw", 0x00, 0, 0x3b), ("subw", 0x20, 0, 0x3b), ("sllw", 0x00, 1, 0x3b),
+    ("srlw", 0x00, 5, 0x3b), ("sraw", 0x20, 5, 0x3b),
+    ("mul", 0x01, 0, 0x33), ("mulh", 0x01, 1, 0x33), ("mulhsu", 0x01, 2, 0x33),
+    ("mulhu", 0x01, 3, 0x33), ("div", 0x01, 4, 0x33), ("divu", 0x01, 5, 0x33),
+    ("rem", 0x01, 6, 0x33), ("remu", 0x01, 7, 0x33),
+    ("mulw", 0x01, 0, 0x3b), ("divw", 0x01, 4, 0x3b), ("divuw", 0x01, 5, 0x3b),
+    ("remw", 0x01, 6, 0x3b), ("remuw", 0x01, 7, 0x3b),
+];
+
+/// Register-immediate instructions with their `funct3` and opcode.
+#[rustfmt::skip]
+const I_TYPE: &[(&str, u32, u32)] = &[
+    ("addi", 0, 0x13), ("slti", 2, 0x13), ("sltiu", 3, 0x13), ("xori", 4, 0x13),
+    ("ori", 6, 0x13), ("andi", 7, 0x13), ("addiw", 0, 0x1b),
+];
+
+/// Shifts by an immediate with their `funct7`, `funct3`, opcode and the size of the shift.
+#[rustfmt::skip]
+const SHIFTS: &[(&str, u32, u32, u32, u32)] = &[
+    ("slli", 0x00, 1, 0x13, 6), ("srli", 0x00, 5, 0x13, 6), ("srai", 0x20, 5, 0x13, 6),
+    ("slliw", 0x00, 1, 0x1b, 5), ("srliw", 0x00, 5, 0x1b, 5), ("sraiw", 0x20, 5, 0x1b, 5),
+];
+
+/// Loads with their `funct3`, opcode and whether they load a floating point register.
+#[rustfmt::skip]
+const LOADS: &[(&str, u32, u32, bool)] = &[
+    ("lb", 0, 0x03, false), ("lh", 1, 0x03, false), ("lw", 2, 0x03, false),
+    ("ld", 3, 0x03, false), ("lbu", 4, 0x03, false), ("lhu", 5, 0x03, false),
+    ("lwu", 6, 0x03, false), ("flw", 2, 0x07, true), ("fld", 3, 0x07, true),
+];
+
+/// Stores with their `funct3`, opcode and whether they store a floating point register.
+#[rustfmt::skip]
+const STORES: &[(&str, u32, u32, bool)] = &[
+    ("sb", 0, 0x23, false), ("sh", 1, 0x23, false), ("sw", 2, 0x23, false),
+    ("sd", 3, 0x23, false), ("fsw", 2, 0x27, true), ("fsd", 3, 0x27, true),
+];
+
+/// Branches with their `funct3` and whether the registers are swapped.
+#[rustfmt::skip]
+const BRANCHES: &[(&str, u32, bool)] = &[
+    ("beq", 0, false), ("bne", 1, false), ("blt", 4, false), ("bge", 5, false),
+    ("bltu", 6, false), ("bgeu", 7, false),
+    ("bgt", 4, true), ("ble", 5, true), ("bgtu", 6, true), ("bleu", 7, true),
+];
+
+/// Branches comparing against zero with their `funct3` and whether zero is the first register.
+#[rustfmt::skip]
+const ZERO_BRANCHES: &[(&str, u32, bool)] = &[
+    ("beqz", 0, false), ("bnez", 1, false), ("blez", 5, true), ("bgez", 5, false),
+    ("bltz", 4, false), ("bgtz", 4, true),
+];
+
+/// Atomic memory operations with their `funct5`.
+#[rustfmt::skip]
+const ATOMICS: &[(&str, u32)] = &[
+    ("lr", 0x02), ("sc", 0x03), ("amoswap", 0x01), ("amoadd", 0x00), ("amoxor", 0x04),
+    ("amoand", 0x0c), ("amoor", 0x08), ("amomin", 0x10), ("amomax", 0x14),
+    ("amominu", 0x18), ("amomaxu", 0x1c),
+];
+
+/// Floating point instructions with their `funct5` and `funct3`, or `None` if it's the
+/// rounding mode.
+#[rustfmt::skip]
+const FP_OPS: &[(&str, u32, Option<u32>)] = &[
+    ("fadd", 0x00, None), ("fsub", 0x01, None), ("fmul", 0x02, None), ("fdiv", 0x03, None),
+    ("fsgnj", 0x04, Some(0)), ("fsgnjn", 0x04, Some(1)), ("fsgnjx", 0x04, Some(2)),
+    ("fmin", 0x05, Some(0)), ("fmax", 0x05, Some(1)),
+];
+
+/// Floating point comparisons with their `funct3`.
+const FP_COMPARISONS: &[(&str, u32)] = &[("feq", 2), ("flt", 1), ("fle", 0)];
+
+/// Fused multiply-add instructions with their opcode.
+const FP_FUSED: &[(&str, u32)] = &[
+    ("fmadd", 0x43),
+    ("fmsub", 0x47),
+    ("fnmsub", 0x4b),
+    ("fnmadd", 0x4f),
+];
+
+const ROUNDING_MODES: &[(&str, u32)] = &[
+    ("rne", 0),
+    ("rtz", 1),
+    ("rdn", 2),
+    ("rup", 3),
+    ("rmm", 4),
+    ("dyn", 7),
+];
+
+/// Integer types of conversions, as encoded in `rs2`.
+const INTEGER_TYPES: [&str; 4] = ["w", "wu", "l", "lu"];
+
+#[rustfmt::skip]
+const CSRS: &[(&str, u32)] = &[
+    ("fflags", 0x001), ("frm", 0x002), ("fcsr", 0x003),
+    ("cycle", 0xc00), ("time", 0xc01), ("instret", 0xc02),
+];
+
+/// Aliases for reading a CSR.
+const CSR_READS: &[(&str, u32)] = &[
+    ("rdcycle", 0xc00),
+    ("rdtime", 0xc01),
+    ("rdinstret", 0xc02),
+    ("frcsr", 0x003),
+    ("frrm", 0x002),
+    ("frflags", 0x001),
+];
+
+/// Aliases for writing a CSR.
+const CSR_WRITES: &[(&str, u32)] = &[("fscsr", 0x003), ("fsrm", 0x002), ("fsflags", 0x001)];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Register {
+    num: u32,
+    float: bool,
+}
+
+fn register(name: &str) -> Option<Register> {
+    let numbered = |prefix: &str| {
+        let num: u32 = name.strip_prefix(prefix)?.parse().ok()?;
+        (num < 32).then_some(num)
+    };
+
+    if let Some(num) = REGISTERS.iter().position(|&reg| reg == name) {
+        return Some(Register {
+            num: num as u32,
+            float: false,
+        });
+    }
+
+    if let Some(num) = FP_REGISTERS.iter().position(|&reg| reg == name) {
+        return Some(Register {
+            num: num as u32,
+            float: true,
+        });
+    }
+
+    match name {
+        "fp" => Some(Register {
+            num: 8,
+            float: false,
+        }),
+        _ => numbered("x")
+            .map(|num| Register { num, float: false })
+            .or_else(|| numbered("f").map(|num| Register { num, float: true })),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Reg(Register),
+    Imm(i64),
+    /// Memory operand `offset(base)`.
+    Mem(i64, u32),
+    /// Names of CSRs, rounding modes and fence sets.
+    Name(String),
+}
+
+/// Reads the base register of a memory operand, `(base)`.
+fn base(p: &mut Parser) -> Result<u32, Error> {
+    p.expect('(', "'('")?;
+    let base = match p.try_word(register) {
+        Some(Register { num, float: false }) => num,
+        _ => return Err(p.expected("base register")),
+    };
+    p.expect(')', "')'")?;
+    Ok(base)
+}
+
+fn operand(p: &mut Parser) -> Result<Operand, Error> {
+    match p.peek() {
+        Some('(') => Ok(Operand::Mem(0, base(p)?)),
+        Some('0'..='9' | '-' | '<') => {
+            let value = p.value()?;
+            match p.peek() {
+                Some('(') => Ok(Operand::Mem(value, base(p)?)),
+                _ => Ok(Operand::Imm(value)),
+            }
+        }
+        _ => {
+            let word = p.word().ok_or_else(|| p.expected("operand"))?;
+            match register(&word) {
+                Some(reg) => Ok(Operand::Reg(reg)),
+                None => Ok(Operand::Name(word)),
+            }
+        }
+    }
+}
+
+/// Assemble a single instruction at `addr`.
+pub fn assemble(src: &str, addr: usize, symbols: &Index) -> Result<Vec<u8>, Error> {
+    let mut p = Parser::new(src, symbols);
+    let mnemonic = p.mnemonic()?;
+
+    let mut ops = Vec::new();
+    if p.peek().is_some() {
+        loop {
+            ops.push(operand(&mut p)?);
+            if !p.eat(',') {
+                break;
+            }
+        }
+    }
+    p.end()?;
+
+    match mnemonic.strip_prefix("c.") {
+        Some(name) => compressed(name, &ops, addr).map(|half| half.to_le_bytes().to_vec()),
+        None => encode(&mnemonic, &ops, addr).map(|word| word.to_le_bytes().to_vec()),
+    }
+}
+
+/// Integer register.
+fn x(op: &Operand) -> Result<u32, Error> {
+    match op {
+        Operand::Reg(Register { num, float: false }) => Ok(*num),
+        _ => Err(Error::InvalidOperands),
+    }
+}
+
+/// Floating point register.
+fn f(op: &Operand) -> Result<u32, Error> {
+    match op {
+        Operand::Reg(Register { num, float: true }) => Ok(*num),
+        _ => Err(Error::InvalidOperands),
+    }
+}
+
+/// Extracts bits `hi` to `lo` of `value`.
+fn bits(value: i64, hi: u32, lo: u32) -> u32 {
+    (value >> lo) as u32 & ((1 << (hi - lo + 1)) - 1)
+}
+
+fn r_type(funct7: u32, rs2: u32, rs1: u32, funct3: u32, rd: u32, opcode: u32) -> u32 {
+    funct7 << 25 | rs2 << 20 | rs1 << 15 | funct3 << 12 | rd << 7 | opcode
+}
+
+fn i_type(imm: i64, rs1: u32, funct3: u32, rd: u32, opcode: u32) -> Result<u32, Error> {
+    if !fits_signed(imm, 12) {
+        return Err(Error::OutOfRange(imm));
+    }
+    Ok(bits(imm, 11, 0) << 20 | rs1 << 15 | funct3 << 12 | rd << 7 | opcode)
+}
+
+fn s_type(imm: i64, rs2: u32, rs1: u32, funct3: u32, opcode: u32) -> Result<u32, Error> {
+    if !fits_signed(imm, 12) {
+        return Err(Error::OutOfRange(imm));
+    }
+    Ok(bits(imm, 11, 5) << 25
+        | rs2 << 20
+        | rs1 << 15
+        | funct3 << 12
+        | bits(imm, 4, 0) << 7
+        | opcode)
+}
+
+fn b_type(target: i64, addr: usize, rs1: u32, rs2: u32, funct3: u32) -> Result<u32, Error> {
+    let imm = offset(target, addr, 12, 2)?;
+    Ok(bits(imm, 12, 12) << 31
+        | bits(imm, 10, 5) << 25
+        | rs2 << 20
+        | rs1 << 15
+        | funct3 << 12
+        | bits(imm, 4, 1) << 8
+        | bits(imm, 11, 11) << 7
+        | 0x63)
+}
+
+fn j_type(target: i64, addr: usize, rd: u32) -> Result<u32, Error> {
+    let imm = offset(target, addr, 20, 2)?;
+    Ok(bits(imm, 20, 20) << 31
+        | bits(imm, 10, 1) << 21
+        | bits(imm, 11, 11) << 20
+        | bits(imm, 19, 12) << 12
+        | rd << 7
+        | 0x6f)
+}
+
+/// Immediate of `lui` and `auipc`, which is either the upper 20 bits or a negative number.
+fn upper(imm: i64) -> Result<u32, Error> {
+    match fits_unsigned(imm, 20) || fits_signed(imm, 20) {
+        true => Ok(bits(imm, 19, 0)),
+        false => Err(Error::OutOfRange(imm)),
+    }
+}
+
+/// Address of a load or store, either `offset(base)` or `base, offset` like the decoder prints.
+fn address(ops: &[Operand]) -> Result<(i64, u32), Error> {
+    match ops {
+        [Operand::Mem(offset, base)] => Ok((*offset, *base)),
+        [base, Operand::Imm(offset)] => Ok((*offset, x(base)?)),
+        _ => Err(Error::InvalidOperands),
+    }
+}
+
+fn csr(op: &Operand) -> Result<u32, Error> {
+    match op {
+        Operand::Imm(imm) if fits_unsigned(*imm, 12) => Ok(*imm as u32),
+        Operand::Imm(imm) => Err(Error::OutOfRange(*imm)),
+        Operand::Name(name) => match CSRS.iter().find(|(csr, _)| csr == name) {
+            Some(&(_, csr)) => Ok(csr),
+            None => Err(Error::InvalidRegister(name.clone())),
+        },
+        _ => Err(Error::InvalidOperands),
+    }
+}
+
+/// Predecessor or successor set of a fence, such as `rw`.
+fn fence_set(op: &Operand) -> Result<u32, Error> {
+    let Operand::Name(name) = op else {
+        return Err(Error::InvalidOperands);
+    };
+
+    name.chars().try_fold(0, |set, chr| match chr {
+        'i' => Ok(set | 0b1000),
+        'o' => Ok(set | 0b0100),
+        'r' => Ok(set | 0b0010),
+        'w' => Ok(set | 0b0001),
+        _ => Err(Error::InvalidOperands),
+    })
+}
+
+/// Splits off the optional rounding mode from the operands of a floating point instruction.
+fn rounding_mode(ops: &[Operand], default: u32) -> Result<(&[Operand], u32), Error> {
+    match ops.split_last() {
+        Some((Operand::Name(name), ops)) => {
+            match ROUNDING_MODES.iter().find(|(mode, _)| mode == name) {
+                Some(&(_, mode)) => Ok((ops, mode)),
+                None => Err(Error::InvalidOperands),
+            }
+        }
+        _ => Ok((ops, default)),
+    }
+}
+
+fn encode(mnemonic: &str, ops: &[Operand], addr: usize) -> Result<u32, Error> {
+    use Operand::*;
+
+    if let Some(&(_, funct7, funct3, opcode)) = R_TYPE.iter().find(|op| op.0 == mnemonic) {
+        let (rd, rs1, rs2) = match ops {
+            [rd, rs1, rs2] => (x(rd)?, x(rs1)?, x(rs2)?),
+            // the decoder leaves out the first source when it's the destination
+            [rd, rs2] => (x(rd)?, x(rd)?, x(rs2)?),
+            _ => return Err(Error::InvalidOperands),
+        };
+        return Ok(r_type(funct7, rs2, rs1, funct3, rd, opcode));
+    }
+
+    if let Some(&(_, funct3, opcode)) = I_TYPE.iter().find(|op| op.0 == mnemonic) {
+        let (rd, rs1, imm) = match ops {
+            [rd, rs1, Imm(imm)] => (x(rd)?, x(rs1)?, *imm),
+            [rd, Imm(imm)] => (x(rd)?, x(rd)?, *imm),
+            _ => return Err(Error::InvalidOperands),
+        };
+        return i_type(imm, rs1, funct3, rd, opcode);
+    }
+
+    if let Some(&(_, funct7, funct3, opcode, size)) = SHIFTS.iter().find(|op| op.0 == mnemonic) {
+        let (rd, rs1, shamt) = match ops {
+            [rd, rs1, Imm(shamt)] => (x(rd)?, x(rs1)?, *shamt),
+            [rd, Imm(shamt)] => (x(rd)?, x(rd)?, *shamt),
+            _ => return Err(Error::InvalidOperands),
+        };
+        if !fits_unsigned(shamt, size) {
+            return Err(Error::OutOfRange(shamt));
+        }
+        return Ok(r_type(funct7, shamt as u32, rs1, funct3, rd, opcode));
+    }
+
+    if let Some(&(_, funct3, opcode, float)) = LOADS.iter().find(|op| op.0 == mnemonic) {
+        let [rd, ops @ ..] = ops else {
+            return Err(Error::InvalidOperands);
+        };
+        let rd = if float { f(rd)? } else { x(rd)? };
+        let (imm, base) = address(ops)?;
+        return i_type(imm, base, funct3, rd, opcode);
+    }
+
+    if let Some(&(_, funct3, opcode, float)) = STORES.iter().find(|op| op.0 == mnemonic) {
+        let [rs2, ops @ ..] = ops else {
+            return Err(Error::InvalidOperands);
+        };
+        let rs2 = if float { f(rs2)? } else { x(rs2)? };
+        let (imm, base) = address(ops)?;
+        return s_type(imm, rs2, base, funct3, opcode);
+    }
+
+    if let Some(&(_, funct3, swap)) = BRANCHES.iter().find(|op| op.0 == mnemonic) {
+        let [rs1, rs2, Imm(target)] = ops else {
+            return Err(Error::InvalidOperands);
+        };
+        let (rs1, rs2) = if swap {
+            (x(rs2)?, x(rs1)?)
+        } else {
+            (x(rs1)?, x(rs2)?)
+        };
+        return b_type(*target, addr, rs1, rs2, funct3);
+    }
+
+    if let Some(&(_, funct3, zero_first)) = ZERO_BRANCHES.iter().find(|op| op.0 == mnemonic) {
+        let [rs, Imm(target)] = ops else {
+            return Err(Error::InvalidOperands);
+        };
+        let (rs1, rs2) = if zero_first { (0, x(rs)?) } else { (x(rs)?, 0) };
+        return b_type(*target, addr, rs1, rs2, funct3);
+    }
+
+    if mnemonic.starts_with("lr.") || mnemonic.starts_with("sc.") || mnemonic.starts_with("amo") {
+        return atomic(mnemonic, ops);
+    }
+
+    if mnemonic.starts_with('f') && mnemonic.contains('.') && mnemonic != "fence.i" {
+        return fp(mnemonic, ops);
+    }
+
+    if let Some(&(_, csr)) = CSR_READS.iter().find(|op| op.0 == mnemonic) {
+        let [rd] = ops else {
+            return Err(Error::InvalidOperands);
+        };
+        return Ok(csr << 20 | 2 << 12 | x(rd)? << 7 | 0x73);
+    }
+
+    if let Some(&(_, csr)) = CSR_WRITES.iter().find(|op| op.0 == mnemonic) {
+        let (rd, rs) = match ops {
+            [rs] => (0, x(rs)?),
+            [rd, rs] => (x(rd)?, x(rs)?),
+            _ => return Err(Error::InvalidOperands),
+        };
+        return Ok(csr << 20 | rs << 15 | 1 << 12 | rd << 7 | 0x73);
+    }
+
+    match (mnemonic, ops) {
+        ("lui", [rd, Imm(imm)]) => Ok(upper(*imm)? << 12 | x(rd)? << 7 | 0x37),
+        // like the decoder prints it, the address formed rather than the upper immediate
+        ("auipc", [rd, Imm(target)]) => {
+            let off = target.wrapping_sub(addr as i64);
+            if off & 0xfff != 0 || !fits_signed(off >> 12, 20) {
+                return Err(Error::OutOfRange(*target));
+            }
+            Ok(bits(off >> 12, 19, 0) << 12 | x(rd)? << 7 | 0x17)
+        }
+        ("jal", [Imm(target)]) => j_type(*target, addr, 1),
+        ("jal", [rd, Imm(target)]) => j_type(*target, addr, x(rd)?),
+        ("j", [Imm(target)]) => j_type(*target, addr, 0),
+        ("jalr" | "jr", _) => {
+            let link = if mnemonic == "jalr" { 1 } else { 0 };
+            let (rd, imm, rs1) = match ops {
+                [rs1] => (link, 0, x(rs1)?),
+                [rd, Mem(imm, rs1)] => (x(rd)?, *imm, *rs1),
+                [rd, rs1] => (x(rd)?, 0, x(rs1)?),
+                [rd, rs1, Imm(imm)] => (x(rd)?, *imm, x(rs1)?),
+                _ => return Err(Error::InvalidOperands),
+            };
+            i_type(imm, rs1, 0, rd, 0x67)
+        }
+        ("ret", []) => Ok(0x00008067),
+        ("nop", []) => Ok(0x00000013),
+        ("li", [rd, Imm(imm)]) => {
+            if fits_signed(*imm, 12) {
+                return i_type(*imm, 0, 0, x(rd)?, 0x13);
+            }
+
+            // larger values have to be loaded with `lui` alone
+            match *imm & 0xfff == 0 && fits_signed(*imm, 32) {
+                true => Ok(bits(*imm, 31, 12) << 12 | x(rd)? << 7 | 0x37),
+                false => Err(Error::OutOfRange(*imm)),
+            }
+        }
+        ("mv", [rd, rs]) => i_type(0, x(rs)?, 0, x(rd)?, 0x13),
+        ("not", [rd, rs]) => i_type(-1, x(rs)?, 4, x(rd)?, 0x13),
+        ("neg", [rd, rs]) => Ok(r_type(0x20, x(rs)?, 0, 0, x(rd)?, 0x33)),
+        ("negw", [rd, rs]) => Ok(r_type(0x20, x(rs)?, 0, 0, x(rd)?, 0x3b)),
+        ("sext.w", [rd, rs]) => i_type(0, x(rs)?, 0, x(rd)?, 0x1b),
+        ("seqz", [rd, rs]) => i_type(1, x(rs)?, 3, x(rd)?, 0x13),
+        ("snez", [rd, rs]) => Ok(r_type(0, x(rs)?, 0, 3, x(rd)?, 0x33)),
+        ("sltz", [rd, rs]) => Ok(r_type(0, 0, x(rs)?, 2, x(rd)?, 0x33)),
+        ("sgtz", [rd, rs]) => Ok(r_type(0, x(rs)?, 0, 2, x(rd)?, 0x33)),
+        ("ecall", []) => Ok(0x00000073),
+        ("ebreak", []) => Ok(0x00100073),
+        ("fence", []) => Ok(0x0ff0000f),
+        ("fence", [pred, succ]) => Ok(fence_set(pred)? << 24 | fence_set(succ)? << 20 | 0x0f),
+        ("fence.i", []) => Ok(0x0000100f),
+        ("csrrw" | "csrrs" | "csrrc", [rd, csr_op, rs1]) => {
+            let funct3 = match mnemonic {
+                "csrrw" => 1,
+                "csrrs" => 2,
+                _ => 3,
+            };
+            Ok(csr(csr_op)? << 20 | x(rs1)? << 15 | funct3 << 12 | x(rd)? << 7 | 0x73)
+        }
+        ("csrrwi" | "csrrsi" | "csrrci", [rd, csr_op, Imm(imm)]) => {
+            let funct3 = match mnemonic {
+                "csrrwi" => 5,
+                "csrrsi" => 6,
+                _ => 7,
+            };
+            if !fits_unsigned(*imm, 5) {
+                return Err(Error::OutOfRange(*imm));
+            }
+            Ok(csr(csr_op)? << 20 | (*imm as u32) << 15 | funct3 << 12 | x(rd)? << 7 | 0x73)
+        }
+        ("csrr", [rd, csr_op]) => Ok(csr(csr_op)? << 20 | 2 << 12 | x(rd)? << 7 | 0x73),
+        ("csrw" | "csrs" | "csrc", [csr_op, rs1]) => {
+            let funct3 = match mnemonic {
+                "csrw" => 1,
+                "csrs" => 2,
+                _ => 3,
+            };
+            Ok(csr(csr_op)? << 20 | x(rs1)? << 15 | funct3 << 12 | 0x73)
+        }
+        ("csrwi" | "csrsi" | "csrci", [csr_op, Imm(imm)]) => {
+            let funct3 = match mnemonic {
+                "csrwi" => 5,
+                "csrsi" => 6,
+                _ => 7,
+            };
+            if !fits_unsigned(*imm, 5) {
+                return Err(Error::OutOfRange(*imm));
+            }
+            Ok(csr(csr_op)? << 20 | (*imm as u32) << 15 | funct3 << 12 | 0x73)
+        }
+        _ => Err(Error::UnknownInstruction(mnemonic.to_string())),
+    }
+}
+
+/// Load-reserved, store-conditional and atomic memory operations, such as `amoadd.w.aqrl`.
+fn atomic(mnemonic: &str, ops: &[Operand]) -> Result<u32, Error> {
+    use Operand::*;
+
+    let (name, ordering) = match mnemonic.rsplit_once('.') {
+        Some((name, "aq")) => (name, 0b10),
+        Some((name, "rl")) => (name, 0b01),
+        Some((name, "aqrl")) => (name, 0b11),
+        _ => (mnemonic, 0b00),
+    };
+
+    let (name, funct3) = match name.rsplit_once('.') {
+        Some((name, "w")) => (name, 2),
+        Some((name, "d")) => (name, 3),
+        _ => return Err(Error::UnknownInstruction(mnemonic.to_string())),
+    };
+
+    let funct5 = match ATOMICS.iter().find(|op| op.0 == name) {
+        Some(&(_, funct5)) => funct5,
+        None => return Err(Error::UnknownInstruction(mnemonic.to_string())),
+    };
+
+    let (rd, rs2, rs1) = match ops {
+        [rd, Mem(0, rs1)] if name == "lr" => (x(rd)?, 0, *rs1),
+        [rd, rs2, Mem(0, rs1)] if name != "lr" => (x(rd)?, x(rs2)?, *rs1),
+        _ => return Err(Error::InvalidOperands),
+    };
+
+    Ok(r_type(funct5 << 2 | ordering, rs2, rs1, funct3, rd, 0x2f))
+}
+
+/// Single and double precision floating point instructions.
+fn fp(mnemonic: &str, ops: &[Operand]) -> Result<u32, Error> {
+    let unknown = || Error::UnknownInstruction(mnemonic.to_string());
+    let fmt = |name: &str| match name {
+        "s" => Ok(0),
+        "d" => Ok(1),
+        _ => Err(unknown()),
+    };
+
+    let parts: Vec<&str> = mnemonic.split('.').collect();
+    match parts[..] {
+        // conversions between integers and floating point
+        ["fcvt", int, float] if INTEGER_TYPES.contains(&int) => {
+            let (ops, rm) = rounding_mode(ops, 7)?;
+            let [rd, rs1] = ops else {
+                return Err(Error::InvalidOperands);
+            };
+            let rs2 = INTEGER_TYPES.iter().position(|&ty| ty == int).unwrap_or_default();
+            let funct7 = 0x18 << 2 | fmt(float)?;
+            Ok(r_type(funct7, rs2 as u32, f(rs1)?, rm, x(rd)?, 0x53))
+        }
+        ["fcvt", float, int] if INTEGER_TYPES.contains(&int) => {
+            // converting 32-bit integers to doubles is exact, so it doesn't round by default
+            let exact = float == "d" && int.starts_with('w');
+            let (ops, rm) = rounding_mode(ops, if exact { 0 } else { 7 })?;
+            let [rd, rs1] = ops else {
+                return Err(Error::InvalidOperands);
+            };
+            let rs2 = INTEGER_TYPES.iter().position(|&ty| ty == int).unwrap_or_default();
+            let funct7 = 0x1a << 2 | fmt(float)?;
+            Ok(r_type(funct7, rs2 as u32, x(rs1)?, rm, f(rd)?, 0x53))
+        }
+        ["fcvt", to, from] => {
+            let (to, from) = (fmt(to)?, fmt(from)?);
+            if to == from {
+                return Err(unknown());
+            }
+            let (ops, rm) = rounding_mode(ops, if to == 1 { 0 } else { 7 })?;
+            let [rd, rs1] = ops else {
+                return Err(Error::InvalidOperands);
+            };
+            Ok(r_type(0x08 << 2 | to, from, f(rs1)?, rm, f(rd)?, 0x53))
+        }
+        ["fmv", "x", float] => {
+            let [rd, rs1] = ops else {
+                return Err(Error::InvalidOperands);
+            };
+            let fmt = match float {
+                "w" => 0,
+                _ => fmt(float)?,
+            };
+            Ok(r_type(0x1c << 2 | fmt, 0, f(rs1)?, 0, x(rd)?, 0x53))
+        }
+        ["fmv", float, "x"] => {
+            let [rd, rs1] = ops else {
+                return Err(Error::InvalidOperands);
+            };
+            let fmt = match float {
+                "w" => 0,
+                _ => fmt(float)?,
+            };
+            Ok(r_type(0x1e << 2 | fmt, 0, x(rs1)?, 0, f(rd)?, 0x53))
+        }
+        ["fmv" | "fabs" | "fneg", float] => {
+            let [rd, rs] = ops else {
+                return Err(Error::InvalidOperands);
+            };
+            let funct3 = match parts[0] {
+                "fmv" => 0,
+                "fneg" => 1,
+                _ => 2,
+            };
+            let rs = f(rs)?;
+            Ok(r_type(
+                0x04 << 2 | fmt(float)?,
+                rs,
+                rs,
+                funct3,
+                f(rd)?,
+                0x53,
+            ))
+        }
+        ["fsqrt", float] => {
+            let (ops, rm) = rounding_mode(ops, 7)?;
+            let [rd, rs1] = ops else {
+                return Err(Error::InvalidOperands);
+            };
+            Ok(r_type(
+                0x0b << 2 | fmt(float)?,
+                0,
+                f(rs1)?,
+                rm,
+                f(rd)?,
+                0x53,
+            ))
+        }
+        ["fclass", float] => {
+            let [rd, rs1] = ops else {
+                return Err(Error::InvalidOperands);
+            };
+            Ok(r_type(0x1c << 2 | fmt(float)?, 0, f(rs1)?, 1, x(rd)?, 0x53))
+        }
+        [name, float] => {
+            let fmt = fmt(float)?;
+
+            if let Some(&(_, funct5, funct3)) = FP_OPS.iter().find(|op| op.0 == name) {
+                let (ops, rm) = match funct3 {
+                    Some(funct3) => (ops, funct3),
+                    None => rounding_mode(ops, 7)?,
+                };
+                let [rd, rs1, rs2] = ops else {
+                    return Err(Error::InvalidOperands);
+                };
+                return Ok(r_type(
+                    funct5 << 2 | fmt,
+                    f(rs2)?,
+                    f(rs1)?,
+                    rm,
+                    f(rd)?,
+                    0x53,
+                ));
+            }
+
+            if let Some(&(_, funct3)) = FP_COMPARISONS.iter().find(|op| op.0 == name) {
+                let [rd, rs1, rs2] = ops else {
+                    return Err(Error::InvalidOperands);
+                };
+                return Ok(r_type(
+                    0x14 << 2 | fmt,
+                    f(rs2)?,
+                    f(rs1)?,
+                    funct3,
+                    x(rd)?,
+                    0x53,
+                ));
+            }
+
+            if let Some(&(_, opcode)) = FP_FUSED.iter().find(|op| op.0 == name) {
+                let (ops, rm) = rounding_mode(ops, 7)?;
+                let [rd, rs1, rs2, rs3] = ops else {
+                    return Err(Error::InvalidOperands);
+                };
+                let funct7 = f(rs3)? << 2 | fmt;
+                return Ok(r_type(funct7, f(rs2)?, f(rs1)?, rm, f(rd)?, opcode));
+            }
+
+            Err(unknown())
+        }
+        _ => Err(unknown()),
+    }
+}
+
+/// Register `x8` to `x15` as used by most compressed instructions.
+fn compressed_register(reg: u32) -> Result<u16, Error> {
+    match reg {
+        8..=15 => Ok(reg as u16 - 8),
+        _ => Err(Error::InvalidOperands),
+    }
+}
+
+/// Immediate of `bits` that's a multiple of `scale`.
+fn scaled(imm: i64, bits: u32, scale: i64, signed: bool) -> Result<i64, Error> {
+    if imm % scale != 0 {
+        return Err(Error::Misaligned(imm));
+    }
+
+    match signed {
+        true if fits_signed(imm, bits) => Ok(imm),
+        false if fits_unsigned(imm, bits) => Ok(imm),
+        _ => Err(Error::OutOfRange(imm)),
+    }
+}
+
+/// Extracts bits `hi` to `lo` of `value` for a compressed instruction.
+fn cbits(value: i64, hi: u32, lo: u32) -> u16 {
+    bits(value, hi, lo) as u16
+}
+
+/// Compressed instructions that the decoder shows with the destination repeated as a source.
+#[rustfmt::skip]
+const REPEATED_DESTINATION: [&str; 15] = [
+    "addi", "addiw", "slli", "srli", "srai", "andi", "add", "sub", "xor", "or", "and", "subw",
+    "addw", "beqz", "bnez",
+];
+
+/// Compressed instructions, given without their `c.` prefix.
+fn compressed(name: &str, ops: &[Operand], addr: usize) -> Result<u16, Error> {
+    use Operand::*;
+
+    // the decoder repeats the destination as the first source
+    let ops = match ops {
+        [Reg(rd), Reg(rs1), rest @ ..]
+            if rd == rs1 && !rest.is_empty() && REPEATED_DESTINATION.contains(&name) =>
+        {
+            let mut ops = vec![Reg(*rd)];
+            ops.extend_from_slice(rest);
+            ops
+        }
+        _ => ops.to_vec(),
+    };
+
+    match (name, &ops[..]) {
+        ("nop", []) => Ok(0x0001),
+        ("ebreak", []) => Ok(0x9002),
+        ("addi" | "addiw" | "li", [rd, Imm(imm)]) => {
+            let funct3 = match name {
+                "addi" => 0b000,
+                "addiw" => 0b001,
+                _ => 0b010,
+            };
+            let rd = x(rd)? as u16;
+            let imm = scaled(*imm, 6, 1, true)?;
+            Ok(funct3 << 13 | cbits(imm, 5, 5) << 12 | rd << 7 | cbits(imm, 4, 0) << 2 | 0b01)
+        }
+        ("lui", [rd, Imm(imm)]) => {
+            let rd = x(rd)? as u16;
+            if rd == 0 || rd == 2 {
+                return Err(Error::InvalidOperands);
+            }
+
+            // either the sign-extended 6-bit immediate or the upper 20 bits it expands to
+            let imm = match *imm {
+                imm @ -32..=31 if imm != 0 => imm,
+                imm @ (0x1..=0x1f | 0xfffe0..=0xfffff) => imm,
+                imm => return Err(Error::OutOfRange(imm)),
+            };
+            Ok(0b011 << 13 | cbits(imm, 5, 5) << 12 | rd << 7 | cbits(imm, 4, 0) << 2 | 0b01)
+        }
+        ("addi16sp", [Imm(imm)])
+        | (
+            "addi16sp",
+            [Reg(Register {
+                num: 2,
+                float: false,
+            }), Imm(imm)],
+        ) => {
+            let imm = scaled(*imm, 10, 16, true)?;
+            Ok(0b011 << 13
+                | cbits(imm, 9, 9) << 12
+                | 2 << 7
+                | cbits(imm, 4, 4) << 6
+                | cbits(imm, 6, 6) << 5
+                | cbits(imm, 8, 7) << 3
+                | cbits(imm, 5, 5) << 2
+                | 0b01)
+        }
+        ("addi4spn", [rd, Imm(imm)])
+        | (
+            "addi4spn",
+            [rd, Reg(Register {
+                num: 2,
+                float: false,
+            }), Imm(imm)],
+        ) => {
+            let rd = compressed_register(x(rd)?)?;
+            let imm = scaled(*imm, 10, 4, false)?;
+            if imm == 0 {
+                return Err(Error::OutOfRange(imm));
+            }
+            Ok(cbits(imm, 5, 4) << 11
+                | cbits(imm, 9, 6) << 7
+                | cbits(imm, 2, 2) << 6
+                | cbits(imm, 3, 3) << 5
+                | rd << 2)
+        }
+        ("srli" | "srai" | "andi", [rd, Imm(imm)]) => {
+            let rd = compressed_register(x(rd)?)?;
+            let (funct2, imm) = match name {
+                "srli" => (0b00, scaled(*imm, 6, 1, false)?),
+                "srai" => (0b01, scaled(*imm, 6, 1, false)?),
+                _ => (0b10, scaled(*imm, 6, 1, true)?),
+            };
+            Ok(0b100 << 13
+                | cbits(imm, 5, 5) << 12
+                | funct2 << 10
+                | rd << 7
+                | cbits(imm, 4, 0) << 2
+                | 0b01)
+        }
+        ("sub" | "xor" | "or" | "and" | "subw" | "addw", [rd, rs2]) => {
+            let rd = compressed_register(x(rd)?)?;
+            let rs2 = compressed_register(x(rs2)?)?;
+            let (funct6, funct2) = match name {
+                "sub" => (0b100011, 0b00),
+                "xor" => (0b100011, 0b01),
+                "or" => (0b100011, 0b10),
+                "and" => (0b100011, 0b11),
+                "subw" => (0b100111, 0b00),
+                _ => (0b100111, 0b01),
+            };
+            Ok(funct6 << 10 | rd << 7 | funct2 << 5 | rs2 << 2 | 0b01)
+        }
+        ("j", [Imm(target)]) => {
+            let imm = offset(*target, addr, 11, 2)?;
+            Ok(0b101 << 13
+                | cbits(imm, 11, 11) << 12
+                | cbits(imm, 4, 4) << 11
+                | cbits(imm, 9, 8) << 9
+                | cbits(imm, 10, 10) << 8
+                | cbits(imm, 6, 6) << 7
+                | cbits(imm, 7, 7) << 6
+                | cbits(imm, 3, 1) << 3
+                | cbits(imm, 5, 5) << 2
+                | 0b01)
+        }
+        ("beqz" | "bnez", [rs1, Imm(target)]) => {
+            let rs1 = compressed_register(x(rs1)?)?;
+            let imm = offset(*target, addr, 8, 2)?;
+            let funct3 = if name == "beqz" { 0b110 } else { 0b111 };
+            Ok(funct3 << 13
+                | cbits(imm, 8, 8) << 12
+                | cbits(imm, 4, 3) << 10
+                | rs1 << 7
+                | cbits(imm, 7, 6) << 5
+                | cbits(imm, 2, 1) << 3
+                | cbits(imm, 5, 5) << 2
+                | 0b01)
+        }
+        ("slli", [rd, Imm(imm)]) => {
+            let rd = x(rd)? as u16;
+            let imm = scaled(*imm, 6, 1, false)?;
+            Ok(cbits(imm, 5, 5) << 12 | rd << 7 | cbits(imm, 4, 0) << 2 | 0b10)
+        }
+        ("jr" | "jalr", [rs1]) => {
+            let rs1 = x(rs1)? as u16;
+            if rs1 == 0 {
+                return Err(Error::InvalidOperands);
+            }
+            let link = if name == "jalr" { 1 } else { 0 };
+            Ok(0b100 << 13 | link << 12 | rs1 << 7 | 0b10)
+        }
+        ("mv" | "add", [rd, rs2]) => {
+            let (rd, rs2) = (x(rd)? as u16, x(rs2)? as u16);
+            if rs2 == 0 {
+                return Err(Error::InvalidOperands);
+            }
+            let add = if name == "add" { 1 } else { 0 };
+            Ok(0b100 << 13 | add << 12 | rd << 7 | rs2 << 2 | 0b10)
+        }
+        ("lwsp" | "ldsp" | "fldsp", [rd, mem @ ..]) => {
+            let (imm, base) = match mem {
+                [Mem(imm, base)] => (*imm, *base),
+                [Imm(imm)] => (*imm, 2),
+                _ => return Err(Error::InvalidOperands),
+            };
+            if base != 2 {
+                return Err(Error::InvalidOperands);
+            }
+
+            let (rd, funct3, low) = match name {
+                "lwsp" => {
+                    let imm = scaled(imm, 8, 4, false)?;
+                    (x(rd)?, 0b010, cbits(imm, 4, 2) << 4 | cbits(imm, 7, 6) << 2)
+                }
+                "ldsp" => {
+                    let imm = scaled(imm, 9, 8, false)?;
+                    (x(rd)?, 0b011, cbits(imm, 4, 3) << 5 | cbits(imm, 8, 6) << 2)
+                }
+                _ => {
+                    let imm = scaled(imm, 9, 8, false)?;
+                    (f(rd)?, 0b001, cbits(imm, 4, 3) << 5 | cbits(imm, 8, 6) << 2)
+                }
+            };
+            if rd == 0 && name != "fldsp" {
+                return Err(Error::InvalidOperands);
+            }
+
+            Ok(funct3 << 13 | cbits(imm, 5, 5) << 12 | (rd as u16) << 7 | low | 0b10)
+        }
+        ("swsp" | "sdsp" | "fsdsp", [rs2, mem @ ..]) => {
+            let (imm, base) = match mem {
+                [Mem(imm, base)] => (*imm, *base),
+                [Imm(imm)] => (*imm, 2),
+                _ => return Err(Error::InvalidOperands),
+            };
+            if base != 2 {
+                return Err(Error::InvalidOperands);
+            }
+
+            let (rs2, funct3, imm) = match name {
+                "swsp" => {
+                    let imm = scaled(imm, 8, 4, false)?;
+                    (
+                        x(rs2)?,
+                        0b110,
+                        cbits(imm, 5, 2) << 9 | cbits(imm, 7, 6) << 7,
+                    )
+                }
+                "sdsp" => {
+                    let imm = scaled(imm, 9, 8, false)?;
+                    (
+                        x(rs2)?,
+                        0b111,
+                        cbits(imm, 5, 3) << 10 | cbits(imm, 8, 6) << 7,
+                    )
+                }
+                _ => {
+                    let imm = scaled(imm, 9, 8, false)?;
+                    (
+                        f(rs2)?,
+                        0b101,
+                        cbits(imm, 5, 3) << 10 | cbits(imm, 8, 6) << 7,
+                    )
+                }
+            };
+
+            Ok(funct3 << 13 | imm | (rs2 as u16) << 2 | 0b10)
+        }
+        ("lw" | "ld" | "fld" | "sw" | "sd" | "fsd", [rs2, mem @ ..]) => {
+            let (imm, base) = address(mem)?;
+            let base = compressed_register(base)?;
+            let float = name.starts_with('f');
+            let rs2 = compressed_register(if float { f(rs2)? } else { x(rs2)? })?;
+
+            let (funct3, imm) = match name {
+                "lw" | "sw" => {
+                    let imm = scaled(imm, 7, 4, false)?;
+                    let funct3 = if name == "lw" { 0b010 } else { 0b110 };
+                    (
+                        funct3,
+                        cbits(imm, 5, 3) << 10 | cbits(imm, 2, 2) << 6 | cbits(imm, 6, 6) << 5,
+                    )
+                }
+                _ => {
+                    let imm = scaled(imm, 8, 8, false)?;
+                    let funct3 = match name {
+                        "ld" => 0b011,
+                        "sd" => 0b111,
+                        "fld" => 0b001,
+                        _ => 0b101,
+                    };
+                    (funct3, cbits(imm, 5, 3) << 10 | cbits(imm, 7, 6) << 5)
+                }
+            };
+
+            Ok(funct3 << 13 | imm | base << 7 | rs2 << 2)
+        }
+        _ => Err(Error::UnknownInstruction(format!("c.{name}"))),
+    }
+}
@@ -0,0 +1,356 @@
+#![cfg(test)]
+
+use crate::{aarch64, riscv, x86_64, Error};
+use debugvault::Index;
+use decoder::{Decodable, Decoded, Reader, ToTokens};
+
+/// Instructions as encoded by other assemblers and how they're displayed, which the assembler
+/// must turn back into the same instruction.
+const X86_64_ROUND_TRIP: &[(&[u8], &str)] = &[
+    (&[0x55], "push rbp"),
+    (&[0x48, 0x89, 0xe5], "mov rbp, rsp"),
+    (&[0x48, 0x29, 0xc8], "sub rax, rcx"),
+    (&[0x41, 0x5e], "pop r14"),
+    (&[0x33, 0x44, 0x65, 0x11], "xor eax, dword [rbp + 0x11]"),
+    (&[0x0f, 0x01, 0x3f], "invlpg byte [rdi]"),
+    (&[0x0f, 0x22, 0xc7], "mov cr0, rdi"),
+    (&[0x0f, 0xae, 0xee], "lfence"),
+    (&[0x0f, 0xc0, 0xcc], "xadd ah, cl"),
+    (&[0x0f, 0xad, 0xc9], "shrd ecx, ecx, cl"),
+    (&[0x72, 0x5a], "jb 0x105c"),
+    (&[0xe2, 0x12], "loop 0x1014"),
+    (&[0x0f, 0xed, 0xc2], "paddsw mm0, mm2"),
+    (&[0x0f, 0x6a, 0x00], "punpckhdq mm0, qword [rax]"),
+    (&[0x4f, 0x0f, 0x50, 0xc1], "movmskps r8d, xmm9"),
+    (&[0x66, 0x0f, 0xd1, 0xc1], "psrlw xmm0, xmm1"),
+    (&[0x66, 0x0f, 0x38, 0x09, 0x06], "psignw xmm0, xmmword [rsi]"),
+    (&[0x66, 0x0f, 0xc6, 0x03, 0x08], "shufpd xmm0, xmmword [rbx], 0x8"),
+    (&[0x66, 0x4f, 0x0f, 0x28, 0x00], "movapd xmm8, xmmword [r8]"),
+    (
+        &[0x66, 0x4f, 0x0f, 0x60, 0x9c, 0x9c, 0x34, 0xaa, 0xbb, 0xcc],
+        "punpcklbw xmm11, xmmword [r12 + r11 * 4 - 0x334455cc]",
+    ),
+    (&[0x67, 0x4f, 0x0f, 0x5b, 0x01], "cvtdq2ps xmm8, xmmword [r9d]"),
+    (&[0xf3, 0x4f, 0x0f, 0x5f, 0x01], "maxss xmm8, dword [r9]"),
+    (&[0xc4, 0x01, 0x39, 0x67, 0xca], "vpackuswb xmm9, xmm8, xmm10"),
+    (&[0xc4, 0x01, 0x3e, 0x5c, 0x0a], "vsubss xmm9, xmm8, dword [r10]"),
+    (&[0xc4, 0x01, 0x7e, 0x2d, 0x0a], "vcvtss2si r9d, dword [r10]"),
+    (&[0xc4, 0x01, 0xbc, 0x59, 0x0a], "vmulps ymm9, ymm8, ymmword [r10]"),
+    (&[0xc4, 0x01, 0xf9, 0x71, 0xe2, 0x77], "vpsraw xmm0, xmm10, 0x77"),
+    (&[0xc4, 0x01, 0xff, 0xe6, 0xca], "vcvtpd2dq xmm9, ymm10"),
+    (&[0xc4, 0x02, 0x75, 0x04, 0x0f], "vpmaddubsw ymm9, ymm1, ymmword [r15]"),
+    (&[0xc4, 0x02, 0x7d, 0x46, 0x0a], "vpsravd ymm9, ymm0, ymmword [r10]"),
+    (&[0xc4, 0xc1, 0x79, 0x71, 0xd2, 0x77], "vpsrlw xmm0, xmm10, 0x77"),
+    (&[0xc4, 0x61, 0x79, 0x71, 0xd2, 0x77], "vpsrlw xmm0, xmm2, 0x77"),
+    (&[0xc4, 0xc2, 0x78, 0xf3, 0x19], "blsi eax, dword [r9]"),
+    (&[0xc5, 0xcd, 0xf1, 0x0f], "vpsllw ymm1, ymm6, xmmword [rdi]"),
+    (&[0xd8, 0x33], "fdiv st(0), dword [rbx]"),
+    (&[0xd9, 0xfe], "fsin"),
+];
+
+const AARCH64_ROUND_TRIP: &[(&[u8], &str)] = &[
+    (&[0xfd, 0x7b, 0x57, 0xa9], "ldp x29, x30, [sp, #0x170]"),
+    (&[0xf6, 0x57, 0x18, 0xa9], "stp x22, x21, [sp, #0x180]"),
+    (&[0xa9, 0xab, 0x78, 0xa9], "ldp x9, x10, [x29, #-0x78]"),
+    (&[0xfd, 0x03, 0x03, 0x91], "add x29, sp, #0xc0"),
+    (&[0xff, 0x83, 0x03, 0x91], "add sp, sp, #0xe0"),
+    (&[0xa0, 0x23, 0x01, 0xd1], "sub x0, x29, #0x48"),
+    (&[0x00, 0x00, 0x80, 0x12], "mov w0, #0xffffffff"),
+    (&[0xc0, 0x7e, 0x80, 0x52], "mov w0, #0x3f6"),
+    (&[0xe0, 0x07, 0x00, 0x32], "mov w0, #0x3"),
+    (&[0xe5, 0x03, 0x00, 0xaa], "mov x5, x0"),
+    (&[0xeb, 0x03, 0x09, 0x2a], "mov w11, w9"),
+    (&[0x00, 0x44, 0x44, 0xf9], "ldr x0, [x0, #0x888]"),
+    (&[0xa8, 0x3e, 0x00, 0xf9], "str x8, [x21, #0x78]"),
+    (&[0xf4, 0x0b, 0x00, 0xb9], "str w20, [sp, #0x8]"),
+    (&[0xe8, 0xbf, 0xc0, 0x39], "ldrsb w8, [sp, #0x2f]"),
+    (&[0xa8, 0x73, 0xdb, 0x38], "ldursb w8, [x29, #-0x49]"),
+    (&[0x1f, 0x01, 0x00, 0x71], "cmp w8, #0x0"),
+    (&[0x35, 0xb1, 0x95, 0x9a], "csel x21, x9, x21, lt"),
+    (&[0xf4, 0x07, 0x9f, 0x1a], "cset w20, ne"),
+    (&[0x11, 0x89, 0x2f, 0x9b], "smsubl x17, w8, w15, x2"),
+    (&[0x14, 0x0a, 0xc0, 0xda], "rev32 x20, x16"),
+    (&[0x22, 0x39, 0x04, 0xb0], "adrp x2, #0x8726000"),
+    (&[0x01, 0x00, 0x00, 0x14], "b #0x1004"),
+    (&[0x81, 0x01, 0x00, 0x54], "b.ne #0x1030"),
+    (&[0x35, 0x03, 0x00, 0xb5], "cbnz x21, #0x1064"),
+    (&[0x88, 0xff, 0x00, 0xd8], "prfm plil1keep, #0x2ff0"),
+    (&[0xbf, 0x35, 0x03, 0xd5], "dmb nshld"),
+    (&[0xc0, 0x03, 0x5f, 0xd6], "ret"),
+];
+
+/// Instructions as the RISC-V decoder displays them.
+const RISCV_ROUND_TRIP: &[&str] = &[
+    "lui a0, 4096",
+    "auipc a0, 0x3000",
+    "li a2, 1024",
+    "addi a0, a1, 16",
+    "addi sp, -352",
+    "slli a0, a1, 3",
+    "add a0, a1, a2",
+    "sub a0, a1, a2",
+    "sltu a0, a1, a2",
+    "xor a0, a1, a2",
+    "lw a1, a0, 8",
+    "sw a1, a0, 8",
+    "ld a1, a1, 1672",
+    "sd s0, sp, 344",
+    "beq a2, a3, 0x1018",
+    "fence",
+    "ecall",
+    "c.li a1, 12",
+    "c.lui a2, 18",
+    "c.mv a0, a1",
+    "c.add s6, s6",
+    "c.sw a1, a0, 0",
+    "c.sdsp a0, 88",
+    "c.ldsp ra, 120",
+    "c.addi4spn a0, 8",
+    "c.addi16sp 128",
+    "c.j 0x1068",
+];
+
+/// Decode `bytes` at 0x1000 and display them the way the listing does.
+fn display<D: Decodable>(decoder: &D, bytes: &[u8], symbols: &Index) -> String {
+    let mut instr = decoder.decode(&mut Reader::new(bytes)).unwrap();
+    assert_eq!(instr.width(), bytes.len(), "{bytes:02x?} wasn't fully decoded");
+    instr.update_rel_addrs(0x1000, None);
+    let mut stream = tokenizing::TokenStream::new();
+    instr.tokenize(&mut stream, symbols);
+    stream.to_string()
+}
+
+/// Check that each displayed instruction assembles to bytes that display the same way.
+fn round_trip<D: Decodable>(
+    decoder: &D,
+    assemble: fn(&str, usize, &Index) -> Result<Vec<u8>, Error>,
+    tests: &[(&[u8], &str)],
+) {
+    let symbols = Index::default();
+
+    for (bytes, text) in tests {
+        assert_eq!(&display(decoder, bytes, &symbols), text);
+        let assembled = match assemble(text, 0x1000, &symbols) {
+            Ok(assembled) => assembled,
+            Err(err) => panic!("failed to assemble '{text}': {err:?}"),
+        };
+        assert_eq!(
+            &display(decoder, &assembled, &symbols),
+            text,
+            "{bytes:02x?} assembled to {assembled:02x?}"
+        );
+    }
+}
+
+fn symbols() -> Index {
+    let mut index = Index::default();
+    index.insert_func(0x2000, "main");
+    index
+}
+
+#[test]
+fn x86_64() {
+    let symbols = symbols();
+    let assemble = |src: &str| x86_64::assemble(src, 0x1000, &symbols);
+
+    assert_eq!(assemble("push rbp"), Ok(vec![0x55]));
+    assert_eq!(assemble("mov rbp, rsp"), Ok(vec![0x48, 0x89, 0xe5]));
+    assert_eq!(
+        assemble("mov rax, qword ptr [rbx + rcx * 8 + 0x10]"),
+        Ok(vec![0x48, 0x8b, 0x44, 0xcb, 0x10])
+    );
+    assert_eq!(assemble("add rsp, 0x18"), Ok(vec![0x48, 0x83, 0xc4, 0x18]));
+    assert_eq!(assemble("xor eax, eax"), Ok(vec![0x31, 0xc0]));
+    assert_eq!(
+        assemble("lea rdi, [rip + 0x200]"),
+        Ok(vec![0x48, 0x8d, 0x3d, 0x00, 0x02, 0x00, 0x00])
+    );
+    assert_eq!(
+        assemble("movzx eax, byte ptr [rdi]"),
+        Ok(vec![0x0f, 0xb6, 0x07])
+    );
+    assert_eq!(
+        assemble("vpsrlw xmm0, xmm10, 0x77"),
+        Ok(vec![0xc4, 0xc1, 0x79, 0x71, 0xd2, 0x77])
+    );
+    assert_eq!(assemble("ret"), Ok(vec![0xc3]));
+}
+
+#[test]
+fn x86_64_branches() {
+    let symbols = symbols();
+    let assemble = |src: &str| x86_64::assemble(src, 0x1000, &symbols);
+
+    assert_eq!(assemble("jmp 0x1002"), Ok(vec![0xeb, 0x00]));
+    assert_eq!(assemble("je 0x1010"), Ok(vec![0x74, 0x0e]));
+    assert_eq!(
+        assemble("jmp 0x2000"),
+        Ok(vec![0xe9, 0xfb, 0x0f, 0x00, 0x00])
+    );
+    assert_eq!(
+        assemble("call 0x1100"),
+        Ok(vec![0xe8, 0xfb, 0x00, 0x00, 0x00])
+    );
+    assert_eq!(
+        assemble("call <main>"),
+        Ok(vec![0xe8, 0xfb, 0x0f, 0x00, 0x00])
+    );
+    assert_eq!(
+        assemble("call <main> + 0x10"),
+        Ok(vec![0xe8, 0x0b, 0x10, 0x00, 0x00])
+    );
+}
+
+#[test]
+fn x86_64_errors() {
+    let symbols = symbols();
+    let assemble = |src: &str| x86_64::assemble(src, 0x1000, &symbols);
+
+    assert_eq!(assemble(""), Err(Error::Empty));
+    assert_eq!(
+        assemble("frobnicate rax"),
+        Err(Error::UnknownInstruction("frobnicate".to_string()))
+    );
+    assert_eq!(assemble("mov rax"), Err(Error::InvalidOperands));
+    assert_eq!(assemble("mov al, rbx"), Err(Error::InvalidOperands));
+    assert_eq!(assemble("add al, 0x1000"), Err(Error::OutOfRange(0x1000)));
+    assert_eq!(
+        assemble("call <missing>"),
+        Err(Error::UnknownSymbol("missing".to_string()))
+    );
+}
+
+#[test]
+fn aarch64() {
+    let symbols = symbols();
+    let assemble = |src: &str| aarch64::assemble(src, 0x1000, &symbols);
+
+    assert_eq!(
+        assemble("stp x29, x30, [sp, #-0x10]!"),
+        Ok(vec![0xfd, 0x7b, 0xbf, 0xa9])
+    );
+    assert_eq!(assemble("mov x29, sp"), Ok(vec![0xfd, 0x03, 0x00, 0x91]));
+    assert_eq!(
+        assemble("ldr x0, [x1, #0x8]"),
+        Ok(vec![0x20, 0x04, 0x40, 0xf9])
+    );
+    assert_eq!(
+        assemble("ldr x0, [x1, #0x3]"),
+        Ok(vec![0x20, 0x30, 0x40, 0xf8])
+    );
+    assert_eq!(
+        assemble("add w0, w1, #0x10"),
+        Ok(vec![0x20, 0x40, 0x00, 0x11])
+    );
+    assert_eq!(
+        assemble("sub sp, sp, #0x20"),
+        Ok(vec![0xff, 0x83, 0x00, 0xd1])
+    );
+    assert_eq!(assemble("cmp x0, x1"), Ok(vec![0x1f, 0x00, 0x01, 0xeb]));
+    assert_eq!(
+        assemble("ldaddal w0, w1, [x2]"),
+        Ok(vec![0x41, 0x00, 0xe0, 0xb8])
+    );
+    assert_eq!(assemble("fmov d0, #1.5"), Ok(vec![0x00, 0x10, 0x6f, 0x1e]));
+    assert_eq!(
+        assemble("mrs x0, tpidr_el0"),
+        Ok(vec![0x40, 0xd0, 0x3b, 0xd5])
+    );
+    assert_eq!(assemble("ret"), Ok(vec![0xc0, 0x03, 0x5f, 0xd6]));
+    assert_eq!(assemble("nop"), Ok(aarch64::NOP.to_vec()));
+}
+
+#[test]
+fn aarch64_branches() {
+    let symbols = symbols();
+    let assemble = |src: &str| aarch64::assemble(src, 0x1000, &symbols);
+
+    assert_eq!(assemble("b.ne #0x1020"), Ok(vec![0x01, 0x01, 0x00, 0x54]));
+    assert_eq!(assemble("bl #0x800"), Ok(vec![0x00, 0xfe, 0xff, 0x97]));
+    assert_eq!(
+        assemble("cbz w0, #0x1008"),
+        Ok(vec![0x40, 0x00, 0x00, 0x34])
+    );
+    assert_eq!(assemble("bl <main>"), Ok(vec![0x00, 0x04, 0x00, 0x94]));
+    assert_eq!(assemble("b #0x8001000"), Err(Error::OutOfRange(0x8001000)));
+    assert_eq!(assemble("b #0x1002"), Err(Error::Misaligned(0x1002)));
+    assert_eq!(
+        assemble("add x0, x1, #0x1001"),
+        Err(Error::OutOfRange(0x1001))
+    );
+}
+
+#[test]
+fn riscv() {
+    let symbols = symbols();
+    let assemble = |src: &str| riscv::assemble(src, 0x1000, &symbols);
+
+    assert_eq!(assemble("lui a0, 4096"), Ok(vec![0x37, 0x05, 0x00, 0x01]));
+    assert_eq!(assemble("auipc a0, 0x0"), Ok(vec![0x17, 0xf5, 0xff, 0xff]));
+    assert_eq!(assemble("auipc a0, 0x1800"), Err(Error::OutOfRange(0x1800)));
+    assert_eq!(
+        assemble("addi a0, a1, 16"),
+        Ok(vec![0x13, 0x85, 0x05, 0x01])
+    );
+    assert_eq!(assemble("sd s0, sp, 344"), Ok(vec![0x23, 0x3c, 0x81, 0x14]));
+    assert_eq!(assemble("c.lui a0, 0xfffff"), Ok(vec![0x7d, 0x75]));
+    assert_eq!(
+        assemble("amoadd.w.aqrl a0, a1, (a2)"),
+        Ok(vec![0x2f, 0x25, 0xb6, 0x06])
+    );
+    assert_eq!(
+        assemble("fcvt.d.s fa0, fa1"),
+        Ok(vec![0x53, 0x85, 0x05, 0x42])
+    );
+    assert_eq!(
+        assemble("beq a0, a1, 0x1010"),
+        Ok(vec![0x63, 0x08, 0xb5, 0x00])
+    );
+    assert_eq!(assemble("nop"), Ok(riscv::NOP.to_vec()));
+    assert_eq!(assemble("c.nop"), Ok(riscv::C_NOP.to_vec()));
+}
+
+#[test]
+fn riscv_errors() {
+    let symbols = symbols();
+    let assemble = |src: &str| riscv::assemble(src, 0x1000, &symbols);
+
+    assert_eq!(
+        assemble("beq a0, a1, 0x1001"),
+        Err(Error::Misaligned(0x1001))
+    );
+    assert_eq!(
+        assemble("addi a0, a1, 4096"),
+        Err(Error::OutOfRange(0x1000))
+    );
+    assert_eq!(
+        assemble("jal <missing>"),
+        Err(Error::UnknownSymbol("missing".to_string()))
+    );
+}
+
+#[test]
+fn x86_64_round_trip() {
+    let decoder = ::x86_64::long_mode::Decoder::default();
+    round_trip(&decoder, x86_64::assemble, X86_64_ROUND_TRIP);
+}
+
+#[test]
+fn aarch64_round_trip() {
+    let decoder = arm::armv8::a64::Decoder::default();
+    round_trip(&decoder, aarch64::assemble, AARCH64_ROUND_TRIP);
+}
+
+#[test]
+fn riscv_round_trip() {
+    let decoder = ::riscv::Decoder { is_64: true };
+    let symbols = Index::default();
+
+    for text in RISCV_ROUND_TRIP {
+        let bytes = match riscv::assemble(text, 0x1000, &symbols) {
+            Ok(bytes) => bytes,
+            Err(err) => panic!("failed to assemble '{text}': {err:?}"),
+        };
+        assert_eq!(&display(&decoder, &bytes, &symbols), text);
+    }
+}
@@ -0,0 +1,2148 @@
+//! Assembler for x86-64 in long mode.
+//!
+//! Accepts the Intel syntax the decoder prints, e.g. `mov rax, qword fs:[0x28]` or
+//! `lea rdi, qword [<main>]`. Memory operands that only contain a symbol are encoded relative
+//! to `rip`, other absolute addresses are encoded as a sign-extended 32-bit displacement. When
+//! there's more than one encoding, the shortest one is used.
+
+use crate::parse::Parser;
+use crate::{fits_signed, fits_unsigned, Error};
+use debugvault::Index;
+
+/// Single byte nop, used to pad instructions.
+pub const NOP: [u8; 1] = [0x90];
+
+const REX_W: u8 = 0b1000;
+const REX_R: u8 = 0b0100;
+const REX_X: u8 = 0b0010;
+const REX_B: u8 = 0b0001;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    /// General purpose register of a size in bytes.
+    Gpr(u8),
+    /// One of `ah`, `ch`, `dh` or `bh`, which can't be used with a REX prefix.
+    HighByte,
+    Xmm,
+    Ymm,
+    Mmx,
+    Segment,
+    Control,
+    Debug,
+    /// Register of the x87 stack, `st(0)` to `st(7)`.
+    St,
+    Rip,
+    Eip,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Register {
+    kind: Kind,
+    num: u8,
+}
+
+impl Register {
+    fn is_gpr(&self) -> bool {
+        matches!(self.kind, Kind::Gpr(_) | Kind::HighByte)
+    }
+
+    fn size(&self) -> Option<u8> {
+        match self.kind {
+            Kind::Gpr(size) => Some(size),
+            Kind::HighByte => Some(1),
+            Kind::Xmm => Some(16),
+            Kind::Ymm => Some(32),
+            Kind::St => Some(10),
+            Kind::Mmx | Kind::Rip => Some(8),
+            Kind::Segment => Some(2),
+            Kind::Eip => Some(4),
+            Kind::Control | Kind::Debug => None,
+        }
+    }
+}
+
+const GPR64: [&str; 16] = [
+    "rax", "rcx", "rdx", "rbx", "rsp", "rbp", "rsi", "rdi", "r8", "r9", "r10", "r11", "r12", "r13",
+    "r14", "r15",
+];
+
+const GPR32: [&str; 16] = [
+    "eax", "ecx", "edx", "ebx", "esp", "ebp", "esi", "edi", "r8d", "r9d", "r10d", "r11d", "r12d",
+    "r13d", "r14d", "r15d",
+];
+
+const GPR16: [&str; 16] = [
+    "ax", "cx", "dx", "bx", "sp", "bp", "si", "di", "r8w", "r9w", "r10w", "r11w", "r12w", "r13w",
+    "r14w", "r15w",
+];
+
+const GPR8: [&str; 16] = [
+    "al", "cl", "dl", "bl", "spl", "bpl", "sil", "dil", "r8b", "r9b", "r10b", "r11b", "r12b",
+    "r13b", "r14b", "r15b",
+];
+
+const SEGMENTS: [&str; 6] = ["es", "cs", "ss", "ds", "fs", "gs"];
+
+/// Prefix bytes that override the segment, in the order of [`SEGMENTS`].
+const SEGMENT_PREFIXES: [u8; 6] = [0x26, 0x2e, 0x36, 0x3e, 0x64, 0x65];
+
+fn register(name: &str) -> Option<Register> {
+    let find = |names: &[&str], kind| {
+        let num = names.iter().position(|&reg| reg == name)?;
+        Some(Register {
+            kind,
+            num: num as u8,
+        })
+    };
+
+    let numbered = |prefix: &str, kind, count: u8| {
+        let num: u8 = name.strip_prefix(prefix)?.parse().ok()?;
+        (num < count).then_some(Register { kind, num })
+    };
+
+    find(&GPR64, Kind::Gpr(8))
+        .or_else(|| find(&GPR32, Kind::Gpr(4)))
+        .or_else(|| find(&GPR16, Kind::Gpr(2)))
+        .or_else(|| find(&GPR8, Kind::Gpr(1)))
+        .or_else(|| find(&["", "", "", "", "ah", "ch", "dh", "bh"], Kind::HighByte))
+        .or_else(|| find(&SEGMENTS, Kind::Segment))
+        .or_else(|| numbered("xmm", Kind::Xmm, 16))
+        .or_else(|| numbered("ymm", Kind::Ymm, 16))
+        .or_else(|| numbered("mm", Kind::Mmx, 8))
+        .or_else(|| numbered("cr", Kind::Control, 16))
+        .or_else(|| numbered("dr", Kind::Debug, 16))
+        .or(match name {
+            "st" => Some(Register {
+                kind: Kind::St,
+                num: 0,
+            }),
+            "rip" => Some(Register {
+                kind: Kind::Rip,
+                num: 5,
+            }),
+            "eip" => Some(Register {
+                kind: Kind::Eip,
+                num: 5,
+            }),
+            _ => None,
+        })
+}
+
+/// Size in bytes of a memory operand's annotation, `0` if it doesn't say.
+fn memory_size(name: &str) -> Option<u8> {
+    Some(match name {
+        "byte" => 1,
+        "word" => 2,
+        "dword" => 4,
+        "qword" => 8,
+        "tword" | "mword" => 10,
+        "xmmword" => 16,
+        "ymmword" => 32,
+        "zmmword" => 64,
+        "ptr" => 0,
+        _ => return None,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Memory {
+    /// Size of the access in bytes, `0` if it isn't known.
+    size: u8,
+    segment: Option<u8>,
+    base: Option<Register>,
+    index: Option<Register>,
+    scale: u8,
+    disp: i64,
+    /// Whether the displacement is the address of a symbol.
+    symbolic: bool,
+}
+
+impl Memory {
+    /// An absolute address without any registers.
+    fn is_absolute(&self) -> bool {
+        self.base.is_none() && self.index.is_none()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Reg(Register),
+    Mem(Memory),
+    Imm(i64),
+}
+
+impl Operand {
+    /// Size in bytes of a register or memory operand.
+    fn size(&self) -> Option<u8> {
+        match self {
+            Self::Reg(reg) => reg.size(),
+            Self::Mem(mem) if mem.size != 0 => Some(mem.size),
+            _ => None,
+        }
+    }
+
+    fn gpr(&self) -> Option<Register> {
+        match self {
+            Self::Reg(reg) if reg.is_gpr() => Some(*reg),
+            _ => None,
+        }
+    }
+
+    /// Whether the operand can be encoded in the `r/m` field as a general purpose register.
+    fn is_gpr_rm(&self) -> bool {
+        matches!(self, Self::Mem(_)) || self.gpr().is_some()
+    }
+
+    fn is_accumulator(&self) -> bool {
+        matches!(
+            self,
+            Self::Reg(Register {
+                kind: Kind::Gpr(_),
+                num: 0
+            })
+        )
+    }
+
+    fn is_vector(&self, kind: Kind) -> bool {
+        matches!(self, Self::Reg(reg) if reg.kind == kind)
+    }
+}
+
+/// Reads a memory operand after its size and segment, `[base + index * scale + disp]`.
+fn memory(p: &mut Parser, size: u8, segment: Option<u8>) -> Result<Memory, Error> {
+    let mut mem = Memory {
+        size,
+        segment,
+        base: None,
+        index: None,
+        scale: 1,
+        disp: 0,
+        symbolic: false,
+    };
+
+    p.expect('[', "'['")?;
+    let mut negative = false;
+    loop {
+        if let Some(reg) = p.try_word(register) {
+            if negative {
+                return Err(p.expected("displacement"));
+            }
+
+            if p.eat('*') {
+                let scale = p.number()?;
+                if !matches!(scale, 1 | 2 | 4 | 8) || mem.index.is_some() {
+                    return Err(Error::InvalidOperands);
+                }
+                mem.index = Some(reg);
+                mem.scale = scale as u8;
+            } else if mem.base.is_none() {
+                mem.base = Some(reg);
+            } else if mem.index.is_none() {
+                mem.index = Some(reg);
+            } else {
+                return Err(Error::InvalidOperands);
+            }
+        } else {
+            mem.symbolic |= p.peek() == Some('<');
+            let disp = p.term()?;
+            mem.disp = if negative {
+                mem.disp.wrapping_sub(disp)
+            } else {
+                mem.disp.wrapping_add(disp)
+            };
+        }
+
+        if p.eat('+') {
+            negative = false;
+        } else if p.eat('-') {
+            negative = true;
+        } else {
+            break;
+        }
+    }
+    p.expect(']', "']'")?;
+
+    Ok(mem)
+}
+
+fn operand(p: &mut Parser) -> Result<Operand, Error> {
+    let size = p.try_word(memory_size);
+    if size.is_some() {
+        p.eat_word("ptr");
+    }
+
+    let mut segment = None;
+    if let Some(reg) = p.try_word(register) {
+        match reg.kind {
+            Kind::Segment if p.eat(':') => segment = Some(reg.num),
+            _ if size.is_some() => return Err(p.expected("'['")),
+            Kind::St if p.eat('(') => {
+                let num = p.number()?;
+                if !(0..8).contains(&num) {
+                    return Err(Error::InvalidOperands);
+                }
+                p.expect(')', "')'")?;
+                return Ok(Operand::Reg(Register {
+                    kind: Kind::St,
+                    num: num as u8,
+                }));
+            }
+            _ => return Ok(Operand::Reg(reg)),
+        }
+    }
+
+    if size.is_some() || segment.is_some() || p.peek() == Some('[') {
+        return memory(p, size.unwrap_or(0), segment).map(Operand::Mem);
+    }
+
+    p.value().map(Operand::Imm)
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+enum Disp {
+    #[default]
+    None,
+    Byte(i8),
+    Dword(i32),
+    /// Displacement from the next instruction to an absolute address.
+    Rip(i64),
+}
+
+/// Fields of a VEX prefix that aren't part of REX, the mandatory prefix or the opcode.
+#[derive(Debug, Clone, Copy, Default)]
+struct Vex {
+    /// Extra register operand.
+    vvvv: u8,
+    /// Whether the vectors are 256 bits long.
+    long: bool,
+}
+
+/// Encoding of an instruction that's being built.
+#[derive(Debug, Clone, Default)]
+struct Inst {
+    lock: bool,
+    rep: Option<u8>,
+    segment: Option<u8>,
+    addr32: bool,
+    opsize: bool,
+    mandatory: Option<u8>,
+    rex: u8,
+    /// A REX prefix is needed even without any bits set, for `spl`, `bpl`, `sil` and `dil`.
+    needs_rex: bool,
+    /// A REX prefix can't be used, for `ah`, `ch`, `dh` and `bh`.
+    forbids_rex: bool,
+    opcode: Vec<u8>,
+    reg: u8,
+    /// Mod and r/m fields of the ModRM byte.
+    modrm: Option<(u8, u8)>,
+    sib: Option<u8>,
+    disp: Disp,
+    imm: Vec<u8>,
+    /// Branch target and the size of its offset.
+    rel: Option<(i64, u8)>,
+    /// VEX prefix, which replaces REX, the mandatory prefix and the opcode's escape bytes.
+    vex: Option<Vex>,
+}
+
+impl Inst {
+    fn opcode(&mut self, opcode: &[u8]) {
+        self.opcode = opcode.to_vec();
+    }
+
+    /// Sets the opcode of an instruction that has a byte sized version at `opcode` and a word,
+    /// dword and qword sized version at `opcode + 1`.
+    fn sized(&mut self, opcode: &[u8], size: u8) -> Result<(), Error> {
+        self.opcode(opcode);
+        if size != 1 {
+            *self.opcode.last_mut().unwrap() += 1;
+        }
+        self.operand_size(size)
+    }
+
+    fn operand_size(&mut self, size: u8) -> Result<(), Error> {
+        match size {
+            1 | 4 => {}
+            2 => self.opsize = true,
+            8 => self.rex |= REX_W,
+            _ => return Err(Error::InvalidOperands),
+        }
+        Ok(())
+    }
+
+    fn byte_reg(&mut self, reg: Register) {
+        match reg.kind {
+            Kind::Gpr(1) if (4..8).contains(&reg.num) => self.needs_rex = true,
+            Kind::HighByte => self.forbids_rex = true,
+            _ => {}
+        }
+    }
+
+    /// Register in the reg field of ModRM.
+    fn reg(&mut self, reg: Register) {
+        self.byte_reg(reg);
+        self.reg = reg.num & 7;
+        if reg.num & 8 != 0 {
+            self.rex |= REX_R;
+        }
+    }
+
+    /// Opcode extension in the reg field of ModRM.
+    fn digit(&mut self, digit: u8) {
+        self.reg = digit;
+    }
+
+    /// Register added to the last byte of the opcode.
+    fn plus_reg(&mut self, reg: Register) {
+        self.byte_reg(reg);
+        *self.opcode.last_mut().unwrap() += reg.num & 7;
+        if reg.num & 8 != 0 {
+            self.rex |= REX_B;
+        }
+    }
+
+    /// Register or memory in the r/m field of ModRM.
+    fn rm(&mut self, op: &Operand) -> Result<(), Error> {
+        match op {
+            Operand::Reg(reg) => {
+                self.byte_reg(*reg);
+                self.modrm = Some((0b11, reg.num & 7));
+                if reg.num & 8 != 0 {
+                    self.rex |= REX_B;
+                }
+                Ok(())
+            }
+            Operand::Mem(mem) => self.memory(mem),
+            Operand::Imm(_) => Err(Error::InvalidOperands),
+        }
+    }
+
+    fn memory(&mut self, mem: &Memory) -> Result<(), Error> {
+        if let Some(segment) = mem.segment {
+            self.segment = Some(SEGMENT_PREFIXES[segment as usize]);
+        }
+
+        for reg in mem.base.iter().chain(mem.index.iter()) {
+            match reg.kind {
+                Kind::Gpr(8) | Kind::Rip => {}
+                Kind::Gpr(4) | Kind::Eip => self.addr32 = true,
+                _ => return Err(Error::InvalidRegister(reg_name(*reg))),
+            }
+        }
+
+        let disp32 = |disp: i64| match fits_signed(disp, 32) || fits_unsigned(disp, 32) {
+            true => Ok(Disp::Dword(disp as i32)),
+            false => Err(Error::OutOfRange(disp)),
+        };
+
+        match (mem.base, mem.index) {
+            (
+                Some(Register {
+                    kind: Kind::Rip | Kind::Eip,
+                    ..
+                }),
+                None,
+            ) => {
+                self.modrm = Some((0b00, 0b101));
+                self.disp = disp32(mem.disp)?;
+            }
+            (
+                Some(Register {
+                    kind: Kind::Rip | Kind::Eip,
+                    ..
+                }),
+                Some(_),
+            ) => {
+                return Err(Error::InvalidOperands);
+            }
+            (None, None) if mem.symbolic => {
+                self.modrm = Some((0b00, 0b101));
+                self.disp = Disp::Rip(mem.disp);
+            }
+            (None, None) => {
+                self.modrm = Some((0b00, 0b100));
+                self.sib = Some(0b00_100_101);
+                self.disp = disp32(mem.disp)?;
+            }
+            (base, Some(index)) if index.num == 4 => {
+                // rsp can't be an index, but it can be the base if the scale is 1
+                match base {
+                    Some(base) if mem.scale == 1 && base.num != 4 => {
+                        let swapped = Memory {
+                            base: Some(index),
+                            index: Some(base),
+                            ..mem.clone()
+                        };
+                        return self.memory(&swapped);
+                    }
+                    _ => return Err(Error::InvalidRegister(reg_name(index))),
+                }
+            }
+            (None, Some(index)) => {
+                self.modrm = Some((0b00, 0b100));
+                self.sib = Some(scale_bits(mem.scale) << 6 | (index.num & 7) << 3 | 0b101);
+                self.disp = disp32(mem.disp)?;
+                if index.num & 8 != 0 {
+                    self.rex |= REX_X;
+                }
+            }
+            (Some(base), index) => {
+                // rbp and r13 as a base always have a displacement
+                let (mode, disp) = if mem.disp == 0 && base.num & 7 != 5 {
+                    (0b00, Disp::None)
+                } else if fits_signed(mem.disp, 8) {
+                    (0b01, Disp::Byte(mem.disp as i8))
+                } else {
+                    (0b10, disp32(mem.disp)?)
+                };
+
+                self.disp = disp;
+                if base.num & 8 != 0 {
+                    self.rex |= REX_B;
+                }
+
+                match index {
+                    // rsp and r12 as a base always need a SIB byte
+                    None if base.num & 7 != 4 => self.modrm = Some((mode, base.num & 7)),
+                    None => {
+                        self.modrm = Some((mode, 0b100));
+                        self.sib = Some(0b00_100_000 | (base.num & 7));
+                    }
+                    Some(index) => {
+                        self.modrm = Some((mode, 0b100));
+                        self.sib = Some(
+                            scale_bits(mem.scale) << 6 | (index.num & 7) << 3 | (base.num & 7),
+                        );
+                        if index.num & 8 != 0 {
+                            self.rex |= REX_X;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Extra register in the VEX prefix.
+    fn vvvv(&mut self, reg: Register) {
+        self.vex.get_or_insert_with(Vex::default).vvvv = reg.num;
+    }
+
+    /// Immediate of `size` bytes.
+    fn imm(&mut self, value: i64, size: u8) {
+        self.imm.extend_from_slice(&value.to_le_bytes()[..size as usize]);
+    }
+
+    /// Branch to `target` with an offset of `size` bytes.
+    fn rel(&mut self, target: i64, size: u8) {
+        self.rel = Some((target, size));
+    }
+
+    fn encode(self, addr: usize) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::with_capacity(15);
+
+        if self.lock {
+            bytes.push(0xf0);
+        }
+        bytes.extend(self.rep);
+        bytes.extend(self.segment);
+        if self.addr32 {
+            bytes.push(0x67);
+        }
+        if self.opsize {
+            bytes.push(0x66);
+        }
+
+        if let Some(vex) = self.vex {
+            let pp = match self.mandatory {
+                Some(0x66) => 0b01,
+                Some(0xf3) => 0b10,
+                Some(0xf2) => 0b11,
+                _ => 0b00,
+            };
+            let (map, opcode) = match self.opcode.as_slice() {
+                [0x0f, 0x38, opcode @ ..] => (0b10, opcode),
+                [0x0f, 0x3a, opcode @ ..] => (0b11, opcode),
+                [0x0f, opcode @ ..] => (0b01, opcode),
+                _ => return Err(Error::InvalidOperands),
+            };
+
+            // R, X, B and vvvv are stored inverted
+            let rxb = !self.rex & 0b111;
+            let last = (!vex.vvvv & 0b1111) << 3 | (vex.long as u8) << 2 | pp;
+            if rxb & 0b011 == 0b011 && self.rex & REX_W == 0 && map == 0b01 {
+                bytes.extend([0xc5, (rxb & 0b100) << 5 | last]);
+            } else {
+                bytes.extend([0xc4, rxb << 5 | map, (self.rex & REX_W) << 4 | last]);
+            }
+            bytes.extend_from_slice(opcode);
+        } else {
+            bytes.extend(self.mandatory);
+            if self.rex != 0 || self.needs_rex {
+                if self.forbids_rex {
+                    return Err(Error::InvalidRegister("ah".to_string()));
+                }
+                bytes.push(0x40 | self.rex);
+            }
+            bytes.extend_from_slice(&self.opcode);
+        }
+
+        if let Some((mode, rm)) = self.modrm {
+            bytes.push(mode << 6 | self.reg << 3 | rm);
+        }
+        bytes.extend(self.sib);
+
+        let disp_len = match self.disp {
+            Disp::None => 0,
+            Disp::Byte(_) => 1,
+            Disp::Dword(_) | Disp::Rip(_) => 4,
+        };
+        let rel_len = self.rel.map_or(0, |(_, size)| size as usize);
+        let len = bytes.len() + disp_len + self.imm.len() + rel_len;
+        let next = (addr + len) as i64;
+
+        match self.disp {
+            Disp::None => {}
+            Disp::Byte(disp) => bytes.push(disp as u8),
+            Disp::Dword(disp) => bytes.extend_from_slice(&disp.to_le_bytes()),
+            Disp::Rip(target) => {
+                let disp = target.wrapping_sub(next);
+                if !fits_signed(disp, 32) {
+                    return Err(Error::OutOfRange(target));
+                }
+                bytes.extend_from_slice(&(disp as i32).to_le_bytes());
+            }
+        }
+
+        bytes.extend_from_slice(&self.imm);
+
+        if let Some((target, size)) = self.rel {
+            let offset = target.wrapping_sub(next);
+            if !fits_signed(offset, size as u32 * 8) {
+                return Err(Error::OutOfRange(target));
+            }
+            bytes.extend_from_slice(&offset.to_le_bytes()[..size as usize]);
+        }
+
+        Ok(bytes)
+    }
+}
+
+fn reg_name(reg: Register) -> String {
+    let names: &[&str] = match reg.kind {
+        Kind::Gpr(8) => &GPR64,
+        Kind::Gpr(4) => &GPR32,
+        Kind::Gpr(2) => &GPR16,
+        Kind::Gpr(_) => &GPR8,
+        Kind::HighByte => &["", "", "", "", "ah", "ch", "dh", "bh"],
+        Kind::Segment => &SEGMENTS,
+        Kind::Xmm => return format!("xmm{}", reg.num),
+        Kind::Ymm => return format!("ymm{}", reg.num),
+        Kind::Mmx => return format!("mm{}", reg.num),
+        Kind::Control => return format!("cr{}", reg.num),
+        Kind::Debug => return format!("dr{}", reg.num),
+        Kind::St => return format!("st({})", reg.num),
+        Kind::Rip => return "rip".to_string(),
+        Kind::Eip => return "eip".to_string(),
+    };
+
+    names[reg.num as usize].to_string()
+}
+
+fn scale_bits(scale: u8) -> u8 {
+    match scale {
+        1 => 0,
+        2 => 1,
+        4 => 2,
+        _ => 3,
+    }
+}
+
+/// Checks that an immediate fits in an operand of `size` bytes, returning it sign-extended.
+/// Qword operands take a sign-extended dword.
+fn immediate(value: i64, size: u8) -> Result<i64, Error> {
+    let bits = size.min(4) as u32 * 8;
+    if fits_signed(value, bits) {
+        Ok(value)
+    } else if size < 8 && fits_unsigned(value, bits) {
+        Ok(value << (64 - bits) >> (64 - bits))
+    } else {
+        Err(Error::OutOfRange(value))
+    }
+}
+
+/// Checks that an immediate fits in an unsigned or signed byte.
+fn imm8(value: i64) -> Result<i64, Error> {
+    immediate(value, 1)
+}
+
+/// Size of the operands, which have to be the same if both are known.
+fn common_size(a: &Operand, b: &Operand) -> Result<u8, Error> {
+    match (a.size(), b.size()) {
+        (Some(a), Some(b)) if a != b => Err(Error::InvalidOperands),
+        (Some(size), _) | (_, Some(size)) => Ok(size),
+        (None, None) => Err(Error::InvalidOperands),
+    }
+}
+
+/// Number of a condition code, as used by `jcc`, `setcc` and `cmovcc`.
+fn condition(name: &str) -> Option<u8> {
+    Some(match name {
+        "o" => 0,
+        "no" => 1,
+        "b" | "c" | "nae" => 2,
+        "nb" | "ae" | "nc" => 3,
+        "z" | "e" => 4,
+        "nz" | "ne" => 5,
+        "na" | "be" => 6,
+        "a" | "nbe" => 7,
+        "s" => 8,
+        "ns" => 9,
+        "p" | "pe" => 10,
+        "np" | "po" => 11,
+        "l" | "nge" => 12,
+        "ge" | "nl" => 13,
+        "le" | "ng" => 14,
+        "g" | "nle" => 15,
+        _ => return None,
+    })
+}
+
+/// Instructions without operands.
+const FIXED: &[(&str, &[u8])] = &[
+    ("nop", &[0x90]),
+    ("pause", &[0xf3, 0x90]),
+    ("hlt", &[0xf4]),
+    ("int3", &[0xcc]),
+    ("int1", &[0xf1]),
+    ("leave", &[0xc9]),
+    ("ret", &[0xc3]),
+    ("retf", &[0xcb]),
+    ("iretq", &[0x48, 0xcf]),
+    ("syscall", &[0x0f, 0x05]),
+    ("sysenter", &[0x0f, 0x34]),
+    ("ud2", &[0x0f, 0x0b]),
+    ("cpuid", &[0x0f, 0xa2]),
+    ("rdtsc", &[0x0f, 0x31]),
+    ("rdtscp", &[0x0f, 0x01, 0xf9]),
+    ("rdmsr", &[0x0f, 0x32]),
+    ("wrmsr", &[0x0f, 0x30]),
+    ("rdpmc", &[0x0f, 0x33]),
+    ("xgetbv", &[0x0f, 0x01, 0xd0]),
+    ("swapgs", &[0x0f, 0x01, 0xf8]),
+    ("endbr64", &[0xf3, 0x0f, 0x1e, 0xfa]),
+    ("endbr32", &[0xf3, 0x0f, 0x1e, 0xfb]),
+    ("cbw", &[0x66, 0x98]),
+    ("cwde", &[0x98]),
+    ("cdqe", &[0x48, 0x98]),
+    ("cwd", &[0x66, 0x99]),
+    ("cdq", &[0x99]),
+    ("cqo", &[0x48, 0x99]),
+    ("clc", &[0xf8]),
+    ("stc", &[0xf9]),
+    ("cmc", &[0xf5]),
+    ("cld", &[0xfc]),
+    ("std", &[0xfd]),
+    ("cli", &[0xfa]),
+    ("sti", &[0xfb]),
+    ("sahf", &[0x9e]),
+    ("lahf", &[0x9f]),
+    ("pushf", &[0x9c]),
+    ("pushfq", &[0x9c]),
+    ("popf", &[0x9d]),
+    ("popfq", &[0x9d]),
+    ("wait", &[0x9b]),
+    ("fwait", &[0x9b]),
+    ("lfence", &[0x0f, 0xae, 0xe8]),
+    ("mfence", &[0x0f, 0xae, 0xf0]),
+    ("sfence", &[0x0f, 0xae, 0xf8]),
+    ("emms", &[0x0f, 0x77]),
+    ("wbinvd", &[0x0f, 0x09]),
+    ("invd", &[0x0f, 0x08]),
+    ("clts", &[0x0f, 0x06]),
+    ("monitor", &[0x0f, 0x01, 0xc8]),
+    ("mwait", &[0x0f, 0x01, 0xc9]),
+    ("clac", &[0x0f, 0x01, 0xca]),
+    ("stac", &[0x0f, 0x01, 0xcb]),
+    ("xend", &[0x0f, 0x01, 0xd5]),
+    ("xtest", &[0x0f, 0x01, 0xd6]),
+    ("rdpkru", &[0x0f, 0x01, 0xee]),
+    ("wrpkru", &[0x0f, 0x01, 0xef]),
+    ("fnop", &[0xd9, 0xd0]),
+    ("fchs", &[0xd9, 0xe0]),
+    ("fabs", &[0xd9, 0xe1]),
+    ("ftst", &[0xd9, 0xe4]),
+    ("fxam", &[0xd9, 0xe5]),
+    ("fld1", &[0xd9, 0xe8]),
+    ("fldl2t", &[0xd9, 0xe9]),
+    ("fldl2e", &[0xd9, 0xea]),
+    ("fldpi", &[0xd9, 0xeb]),
+    ("fldlg2", &[0xd9, 0xec]),
+    ("fldln2", &[0xd9, 0xed]),
+    ("fldz", &[0xd9, 0xee]),
+    ("f2xm1", &[0xd9, 0xf0]),
+    ("fyl2x", &[0xd9, 0xf1]),
+    ("fptan", &[0xd9, 0xf2]),
+    ("fpatan", &[0xd9, 0xf3]),
+    ("fxtract", &[0xd9, 0xf4]),
+    ("fprem1", &[0xd9, 0xf5]),
+    ("fdecstp", &[0xd9, 0xf6]),
+    ("fincstp", &[0xd9, 0xf7]),
+    ("fprem", &[0xd9, 0xf8]),
+    ("fyl2xp1", &[0xd9, 0xf9]),
+    ("fsqrt", &[0xd9, 0xfa]),
+    ("fsincos", &[0xd9, 0xfb]),
+    ("frndint", &[0xd9, 0xfc]),
+    ("fscale", &[0xd9, 0xfd]),
+    ("fsin", &[0xd9, 0xfe]),
+    ("fcos", &[0xd9, 0xff]),
+    ("fucompp", &[0xda, 0xe9]),
+    ("fnclex", &[0xdb, 0xe2]),
+    ("fninit", &[0xdb, 0xe3]),
+    ("fcompp", &[0xde, 0xd9]),
+    ("xlat", &[0xd7]),
+    ("vzeroupper", &[0xc5, 0xf8, 0x77]),
+    ("vzeroall", &[0xc5, 0xfc, 0x77]),
+];
+
+/// Instructions with only a memory operand, their opcode and opcode extension.
+const MEMORY: &[(&str, &[u8], u8)] = &[
+    ("prefetchnta", &[0x0f, 0x18], 0),
+    ("prefetch0", &[0x0f, 0x18], 1),
+    ("prefetch1", &[0x0f, 0x18], 2),
+    ("prefetch2", &[0x0f, 0x18], 3),
+    ("prefetchw", &[0x0f, 0x0d], 1),
+    ("fxsave", &[0x0f, 0xae], 0),
+    ("fxrstor", &[0x0f, 0xae], 1),
+    ("ldmxcsr", &[0x0f, 0xae], 2),
+    ("stmxcsr", &[0x0f, 0xae], 3),
+    ("xsave", &[0x0f, 0xae], 4),
+    ("xrstor", &[0x0f, 0xae], 5),
+    ("xsaveopt", &[0x0f, 0xae], 6),
+    ("clflush", &[0x0f, 0xae], 7),
+    ("sgdt", &[0x0f, 0x01], 0),
+    ("sidt", &[0x0f, 0x01], 1),
+    ("lgdt", &[0x0f, 0x01], 2),
+    ("lidt", &[0x0f, 0x01], 3),
+    ("invlpg", &[0x0f, 0x01], 7),
+];
+
+/// x87 instructions with a memory operand: mnemonic, size of the operand, opcode and opcode
+/// extension. Sizes of `0` are for operands without a size, such as the FPU environment.
+#[rustfmt::skip]
+const X87_MEMORY: &[(&str, u8, u8, u8)] = &[
+    ("fld", 4, 0xd9, 0), ("fld", 8, 0xdd, 0), ("fld", 10, 0xdb, 5),
+    ("fst", 4, 0xd9, 2), ("fst", 8, 0xdd, 2),
+    ("fstp", 4, 0xd9, 3), ("fstp", 8, 0xdd, 3), ("fstp", 10, 0xdb, 7),
+    ("fild", 2, 0xdf, 0), ("fild", 4, 0xdb, 0), ("fild", 8, 0xdf, 5),
+    ("fist", 2, 0xdf, 2), ("fist", 4, 0xdb, 2),
+    ("fistp", 2, 0xdf, 3), ("fistp", 4, 0xdb, 3), ("fistp", 8, 0xdf, 7),
+    ("fisttp", 2, 0xdf, 1), ("fisttp", 4, 0xdb, 1), ("fisttp", 8, 0xdd, 1),
+    ("fbld", 10, 0xdf, 4), ("fbstp", 10, 0xdf, 6),
+    ("fadd", 4, 0xd8, 0), ("fadd", 8, 0xdc, 0),
+    ("fmul", 4, 0xd8, 1), ("fmul", 8, 0xdc, 1),
+    ("fcom", 4, 0xd8, 2), ("fcom", 8, 0xdc, 2),
+    ("fcomp", 4, 0xd8, 3), ("fcomp", 8, 0xdc, 3),
+    ("fsub", 4, 0xd8, 4), ("fsub", 8, 0xdc, 4),
+    ("fsubr", 4, 0xd8, 5), ("fsubr", 8, 0xdc, 5),
+    ("fdiv", 4, 0xd8, 6), ("fdiv", 8, 0xdc, 6),
+    ("fdivr", 4, 0xd8, 7), ("fdivr", 8, 0xdc, 7),
+    ("fiadd", 2, 0xde, 0), ("fiadd", 4, 0xda, 0),
+    ("fimul", 2, 0xde, 1), ("fimul", 4, 0xda, 1),
+    ("ficom", 2, 0xde, 2), ("ficom", 4, 0xda, 2),
+    ("ficomp", 2, 0xde, 3), ("ficomp", 4, 0xda, 3),
+    ("fisub", 2, 0xde, 4), ("fisub", 4, 0xda, 4),
+    ("fisubr", 2, 0xde, 5), ("fisubr", 4, 0xda, 5),
+    ("fidiv", 2, 0xde, 6), ("fidiv", 4, 0xda, 6),
+    ("fidivr", 2, 0xde, 7), ("fidivr", 4, 0xda, 7),
+    ("fldcw", 2, 0xd9, 5), ("fnstcw", 2, 0xd9, 7), ("fnstsw", 2, 0xdd, 7),
+    ("fldenv", 0, 0xd9, 4), ("fnstenv", 0, 0xd9, 6),
+    ("frstor", 0, 0xdd, 4), ("fnsave", 0, 0xdd, 6),
+];
+
+/// x87 instructions with a register operand: mnemonic, opcode, second byte that the register
+/// is added to and whether the register comes before `st(0)`.
+#[rustfmt::skip]
+const X87_REGISTER: &[(&str, u8, u8, bool)] = &[
+    ("fld", 0xd9, 0xc0, false), ("fxch", 0xd9, 0xc8, false),
+    ("fst", 0xdd, 0xd0, true), ("fstp", 0xdd, 0xd8, true), ("ffree", 0xdd, 0xc0, true),
+    ("fucom", 0xdd, 0xe0, false), ("fucomp", 0xdd, 0xe8, false),
+    ("fadd", 0xd8, 0xc0, false), ("fadd", 0xdc, 0xc0, true),
+    ("fmul", 0xd8, 0xc8, false), ("fmul", 0xdc, 0xc8, true),
+    ("fcom", 0xd8, 0xd0, false), ("fcomp", 0xd8, 0xd8, false),
+    ("fsub", 0xd8, 0xe0, false), ("fsub", 0xdc, 0xe8, true),
+    ("fsubr", 0xd8, 0xe8, false), ("fsubr", 0xdc, 0xe0, true),
+    ("fdiv", 0xd8, 0xf0, false), ("fdiv", 0xdc, 0xf8, true),
+    ("fdivr", 0xd8, 0xf8, false), ("fdivr", 0xdc, 0xf0, true),
+    ("faddp", 0xde, 0xc0, true), ("fmulp", 0xde, 0xc8, true),
+    ("fsubrp", 0xde, 0xe0, true), ("fsubp", 0xde, 0xe8, true),
+    ("fdivrp", 0xde, 0xf0, true), ("fdivp", 0xde, 0xf8, true),
+    ("fcmovb", 0xda, 0xc0, false), ("fcmove", 0xda, 0xc8, false),
+    ("fcmovbe", 0xda, 0xd0, false), ("fcmovu", 0xda, 0xd8, false),
+    ("fcmovnb", 0xdb, 0xc0, false), ("fcmovne", 0xdb, 0xc8, false),
+    ("fcmovnbe", 0xdb, 0xd0, false), ("fcmovnu", 0xdb, 0xd8, false),
+    ("fucomi", 0xdb, 0xe8, false), ("fcomi", 0xdb, 0xf0, false),
+    ("fucomip", 0xdf, 0xe8, false), ("fcomip", 0xdf, 0xf0, false),
+];
+
+/// Arithmetic instructions sharing the same encodings, by their opcode extension.
+const ARITHMETIC: [&str; 8] = ["add", "or", "adc", "sbb", "and", "sub", "xor", "cmp"];
+
+/// Instructions with a single r/m operand, their byte sized opcode and opcode extension.
+const UNARY: &[(&str, u8, u8)] = &[
+    ("inc", 0xfe, 0),
+    ("dec", 0xfe, 1),
+    ("not", 0xf6, 2),
+    ("neg", 0xf6, 3),
+    ("mul", 0xf6, 4),
+    ("div", 0xf6, 6),
+    ("idiv", 0xf6, 7),
+];
+
+/// Shifts and rotates by their opcode extension. `sal` has its own extension so it's shown
+/// the same way after being decoded.
+const SHIFTS: [&str; 8] = ["rol", "ror", "rcl", "rcr", "shl", "shr", "sal", "sar"];
+
+/// Instructions taking a register and r/m operand of the same size.
+const REG_RM: &[(&str, Option<u8>, &[u8])] = &[
+    ("bsf", None, &[0x0f, 0xbc]),
+    ("bsr", None, &[0x0f, 0xbd]),
+    ("tzcnt", Some(0xf3), &[0x0f, 0xbc]),
+    ("lzcnt", Some(0xf3), &[0x0f, 0xbd]),
+    ("popcnt", Some(0xf3), &[0x0f, 0xb8]),
+];
+
+/// String instructions and the opcode of their byte sized versions.
+const STRINGS: &[(&str, u8)] = &[
+    ("movs", 0xa4),
+    ("cmps", 0xa6),
+    ("stos", 0xaa),
+    ("lods", 0xac),
+    ("scas", 0xae),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Form {
+    /// `xmm, xmm/m`
+    Rm,
+    /// `xmm, m`
+    Load,
+    /// `m, xmm` and `xmm, xmm` with the operands swapped.
+    Mr,
+    /// `xmm, xmm/m, imm8`
+    RmImm,
+    /// `xmm, imm8` with an opcode extension.
+    Shift(u8),
+    /// `r32/r64, xmm/m`
+    GprRm,
+    /// `r32/r64, xmm, imm8`
+    GprRmImm,
+    /// `xmm, r/m32` or `xmm, r/m64`
+    RmGpr,
+    /// `xmm, r/m32, imm8`
+    RmGprImm,
+    /// `r/m32, xmm` or `r/m64, xmm`
+    GprMr,
+    /// `xmm/m, xmm, imm8`
+    MrImm,
+}
+
+impl Form {
+    /// Number of operands without the extra one that VEX encoded versions can have.
+    fn operands(self) -> usize {
+        match self {
+            Self::RmImm | Self::GprRmImm | Self::RmGprImm | Self::MrImm => 3,
+            _ => 2,
+        }
+    }
+}
+
+/// SSE instructions: mnemonic, mandatory prefix, opcode, operands and whether they also
+/// have an MMX version without the prefix.
+#[rustfmt::skip]
+const SSE: &[(&str, u8, &[u8], Form, bool)] = &[
+    ("movups", 0, &[0x0f, 0x10], Form::Rm, false),
+    ("movups", 0, &[0x0f, 0x11], Form::Mr, false),
+    ("movupd", 0x66, &[0x0f, 0x10], Form::Rm, false),
+    ("movupd", 0x66, &[0x0f, 0x11], Form::Mr, false),
+    ("movss", 0xf3, &[0x0f, 0x10], Form::Rm, false),
+    ("movss", 0xf3, &[0x0f, 0x11], Form::Mr, false),
+    ("movsd", 0xf2, &[0x0f, 0x10], Form::Rm, false),
+    ("movsd", 0xf2, &[0x0f, 0x11], Form::Mr, false),
+    ("movlps", 0, &[0x0f, 0x12], Form::Load, false),
+    ("movlps", 0, &[0x0f, 0x13], Form::Mr, false),
+    ("movhlps", 0, &[0x0f, 0x12], Form::Rm, false),
+    ("movlpd", 0x66, &[0x0f, 0x12], Form::Load, false),
+    ("movlpd", 0x66, &[0x0f, 0x13], Form::Mr, false),
+    ("unpcklps", 0, &[0x0f, 0x14], Form::Rm, false),
+    ("unpcklpd", 0x66, &[0x0f, 0x14], Form::Rm, false),
+    ("unpckhps", 0, &[0x0f, 0x15], Form::Rm, false),
+    ("unpckhpd", 0x66, &[0x0f, 0x15], Form::Rm, false),
+    ("movhps", 0, &[0x0f, 0x16], Form::Load, false),
+    ("movhps", 0, &[0x0f, 0x17], Form::Mr, false),
+    ("movlhps", 0, &[0x0f, 0x16], Form::Rm, false),
+    ("movhpd", 0x66, &[0x0f, 0x16], Form::Load, false),
+    ("movhpd", 0x66, &[0x0f, 0x17], Form::Mr, false),
+    ("movaps", 0, &[0x0f, 0x28], Form::Rm, false),
+    ("movaps", 0, &[0x0f, 0x29], Form::Mr, false),
+    ("movapd", 0x66, &[0x0f, 0x28], Form::Rm, false),
+    ("movapd", 0x66, &[0x0f, 0x29], Form::Mr, false),
+    ("cvtsi2ss", 0xf3, &[0x0f, 0x2a], Form::RmGpr, false),
+    ("cvtsi2sd", 0xf2, &[0x0f, 0x2a], Form::RmGpr, false),
+    ("movntps", 0, &[0x0f, 0x2b], Form::Mr, false),
+    ("movntpd", 0x66, &[0x0f, 0x2b], Form::Mr, false),
+    ("cvttss2si", 0xf3, &[0x0f, 0x2c], Form::GprRm, false),
+    ("cvttsd2si", 0xf2, &[0x0f, 0x2c], Form::GprRm, false),
+    ("cvtss2si", 0xf3, &[0x0f, 0x2d], Form::GprRm, false),
+    ("cvtsd2si", 0xf2, &[0x0f, 0x2d], Form::GprRm, false),
+    ("ucomiss", 0, &[0x0f, 0x2e], Form::Rm, false),
+    ("ucomisd", 0x66, &[0x0f, 0x2e], Form::Rm, false),
+    ("comiss", 0, &[0x0f, 0x2f], Form::Rm, false),
+    ("comisd", 0x66, &[0x0f, 0x2f], Form::Rm, false),
+    ("movmskps", 0, &[0x0f, 0x50], Form::GprRm, false),
+    ("movmskpd", 0x66, &[0x0f, 0x50], Form::GprRm, false),
+    ("sqrtps", 0, &[0x0f, 0x51], Form::Rm, false),
+    ("sqrtpd", 0x66, &[0x0f, 0x51], Form::Rm, false),
+    ("sqrtss", 0xf3, &[0x0f, 0x51], Form::Rm, false),
+    ("sqrtsd", 0xf2, &[0x0f, 0x51], Form::Rm, false),
+    ("rsqrtps", 0, &[0x0f, 0x52], Form::Rm, false),
+    ("rsqrtss", 0xf3, &[0x0f, 0x52], Form::Rm, false),
+    ("rcpps", 0, &[0x0f, 0x53], Form::Rm, false),
+    ("rcpss", 0xf3, &[0x0f, 0x53], Form::Rm, false),
+    ("andps", 0, &[0x0f, 0x54], Form::Rm, false),
+    ("andpd", 0x66, &[0x0f, 0x54], Form::Rm, false),
+    ("andnps", 0, &[0x0f, 0x55], Form::Rm, false),
+    ("andnpd", 0x66, &[0x0f, 0x55], Form::Rm, false),
+    ("orps", 0, &[0x0f, 0x56], Form::Rm, false),
+    ("orpd", 0x66, &[0x0f, 0x56], Form::Rm, false),
+    ("xorps", 0, &[0x0f, 0x57], Form::Rm, false),
+    ("xorpd", 0x66, &[0x0f, 0x57], Form::Rm, false),
+    ("addps", 0, &[0x0f, 0x58], Form::Rm, false),
+    ("addpd", 0x66, &[0x0f, 0x58], Form::Rm, false),
+    ("addss", 0xf3, &[0x0f, 0x58], Form::Rm, false),
+    ("addsd", 0xf2, &[0x0f, 0x58], Form::Rm, false),
+    ("mulps", 0, &[0x0f, 0x59], Form::Rm, false),
+    ("mulpd", 0x66, &[0x0f, 0x59], Form::Rm, false),
+    ("mulss", 0xf3, &[0x0f, 0x59], Form::Rm, false),
+    ("mulsd", 0xf2, &[0x0f, 0x59], Form::Rm, false),
+    ("cvtps2pd", 0, &[0x0f, 0x5a], Form::Rm, false),
+    ("cvtpd2ps", 0x66, &[0x0f, 0x5a], Form::Rm, false),
+    ("cvtss2sd", 0xf3, &[0x0f, 0x5a], Form::Rm, false),
+    ("cvtsd2ss", 0xf2, &[0x0f, 0x5a], Form::Rm, false),
+    ("cvtdq2ps", 0, &[0x0f, 0x5b], Form::Rm, false),
+    ("cvtps2dq", 0x66, &[0x0f, 0x5b], Form::Rm, false),
+    ("cvttps2dq", 0xf3, &[0x0f, 0x5b], Form::Rm, false),
+    ("subps", 0, &[0x0f, 0x5c], Form::Rm, false),
+    ("subpd", 0x66, &[0x0f, 0x5c], Form::Rm, false),
+    ("subss", 0xf3, &[0x0f, 0x5c], Form::Rm, false),
+    ("subsd", 0xf2, &[0x0f, 0x5c], Form::Rm, false),
+    ("minps", 0, &[0x0f, 0x5d], Form::Rm, false),
+    ("minpd", 0x66, &[0x0f, 0x5d], Form::Rm, false),
+    ("minss", 0xf3, &[0x0f, 0x5d], Form::Rm, false),
+    ("minsd", 0xf2, &[0x0f, 0x5d], Form::Rm, false),
+    ("divps", 0, &[0x0f, 0x5e], Form::Rm, false),
+    ("divpd", 0x66, &[0x0f, 0x5e], Form::Rm, false),
+    ("divss", 0xf3, &[0x0f, 0x5e], Form::Rm, false),
+    ("divsd", 0xf2, &[0x0f, 0x5e], Form::Rm, false),
+    ("maxps", 0, &[0x0f, 0x5f], Form::Rm, false),
+    ("maxpd", 0x66, &[0x0f, 0x5f], Form::Rm, false),
+    ("maxss", 0xf3, &[0x0f, 0x5f], Form::Rm, false),
+    ("maxsd", 0xf2, &[0x0f, 0x5f], Form::Rm, false),
+    ("punpcklbw", 0x66, &[0x0f, 0x60], Form::Rm, true),
+    ("punpcklwd", 0x66, &[0x0f, 0x61], Form::Rm, true),
+    ("punpckldq", 0x66, &[0x0f, 0x62], Form::Rm, true),
+    ("packsswb", 0x66, &[0x0f, 0x63], Form::Rm, true),
+    ("pcmpgtb", 0x66, &[0x0f, 0x64], Form::Rm, true),
+    ("pcmpgtw", 0x66, &[0x0f, 0x65], Form::Rm, true),
+    ("pcmpgtd", 0x66, &[0x0f, 0x66], Form::Rm, true),
+    ("packuswb", 0x66, &[0x0f, 0x67], Form::Rm, true),
+    ("punpckhbw", 0x66, &[0x0f, 0x68], Form::Rm, true),
+    ("punpckhwd", 0x66, &[0x0f, 0x69], Form::Rm, true),
+    ("punpckhdq", 0x66, &[0x0f, 0x6a], Form::Rm, true),
+    ("packssdw", 0x66, &[0x0f, 0x6b], Form::Rm, true),
+    ("punpcklqdq", 0x66, &[0x0f, 0x6c], Form::Rm, false),
+    ("punpckhqdq", 0x66, &[0x0f, 0x6d], Form::Rm, false),
+    ("movdqa", 0x66, &[0x0f, 0x6f], Form::Rm, false),
+    ("movdqa", 0x66, &[0x0f, 0x7f], Form::Mr, false),
+    ("movdqu", 0xf3, &[0x0f, 0x6f], Form::Rm, false),
+    ("movdqu", 0xf3, &[0x0f, 0x7f], Form::Mr, false),
+    ("pshufd", 0x66, &[0x0f, 0x70], Form::RmImm, false),
+    ("pshufhw", 0xf3, &[0x0f, 0x70], Form::RmImm, false),
+    ("pshuflw", 0xf2, &[0x0f, 0x70], Form::RmImm, false),
+    ("psrlw", 0x66, &[0x0f, 0x71], Form::Shift(2), true),
+    ("psraw", 0x66, &[0x0f, 0x71], Form::Shift(4), true),
+    ("psllw", 0x66, &[0x0f, 0x71], Form::Shift(6), true),
+    ("psrld", 0x66, &[0x0f, 0x72], Form::Shift(2), true),
+    ("psrad", 0x66, &[0x0f, 0x72], Form::Shift(4), true),
+    ("pslld", 0x66, &[0x0f, 0x72], Form::Shift(6), true),
+    ("psrlq", 0x66, &[0x0f, 0x73], Form::Shift(2), true),
+    ("psrldq", 0x66, &[0x0f, 0x73], Form::Shift(3), false),
+    ("psllq", 0x66, &[0x0f, 0x73], Form::Shift(6), true),
+    ("pslldq", 0x66, &[0x0f, 0x73], Form::Shift(7), false),
+    ("pcmpeqb", 0x66, &[0x0f, 0x74], Form::Rm, true),
+    ("pcmpeqw", 0x66, &[0x0f, 0x75], Form::Rm, true),
+    ("pcmpeqd", 0x66, &[0x0f, 0x76], Form::Rm, true),
+    ("haddpd", 0x66, &[0x0f, 0x7c], Form::Rm, false),
+    ("haddps", 0xf2, &[0x0f, 0x7c], Form::Rm, false),
+    ("hsubpd", 0x66, &[0x0f, 0x7d], Form::Rm, false),
+    ("hsubps", 0xf2, &[0x0f, 0x7d], Form::Rm, false),
+    ("movq", 0xf3, &[0x0f, 0x7e], Form::Rm, false),
+    ("movq", 0x66, &[0x0f, 0xd6], Form::Mr, false),
+    ("movq", 0x66, &[0x0f, 0x6e], Form::RmGpr, false),
+    ("movq", 0x66, &[0x0f, 0x7e], Form::GprMr, false),
+    ("movd", 0x66, &[0x0f, 0x6e], Form::RmGpr, true),
+    ("movd", 0x66, &[0x0f, 0x7e], Form::GprMr, true),
+    ("cmpps", 0, &[0x0f, 0xc2], Form::RmImm, false),
+    ("cmppd", 0x66, &[0x0f, 0xc2], Form::RmImm, false),
+    ("cmpss", 0xf3, &[0x0f, 0xc2], Form::RmImm, false),
+    ("cmpsd", 0xf2, &[0x0f, 0xc2], Form::RmImm, false),
+    ("pinsrw", 0x66, &[0x0f, 0xc4], Form::RmGprImm, true),
+    ("pextrw", 0x66, &[0x0f, 0xc5], Form::GprRmImm, true),
+    ("shufps", 0, &[0x0f, 0xc6], Form::RmImm, false),
+    ("shufpd", 0x66, &[0x0f, 0xc6], Form::RmImm, false),
+    ("psrlw", 0x66, &[0x0f, 0xd1], Form::Rm, true),
+    ("psrld", 0x66, &[0x0f, 0xd2], Form::Rm, true),
+    ("psrlq", 0x66, &[0x0f, 0xd3], Form::Rm, true),
+    ("paddq", 0x66, &[0x0f, 0xd4], Form::Rm, true),
+    ("pmullw", 0x66, &[0x0f, 0xd5], Form::Rm, true),
+    ("pmovmskb", 0x66, &[0x0f, 0xd7], Form::GprRm, true),
+    ("psubusb", 0x66, &[0x0f, 0xd8], Form::Rm, true),
+    ("psubusw", 0x66, &[0x0f, 0xd9], Form::Rm, true),
+    ("pminub", 0x66, &[0x0f, 0xda], Form::Rm, true),
+    ("pand", 0x66, &[0x0f, 0xdb], Form::Rm, true),
+    ("paddusb", 0x66, &[0x0f, 0xdc], Form::Rm, true),
+    ("paddusw", 0x66, &[0x0f, 0xdd], Form::Rm, true),
+    ("pmaxub", 0x66, &[0x0f, 0xde], Form::Rm, true),
+    ("pandn", 0x66, &[0x0f, 0xdf], Form::Rm, true),
+    ("pavgb", 0x66, &[0x0f, 0xe0], Form::Rm, true),
+    ("psraw", 0x66, &[0x0f, 0xe1], Form::Rm, true),
+    ("psrad", 0x66, &[0x0f, 0xe2], Form::Rm, true),
+    ("pavgw", 0x66, &[0x0f, 0xe3], Form::Rm, true),
+    ("pmulhuw", 0x66, &[0x0f, 0xe4], Form::Rm, true),
+    ("pmulhw", 0x66, &[0x0f, 0xe5], Form::Rm, true),
+    ("cvttpd2dq", 0x66, &[0x0f, 0xe6], Form::Rm, false),
+    ("cvtdq2pd", 0xf3, &[0x0f, 0xe6], Form::Rm, false),
+    ("cvtpd2dq", 0xf2, &[0x0f, 0xe6], Form::Rm, false),
+    ("movntdq", 0x66, &[0x0f, 0xe7], Form::Mr, false),
+    ("psubsb", 0x66, &[0x0f, 0xe8], Form::Rm, true),
+    ("psubsw", 0x66, &[0x0f, 0xe9], Form::Rm, true),
+    ("pminsw", 0x66, &[0x0f, 0xea], Form::Rm, true),
+    ("por", 0x66, &[0x0f, 0xeb], Form::Rm, true),
+    ("paddsb", 0x66, &[0x0f, 0xec], Form::Rm, true),
+    ("paddsw", 0x66, &[0x0f, 0xed], Form::Rm, true),
+    ("pmaxsw", 0x66, &[0x0f, 0xee], Form::Rm, true),
+    ("pxor", 0x66, &[0x0f, 0xef], Form::Rm, true),
+    ("lddqu", 0xf2, &[0x0f, 0xf0], Form::Load, false),
+    ("psllw", 0x66, &[0x0f, 0xf1], Form::Rm, true),
+    ("pslld", 0x66, &[0x0f, 0xf2], Form::Rm, true),
+    ("psllq", 0x66, &[0x0f, 0xf3], Form::Rm, true),
+    ("pmuludq", 0x66, &[0x0f, 0xf4], Form::Rm, true),
+    ("pmaddwd", 0x66, &[0x0f, 0xf5], Form::Rm, true),
+    ("psadbw", 0x66, &[0x0f, 0xf6], Form::Rm, true),
+    ("psubb", 0x66, &[0x0f, 0xf8], Form::Rm, true),
+    ("psubw", 0x66, &[0x0f, 0xf9], Form::Rm, true),
+    ("psubd", 0x66, &[0x0f, 0xfa], Form::Rm, true),
+    ("psubq", 0x66, &[0x0f, 0xfb], Form::Rm, true),
+    ("paddb", 0x66, &[0x0f, 0xfc], Form::Rm, true),
+    ("paddw", 0x66, &[0x0f, 0xfd], Form::Rm, true),
+    ("paddd", 0x66, &[0x0f, 0xfe], Form::Rm, true),
+    ("pshufb", 0x66, &[0x0f, 0x38, 0x00], Form::Rm, true),
+    ("phaddw", 0x66, &[0x0f, 0x38, 0x01], Form::Rm, true),
+    ("phaddd", 0x66, &[0x0f, 0x38, 0x02], Form::Rm, true),
+    ("pmaddubsw", 0x66, &[0x0f, 0x38, 0x04], Form::Rm, true),
+    ("psignb", 0x66, &[0x0f, 0x38, 0x08], Form::Rm, true),
+    ("psignw", 0x66, &[0x0f, 0x38, 0x09], Form::Rm, true),
+    ("psignd", 0x66, &[0x0f, 0x38, 0x0a], Form::Rm, true),
+    ("ptest", 0x66, &[0x0f, 0x38, 0x17], Form::Rm, false),
+    ("pabsb", 0x66, &[0x0f, 0x38, 0x1c], Form::Rm, true),
+    ("pabsw", 0x66, &[0x0f, 0x38, 0x1d], Form::Rm, true),
+    ("pabsd", 0x66, &[0x0f, 0x38, 0x1e], Form::Rm, true),
+    ("pcmpeqq", 0x66, &[0x0f, 0x38, 0x29], Form::Rm, false),
+    ("pcmpgtq", 0x66, &[0x0f, 0x38, 0x37], Form::Rm, false),
+    ("pminsb", 0x66, &[0x0f, 0x38, 0x38], Form::Rm, false),
+    ("pminsd", 0x66, &[0x0f, 0x38, 0x39], Form::Rm, false),
+    ("pminuw", 0x66, &[0x0f, 0x38, 0x3a], Form::Rm, false),
+    ("pminud", 0x66, &[0x0f, 0x38, 0x3b], Form::Rm, false),
+    ("pmaxsb", 0x66, &[0x0f, 0x38, 0x3c], Form::Rm, false),
+    ("pmaxsd", 0x66, &[0x0f, 0x38, 0x3d], Form::Rm, false),
+    ("pmaxuw", 0x66, &[0x0f, 0x38, 0x3e], Form::Rm, false),
+    ("pmaxud", 0x66, &[0x0f, 0x38, 0x3f], Form::Rm, false),
+    ("pmulld", 0x66, &[0x0f, 0x38, 0x40], Form::Rm, false),
+    ("palignr", 0x66, &[0x0f, 0x3a, 0x0f], Form::RmImm, true),
+    ("pcmpestri", 0x66, &[0x0f, 0x3a, 0x61], Form::RmImm, false),
+    ("pcmpistri", 0x66, &[0x0f, 0x3a, 0x63], Form::RmImm, false),
+];
+
+/// Instructions that only have a VEX encoding: mnemonic, mandatory prefix, opcode, operands
+/// and whether they need VEX.W set.
+#[rustfmt::skip]
+const AVX: &[(&str, u8, &[u8], Form, bool)] = &[
+    ("vpermps", 0x66, &[0x0f, 0x38, 0x16], Form::Rm, false),
+    ("vbroadcastss", 0x66, &[0x0f, 0x38, 0x18], Form::Rm, false),
+    ("vbroadcastsd", 0x66, &[0x0f, 0x38, 0x19], Form::Rm, false),
+    ("vpermd", 0x66, &[0x0f, 0x38, 0x36], Form::Rm, false),
+    ("vpsrlvd", 0x66, &[0x0f, 0x38, 0x45], Form::Rm, false),
+    ("vpsrlvq", 0x66, &[0x0f, 0x38, 0x45], Form::Rm, true),
+    ("vpsravd", 0x66, &[0x0f, 0x38, 0x46], Form::Rm, false),
+    ("vpsllvd", 0x66, &[0x0f, 0x38, 0x47], Form::Rm, false),
+    ("vpsllvq", 0x66, &[0x0f, 0x38, 0x47], Form::Rm, true),
+    ("vpbroadcastd", 0x66, &[0x0f, 0x38, 0x58], Form::Rm, false),
+    ("vpbroadcastq", 0x66, &[0x0f, 0x38, 0x59], Form::Rm, false),
+    ("vpbroadcastb", 0x66, &[0x0f, 0x38, 0x78], Form::Rm, false),
+    ("vpbroadcastw", 0x66, &[0x0f, 0x38, 0x79], Form::Rm, false),
+    ("vpermq", 0x66, &[0x0f, 0x3a, 0x00], Form::RmImm, true),
+    ("vpermpd", 0x66, &[0x0f, 0x3a, 0x01], Form::RmImm, true),
+    ("vpblendd", 0x66, &[0x0f, 0x3a, 0x02], Form::RmImm, false),
+    ("vperm2f128", 0x66, &[0x0f, 0x3a, 0x06], Form::RmImm, false),
+    ("vinsertf128", 0x66, &[0x0f, 0x3a, 0x18], Form::RmImm, false),
+    ("vextractf128", 0x66, &[0x0f, 0x3a, 0x19], Form::MrImm, false),
+    ("vinserti128", 0x66, &[0x0f, 0x3a, 0x38], Form::RmImm, false),
+    ("vextracti128", 0x66, &[0x0f, 0x3a, 0x39], Form::MrImm, false),
+    ("vperm2i128", 0x66, &[0x0f, 0x3a, 0x46], Form::RmImm, false),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Bmi {
+    /// `r, r/m, r` with the last register in VEX.vvvv.
+    RmV,
+    /// `r, r, r/m` with the middle register in VEX.vvvv.
+    VRm,
+    /// `r, r/m` with the destination in VEX.vvvv and an opcode extension.
+    Digit(u8),
+    /// `r, r/m, imm8`
+    RmImm,
+}
+
+/// Bit manipulation instructions that operate on general purpose registers, their mandatory
+/// prefix, opcode and operands.
+#[rustfmt::skip]
+const BMI: &[(&str, u8, &[u8], Bmi)] = &[
+    ("andn", 0, &[0x0f, 0x38, 0xf2], Bmi::VRm),
+    ("blsr", 0, &[0x0f, 0x38, 0xf3], Bmi::Digit(1)),
+    ("blsmsk", 0, &[0x0f, 0x38, 0xf3], Bmi::Digit(2)),
+    ("blsi", 0, &[0x0f, 0x38, 0xf3], Bmi::Digit(3)),
+    ("bzhi", 0, &[0x0f, 0x38, 0xf5], Bmi::RmV),
+    ("pext", 0xf3, &[0x0f, 0x38, 0xf5], Bmi::VRm),
+    ("pdep", 0xf2, &[0x0f, 0x38, 0xf5], Bmi::VRm),
+    ("mulx", 0xf2, &[0x0f, 0x38, 0xf6], Bmi::VRm),
+    ("bextr", 0, &[0x0f, 0x38, 0xf7], Bmi::RmV),
+    ("shlx", 0x66, &[0x0f, 0x38, 0xf7], Bmi::RmV),
+    ("sarx", 0xf3, &[0x0f, 0x38, 0xf7], Bmi::RmV),
+    ("shrx", 0xf2, &[0x0f, 0x38, 0xf7], Bmi::RmV),
+    ("rorx", 0xf2, &[0x0f, 0x3a, 0xf0], Bmi::RmImm),
+];
+
+/// Assemble a single instruction at `addr`.
+pub fn assemble(src: &str, addr: usize, symbols: &Index) -> Result<Vec<u8>, Error> {
+    let mut p = Parser::new(src, symbols);
+    let mut inst = Inst::default();
+
+    let mut mnemonic = p.mnemonic()?;
+    loop {
+        match mnemonic.as_str() {
+            "lock" => inst.lock = true,
+            "rep" | "repe" | "repz" => inst.rep = Some(0xf3),
+            "repne" | "repnz" => inst.rep = Some(0xf2),
+            _ => break,
+        }
+        mnemonic = p.mnemonic()?;
+    }
+
+    let mut ops = Vec::new();
+    if p.peek().is_some() {
+        loop {
+            ops.push(operand(&mut p)?);
+            if !p.eat(',') {
+                break;
+            }
+        }
+    }
+    p.end()?;
+
+    encode(inst, &mnemonic, &ops, addr)
+}
+
+fn encode(mut inst: Inst, mnemonic: &str, ops: &[Operand], addr: usize) -> Result<Vec<u8>, Error> {
+    use Operand::*;
+
+    if ops.is_empty() {
+        if let Some((_, bytes)) = FIXED.iter().find(|(name, _)| *name == mnemonic) {
+            inst.opcode(bytes);
+            return inst.encode(addr);
+        }
+    }
+
+    if let Some(n) = ARITHMETIC.iter().position(|&name| name == mnemonic) {
+        let n = n as u8;
+        match ops {
+            [dst, Imm(value)] if dst.is_gpr_rm() => {
+                let size = dst.size().ok_or(Error::InvalidOperands)?;
+                let value = immediate(*value, size)?;
+                if size == 1 {
+                    if dst.is_accumulator() {
+                        inst.opcode(&[n * 8 + 4]);
+                    } else {
+                        inst.opcode(&[0x80]);
+                        inst.digit(n);
+                        inst.rm(dst)?;
+                    }
+                    inst.imm(value, 1);
+                } else {
+                    inst.operand_size(size)?;
+                    if fits_signed(value, 8) {
+                        inst.opcode(&[0x83]);
+                        inst.digit(n);
+                        inst.rm(dst)?;
+                        inst.imm(value, 1);
+                    } else {
+                        if dst.is_accumulator() {
+                            inst.opcode(&[n * 8 + 5]);
+                        } else {
+                            inst.opcode(&[0x81]);
+                            inst.digit(n);
+                            inst.rm(dst)?;
+                        }
+                        inst.imm(value, size.min(4));
+                    }
+                }
+            }
+            [dst, src @ Reg(reg)] if dst.is_gpr_rm() && reg.is_gpr() => {
+                inst.sized(&[n * 8], common_size(dst, src)?)?;
+                inst.reg(*reg);
+                inst.rm(dst)?;
+            }
+            [dst @ Reg(reg), src @ Mem(_)] if reg.is_gpr() => {
+                inst.sized(&[n * 8 + 2], common_size(dst, src)?)?;
+                inst.reg(*reg);
+                inst.rm(src)?;
+            }
+            _ => return Err(Error::InvalidOperands),
+        }
+        return inst.encode(addr);
+    }
+
+    if let Some(&(_, opcode, digit)) = UNARY.iter().find(|(name, ..)| *name == mnemonic) {
+        match ops {
+            [dst] if dst.is_gpr_rm() => {
+                inst.sized(&[opcode], dst.size().ok_or(Error::InvalidOperands)?)?;
+                inst.digit(digit);
+                inst.rm(dst)?;
+            }
+            _ => return Err(Error::InvalidOperands),
+        }
+        return inst.encode(addr);
+    }
+
+    if let Some(n) = SHIFTS.iter().position(|&name| name == mnemonic) {
+        let dst = match ops {
+            [dst, _] if dst.is_gpr_rm() => dst,
+            _ => return Err(Error::InvalidOperands),
+        };
+
+        let size = dst.size().ok_or(Error::InvalidOperands)?;
+        match ops[1] {
+            Imm(1) => inst.sized(&[0xd0], size)?,
+            Reg(Register {
+                kind: Kind::Gpr(1),
+                num: 1,
+            }) => inst.sized(&[0xd2], size)?,
+            Imm(value) => {
+                inst.sized(&[0xc0], size)?;
+                inst.imm(imm8(value)?, 1);
+            }
+            _ => return Err(Error::InvalidOperands),
+        }
+        inst.digit(n as u8);
+        inst.rm(dst)?;
+        return inst.encode(addr);
+    }
+
+    if let Some(&(_, prefix, opcode)) = REG_RM.iter().find(|(name, ..)| *name == mnemonic) {
+        match ops {
+            [dst @ Reg(reg), src] if reg.is_gpr() && src.is_gpr_rm() => {
+                inst.mandatory = prefix;
+                inst.opcode(opcode);
+                inst.operand_size(common_size(dst, src)?)?;
+                inst.reg(*reg);
+                inst.rm(src)?;
+            }
+            _ => return Err(Error::InvalidOperands),
+        }
+        return inst.encode(addr);
+    }
+
+    if let Some(&(_, opcode, digit)) = MEMORY.iter().find(|(name, ..)| *name == mnemonic) {
+        match ops {
+            [dst @ Mem(_)] => {
+                inst.opcode(opcode);
+                inst.digit(digit);
+                inst.rm(dst)?;
+            }
+            _ => return Err(Error::InvalidOperands),
+        }
+        return inst.encode(addr);
+    }
+
+    if let Some(&(_, prefix, opcode, form)) = BMI.iter().find(|(name, ..)| *name == mnemonic) {
+        return bmi(inst, prefix, opcode, form, ops, addr);
+    }
+
+    if let Some(&(_, opcode)) = STRINGS.iter().find(|(name, _)| *name == mnemonic) {
+        return string(inst, opcode, ops, addr);
+    }
+
+    // string instructions with their size as a suffix
+    if ops.is_empty() {
+        for &(name, opcode) in STRINGS {
+            let size = match mnemonic.strip_prefix(name) {
+                Some("b") => 1,
+                Some("w") => 2,
+                Some("d") => 4,
+                Some("q") => 8,
+                _ => continue,
+            };
+
+            inst.sized(&[opcode], size)?;
+            return inst.encode(addr);
+        }
+    }
+
+    if let Some(cc) = mnemonic.strip_prefix("set").and_then(condition) {
+        match ops {
+            [dst] if dst.is_gpr_rm() && dst.size().unwrap_or(1) == 1 => {
+                inst.opcode(&[0x0f, 0x90 + cc]);
+                inst.rm(dst)?;
+            }
+            _ => return Err(Error::InvalidOperands),
+        }
+        return inst.encode(addr);
+    }
+
+    if let Some(cc) = mnemonic.strip_prefix("cmov").and_then(condition) {
+        match ops {
+            [dst @ Reg(reg), src] if reg.is_gpr() && src.is_gpr_rm() => {
+                inst.opcode(&[0x0f, 0x40 + cc]);
+                inst.operand_size(common_size(dst, src)?)?;
+                inst.reg(*reg);
+                inst.rm(src)?;
+            }
+            _ => return Err(Error::InvalidOperands),
+        }
+        return inst.encode(addr);
+    }
+
+    if let Some(cc) = mnemonic.strip_prefix('j').and_then(condition) {
+        return match ops {
+            [Imm(target)] => branch(inst, &[0x70 + cc], &[0x0f, 0x80 + cc], *target, addr),
+            _ => Err(Error::InvalidOperands),
+        };
+    }
+
+    match (mnemonic, ops) {
+        ("mov", _) => mov(inst, ops, addr),
+        ("movzx" | "movsx", [dst @ Reg(reg), src]) if reg.is_gpr() && src.is_gpr_rm() => {
+            let opcode = if mnemonic == "movzx" { 0xb6 } else { 0xbe };
+            match src.size() {
+                Some(1) => inst.opcode(&[0x0f, opcode]),
+                Some(2) => inst.opcode(&[0x0f, opcode + 1]),
+                _ => return Err(Error::InvalidOperands),
+            }
+            inst.operand_size(dst.size().unwrap_or(0))?;
+            inst.reg(*reg);
+            inst.rm(src)?;
+            inst.encode(addr)
+        }
+        ("movsxd", [dst @ Reg(reg), src]) if reg.is_gpr() && src.is_gpr_rm() => {
+            inst.opcode(&[0x63]);
+            inst.operand_size(dst.size().unwrap_or(0))?;
+            inst.reg(*reg);
+            inst.rm(src)?;
+            inst.encode(addr)
+        }
+        ("lea", [dst @ Reg(reg), src @ Mem(_)]) if reg.is_gpr() => {
+            inst.opcode(&[0x8d]);
+            inst.operand_size(dst.size().unwrap_or(0))?;
+            inst.reg(*reg);
+            inst.rm(src)?;
+            inst.encode(addr)
+        }
+        ("test", [dst, Imm(value)]) if dst.is_gpr_rm() => {
+            let size = dst.size().ok_or(Error::InvalidOperands)?;
+            let value = immediate(*value, size)?;
+            if dst.is_accumulator() {
+                inst.sized(&[0xa8], size)?;
+            } else {
+                inst.sized(&[0xf6], size)?;
+                inst.digit(0);
+                inst.rm(dst)?;
+            }
+            inst.imm(value, size.min(4));
+            inst.encode(addr)
+        }
+        ("test" | "xchg", [dst, src @ Reg(reg)]) if dst.is_gpr_rm() && reg.is_gpr() => {
+            let opcode = if mnemonic == "test" { 0x84 } else { 0x86 };
+            inst.sized(&[opcode], common_size(dst, src)?)?;
+            inst.reg(*reg);
+            inst.rm(dst)?;
+            inst.encode(addr)
+        }
+        ("test" | "xchg", [dst @ Reg(reg), src @ Mem(_)]) if reg.is_gpr() => {
+            let opcode = if mnemonic == "test" { 0x84 } else { 0x86 };
+            inst.sized(&[opcode], common_size(dst, src)?)?;
+            inst.reg(*reg);
+            inst.rm(src)?;
+            inst.encode(addr)
+        }
+        ("imul", [src]) if src.is_gpr_rm() => {
+            inst.sized(&[0xf6], src.size().ok_or(Error::InvalidOperands)?)?;
+            inst.digit(5);
+            inst.rm(src)?;
+            inst.encode(addr)
+        }
+        ("imul", [dst @ Reg(reg), src]) if reg.is_gpr() && src.is_gpr_rm() => {
+            inst.opcode(&[0x0f, 0xaf]);
+            inst.operand_size(common_size(dst, src)?)?;
+            inst.reg(*reg);
+            inst.rm(src)?;
+            inst.encode(addr)
+        }
+        ("imul", [dst @ Reg(reg), src, Imm(value)]) if reg.is_gpr() && src.is_gpr_rm() => {
+            let size = common_size(dst, src)?;
+            let value = immediate(*value, size)?;
+            inst.operand_size(size)?;
+            if fits_signed(value, 8) {
+                inst.opcode(&[0x6b]);
+                inst.imm(value, 1);
+            } else {
+                inst.opcode(&[0x69]);
+                inst.imm(value, size.min(4));
+            }
+            inst.reg(*reg);
+            inst.rm(src)?;
+            inst.encode(addr)
+        }
+        ("shld" | "shrd", [dst, src @ Reg(reg), count]) if dst.is_gpr_rm() && reg.is_gpr() => {
+            let opcode = if mnemonic == "shld" { 0xa4 } else { 0xac };
+            inst.operand_size(common_size(dst, src)?)?;
+            match count {
+                Imm(value) => {
+                    inst.opcode(&[0x0f, opcode]);
+                    inst.imm(imm8(*value)?, 1);
+                }
+                Reg(Register {
+                    kind: Kind::Gpr(1),
+                    num: 1,
+                }) => inst.opcode(&[0x0f, opcode + 1]),
+                _ => return Err(Error::InvalidOperands),
+            }
+            inst.reg(*reg);
+            inst.rm(dst)?;
+            inst.encode(addr)
+        }
+        ("bt" | "bts" | "btr" | "btc", [dst, src]) if dst.is_gpr_rm() => {
+            let n = ["bt", "bts", "btr", "btc"].iter().position(|&name| name == mnemonic);
+            let n = n.unwrap() as u8;
+            match src {
+                Reg(reg) if reg.is_gpr() => {
+                    inst.opcode(&[0x0f, 0xa3 + n * 8]);
+                    inst.operand_size(common_size(dst, src)?)?;
+                    inst.reg(*reg);
+                }
+                Imm(value) => {
+                    inst.opcode(&[0x0f, 0xba]);
+                    inst.operand_size(dst.size().ok_or(Error::InvalidOperands)?)?;
+                    inst.digit(4 + n);
+                    inst.imm(imm8(*value)?, 1);
+                }
+                _ => return Err(Error::InvalidOperands),
+            }
+            inst.rm(dst)?;
+            inst.encode(addr)
+        }
+        ("bswap", [Reg(reg)]) if matches!(reg.kind, Kind::Gpr(4 | 8)) => {
+            inst.opcode(&[0x0f, 0xc8]);
+            inst.operand_size(reg.size().unwrap())?;
+            inst.plus_reg(*reg);
+            inst.encode(addr)
+        }
+        ("xadd" | "cmpxchg", [dst, src @ Reg(reg)]) if dst.is_gpr_rm() && reg.is_gpr() => {
+            let opcode = if mnemonic == "xadd" { 0xc0 } else { 0xb0 };
+            inst.sized(&[0x0f, opcode], common_size(dst, src)?)?;
+            inst.reg(*reg);
+            inst.rm(dst)?;
+            inst.encode(addr)
+        }
+        ("cmpxchg8b" | "cmpxchg16b", [dst @ Mem(_)]) => {
+            inst.opcode(&[0x0f, 0xc7]);
+            if mnemonic == "cmpxchg16b" {
+                inst.rex |= REX_W;
+            }
+            inst.digit(1);
+            inst.rm(dst)?;
+            inst.encode(addr)
+        }
+        ("movnti", [dst @ Mem(_), Reg(reg)]) if matches!(reg.kind, Kind::Gpr(4 | 8)) => {
+            inst.opcode(&[0x0f, 0xc3]);
+            inst.operand_size(reg.size().unwrap())?;
+            inst.reg(*reg);
+            inst.rm(dst)?;
+            inst.encode(addr)
+        }
+        ("rdrand" | "rdseed", [dst @ Reg(reg)]) if matches!(reg.kind, Kind::Gpr(2 | 4 | 8)) => {
+            inst.opcode(&[0x0f, 0xc7]);
+            inst.operand_size(reg.size().unwrap())?;
+            inst.digit(if mnemonic == "rdrand" { 6 } else { 7 });
+            inst.rm(dst)?;
+            inst.encode(addr)
+        }
+        ("movbe", [dst @ Reg(reg), src @ Mem(_)]) if reg.is_gpr() => {
+            inst.opcode(&[0x0f, 0x38, 0xf0]);
+            inst.operand_size(common_size(dst, src)?)?;
+            inst.reg(*reg);
+            inst.rm(src)?;
+            inst.encode(addr)
+        }
+        ("movbe", [dst @ Mem(_), src @ Reg(reg)]) if reg.is_gpr() => {
+            inst.opcode(&[0x0f, 0x38, 0xf1]);
+            inst.operand_size(common_size(dst, src)?)?;
+            inst.reg(*reg);
+            inst.rm(dst)?;
+            inst.encode(addr)
+        }
+        ("in", [dst, port]) | ("out", [port, dst])
+            if dst.is_accumulator() && matches!(dst.size(), Some(1 | 2 | 4)) =>
+        {
+            let size = dst.size().unwrap();
+            let opcode = if mnemonic == "in" { 0xe4 } else { 0xe6 };
+            match port {
+                Imm(port) if fits_unsigned(*port, 8) => {
+                    inst.sized(&[opcode], size)?;
+                    inst.imm(*port, 1);
+                }
+                Reg(Register {
+                    kind: Kind::Gpr(2),
+                    num: 2,
+                }) => inst.sized(&[opcode + 8], size)?,
+                _ => return Err(Error::InvalidOperands),
+            }
+            inst.encode(addr)
+        }
+        ("xbegin", [Imm(target)]) => {
+            inst.opcode(&[0xc7, 0xf8]);
+            inst.rel(*target, 4);
+            inst.encode(addr)
+        }
+        ("xabort", [Imm(value)]) => {
+            inst.opcode(&[0xc6, 0xf8]);
+            inst.imm(imm8(*value)?, 1);
+            inst.encode(addr)
+        }
+        ("nop", [dst]) if dst.is_gpr_rm() => {
+            inst.opcode(&[0x0f, 0x1f]);
+            inst.operand_size(dst.size().unwrap_or(4))?;
+            inst.digit(0);
+            inst.rm(dst)?;
+            inst.encode(addr)
+        }
+        ("push", [Reg(reg)]) if matches!(reg.kind, Kind::Gpr(2 | 8)) => {
+            inst.opcode(&[0x50]);
+            inst.opsize = reg.kind == Kind::Gpr(2);
+            inst.plus_reg(*reg);
+            inst.encode(addr)
+        }
+        ("pop", [Reg(reg)]) if matches!(reg.kind, Kind::Gpr(2 | 8)) => {
+            inst.opcode(&[0x58]);
+            inst.opsize = reg.kind == Kind::Gpr(2);
+            inst.plus_reg(*reg);
+            inst.encode(addr)
+        }
+        (
+            "push" | "pop",
+            [Reg(Register {
+                kind: Kind::Segment,
+                num: num @ (4 | 5),
+            })],
+        ) => {
+            let opcode = if *num == 4 { 0xa0 } else { 0xa8 };
+            inst.opcode(&[0x0f, opcode + (mnemonic == "pop") as u8]);
+            inst.encode(addr)
+        }
+        ("push", [src @ Mem(_)]) => {
+            inst.opcode(&[0xff]);
+            inst.opsize = src.size() == Some(2);
+            inst.digit(6);
+            inst.rm(src)?;
+            inst.encode(addr)
+        }
+        ("pop", [dst @ Mem(_)]) => {
+            inst.opcode(&[0x8f]);
+            inst.opsize = dst.size() == Some(2);
+            inst.digit(0);
+            inst.rm(dst)?;
+            inst.encode(addr)
+        }
+        ("push", [Imm(value)]) => {
+            let value = immediate(*value, 4)?;
+            if fits_signed(value, 8) {
+                inst.opcode(&[0x6a]);
+                inst.imm(value, 1);
+            } else {
+                inst.opcode(&[0x68]);
+                inst.imm(value, 4);
+            }
+            inst.encode(addr)
+        }
+        ("call", [Imm(target)]) => {
+            inst.opcode(&[0xe8]);
+            inst.rel(*target, 4);
+            inst.encode(addr)
+        }
+        ("jmp", [Imm(target)]) => branch(inst, &[0xeb], &[0xe9], *target, addr),
+        ("call" | "jmp", [dst]) if dst.is_gpr_rm() && dst.size().unwrap_or(8) == 8 => {
+            inst.opcode(&[0xff]);
+            inst.digit(if mnemonic == "call" { 2 } else { 4 });
+            inst.rm(dst)?;
+            inst.encode(addr)
+        }
+        ("loopnz" | "loopne" | "loopz" | "loope" | "loop" | "jrcxz", [Imm(target)]) => {
+            let opcode = match mnemonic {
+                "loopnz" | "loopne" => 0xe0,
+                "loopz" | "loope" => 0xe1,
+                "loop" => 0xe2,
+                _ => 0xe3,
+            };
+            inst.opcode(&[opcode]);
+            inst.rel(*target, 1);
+            inst.encode(addr)
+        }
+        ("ret" | "retf", [Imm(value)]) if fits_unsigned(*value, 16) => {
+            inst.opcode(&[if mnemonic == "ret" { 0xc2 } else { 0xca }]);
+            inst.imm(*value, 2);
+            inst.encode(addr)
+        }
+        ("int", [Imm(3)]) => {
+            inst.opcode(&[0xcc]);
+            inst.encode(addr)
+        }
+        ("int", [Imm(1)]) => {
+            inst.opcode(&[0xf1]);
+            inst.encode(addr)
+        }
+        ("int", [Imm(value)]) if fits_unsigned(*value, 8) => {
+            inst.opcode(&[0xcd]);
+            inst.imm(*value, 1);
+            inst.encode(addr)
+        }
+        ("enter", [Imm(size), Imm(level)])
+            if fits_unsigned(*size, 16) && fits_unsigned(*level, 8) =>
+        {
+            inst.opcode(&[0xc8]);
+            inst.imm(*size, 2);
+            inst.imm(*level, 1);
+            inst.encode(addr)
+        }
+        (
+            "fnstsw",
+            [Reg(Register {
+                kind: Kind::Gpr(2),
+                num: 0,
+            })],
+        ) => {
+            inst.opcode(&[0xdf, 0xe0]);
+            inst.encode(addr)
+        }
+        _ if mnemonic.starts_with('f') => x87(inst, mnemonic, ops, addr),
+        _ => sse(inst, mnemonic, ops, addr),
+    }
+}
+
+fn x87(mut inst: Inst, mnemonic: &str, ops: &[Operand], addr: usize) -> Result<Vec<u8>, Error> {
+    let st0 = Operand::Reg(Register {
+        kind: Kind::St,
+        num: 0,
+    });
+
+    // the decoder shows `st(0)` when it's implied
+    if let Some(mem @ Operand::Mem(Memory { size, .. })) = ops.iter().find(|op| **op != st0) {
+        if ops.iter().filter(|&op| op != mem).any(|op| *op != st0) {
+            return Err(Error::InvalidOperands);
+        }
+
+        let mut forms = X87_MEMORY.iter().filter(|(name, ..)| *name == mnemonic);
+        let &(_, _, opcode, digit) = match forms.clone().find(|form| form.1 == *size) {
+            Some(form) => form,
+            None if *size == 0 && forms.clone().count() == 1 => forms.next().unwrap(),
+            None => return Err(Error::InvalidOperands),
+        };
+
+        inst.opcode(&[opcode]);
+        inst.digit(digit);
+        inst.rm(mem)?;
+        return inst.encode(addr);
+    }
+
+    for &(name, opcode, base, is_first) in X87_REGISTER {
+        if name != mnemonic {
+            continue;
+        }
+
+        let reg = match ops {
+            [Operand::Reg(reg)] => reg,
+            [Operand::Reg(reg), op] if is_first && *op == st0 => reg,
+            [op, Operand::Reg(reg)] if !is_first && *op == st0 => reg,
+            _ => continue,
+        };
+
+        if reg.kind != Kind::St {
+            return Err(Error::InvalidRegister(reg_name(*reg)));
+        }
+
+        inst.opcode(&[opcode, base + reg.num]);
+        return inst.encode(addr);
+    }
+
+    Err(Error::UnknownInstruction(mnemonic.to_string()))
+}
+
+/// Relative branch that's encoded with a byte offset if it's in range.
+fn branch(
+    inst: Inst,
+    short: &[u8],
+    near: &[u8],
+    target: i64,
+    addr: usize,
+) -> Result<Vec<u8>, Error> {
+    let mut short_inst = inst.clone();
+    short_inst.opcode(short);
+    short_inst.rel(target, 1);
+    match short_inst.encode(addr) {
+        Err(Error::OutOfRange(_)) => {
+            let mut inst = inst;
+            inst.opcode(near);
+            inst.rel(target, 4);
+            inst.encode(addr)
+        }
+        result => result,
+    }
+}
+
+fn mov(mut inst: Inst, ops: &[Operand], addr: usize) -> Result<Vec<u8>, Error> {
+    use Operand::*;
+
+    match ops {
+        [Reg(dst), src @ Reg(reg)] if dst.is_gpr() && reg.is_gpr() => {
+            inst.sized(&[0x88], common_size(&ops[0], src)?)?;
+            inst.reg(*reg);
+            inst.rm(&ops[0])?;
+        }
+        // moving to and from the accumulator has a 64-bit address form
+        [dst, Mem(mem)] | [Mem(mem), dst]
+            if dst.is_accumulator()
+                && mem.is_absolute()
+                && !mem.symbolic
+                && !fits_signed(mem.disp, 32)
+                && !fits_unsigned(mem.disp, 32) =>
+        {
+            let opcode = if matches!(ops[0], Mem(_)) { 0xa2 } else { 0xa0 };
+            inst.sized(&[opcode], dst.size().unwrap())?;
+            if let Some(segment) = mem.segment {
+                inst.segment = Some(SEGMENT_PREFIXES[segment as usize]);
+            }
+            inst.imm(mem.disp, 8);
+        }
+        [dst @ Mem(_), src @ Reg(reg)] if reg.is_gpr() => {
+            inst.sized(&[0x88], common_size(dst, src)?)?;
+            inst.reg(*reg);
+            inst.rm(dst)?;
+        }
+        [dst @ Reg(reg), src @ Mem(_)] if reg.is_gpr() => {
+            inst.sized(&[0x8a], common_size(dst, src)?)?;
+            inst.reg(*reg);
+            inst.rm(src)?;
+        }
+        [Reg(reg), Imm(value)] if reg.is_gpr() => {
+            let size = reg.size().unwrap();
+            // negative bytes use the form that's shown signed
+            if (size == 8 && fits_signed(*value, 32)) || (size == 1 && *value < 0) {
+                let value = immediate(*value, size)?;
+                inst.sized(&[0xc6], size)?;
+                inst.digit(0);
+                inst.rm(&ops[0])?;
+                inst.imm(value, size.min(4));
+            } else {
+                let value = if size == 8 {
+                    *value
+                } else {
+                    immediate(*value, size)?
+                };
+                inst.opcode(&[if size == 1 { 0xb0 } else { 0xb8 }]);
+                inst.operand_size(size)?;
+                inst.plus_reg(*reg);
+                inst.imm(value, size);
+            }
+        }
+        [dst @ Mem(_), Imm(value)] => {
+            let size = dst.size().ok_or(Error::InvalidOperands)?;
+            inst.sized(&[0xc6], size)?;
+            inst.digit(0);
+            inst.rm(dst)?;
+            inst.imm(immediate(*value, size)?, size.min(4));
+        }
+        [Reg(Register {
+            kind: Kind::Segment,
+            num,
+        }), src]
+            if src.is_gpr_rm() =>
+        {
+            inst.opcode(&[0x8e]);
+            inst.digit(*num);
+            inst.rm(src)?;
+        }
+        [dst, Reg(Register {
+            kind: Kind::Segment,
+            num,
+        })] if dst.is_gpr_rm() => {
+            inst.opcode(&[0x8c]);
+            if let Some(size @ (2 | 8)) = dst.gpr().and_then(|reg| reg.size()) {
+                inst.operand_size(size)?;
+            }
+            inst.digit(*num);
+            inst.rm(dst)?;
+        }
+        [dst @ Reg(gpr), Reg(special)] | [Reg(special), dst @ Reg(gpr)]
+            if gpr.kind == Kind::Gpr(8) && matches!(special.kind, Kind::Control | Kind::Debug) =>
+        {
+            let to_special = matches!(
+                ops[0],
+                Reg(Register {
+                    kind: Kind::Control | Kind::Debug,
+                    ..
+                })
+            );
+            let opcode = match special.kind {
+                Kind::Control => 0x20,
+                _ => 0x21,
+            };
+            inst.opcode(&[0x0f, opcode + if to_special { 2 } else { 0 }]);
+            inst.reg(*special);
+            inst.rm(dst)?;
+        }
+        _ => return Err(Error::InvalidOperands),
+    }
+
+    inst.encode(addr)
+}
+
+fn string(mut inst: Inst, opcode: u8, ops: &[Operand], addr: usize) -> Result<Vec<u8>, Error> {
+    let size = ops.iter().find_map(Operand::size).ok_or(Error::InvalidOperands)?;
+    inst.sized(&[opcode], size)?;
+
+    for op in ops {
+        match op {
+            Operand::Reg(reg) if reg.is_gpr() && reg.num == 0 => {}
+            Operand::Mem(mem) if mem.disp == 0 && mem.index.is_none() => {
+                let base = mem.base.ok_or(Error::InvalidOperands)?;
+                match base.kind {
+                    Kind::Gpr(8) => {}
+                    Kind::Gpr(4) => inst.addr32 = true,
+                    _ => return Err(Error::InvalidRegister(reg_name(base))),
+                }
+
+                // only the source can have its segment overridden
+                let default = if base.num == 6 { 3 } else { 0 };
+                match mem.segment {
+                    Some(segment) if segment != default && base.num == 6 => {
+                        inst.segment = Some(SEGMENT_PREFIXES[segment as usize]);
+                    }
+                    Some(segment) if segment != default => return Err(Error::InvalidOperands),
+                    _ => {}
+                }
+            }
+            _ => return Err(Error::InvalidOperands),
+        }
+    }
+
+    inst.encode(addr)
+}
+
+/// BMI instructions, which are VEX encoded but operate on general purpose registers.
+fn bmi(
+    mut inst: Inst,
+    prefix: u8,
+    opcode: &[u8],
+    form: Bmi,
+    ops: &[Operand],
+    addr: usize,
+) -> Result<Vec<u8>, Error> {
+    use Operand::*;
+
+    let is_gpr = |op: &Operand| matches!(op, Reg(reg) if matches!(reg.kind, Kind::Gpr(4 | 8)));
+    let (reg, rm, vvvv) = match (form, ops) {
+        (Bmi::RmV, [reg, rm, Reg(vvvv)]) if is_gpr(reg) && rm.is_gpr_rm() => (reg, rm, Some(vvvv)),
+        (Bmi::VRm, [reg, Reg(vvvv), rm]) if is_gpr(reg) && rm.is_gpr_rm() => (reg, rm, Some(vvvv)),
+        (Bmi::Digit(digit), [Reg(vvvv), rm]) if rm.is_gpr_rm() => {
+            inst.digit(digit);
+            (&ops[0], rm, Some(vvvv))
+        }
+        (Bmi::RmImm, [reg, rm, Imm(imm)]) if is_gpr(reg) && rm.is_gpr_rm() => {
+            inst.imm(imm8(*imm)?, 1);
+            (reg, rm, None)
+        }
+        _ => return Err(Error::InvalidOperands),
+    };
+
+    let size = common_size(reg, rm)?;
+    if vvvv.is_some_and(|vvvv| vvvv.kind != Kind::Gpr(size)) || !matches!(size, 4 | 8) {
+        return Err(Error::InvalidOperands);
+    }
+
+    inst.vex = Some(Vex::default());
+    if let Some(vvvv) = vvvv {
+        inst.vvvv(*vvvv);
+    }
+    if prefix != 0 {
+        inst.mandatory = Some(prefix);
+    }
+    inst.opcode(opcode);
+    inst.operand_size(size)?;
+    if let (Reg(reg), false) = (reg, matches!(form, Bmi::Digit(_))) {
+        inst.reg(*reg);
+    }
+    inst.rm(rm)?;
+    inst.encode(addr)
+}
+
+fn sse(inst: Inst, mnemonic: &str, ops: &[Operand], addr: usize) -> Result<Vec<u8>, Error> {
+    // AVX versions of SSE instructions start with a 'v'
+    let legacy = mnemonic.strip_prefix('v').filter(|name| SSE.iter().any(|form| form.0 == *name));
+    let vex = legacy.is_some() || AVX.iter().any(|form| form.0 == mnemonic);
+    let name = legacy.unwrap_or(mnemonic);
+
+    let forms = SSE
+        .iter()
+        .filter(|form| form.0 == name)
+        .map(|&(_, prefix, opcode, form, mmx)| (prefix, opcode, form, mmx, false))
+        .chain(
+            AVX.iter()
+                .filter(|form| form.0 == mnemonic)
+                .map(|&(_, prefix, opcode, form, w)| (prefix, opcode, form, false, w)),
+        );
+
+    let mut known = false;
+    for (prefix, opcode, form, mmx, w) in forms {
+        known = true;
+
+        let mut inst = inst.clone();
+        let mut ops = ops.to_vec();
+        if vex {
+            let long = ops.iter().any(|op| op.size() == Some(32));
+            inst.vex = Some(Vex { vvvv: 0, long });
+            if w {
+                inst.rex |= REX_W;
+            }
+
+            // the extra source operand, which shifts use for their destination
+            let extra = if matches!(form, Form::Shift(_)) { 0 } else { 1 };
+            if ops.len() == form.operands() + 1 {
+                match ops.remove(extra) {
+                    Operand::Reg(reg) if matches!(reg.kind, Kind::Xmm | Kind::Ymm) => {
+                        inst.vvvv(reg)
+                    }
+                    _ => continue,
+                }
+            } else if let (Form::Shift(_), Some(Operand::Reg(reg))) = (form, ops.first()) {
+                inst.vvvv(*reg);
+            }
+        }
+
+        if sse_form(&mut inst, name, prefix, opcode, form, mmx, &ops)? {
+            return inst.encode(addr);
+        }
+    }
+
+    match known {
+        true => Err(Error::InvalidOperands),
+        false => Err(Error::UnknownInstruction(mnemonic.to_string())),
+    }
+}
+
+/// Encodes the operands of an SSE instruction if they match `form`.
+fn sse_form(
+    inst: &mut Inst,
+    name: &str,
+    prefix: u8,
+    opcode: &[u8],
+    form: Form,
+    mmx: bool,
+    ops: &[Operand],
+) -> Result<bool, Error> {
+    use Operand::*;
+
+    // mmx versions don't have a prefix
+    let vex = inst.vex.is_some();
+    let kind = match ops.iter().find(|op| op.is_vector(Kind::Xmm) || op.is_vector(Kind::Mmx)) {
+        Some(Reg(reg)) if reg.kind == Kind::Mmx && mmx && !vex => Kind::Mmx,
+        _ => Kind::Xmm,
+    };
+    let is_vec = |op: &Operand| op.is_vector(kind) || (vex && op.is_vector(Kind::Ymm));
+    let is_vec_rm = |op: &Operand| is_vec(op) || matches!(op, Mem(_));
+    let is_gpr = |op: &Operand| matches!(op, Reg(reg) if matches!(reg.kind, Kind::Gpr(4 | 8)));
+
+    let (reg, rm, imm) = match (form, ops) {
+        (Form::Rm, [dst, src]) if is_vec(dst) && is_vec_rm(src) => (dst, src, None),
+        (Form::Load, [dst, src @ Mem(_)]) if is_vec(dst) => (dst, src, None),
+        (Form::Mr, [dst, src]) if is_vec_rm(dst) && is_vec(src) => (src, dst, None),
+        (Form::RmImm, [dst, src, Imm(imm)]) if is_vec(dst) && is_vec_rm(src) => {
+            (dst, src, Some(*imm))
+        }
+        (Form::MrImm, [dst, src, Imm(imm)]) if is_vec_rm(dst) && is_vec(src) => {
+            (src, dst, Some(*imm))
+        }
+        (Form::Shift(digit), [dst, Imm(imm)]) if is_vec(dst) => {
+            inst.digit(digit);
+            inst.rm(dst)?;
+            (dst, dst, Some(*imm))
+        }
+        (Form::GprRm, [dst, src]) if is_gpr(dst) && is_vec_rm(src) => (dst, src, None),
+        (Form::GprRmImm, [dst, src, Imm(imm)]) if is_gpr(dst) && is_vec(src) => {
+            (dst, src, Some(*imm))
+        }
+        (Form::RmGpr, [dst, src]) if is_vec(dst) && (is_gpr(src) || matches!(src, Mem(_))) => {
+            (dst, src, None)
+        }
+        (Form::RmGprImm, [dst, src, Imm(imm)])
+            if is_vec(dst) && (is_gpr(src) || matches!(src, Mem(_))) =>
+        {
+            (dst, src, Some(*imm))
+        }
+        (Form::GprMr, [dst, src]) if (is_gpr(dst) || matches!(dst, Mem(_))) && is_vec(src) => {
+            (src, dst, None)
+        }
+        _ => return Ok(false),
+    };
+
+    if kind == Kind::Xmm && prefix != 0 {
+        inst.mandatory = Some(prefix);
+    }
+    inst.opcode(opcode);
+
+    let gpr_size = match form {
+        Form::GprRm | Form::GprRmImm => reg.size(),
+        Form::RmGpr | Form::GprMr => rm.size(),
+        _ => None,
+    };
+    if gpr_size == Some(8) || (name == "movq" && matches!(form, Form::RmGpr | Form::GprMr)) {
+        inst.rex |= REX_W;
+    }
+
+    if !matches!(form, Form::Shift(_)) {
+        let Reg(reg) = reg else { unreachable!() };
+        inst.reg(*reg);
+        inst.rm(rm)?;
+    }
+    if let Some(imm) = imm {
+        inst.imm(imm8(imm)?, 1);
+    }
+
+    Ok(true)
+}
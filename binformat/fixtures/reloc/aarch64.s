@@ -0,0 +1,15 @@
+.text
+.globl f
+f:
+    bl printf
+    b puts
+    adrp x0, msg
+    add x0, x0, :lo12:msg
+    adrp x1, msg
+    ldr x1, [x1, :lo12:msg]
+    ret
+
+.data
+.p2align 3
+msg:
+    .quad f
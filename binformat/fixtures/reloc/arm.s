@@ -0,0 +1,14 @@
+.text
+.globl h
+h:
+    bl printf
+    b puts
+    bx lr
+
+.thumb
+.globl t
+.thumb_func
+t:
+    bl printf
+    b.w puts
+    bx lr
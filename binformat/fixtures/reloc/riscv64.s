@@ -0,0 +1,6 @@
+.text
+.globl g
+g:
+    call printf
+    tail puts
+    .space 0x7f0
@@ -0,0 +1,11 @@
+.text
+.globl k
+k:
+    call printf
+    mov counter(%rip), %eax
+    mov stdout@GOTPCREL(%rip), %rax
+    ret
+
+.data
+counter:
+    .long 5
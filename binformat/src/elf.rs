@@ -1,13 +1,26 @@
+mod plt;
+mod reloc;
+
+pub use reloc::Layout;
+
 use std::fmt;
+use std::ops::Range;
 use crate::{datastructure, RawSymbol};
-use processor_shared::{AddressMap, Addressed, Section, SectionKind};
+use processor_shared::{AddressMap, Addressed, Permissions, Section, SectionKind};
 use object::elf;
-use object::read::elf::{ElfFile, FileHeader, SectionHeader};
+use object::read::elf::{Dyn, ElfFile, FileHeader, SectionHeader};
 use object::{
-    Endian, Object, ObjectSection, ObjectSymbol, ObjectSymbolTable, RelocationKind,
-    RelocationTarget,
+    Architecture, Endian, Object, ObjectKind, ObjectSymbol, ObjectSymbolTable, RelocationKind,
+    RelocationTarget, SymbolKind,
 };
 
+/// Instruction set ARM code is encoded in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArmMode {
+    Arm,
+    Thumb,
+}
+
 pub struct ElfDebugInfo<'data, Elf: FileHeader> {
     /// Parsed ELF header.
     obj: &'data ElfFile<'data, Elf>,
@@ -15,6 +28,17 @@ pub struct ElfDebugInfo<'data, Elf: FileHeader> {
     pub sections: Vec<Section>,
     /// Any parsed but not yet relocated symbols.
     pub syms: AddressMap<RawSymbol<'data>>,
+    /// Addresses where ARM code switches instruction set, sorted by address.
+    pub arm_modes: AddressMap<ArmMode>,
+    /// Parts of code sections that hold data, sorted by address.
+    pub data_ranges: Vec<Range<usize>>,
+    /// PLT stubs named `name@plt` after the import they jump to.
+    pub plt_syms: AddressMap<String>,
+    /// Targets of relocations in relocatable objects that should be shown next to the
+    /// instructions they're in, sorted by address.
+    pub relocations: AddressMap<String>,
+    /// Addresses given to relocatable objects, as they don't have any yet.
+    pub layout: Option<reloc::Layout>,
 }
 
 impl<'data, Elf: FileHeader> ElfDebugInfo<'data, Elf> {
@@ -23,87 +47,461 @@ impl<'data, Elf: FileHeader> ElfDebugInfo<'data, Elf> {
             obj,
             syms: AddressMap::default(),
             sections: Vec::new(),
+            arm_modes: AddressMap::default(),
+            data_ranges: Vec::new(),
+            plt_syms: AddressMap::default(),
+            relocations: AddressMap::default(),
+            layout: None,
         };
         this.sections = parse_sections(obj);
+        if obj.kind() == ObjectKind::Relocatable {
+            let layout = reloc::Layout::new(obj, &mut this.sections);
+            this.relocations = reloc::relocate(obj, &layout, &mut this.sections);
+            this.layout = Some(layout);
+        }
         this.parse_symbols();
         this.parse_imports();
         Ok(this)
     }
 
     pub fn parse_imports(&mut self) {
-        let relocations = match self.obj.dynamic_relocations() {
-            Some(relocations) => relocations,
-            None => return,
-        };
-
         let dyn_syms = match self.obj.dynamic_symbol_table() {
             Some(dyn_syms) => dyn_syms,
             None => return,
         };
 
-        for (r_offset, reloc) in relocations {
-            if let RelocationTarget::Symbol(idx) = reloc.target() {
-                let opt_section = self.obj.sections().find(|section| {
-                    (section.address()..section.address() + section.size()).contains(&r_offset)
+        let arch = self.obj.architecture();
+        let name_of = |idx| dyn_syms.symbol_by_index(idx).and_then(|sym| sym.name()).ok();
+
+        // GOT slots that will hold the address of an imported symbol
+        let mut slots = AddressMap::default();
+
+        for (r_offset, reloc) in self.obj.dynamic_relocations().into_iter().flatten() {
+            let idx = match reloc.target() {
+                RelocationTarget::Symbol(idx) => idx,
+                _ => continue,
+            };
+
+            let is_slot = match reloc.kind() {
+                // hard-coded address to function which doesn't require a relocation
+                RelocationKind::Absolute => true,
+                RelocationKind::Elf(r_type) => is_got_relocation(arch, r_type),
+                _ => false,
+            };
+
+            if let (true, Some(name)) = (is_slot, name_of(idx)) {
+                slots.push(Addressed { addr: r_offset as usize, item: name });
+            }
+        }
+
+        let (pltgot, mips_got) = self.parse_dynamic();
+
+        // MIPS doesn't relocate global GOT entries, instead they map onto the dynamic symbols
+        if let Some(got) = mips_got {
+            let width = if self.obj.is_64() { 8 } else { 4 };
+            for (entry, sym) in (got.local_gotno..).zip(got.gotsym..got.symtabno) {
+                if let Some(name) = name_of(object::SymbolIndex(sym)) {
+                    slots.push(Addressed { addr: pltgot + entry * width, item: name });
+                }
+            }
+        }
+
+        slots.sort_unstable_by_key(|slot| slot.addr);
+        slots.dedup_by_key(|slot| slot.addr);
+
+        let endian = self.obj.endian();
+        let endian = if endian.is_little_endian() {
+            object::Endianness::Little
+        } else {
+            object::Endianness::Big
+        };
+
+        for section in &self.sections {
+            if section.kind != SectionKind::Code {
+                continue;
+            }
+
+            if section.name == ".MIPS.stubs" {
+                let stubs = plt::mips_lazy_stubs(endian, section.start, section.bytes());
+                for (addr, idx) in stubs {
+                    if let Some(name) = name_of(object::SymbolIndex(idx)) {
+                        self.plt_syms.push(Addressed { addr, item: format!("{name}@plt") });
+                    }
+                }
+                continue;
+            }
+
+            if !matches!(&section.name[..], ".plt" | ".plt.sec" | ".plt.got" | ".plt.bnd") {
+                continue;
+            }
+
+            for (addr, slot) in plt::stubs(arch, endian, section.start, section.bytes(), pltgot) {
+                if let Some(name) = slots.search(slot).ok().map(|idx| slots[idx].item) {
+                    self.plt_syms.push(Addressed { addr, item: format!("{name}@plt") });
+                }
+            }
+        }
+
+        // TODO: find modules
+        self.syms.extend(AddressMap {
+            mapping: slots
+                .mapping
+                .into_iter()
+                .map(|slot| Addressed {
+                    addr: slot.addr,
+                    item: RawSymbol { name: slot.item, module: None },
+                })
+                .collect(),
+        });
+    }
+
+    /// Address of the GOT as given by `DT_PLTGOT` and the layout of MIPS' GOT.
+    fn parse_dynamic(&self) -> (usize, Option<MipsGot>) {
+        let endian = self.obj.endian();
+        let data = self.obj.data();
+
+        let dynamic = self
+            .obj
+            .raw_header()
+            .sections(endian, data)
+            .ok()
+            .and_then(|section_headers| section_headers.dynamic(endian, data).ok().flatten());
+
+        let entries = match dynamic {
+            Some((entries, _)) => entries,
+            None => return (0, None),
+        };
+
+        let mut pltgot = 0;
+        let (mut local_gotno, mut gotsym, mut symtabno) = (None, None, None);
+        for entry in entries {
+            let val: u64 = entry.d_val(endian).into();
+            let val = val as usize;
+            let tag: u64 = entry.d_tag(endian).into();
+            match tag as u32 {
+                elf::DT_PLTGOT => pltgot = val,
+                elf::DT_MIPS_LOCAL_GOTNO => local_gotno = Some(val),
+                elf::DT_MIPS_GOTSYM => gotsym = Some(val),
+                elf::DT_MIPS_SYMTABNO => symtabno = Some(val),
+                _ => {}
+            }
+        }
+
+        let mips_got = match (local_gotno, gotsym, symtabno) {
+            (Some(local_gotno), Some(gotsym), Some(symtabno)) => Some(MipsGot {
+                local_gotno,
+                gotsym,
+                symtabno,
+            }),
+            _ => None,
+        };
+
+        (pltgot, mips_got)
+    }
+
+    pub fn parse_symbols(&mut self) {
+        match self.layout {
+            Some(ref layout) => self.syms.extend(relocatable_symbol_table(self.obj, layout)),
+            None => {
+                self.syms.extend(crate::parse_symbol_table(self.obj));
+                self.syms.push(Addressed {
+                    addr: self.obj.entry() as usize,
+                    item: RawSymbol {
+                        name: "entry",
+                        module: None,
+                    },
                 });
+            }
+        }
+
+        if matches!(
+            self.obj.architecture(),
+            Architecture::Arm
+                | Architecture::Aarch64
+                | Architecture::Aarch64_Ilp32
+                | Architecture::Riscv32
+                | Architecture::Riscv64
+        ) {
+            self.parse_mapping_symbols();
+        }
+    }
+
+    /// Split code sections into code and data using the mapping symbols, and for ARM also find
+    /// where code is Arm or Thumb code.
+    ///
+    /// Mapping symbols are dropped as they would otherwise show up as labels everywhere.
+    fn parse_mapping_symbols(&mut self) {
+        let mut mapping_syms = Vec::new();
+        let mut func_syms = Vec::new();
 
-                let section = match opt_section {
-                    Some(section) => section,
+        for sym in self.obj.symbols().chain(self.obj.dynamic_symbols()) {
+            let addr = match self.layout {
+                Some(ref layout) => match layout.symbol_addr(&sym) {
+                    Some(addr) => addr,
                     None => continue,
-                };
-
-                if let Ok(sym) = dyn_syms.symbol_by_index(idx) {
-                    let name = match sym.name() {
-                        Ok(name) => name,
-                        Err(..) => continue,
-                    };
-
-                    let addr = match reloc.kind() {
-                        // hard-coded address to function which doesn't require a relocation
-                        RelocationKind::Absolute => r_offset as usize,
-                        RelocationKind::Elf(elf::R_X86_64_GLOB_DAT) => r_offset as usize,
-                        RelocationKind::Elf(elf::R_X86_64_COPY) => r_offset as usize,
-                        // address in .got.plt section which contains an address to the function
-                        RelocationKind::Elf(elf::R_X86_64_JUMP_SLOT) => {
-                            let width = if self.obj.is_64() { 8 } else { 4 };
-
-                            let bytes = match section.data_range(r_offset, width) {
-                                Ok(Some(bytes)) => bytes,
-                                _ => continue,
-                            };
-
-                            let phys_addr = if self.obj.is_64() {
-                                self.obj.endian().read_u64_bytes(bytes.try_into().unwrap()) as usize
-                            } else {
-                                self.obj.endian().read_u32_bytes(bytes.try_into().unwrap()) as usize
-                            };
-
-                            // idk why we need this
-                            phys_addr.saturating_sub(6)
-                        }
-                        _ => continue,
-                    };
-
-                    // TODO: find modules
-                    self.syms.push(Addressed {
-                        addr,
-                        item: RawSymbol { name, module: None },
-                    });
+                },
+                None => sym.address() as usize,
+            };
+            match mapping_symbol(sym.name().unwrap_or_default()) {
+                Some(mapping) => mapping_syms.push((addr, mapping)),
+                None if sym.kind() == SymbolKind::Text && sym.is_definition() => {
+                    func_syms.push(addr)
                 }
+                None => {}
             }
         }
+
+        mapping_syms.sort_by_key(|(addr, _)| *addr);
+        self.data_ranges = data_ranges(&self.sections, &mapping_syms);
+        self.syms.retain(|sym| mapping_symbol(sym.item.name).is_none());
+
+        if self.obj.architecture() == Architecture::Arm {
+            self.parse_arm_modes(&mapping_syms, func_syms);
+        }
     }
 
-    pub fn parse_symbols(&mut self) {
-        self.syms.extend(crate::parse_symbol_table(self.obj));
-        self.syms.push(Addressed {
-            addr: self.obj.entry() as usize,
-            item: RawSymbol {
-                name: "entry",
-                module: None,
-            },
+    /// Find which parts of ARM code are Arm or Thumb code using the mapping symbols (`$a` and
+    /// `$t`), bit 0 of function symbols and the entrypoint, and whether the target only runs
+    /// Thumb code.
+    ///
+    /// Bit 0 is cleared from Thumb function symbols.
+    fn parse_arm_modes(&mut self, mapping_syms: &[(usize, Mapping)], mut func_syms: Vec<usize>) {
+        func_syms.push(self.obj.entry() as usize);
+        func_syms.sort_unstable();
+        func_syms.dedup();
+
+        // mapping symbols take priority, as they're sorted first among modes at the same address
+        let mut modes: Vec<(usize, ArmMode)> = mapping_syms
+            .iter()
+            .filter_map(|&(addr, mapping)| match mapping {
+                Mapping::Arm => Some((addr, ArmMode::Arm)),
+                Mapping::Thumb => Some((addr, ArmMode::Thumb)),
+                _ => None,
+            })
+            .collect();
+
+        if is_thumb_only(self.obj).unwrap_or(false) {
+            modes.push((0, ArmMode::Thumb));
+        } else {
+            modes.extend(func_syms.iter().filter(|&&addr| addr != 0).map(|&addr| {
+                match addr & 1 {
+                    1 => (addr & !1, ArmMode::Thumb),
+                    _ => (addr, ArmMode::Arm),
+                }
+            }));
+        }
+
+        modes.sort_by_key(|(addr, _)| *addr);
+        modes.dedup_by_key(|(addr, _)| *addr);
+        self.arm_modes.extend(AddressMap {
+            mapping: modes.into_iter().map(|(addr, item)| Addressed { addr, item }).collect(),
         });
+
+        for sym in self.syms.iter_mut() {
+            if sym.addr & 1 == 1 && func_syms.binary_search(&sym.addr).is_ok() {
+                sym.addr &= !1;
+            }
+        }
+    }
+}
+
+/// Symbols of a relocatable object at the addresses they're laid out at. Section and file
+/// symbols are left out, as sections are named elsewhere.
+fn relocatable_symbol_table<'data, Elf: FileHeader>(
+    obj: &'data ElfFile<'data, Elf>,
+    layout: &reloc::Layout,
+) -> AddressMap<RawSymbol<'data>> {
+    let mut syms = AddressMap::default();
+    for sym in obj.symbols() {
+        if matches!(sym.kind(), SymbolKind::Section | SymbolKind::File) {
+            continue;
+        }
+
+        if let (Ok(name), Some(addr)) = (sym.name(), layout.symbol_addr(&sym)) {
+            if !name.is_empty() {
+                syms.push(Addressed { addr, item: RawSymbol { name, module: None } });
+            }
+        }
+    }
+    syms
+}
+
+/// Layout of the GOT on MIPS, where the GOT entries starting at `local_gotno` hold the
+/// addresses of the dynamic symbols starting at `gotsym`.
+struct MipsGot {
+    local_gotno: usize,
+    gotsym: usize,
+    symtabno: usize,
+}
+
+/// Whether a relocation of type `r_type` fills in a GOT slot (or copies a symbol) with the
+/// address of an imported symbol.
+fn is_got_relocation(arch: Architecture, r_type: u32) -> bool {
+    match arch {
+        Architecture::X86_64 | Architecture::X86_64_X32 => matches!(
+            r_type,
+            elf::R_X86_64_GLOB_DAT | elf::R_X86_64_JUMP_SLOT | elf::R_X86_64_COPY
+        ),
+        Architecture::I386 => {
+            matches!(r_type, elf::R_386_GLOB_DAT | elf::R_386_JMP_SLOT | elf::R_386_COPY)
+        }
+        Architecture::Arm => matches!(
+            r_type,
+            elf::R_ARM_GLOB_DAT | elf::R_ARM_JUMP_SLOT | elf::R_ARM_COPY
+        ),
+        Architecture::Aarch64 => matches!(
+            r_type,
+            elf::R_AARCH64_GLOB_DAT | elf::R_AARCH64_JUMP_SLOT | elf::R_AARCH64_COPY
+        ),
+        Architecture::Aarch64_Ilp32 => matches!(
+            r_type,
+            elf::R_AARCH64_P32_GLOB_DAT | elf::R_AARCH64_P32_JUMP_SLOT | elf::R_AARCH64_P32_COPY
+        ),
+        Architecture::Riscv32 | Architecture::Riscv64 => {
+            matches!(r_type, elf::R_RISCV_JUMP_SLOT | elf::R_RISCV_COPY)
+        }
+        Architecture::Mips | Architecture::Mips64 => {
+            matches!(r_type, elf::R_MIPS_JUMP_SLOT | elf::R_MIPS_COPY)
+        }
+        _ => false,
+    }
+}
+
+/// What the bytes following a mapping symbol are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mapping {
+    /// `$a`, Arm code.
+    Arm,
+    /// `$t`, Thumb code.
+    Thumb,
+    /// `$x`, AArch64 or RISC-V code.
+    Code,
+    /// `$d`, data such as literal pools.
+    Data,
+}
+
+/// Mapping symbols are named `$<kind>` or `$<kind>.<anything>`. RISC-V's `$x` may also be
+/// followed by the ISA string, e.g. `$xrv64i2p1`.
+fn mapping_symbol(name: &str) -> Option<Mapping> {
+    let mut chars = name.strip_prefix('$')?.chars();
+    let mapping = match chars.next()? {
+        'a' => Mapping::Arm,
+        't' => Mapping::Thumb,
+        'x' => Mapping::Code,
+        'd' => Mapping::Data,
+        _ => return None,
+    };
+
+    let suffix = chars.as_str();
+    if suffix.is_empty()
+        || suffix.starts_with('.')
+        || (mapping == Mapping::Code && suffix.starts_with("rv"))
+    {
+        Some(mapping)
+    } else {
+        None
+    }
+}
+
+/// Ranges of code sections that are marked as data by `$d`, lasting until the next mapping
+/// symbol marking code or the end of the section.
+fn data_ranges(sections: &[Section], mapping_syms: &[(usize, Mapping)]) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+
+    for section in sections.iter().filter(|s| s.kind == SectionKind::Code) {
+        let start = mapping_syms.partition_point(|(addr, _)| *addr < section.start);
+        let end = mapping_syms.partition_point(|(addr, _)| *addr < section.end);
+        let mut data_start = None;
+
+        for &(addr, mapping) in &mapping_syms[start..end] {
+            match (mapping, data_start) {
+                (Mapping::Data, None) => data_start = Some(addr),
+                (Mapping::Data, Some(_)) => {}
+                (_, Some(start)) => {
+                    ranges.push(start..addr);
+                    data_start = None;
+                }
+                (_, None) => {}
+            }
+        }
+
+        if let Some(start) = data_start {
+            ranges.push(start..section.end);
+        }
+    }
+
+    ranges.retain(|range| !range.is_empty());
+    ranges.sort_unstable_by_key(|range| range.start);
+    ranges
+}
+
+/// Whether the target can only run Thumb code, being an M-profile CPU or one without support
+/// for the Arm instruction set.
+///
+/// EABI binaries don't record this in `e_flags`, so it's read from the build attributes.
+fn is_thumb_only<'data, Elf: FileHeader>(
+    obj: &'data ElfFile<'data, Elf>,
+) -> Result<bool, object::Error> {
+    const TAG_CPU_ARCH_PROFILE: u64 = 7;
+    const TAG_ARM_ISA_USE: u64 = 8;
+
+    let endian = obj.endian();
+    let section_headers = obj.raw_header().sections(endian, obj.data())?;
+    let header = match section_headers
+        .iter()
+        .find(|header| header.sh_type(endian) == elf::SHT_ARM_ATTRIBUTES)
+    {
+        Some(header) => header,
+        None => return Ok(false),
+    };
+
+    let mut subsections = header.attributes(endian, obj.data())?.subsections()?;
+    while let Some(subsection) = subsections.next()? {
+        if subsection.vendor() != b"aeabi" {
+            continue;
+        }
+
+        let mut subsubsections = subsection.subsubsections();
+        while let Some(subsubsection) = subsubsections.next()? {
+            if subsubsection.tag() != elf::Tag_File {
+                continue;
+            }
+
+            let mut attributes = subsubsection.attributes();
+            while let Some(tag) = attributes.read_tag()? {
+                match tag {
+                    TAG_CPU_ARCH_PROFILE => {
+                        if attributes.read_integer()? == b'M' as u64 {
+                            return Ok(true);
+                        }
+                    }
+                    TAG_ARM_ISA_USE => {
+                        if attributes.read_integer()? == 0 {
+                            return Ok(true);
+                        }
+                    }
+                    // Tag_CPU_raw_name, Tag_CPU_name and Tag_conformance
+                    4 | 5 | 67 => {
+                        attributes.read_string()?;
+                    }
+                    // Tag_compatibility
+                    32 => {
+                        attributes.read_integer()?;
+                        attributes.read_string()?;
+                    }
+                    // past Tag_compatibility, odd tags are strings and even tags are integers
+                    tag if tag > 32 && tag % 2 == 1 => {
+                        attributes.read_string()?;
+                    }
+                    _ => {
+                        attributes.read_integer()?;
+                    }
+                }
+            }
+        }
     }
+
+    Ok(false)
 }
 
 /// Common ELF dwarf section names I've found so far.
@@ -247,14 +645,14 @@ fn parse_sections<'data, Elf: FileHeader>(obj: &'data ElfFile<'data, Elf>) -> Ve
             kind = SectionKind::Debug;
         }
 
-        sections.push(Section::new(
-            name,
-            ident,
-            kind,
-            bytes,
-            start,
-            end
-        ));
+        let mut parsed = Section::new(name, ident, kind, bytes, start, end);
+        parsed.permissions = Permissions {
+            read: sh_flags as u32 & elf::SHF_ALLOC != 0,
+            write: sh_flags as u32 & elf::SHF_WRITE != 0,
+            execute: sh_flags as u32 & elf::SHF_EXECINSTR != 0,
+        };
+        crate::parse_section_attributes(&section, &mut parsed);
+        sections.push(parsed);
     }
 
     sections
@@ -386,3 +784,60 @@ datastructure! {
         d_val: u32,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mapping_symbols() {
+        assert_eq!(mapping_symbol("$a"), Some(Mapping::Arm));
+        assert_eq!(mapping_symbol("$t"), Some(Mapping::Thumb));
+        assert_eq!(mapping_symbol("$x"), Some(Mapping::Code));
+        assert_eq!(mapping_symbol("$d"), Some(Mapping::Data));
+        assert_eq!(mapping_symbol("$x.foo"), Some(Mapping::Code));
+        assert_eq!(mapping_symbol("$d.12"), Some(Mapping::Data));
+        assert_eq!(mapping_symbol("$xrv64i2p1"), Some(Mapping::Code));
+
+        // only `$x` is followed by an isa string
+        assert_eq!(mapping_symbol("$drv64i2p1"), None);
+        assert_eq!(mapping_symbol("$tx"), None);
+        assert_eq!(mapping_symbol("$xfoo"), None);
+        assert_eq!(mapping_symbol("$f"), None);
+        assert_eq!(mapping_symbol("$"), None);
+        assert_eq!(mapping_symbol("d"), None);
+        assert_eq!(mapping_symbol("main"), None);
+    }
+
+    #[test]
+    fn data_in_code() {
+        static BYTES: [u8; 0x100] = [0; 0x100];
+        let sections = [
+            Section::new(".text".into(), "", SectionKind::Code, &BYTES, 0x1000, 0x1100),
+            Section::new(".init".into(), "", SectionKind::Code, &BYTES, 0x2000, 0x2100),
+            Section::new(".data".into(), "", SectionKind::Raw, &BYTES, 0x3000, 0x3100),
+        ];
+
+        let mapping_syms = [
+            (0x1000, Mapping::Code),
+            (0x1010, Mapping::Data),
+            (0x1018, Mapping::Data),
+            (0x1020, Mapping::Code),
+            // lasts until the end of the section
+            (0x10f0, Mapping::Data),
+            (0x2000, Mapping::Data),
+            (0x2040, Mapping::Thumb),
+            // data without any bytes
+            (0x2080, Mapping::Data),
+            (0x2080, Mapping::Arm),
+            // not a code section
+            (0x3000, Mapping::Data),
+        ];
+
+        assert_eq!(data_ranges(&sections, &mapping_syms), [
+            0x1010..0x1020,
+            0x10f0..0x1100,
+            0x2000..0x2040,
+        ]);
+    }
+}
@@ -0,0 +1,398 @@
+//! Discovery of PLT stubs, the trampolines that calls to imported functions go through.
+//!
+//! Each stub loads the address of the function from a GOT slot that's filled in by the dynamic
+//! loader. The layout of a stub differs per architecture, so the instructions computing the
+//! slot's address are matched here to find out which import a stub belongs to.
+
+use object::{Architecture, Endian, Endianness};
+
+/// PLT stubs in a section, given by their address and the address of the GOT slot they jump
+/// through.
+///
+/// `got` is the address of the GOT, which i386's position independent stubs are relative to.
+pub fn stubs(
+    arch: Architecture,
+    endian: Endianness,
+    addr: usize,
+    bytes: &[u8],
+    got: usize,
+) -> Vec<(usize, usize)> {
+    match arch {
+        Architecture::X86_64 | Architecture::X86_64_X32 => x86_stubs(addr, bytes, None),
+        Architecture::I386 => x86_stubs(addr, bytes, Some(got)),
+        Architecture::Aarch64 | Architecture::Aarch64_Ilp32 => {
+            aarch64_stubs(endian, addr, bytes)
+        }
+        Architecture::Arm => arm_stubs(endian, addr, bytes),
+        Architecture::Riscv32 | Architecture::Riscv64 => riscv_stubs(endian, addr, bytes),
+        Architecture::Mips | Architecture::Mips64 => mips_stubs(endian, addr, bytes),
+        _ => Vec::new(),
+    }
+}
+
+fn words(endian: Endianness, bytes: &[u8]) -> Vec<u32> {
+    bytes
+        .chunks_exact(4)
+        .map(|word| endian.read_u32_bytes(word.try_into().unwrap()))
+        .collect()
+}
+
+fn offset(addr: usize, offset: i64) -> usize {
+    (addr as i64).wrapping_add(offset) as usize
+}
+
+/// `jmp [rip + disp32]` on x86_64 and `jmp [abs32]` or `jmp [ebx + disp32]` on i386. Stubs may
+/// start with an `endbr` and the jump may have a `bnd` prefix.
+fn x86_stubs(addr: usize, bytes: &[u8], got: Option<usize>) -> Vec<(usize, usize)> {
+    let mut stubs = Vec::new();
+
+    for idx in 0..bytes.len().saturating_sub(5) {
+        let imm = i32::from_le_bytes(bytes[idx + 2..idx + 6].try_into().unwrap());
+        let slot = match (bytes[idx], bytes[idx + 1], got) {
+            (0xff, 0x25, None) => offset(addr + idx + 6, imm as i64),
+            (0xff, 0x25, Some(_)) => imm as u32 as usize,
+            (0xff, 0xa3, Some(got)) => offset(got, imm as i64),
+            _ => continue,
+        };
+
+        let mut start = idx;
+        if start >= 1 && bytes[start - 1] == 0xf2 {
+            start -= 1;
+        }
+        if start >= 4 && matches!(bytes[start - 4..start], [0xf3, 0x0f, 0x1e, 0xfa | 0xfb]) {
+            start -= 4;
+        }
+
+        stubs.push((addr + start, slot));
+    }
+
+    stubs
+}
+
+/// `adrp x16, page; ldr x17, [x16, #off]`, optionally preceded by a `bti c`.
+fn aarch64_stubs(endian: Endianness, addr: usize, bytes: &[u8]) -> Vec<(usize, usize)> {
+    const BTI_C: u32 = 0xd503245f;
+
+    let words = words(endian, bytes);
+    let mut stubs = Vec::new();
+
+    for (idx, pair) in words.windows(2).enumerate() {
+        let (adrp, ldr) = (pair[0], pair[1]);
+
+        // adrp x16
+        if adrp & 0x9f00001f != 0x90000010 {
+            continue;
+        }
+
+        // ldr x17, [x16, #off] or ldr w17, [x16, #off]
+        let off = match ldr & 0xffc003ff {
+            0xf9400211 => ((ldr >> 10) & 0xfff) as usize * 8,
+            0xb9400211 => ((ldr >> 10) & 0xfff) as usize * 4,
+            _ => continue,
+        };
+
+        let pc = addr + idx * 4;
+        let immlo = ((adrp >> 29) & 0b11) as i64;
+        let immhi = (((adrp as i32) << 8) >> 13) as i64;
+        let page = offset(pc & !0xfff, ((immhi << 2) | immlo) << 12);
+
+        let start = if idx > 0 && words[idx - 1] == BTI_C { pc - 4 } else { pc };
+        stubs.push((start, page + off));
+    }
+
+    stubs
+}
+
+/// `add ip, pc, #imm` followed by any number of `add ip, ip, #imm` and `ldr pc, [ip, #imm]!`.
+/// Stubs called from Thumb code are preceded by `bx pc; nop`.
+fn arm_stubs(endian: Endianness, addr: usize, bytes: &[u8]) -> Vec<(usize, usize)> {
+    // an arm modified immediate, being an 8-bit value rotated right by twice the rotation
+    let imm = |word: u32| (word & 0xff).rotate_right(2 * ((word >> 8) & 0xf)) as usize;
+
+    let words = words(endian, bytes);
+    let mut stubs = Vec::new();
+
+    for (idx, &word) in words.iter().enumerate() {
+        // add ip, pc, #imm
+        if word & 0xfffff000 != 0xe28fc000 {
+            continue;
+        }
+
+        let pc = addr + idx * 4;
+        let mut slot = pc + 8 + imm(word);
+
+        for &word in &words[idx + 1..] {
+            match word & 0xfffff000 {
+                // add ip, ip, #imm
+                0xe28cc000 => slot += imm(word),
+                // ldr pc, [ip, #imm]!
+                0xe5bcf000 => {
+                    slot += (word & 0xfff) as usize;
+
+                    let thumb_prefix =
+                        idx > 0 && bytes[(idx - 1) * 4..idx * 4] == [0x78, 0x47, 0xc0, 0x46];
+                    let start = if thumb_prefix { pc - 4 } else { pc };
+                    stubs.push((start, slot));
+                    break;
+                }
+                _ => break,
+            }
+        }
+    }
+
+    stubs
+}
+
+/// `auipc t3, hi; lw t3, lo(t3)` on RISC-V 32 and `auipc t3, hi; ld t3, lo(t3)` on RISC-V 64.
+fn riscv_stubs(endian: Endianness, addr: usize, bytes: &[u8]) -> Vec<(usize, usize)> {
+    let words = words(endian, bytes);
+    let mut stubs = Vec::new();
+
+    for (idx, pair) in words.windows(2).enumerate() {
+        let (auipc, load) = (pair[0], pair[1]);
+
+        // auipc t3, hi
+        if auipc & 0xfff != 0xe17 {
+            continue;
+        }
+
+        // lw/ld t3, lo(t3)
+        let funct3 = (load >> 12) & 0b111;
+        if load & 0xf8fff != 0xe0e03 || !matches!(funct3, 0b010 | 0b011) {
+            continue;
+        }
+
+        let pc = addr + idx * 4;
+        let hi = (auipc & 0xfffff000) as i32 as i64;
+        let lo = (load as i32 >> 20) as i64;
+        stubs.push((pc, offset(pc, hi + lo)));
+    }
+
+    stubs
+}
+
+/// `lui t7, %hi(slot); lw t9, %lo(slot)(t7)`, or `ld` on MIPS64.
+fn mips_stubs(endian: Endianness, addr: usize, bytes: &[u8]) -> Vec<(usize, usize)> {
+    let words = words(endian, bytes);
+    let mut stubs = Vec::new();
+
+    for (idx, pair) in words.windows(2).enumerate() {
+        let (lui, load) = (pair[0], pair[1]);
+
+        // lui t7, %hi(slot)
+        if lui & 0xffff0000 != 0x3c0f0000 {
+            continue;
+        }
+
+        // lw t9, %lo(slot)(t7) or ld t9, %lo(slot)(t7)
+        if !matches!(load & 0xffff0000, 0x8df90000 | 0xddf90000) {
+            continue;
+        }
+
+        let hi = ((lui & 0xffff) << 16) as i32 as i64;
+        let lo = (load & 0xffff) as i16 as i64;
+        stubs.push((addr + idx * 4, (hi + lo) as usize));
+    }
+
+    stubs
+}
+
+/// Lazy binding stubs in `.MIPS.stubs`, given by their address and the index of the dynamic
+/// symbol they resolve. These start with `lw t9, -0x7ff0(gp)` and load the index into `t8`.
+pub fn mips_lazy_stubs(endian: Endianness, addr: usize, bytes: &[u8]) -> Vec<(usize, usize)> {
+    let words = words(endian, bytes);
+    let mut stubs = Vec::new();
+
+    for (idx, &word) in words.iter().enumerate() {
+        // lw t9, -0x7ff0(gp) or ld t9, -0x7ff0(gp)
+        if !matches!(word, 0x8f998010 | 0xdf998010) {
+            continue;
+        }
+
+        let mut hi = 0;
+        for &word in words[idx + 1..].iter().take(4) {
+            let imm = (word & 0xffff) as usize;
+            match word & 0xffff0000 {
+                // lui t8, hi
+                0x3c180000 => hi = imm << 16,
+                // ori t8, t8, lo
+                0x37180000 => {
+                    stubs.push((addr + idx * 4, hi | imm));
+                    break;
+                }
+                // addiu t8, zero, idx, daddiu t8, zero, idx or ori t8, zero, idx
+                0x24180000 | 0x64180000 | 0x34180000 => {
+                    stubs.push((addr + idx * 4, imm));
+                    break;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    stubs
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(endian: Endianness, words: &[u32]) -> Vec<u8> {
+        words.iter().flat_map(|&word| endian.write_u32_bytes(word)).collect()
+    }
+
+    #[test]
+    fn x86_64() {
+        #[rustfmt::skip]
+        let plt = [
+            // push [rip + 0x2fe2]; jmp [rip + 0x2fe4]; nop
+            0xff, 0x35, 0xe2, 0x2f, 0x00, 0x00,
+            0xff, 0x25, 0xe4, 0x2f, 0x00, 0x00,
+            0x0f, 0x1f, 0x40, 0x00,
+            // jmp [rip + 0x2fe2]; push 0; jmp .plt
+            0xff, 0x25, 0xe2, 0x2f, 0x00, 0x00,
+            0x68, 0x00, 0x00, 0x00, 0x00,
+            0xe9, 0xe0, 0xff, 0xff, 0xff,
+        ];
+
+        assert_eq!(x86_stubs(0x1020, &plt, None), [(0x1026, 0x4010), (0x1030, 0x4018)]);
+    }
+
+    #[test]
+    fn x86_64_ibt() {
+        #[rustfmt::skip]
+        let plt_sec = [
+            // endbr64; bnd jmp [rip + 0x2fad]; nop
+            0xf3, 0x0f, 0x1e, 0xfa,
+            0xf2, 0xff, 0x25, 0xad, 0x2f, 0x00, 0x00,
+            0x0f, 0x1f, 0x44, 0x00, 0x00,
+        ];
+
+        assert_eq!(x86_stubs(0x1060, &plt_sec, None), [(0x1060, 0x4018)]);
+    }
+
+    #[test]
+    fn i386() {
+        #[rustfmt::skip]
+        let pic = [
+            // jmp [ebx + 0xc]; push 0; jmp .plt
+            0xff, 0xa3, 0x0c, 0x00, 0x00, 0x00,
+            0x68, 0x00, 0x00, 0x00, 0x00,
+            0xe9, 0xe0, 0xff, 0xff, 0xff,
+        ];
+
+        #[rustfmt::skip]
+        let absolute = [
+            // jmp [0x804c00c]; push 0; jmp .plt
+            0xff, 0x25, 0x0c, 0xc0, 0x04, 0x08,
+            0x68, 0x00, 0x00, 0x00, 0x00,
+            0xe9, 0xe0, 0xff, 0xff, 0xff,
+        ];
+
+        assert_eq!(x86_stubs(0x1030, &pic, Some(0x4000)), [(0x1030, 0x400c)]);
+        assert_eq!(x86_stubs(0x8049030, &absolute, Some(0x804c000)), [(0x8049030, 0x804c00c)]);
+    }
+
+    #[test]
+    fn aarch64() {
+        let words = [
+            0x90000090, // adrp x16, 0x10000
+            0xf947c211, // ldr x17, [x16, #0xf80]
+            0x913e0210, // add x16, x16, #0xf80
+            0xd61f0220, // br x17
+            0xd503245f, // bti c
+            0x90000090, // adrp x16, 0x10000
+            0xf947c611, // ldr x17, [x16, #0xf88]
+            0x913e2210, // add x16, x16, #0xf88
+            0xd61f0220, // br x17
+        ];
+
+        let plt = encode(Endianness::Little, &words);
+        assert_eq!(aarch64_stubs(Endianness::Little, 0x10400, &plt), [
+            (0x10400, 0x20f80),
+            (0x10410, 0x20f88),
+        ]);
+    }
+
+    #[test]
+    fn arm() {
+        let words = [
+            0xe28fc600, // add ip, pc, #0, 12
+            0xe28cca10, // add ip, ip, #16, 20
+            0xe5bcfbb4, // ldr pc, [ip, #0xbb4]!
+            0x46c04778, // bx pc; nop
+            0xe28fc600, // add ip, pc, #0, 12
+            0xe28cca10, // add ip, ip, #16, 20
+            0xe5bcfbac, // ldr pc, [ip, #0xbac]!
+        ];
+
+        // stubs called from thumb code start at the `bx pc`
+        let plt = encode(Endianness::Little, &words);
+        assert_eq!(arm_stubs(Endianness::Little, 0x1000, &plt), [
+            (0x1000, 0x11bbc),
+            (0x100c, 0x11bc4),
+        ]);
+    }
+
+    #[test]
+    fn riscv() {
+        let words = [
+            0x00002e17, // auipc t3, 0x2
+            0xff0e3e03, // ld t3, -0x10(t3)
+            0x000e0367, // jalr t1, t3
+            0x00000013, // nop
+            0x00002e17, // auipc t3, 0x2
+            0x008e2e03, // lw t3, 0x8(t3)
+            0x000e0367, // jalr t1, t3
+            0x00000013, // nop
+        ];
+
+        let plt = encode(Endianness::Little, &words);
+        assert_eq!(riscv_stubs(Endianness::Little, 0x1020, &plt), [
+            (0x1020, 0x3010),
+            (0x1030, 0x3038),
+        ]);
+    }
+
+    #[test]
+    fn mips() {
+        let words = [
+            0x3c0f0041, // lui t7, 0x41
+            0x8df90010, // lw t9, 0x10(t7)
+            0x03200008, // jr t9
+            0x25f80010, // addiu t8, t7, 0x10
+            0x3c0f0041, // lui t7, 0x41
+            0xddf9fff0, // ld t9, -0x10(t7)
+            0x03200008, // jr t9
+            0x65f8fff0, // daddiu t8, t7, -0x10
+        ];
+
+        let plt = encode(Endianness::Big, &words);
+        assert_eq!(mips_stubs(Endianness::Big, 0x400500, &plt), [
+            (0x400500, 0x410010),
+            (0x400510, 0x40fff0),
+        ]);
+    }
+
+    #[test]
+    fn mips_lazy() {
+        let words = [
+            0x8f998010, // lw t9, -0x7ff0(gp)
+            0x03e07825, // move t7, ra
+            0x0320f809, // jalr t9
+            0x24180005, // addiu t8, zero, 5
+            0x8f998010, // lw t9, -0x7ff0(gp)
+            0x03e07825, // move t7, ra
+            0x3c180001, // lui t8, 0x1
+            0x0320f809, // jalr t9
+            0x37182345, // ori t8, t8, 0x2345
+        ];
+
+        let stubs = encode(Endianness::Little, &words);
+        assert_eq!(mips_lazy_stubs(Endianness::Little, 0x400800, &stubs), [
+            (0x400800, 5),
+            (0x400810, 0x12345),
+        ]);
+    }
+}
@@ -0,0 +1,562 @@
+//! Support for relocatable objects (e.g. `.o` and `.ko` files), whose sections aren't given
+//! addresses until they're linked.
+//!
+//! The loaded sections are laid out one after another at made up addresses, followed by a slot
+//! for each undefined symbol. Relocations that can be computed from that are applied, so that
+//! calls and references point at their targets instead of at zero.
+
+use object::elf;
+use object::read::elf::{ElfFile, FileHeader};
+use object::{
+    Architecture, Endian, Endianness, Object, ObjectSection, ObjectSymbol, Relocation,
+    RelocationEncoding, RelocationKind, RelocationTarget, SymbolKind, SymbolSection,
+};
+use processor_shared::{AddressMap, Addressed, Section};
+use std::collections::HashMap;
+
+/// Address the first section is placed at.
+const BASE: usize = 0x10000;
+
+/// Space given to each undefined symbol.
+const EXTERN_SIZE: usize = 16;
+
+/// Whether a relocation was applied and if so, whether its target shows up in the listing.
+#[derive(Debug, PartialEq, Eq)]
+enum Applied {
+    No,
+    /// The target is split over multiple instructions, so it's not shown in the listing.
+    Hidden,
+    Visible,
+}
+
+/// Addresses given to the sections and undefined symbols of a relocatable object.
+pub struct Layout {
+    /// Address of each section by its index, `None` if the section isn't loaded.
+    sections: Vec<Option<usize>>,
+    /// Address of each undefined symbol by its index.
+    externs: HashMap<usize, usize>,
+}
+
+impl Layout {
+    /// Place the loaded `sections` one after another, followed by the undefined symbols.
+    /// `sections` must be in the order of their index.
+    pub fn new<'data, Elf: FileHeader>(
+        obj: &'data ElfFile<'data, Elf>,
+        sections: &mut [Section],
+    ) -> Self {
+        let mut addr = BASE;
+        let mut layout = Self {
+            sections: Vec::with_capacity(sections.len()),
+            externs: HashMap::new(),
+        };
+
+        for section in sections.iter_mut() {
+            if !section.permissions.read {
+                layout.sections.push(None);
+                continue;
+            }
+
+            let size = section.end - section.start;
+            addr = addr.next_multiple_of(section.alignment);
+            section.start = addr;
+            section.end = addr + size;
+            layout.sections.push(Some(addr));
+            addr += size;
+        }
+
+        addr = addr.next_multiple_of(EXTERN_SIZE);
+        for sym in obj.symbols() {
+            let is_named = sym.name().is_ok_and(|name| !name.is_empty());
+            if is_named && (sym.is_undefined() || sym.is_common()) {
+                layout.externs.insert(sym.index().0, addr);
+                addr += EXTERN_SIZE;
+            }
+        }
+
+        layout
+    }
+
+    /// Address of `sym` once it's laid out, `None` if it's in a section that isn't loaded.
+    pub fn symbol_addr<'data>(&self, sym: &impl ObjectSymbol<'data>) -> Option<usize> {
+        match sym.section() {
+            SymbolSection::Section(idx) => {
+                let base = self.sections.get(idx.0).copied().flatten()?;
+                Some(base + sym.address() as usize)
+            }
+            SymbolSection::Absolute => Some(sym.address() as usize),
+            _ => self.externs.get(&sym.index().0).copied(),
+        }
+    }
+}
+
+/// Apply the relocations of all loaded sections that can be applied.
+///
+/// Returns the targets of relocations that aren't visible in the listing by their address,
+/// either because they couldn't be applied or because they refer to an unnamed part of a
+/// section.
+pub fn relocate<'data, Elf: FileHeader>(
+    obj: &'data ElfFile<'data, Elf>,
+    layout: &Layout,
+    sections: &mut [Section],
+) -> AddressMap<String> {
+    let arch = obj.architecture();
+    let endian = if obj.endian().is_little_endian() {
+        Endianness::Little
+    } else {
+        Endianness::Big
+    };
+
+    let mut annotations = AddressMap::default();
+    for (section, obj_section) in sections.iter_mut().zip(obj.sections()) {
+        let base = match layout.sections.get(obj_section.index().0).copied().flatten() {
+            Some(base) => base,
+            None => continue,
+        };
+
+        let mut bytes = section.original_bytes().to_vec();
+        let mut is_relocated = false;
+
+        for (offset, reloc) in obj_section.relocations() {
+            let offset = offset as usize;
+            let (target, name, is_named) = match reloc.target() {
+                RelocationTarget::Symbol(idx) => match obj.symbol_by_index(idx) {
+                    Ok(sym) if sym.kind() == SymbolKind::Section => {
+                        let name = section_name(obj, sym.section());
+                        (layout.symbol_addr(&sym), name, false)
+                    }
+                    Ok(sym) => {
+                        let name = sym.name().ok().map(str::to_string);
+                        (layout.symbol_addr(&sym), name, true)
+                    }
+                    Err(_) => (None, None, false),
+                },
+                RelocationTarget::Section(idx) => {
+                    let addr = layout.sections.get(idx.0).copied().flatten();
+                    let name = section_name(obj, SymbolSection::Section(idx));
+                    (addr, name, false)
+                }
+                _ => (None, None, false),
+            };
+
+            let applied = match target {
+                Some(target) => apply(arch, endian, &mut bytes, offset, base, target, &reloc),
+                None => Applied::No,
+            };
+
+            is_relocated |= applied != Applied::No;
+
+            if let Some(name) = name.filter(|_| applied != Applied::Visible || !is_named) {
+                let item = match reloc.addend() {
+                    0 => name,
+                    addend if addend < 0 => format!("{name}-{:#x}", addend.unsigned_abs()),
+                    addend => format!("{name}+{addend:#x}"),
+                };
+
+                annotations.push(Addressed { addr: base + offset, item });
+            }
+        }
+
+        if is_relocated {
+            section.relocate(bytes);
+        }
+    }
+
+    annotations.sort_unstable_by_key(|annotation| annotation.addr);
+
+    log::complex!(
+        w "[elf::relocate] ",
+        g annotations.len().to_string(),
+        w " relocations to annotate."
+    );
+
+    annotations
+}
+
+fn section_name<'data, Elf: FileHeader>(
+    obj: &'data ElfFile<'data, Elf>,
+    section: SymbolSection,
+) -> Option<String> {
+    let idx = section.index()?;
+    let section = obj.section_by_index(idx).ok()?;
+    section.name().ok().map(str::to_string)
+}
+
+/// Write the value of `reloc` at `offset` in `bytes`, a section at `base`.
+fn apply(
+    arch: Architecture,
+    endian: Endianness,
+    bytes: &mut [u8],
+    offset: usize,
+    base: usize,
+    target: usize,
+    reloc: &Relocation,
+) -> Applied {
+    let field = match bytes.get_mut(offset..) {
+        Some(field) => field,
+        None => return Applied::No,
+    };
+
+    // relocations without explicit addends have them stored in the field
+    let addend = |implicit: i64| match reloc.has_implicit_addend() {
+        true => implicit,
+        false => reloc.addend(),
+    };
+
+    let place = (base + offset) as i64;
+    let target = target as i64;
+
+    match (reloc.kind(), reloc.encoding()) {
+        (
+            kind @ (RelocationKind::Absolute
+            | RelocationKind::Relative
+            | RelocationKind::PltRelative),
+            RelocationEncoding::Generic
+            | RelocationEncoding::X86Signed
+            | RelocationEncoding::X86RipRelative
+            | RelocationEncoding::X86Branch,
+        ) => {
+            let len = reloc.size() as usize / 8;
+            if !matches!(len, 1 | 2 | 4 | 8) || field.len() < len {
+                return Applied::No;
+            }
+
+            let mut value = target.wrapping_add(addend(read_int(endian, &field[..len])));
+            if kind != RelocationKind::Absolute {
+                value = value.wrapping_sub(place);
+            }
+
+            write_int(endian, &mut field[..len], value);
+            Applied::Visible
+        }
+        // the GOT isn't laid out, so like a linker relaxing the load, the symbol is referenced
+        // directly
+        (RelocationKind::GotRelative, _)
+        | (RelocationKind::Elf(elf::R_X86_64_GOTPCRELX | elf::R_X86_64_REX_GOTPCRELX), _)
+            if arch == Architecture::X86_64 =>
+        {
+            let field = match field.get_mut(..4) {
+                Some(field) => field,
+                None => return Applied::No,
+            };
+
+            let value = target.wrapping_add(addend(read_int(endian, field))) - place;
+            write_int(endian, field, value);
+            Applied::Visible
+        }
+        (RelocationKind::PltRelative, RelocationEncoding::AArch64Call) => {
+            branch(field, Endianness::Little, 26, target + addend(0) - place)
+        }
+        (RelocationKind::Elf(elf::R_AARCH64_JUMP26), _) if arch == Architecture::Aarch64 => {
+            branch(field, Endianness::Little, 26, target + addend(0) - place)
+        }
+        (
+            RelocationKind::Elf(
+                elf::R_AARCH64_ADR_PREL_PG_HI21 | elf::R_AARCH64_ADR_PREL_PG_HI21_NC,
+            ),
+            _,
+        ) if arch == Architecture::Aarch64 => {
+            let pages = ((target + addend(0)) >> 12) - (place >> 12);
+            let lo = (pages & 0b11) << 29;
+            let hi = (pages >> 2 & 0x7ffff) << 5;
+            match insert(field, Endianness::Little, 0x6000_0000 | 0x00ff_ffe0, lo | hi) {
+                // the page is shown, the rest of the address is added by the next instruction
+                Applied::Visible => Applied::Hidden,
+                applied => applied,
+            }
+        }
+        (
+            RelocationKind::Elf(
+                r_type @ (elf::R_AARCH64_ADD_ABS_LO12_NC
+                | elf::R_AARCH64_LDST8_ABS_LO12_NC
+                | elf::R_AARCH64_LDST16_ABS_LO12_NC
+                | elf::R_AARCH64_LDST32_ABS_LO12_NC
+                | elf::R_AARCH64_LDST64_ABS_LO12_NC
+                | elf::R_AARCH64_LDST128_ABS_LO12_NC),
+            ),
+            _,
+        ) if arch == Architecture::Aarch64 => {
+            // loads and stores scale the offset by their size
+            let scale = match r_type {
+                elf::R_AARCH64_LDST16_ABS_LO12_NC => 1,
+                elf::R_AARCH64_LDST32_ABS_LO12_NC => 2,
+                elf::R_AARCH64_LDST64_ABS_LO12_NC => 3,
+                elf::R_AARCH64_LDST128_ABS_LO12_NC => 4,
+                _ => 0,
+            };
+
+            let lo12 = (target + addend(0)) & 0xfff;
+            insert(field, Endianness::Little, 0x003f_fc00, (lo12 >> scale) << 10)
+        }
+        // `R_ARM_THM_PC22` is the old name of `R_ARM_THM_CALL`
+        (RelocationKind::Elf(elf::R_ARM_THM_PC22 | elf::R_ARM_THM_JUMP24), _)
+            if arch == Architecture::Arm =>
+        {
+            thumb_branch(field, endian, |implicit| target + addend(implicit) - place)
+        }
+        (RelocationKind::Elf(elf::R_ARM_CALL | elf::R_ARM_JUMP24 | elf::R_ARM_PC24), _)
+            if arch == Architecture::Arm =>
+        {
+            let implicit = match field.get(..4) {
+                Some(insn) => read_int(endian, insn) << 40 >> 38,
+                None => return Applied::No,
+            };
+
+            branch(field, endian, 24, target + addend(implicit) - place)
+        }
+        (RelocationKind::Elf(elf::R_RISCV_CALL | elf::R_RISCV_CALL_PLT), _)
+            if matches!(arch, Architecture::Riscv32 | Architecture::Riscv64) =>
+        {
+            if field.len() < 8 {
+                return Applied::No;
+            }
+
+            // auipc followed by jalr
+            let value = target + addend(0) - place;
+            let auipc = read_int(Endianness::Little, &field[..4]) as u32;
+            let jalr = read_int(Endianness::Little, &field[4..8]) as u32;
+            let hi = ((value + 0x800) >> 12) as u32;
+            let lo = (value & 0xfff) as u32;
+            let auipc = (auipc & 0xfff) | (hi << 12);
+            let jalr = (jalr & 0xfffff) | (lo << 20);
+            write_int(Endianness::Little, &mut field[..4], auipc as i64);
+            write_int(Endianness::Little, &mut field[4..8], jalr as i64);
+            Applied::Hidden
+        }
+        _ => Applied::No,
+    }
+}
+
+/// Fill in the low `bits` of a branch instruction with the word offset `value`.
+fn branch(field: &mut [u8], endian: Endianness, bits: u32, value: i64) -> Applied {
+    let insn = match field.get_mut(..4) {
+        Some(insn) => insn,
+        None => return Applied::No,
+    };
+
+    let mask = (1 << bits) - 1;
+    let encoded = (read_int(endian, insn) & !mask) | ((value >> 2) & mask);
+    write_int(endian, insn, encoded);
+    Applied::Visible
+}
+
+/// Replace the `mask`ed bits of an instruction with `value`.
+fn insert(field: &mut [u8], endian: Endianness, mask: i64, value: i64) -> Applied {
+    let insn = match field.get_mut(..4) {
+        Some(insn) => insn,
+        None => return Applied::No,
+    };
+
+    let encoded = (read_int(endian, insn) & !mask) | (value & mask);
+    write_int(endian, insn, encoded);
+    Applied::Visible
+}
+
+/// Fill in the offset of a Thumb-2 `bl` or `b.w`, made of two halfwords. `value` is given the
+/// offset already stored in the instruction.
+fn thumb_branch(field: &mut [u8], endian: Endianness, value: impl Fn(i64) -> i64) -> Applied {
+    if field.len() < 4 {
+        return Applied::No;
+    }
+
+    let hw1 = read_int(endian, &field[..2]) & 0xffff;
+    let hw2 = read_int(endian, &field[2..4]) & 0xffff;
+
+    // the offset is S:I1:I2:imm10:imm11:0 where I1 = !(J1 ^ S) and I2 = !(J2 ^ S)
+    let s = hw1 >> 10 & 1;
+    let i1 = !(hw2 >> 13 ^ s) & 1;
+    let i2 = !(hw2 >> 11 ^ s) & 1;
+    let implicit = s << 24 | i1 << 23 | i2 << 22 | (hw1 & 0x3ff) << 12 | (hw2 & 0x7ff) << 1;
+    let implicit = implicit << 39 >> 39;
+
+    let value = value(implicit);
+    let s = value >> 24 & 1;
+    let j1 = !(value >> 23 ^ s) & 1;
+    let j2 = !(value >> 22 ^ s) & 1;
+    let hw1 = (hw1 & 0xf800) | s << 10 | (value >> 12 & 0x3ff);
+    let hw2 = (hw2 & 0xd000) | j1 << 13 | j2 << 11 | (value >> 1 & 0x7ff);
+
+    write_int(endian, &mut field[..2], hw1);
+    write_int(endian, &mut field[2..4], hw2);
+    Applied::Visible
+}
+
+/// Read a sign extended integer of 1, 2, 4 or 8 bytes.
+fn read_int(endian: Endianness, bytes: &[u8]) -> i64 {
+    let mut buf = [0; 8];
+    match endian {
+        Endianness::Little => buf[..bytes.len()].copy_from_slice(bytes),
+        Endianness::Big => buf[8 - bytes.len()..].copy_from_slice(bytes),
+    }
+
+    let value = match endian {
+        Endianness::Little => i64::from_le_bytes(buf),
+        Endianness::Big => i64::from_be_bytes(buf),
+    };
+
+    let shift = 64 - bytes.len() as u32 * 8;
+    value << shift >> shift
+}
+
+/// Write the low bytes of `value`, the rest are truncated.
+fn write_int(endian: Endianness, bytes: &mut [u8], value: i64) {
+    let len = bytes.len();
+    match endian {
+        Endianness::Little => bytes.copy_from_slice(&value.to_le_bytes()[..len]),
+        Endianness::Big => bytes.copy_from_slice(&value.to_be_bytes()[8 - len..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object::read::elf::{ElfFile32, ElfFile64};
+
+    /// The fixtures are assembled from the `.s` files next to them with
+    /// `llvm-mc -triple <arch> -filetype=obj`, passing `-mattr=-relax` for RISC-V.
+    fn relocated<Elf: FileHeader<Endian = Endianness>>(
+        obj: &ElfFile<Elf>,
+    ) -> (Vec<Section>, Vec<(usize, String)>) {
+        let mut sections = super::super::parse_sections(obj);
+        let layout = Layout::new(obj, &mut sections);
+        let annotations = relocate(obj, &layout, &mut sections);
+        let annotations = annotations.iter().map(|a| (a.addr, a.item.clone())).collect();
+        (sections, annotations)
+    }
+
+    fn section<'a>(sections: &'a [Section], name: &str) -> &'a Section {
+        sections.iter().find(|section| section.name == name).unwrap()
+    }
+
+    fn words(section: &Section) -> Vec<u32> {
+        section
+            .bytes()
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn x86_64() {
+        let fixture = include_bytes!("../../fixtures/reloc/x86_64.o");
+        let obj = ElfFile64::<Endianness>::parse(&fixture[..]).unwrap();
+        let (sections, annotations) = relocated(&obj);
+
+        let text = section(&sections, ".text");
+        assert_eq!(text.start, 0x10000);
+        assert_eq!(section(&sections, ".data").start, 0x10013);
+
+        #[rustfmt::skip]
+        assert_eq!(text.bytes(), [
+            0xe8, 0x1b, 0x00, 0x00, 0x00,             // call 0x10020 (printf)
+            0x8b, 0x05, 0x08, 0x00, 0x00, 0x00,       // mov eax, [rip + 0x8] (counter)
+            0x48, 0x8b, 0x05, 0x1e, 0x00, 0x00, 0x00, // mov rax, [rip + 0x1e] (stdout)
+            0xc3,                                     // ret
+        ]);
+
+        // the reference to `counter` is made through the section
+        assert_eq!(annotations, [(0x10007, ".data-0x4".to_string())]);
+    }
+
+    #[test]
+    fn aarch64() {
+        let fixture = include_bytes!("../../fixtures/reloc/aarch64.o");
+        let obj = ElfFile64::<Endianness>::parse(&fixture[..]).unwrap();
+        let (sections, annotations) = relocated(&obj);
+
+        assert_eq!(words(section(&sections, ".text")), [
+            0x9400000c, // bl 0x10030 (printf)
+            0x1400000f, // b 0x10040 (puts)
+            0x90000000, // adrp x0, 0x10000
+            0x91008000, // add x0, x0, #0x20 (msg at 0x10020)
+            0x90000001, // adrp x1, 0x10000
+            0xf9401021, // ldr x1, [x1, #0x20] (msg at 0x10020)
+            0xd65f03c0, // ret
+        ]);
+
+        // `.data` is aligned and holds the address of `f`
+        let data = section(&sections, ".data");
+        assert_eq!(data.start, 0x10020);
+        assert_eq!(data.bytes(), 0x10000u64.to_le_bytes());
+
+        // neither half of the address shows which section it's in
+        let data = ".data".to_string();
+        assert_eq!(annotations, [
+            (0x10008, data.clone()),
+            (0x1000c, data.clone()),
+            (0x10010, data.clone()),
+            (0x10014, data),
+        ]);
+    }
+
+    #[test]
+    fn arm() {
+        let fixture = include_bytes!("../../fixtures/reloc/arm.o");
+        let obj = ElfFile32::<Endianness>::parse(&fixture[..]).unwrap();
+        let (sections, annotations) = relocated(&obj);
+
+        // the branches have an implicit addend of -8 stored in them (-4 for thumb)
+        let text = section(&sections, ".text");
+        assert_eq!(words(text)[..3], [
+            0xeb000006, // bl 0x10020 (printf)
+            0xea000009, // b 0x10030 (puts)
+            0xe12fff1e, // bx lr
+        ]);
+
+        #[rustfmt::skip]
+        assert_eq!(text.bytes()[12..], [
+            0x00, 0xf0, 0x08, 0xf8, // bl 0x10020 (printf)
+            0x00, 0xf0, 0x0e, 0xb8, // b.w 0x10030 (puts)
+            0x70, 0x47,             // bx lr
+        ]);
+
+        assert_eq!(annotations, []);
+    }
+
+    #[test]
+    fn riscv64() {
+        let fixture = include_bytes!("../../fixtures/reloc/riscv64.o");
+        let obj = ElfFile64::<Endianness>::parse(&fixture[..]).unwrap();
+        let (sections, annotations) = relocated(&obj);
+
+        // the low 12 bits are sign extended, so the high 20 bits are rounded up
+        assert_eq!(words(section(&sections, ".text"))[..4], [
+            0x00001097, // auipc ra, 0x1
+            0x800080e7, // jalr ra, -0x800(ra) (printf at 0x10800)
+            0x00001317, // auipc t1, 0x1
+            0x80830067, // jr -0x7f8(t1) (puts at 0x10810)
+        ]);
+
+        // targets split over two instructions aren't shown in the listing
+        assert_eq!(annotations, [(0x10000, "printf".to_string()), (0x10008, "puts".to_string())]);
+    }
+
+    #[test]
+    fn ints() {
+        assert_eq!(read_int(Endianness::Little, &[0x78, 0x56, 0x34, 0x12]), 0x12345678);
+        assert_eq!(read_int(Endianness::Big, &[0x12, 0x34, 0x56, 0x78]), 0x12345678);
+        assert_eq!(read_int(Endianness::Big, &[0xff, 0xfe]), -2);
+        assert_eq!(read_int(Endianness::Little, &[0x80]), -0x80);
+
+        let mut bytes = [0; 4];
+        write_int(Endianness::Big, &mut bytes, -8);
+        assert_eq!(bytes, [0xff, 0xff, 0xff, 0xf8]);
+
+        // values are truncated to the field
+        write_int(Endianness::Big, &mut bytes[..2], 0x12345678);
+        assert_eq!(bytes, [0x56, 0x78, 0xff, 0xf8]);
+        write_int(Endianness::Little, &mut bytes[2..], 0x12345678);
+        assert_eq!(bytes, [0x56, 0x78, 0x78, 0x56]);
+    }
+
+    #[test]
+    fn big_endian_branch() {
+        // `bl .` on big endian arm, which has an implicit addend of -8
+        let mut insn = [0xeb, 0xff, 0xff, 0xfe];
+        assert_eq!(read_int(Endianness::Big, &insn) << 40 >> 38, -8);
+
+        assert_eq!(branch(&mut insn, Endianness::Big, 24, 0x100 - 8), Applied::Visible);
+        assert_eq!(insn, [0xeb, 0x00, 0x00, 0x3e]);
+
+        assert_eq!(branch(&mut [0; 2], Endianness::Big, 24, 0x100), Applied::No);
+    }
+}
@@ -1,5 +1,5 @@
 use object::{Object, ObjectSection, ObjectSymbol};
-use processor_shared::{AddressMap, Addressed};
+use processor_shared::{AddressMap, Addressed, Section};
 
 pub mod elf;
 pub mod macho;
@@ -67,6 +67,16 @@ fn parse_section_generics<'data, Obj: ObjectSection<'data>>(
     (name.to_string(), bytes, start, end)
 }
 
+/// Fill in where a section is stored in the file, its alignment and its flags.
+fn parse_section_attributes<'data, Obj: ObjectSection<'data>>(
+    section: &'data Obj,
+    parsed: &mut Section,
+) {
+    parsed.file_offset = section.file_range().map(|(offset, _)| offset as usize);
+    parsed.alignment = std::cmp::max(section.align() as usize, 1);
+    parsed.flags = section.flags();
+}
+
 pub struct Datastructure {
     pub ident: &'static str,
     pub fields: Vec<(usize, &'static str, &'static str, String)>,
@@ -88,7 +98,7 @@ macro_rules! datastructure {
         #[repr(C)]
         #[derive(Copy, Clone, Debug)]
         pub struct $name {
-            pub $($field: $ftype),*
+            $(pub $field: $ftype),*
         }
 
         impl $crate::ToData for $name {
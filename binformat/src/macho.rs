@@ -1,7 +1,7 @@
 use crate::RawSymbol;
-use processor_shared::{AddressMap, Addressed, Section, SectionKind};
+use processor_shared::{AddressMap, Addressed, Permissions, Section, SectionKind};
 use object::macho::{self, DyldInfoCommand, DysymtabCommand, LinkeditDataCommand};
-use object::read::macho::{MachHeader, MachOFile, SymbolTable};
+use object::read::macho::{MachHeader, MachOFile, Segment, SymbolTable};
 use object::{Endianness, Object, ObjectSection, ObjectSegment, ReadRef, SectionFlags};
 use std::mem::size_of;
 
@@ -285,7 +285,25 @@ const DWARF_SECTIONS: [&str; 20] = [
     "__debug_types",
 ];
 
+/// Initial protections of each segment, by name.
+fn segment_protections<'data, Mach: MachHeader>(
+    obj: &'data MachOFile<'data, Mach>,
+) -> Result<Vec<(&'data [u8], u32)>, object::Error> {
+    let endian = obj.endian();
+    let mut protections = Vec::new();
+    let mut load_cmds_iter = obj.raw_header().load_commands(endian, obj.data(), 0)?;
+    while let Some(lcmd) = load_cmds_iter.next()? {
+        if let Some((segment, _)) = Mach::Segment::from_command(lcmd)? {
+            protections.push((segment.name(), segment.initprot(endian)));
+        }
+    }
+
+    Ok(protections)
+}
+
 fn parse_sections<'data, Mach: MachHeader>(obj: &'data MachOFile<'data, Mach>) -> Vec<Section> {
+    let protections = segment_protections(obj).unwrap_or_default();
+
     let mut sections = Vec::new();
     for section in obj.sections() {
         let (name, bytes, start, end) = crate::parse_section_generics(&section);
@@ -387,14 +405,27 @@ fn parse_sections<'data, Mach: MachHeader>(obj: &'data MachOFile<'data, Mach>) -
             kind = SectionKind::Debug;
         }
 
-        sections.push(Section::new(
-            name,
-            ident,
-            kind,
-            bytes,
-            start,
-            end
-        ));
+        // Sections don't have protections of their own, they're those of the segment they're
+        // in. Object files put all sections in a single unnamed segment, so there the
+        // protections are guessed from the section.
+        let segment_name = section.segment_name_bytes().ok().flatten().unwrap_or_default();
+        let permissions = match protections.iter().find(|(name, _)| *name == segment_name) {
+            Some(&(_, prot)) => Permissions {
+                read: prot & macho::VM_PROT_READ != 0,
+                write: prot & macho::VM_PROT_WRITE != 0,
+                execute: prot & macho::VM_PROT_EXECUTE != 0,
+            },
+            None => Permissions {
+                read: true,
+                write: segment_name.starts_with(b"__DATA"),
+                execute: kind == SectionKind::Code,
+            },
+        };
+
+        let mut parsed = Section::new(name, ident, kind, bytes, start, end);
+        parsed.permissions = permissions;
+        crate::parse_section_attributes(&section, &mut parsed);
+        sections.push(parsed);
     }
 
     sections
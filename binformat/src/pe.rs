@@ -1,5 +1,5 @@
 use crate::{datastructure, RawSymbol};
-use processor_shared::{AddressMap, Addressed, Section, SectionKind};
+use processor_shared::{AddressMap, Addressed, Permissions, Section, SectionKind};
 use object::pe;
 use object::read::pe::{ImageNtHeaders, ImageThunkData, PeFile};
 use object::LittleEndian as LE;
@@ -153,8 +153,9 @@ fn parse_sections<'data, Pe: ImageNtHeaders>(obj: &'data PeFile<'data, Pe>) -> V
         let characteristics = header.characteristics.get(LE);
         let (mut kind, ident) = (SectionKind::Raw, "UNKNOWN");
 
-        // Section contains code.
-        if characteristics & pe::IMAGE_SCN_CNT_CODE != 0 {
+        // Section contains code, packers tend to leave out that it does so anything executable
+        // is treated as code.
+        if characteristics & (pe::IMAGE_SCN_CNT_CODE | pe::IMAGE_SCN_MEM_EXECUTE) != 0 {
             kind = SectionKind::Code;
         }
 
@@ -168,14 +169,14 @@ fn parse_sections<'data, Pe: ImageNtHeaders>(obj: &'data PeFile<'data, Pe>) -> V
             kind = SectionKind::Debug;
         }
 
-        sections.push(Section::new(
-            name,
-            ident,
-            kind,
-            bytes,
-            start,
-            end
-        ));
+        let mut parsed = Section::new(name, ident, kind, bytes, start, end);
+        parsed.permissions = Permissions {
+            read: characteristics & pe::IMAGE_SCN_MEM_READ != 0,
+            write: characteristics & pe::IMAGE_SCN_MEM_WRITE != 0,
+            execute: characteristics & pe::IMAGE_SCN_MEM_EXECUTE != 0,
+        };
+        crate::parse_section_attributes(&section, &mut parsed);
+        sections.push(parsed);
     }
 
     sections
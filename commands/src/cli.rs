@@ -1,3 +1,4 @@
+use crate::debug::CompleteExpr;
 use std::path::{Path, PathBuf};
 
 macro_rules! exit {
@@ -7,15 +8,37 @@ macro_rules! exit {
     }};
 }
 
+/// Stop parsing the arguments with an error message.
+macro_rules! fail {
+    ($($arg:tt)*) => {
+        return Err(format!($($arg)*))
+    };
+}
+
 const HELP: &str = "OVERVIEW: Debugger/Decompilation tool
 
 USAGE: bite [options] <OBJECT>
+       bite signatures <OUTPUT> <ARCHIVE>...
 
 OPTIONS:
   -H, --help          Print usage information
   -D, --disassemble   Path to object you're disassembling
   -C, --config        Path to config used for disassembling
-  -B, --debug         Enable verbose internal info";
+  -B, --debug         Enable verbose internal info
+  --dump              Print the listing to stdout instead of opening the GUI
+  --json              Print the analysis as JSON to stdout instead of opening the GUI
+  --lazy              Decode code when it's first looked at, for very large binaries
+  --section <name>    Only dump the section with the given name
+  --start <addr>      Address to start dumping from
+  --end <addr>        Address to stop dumping at
+  --symbol <name>     Only dump the function with the given name
+  --range <expr>..<expr>
+                      Only dump the blocks within the range of two expressions
+
+SIGNATURES:
+  Generates signatures of the functions in static libraries (.a or .rlib), objects or binaries
+  with symbols and saves them to <OUTPUT>. Signatures in the data directory's `bite/signatures`
+  are used to name the functions of stripped binaries.";
 
 const ABBRV: &[&str] = &["-H", "-D", "-C", "-B"];
 const NAMES: &[&str] = &[
@@ -23,6 +46,14 @@ const NAMES: &[&str] = &[
     "--disassemble",
     "--config",
     "--debug",
+    "--dump",
+    "--json",
+    "--lazy",
+    "--section",
+    "--start",
+    "--end",
+    "--symbol",
+    "--range",
 ];
 
 #[derive(Default, Debug, Clone)]
@@ -35,42 +66,171 @@ pub struct Cli {
 
     /// Show egui debug overlay.
     pub debug: bool,
+
+    /// Print the listing to stdout instead of starting the GUI.
+    pub dump: bool,
+
+    /// Print the analysis as JSON instead of starting the GUI.
+    pub json: bool,
+
+    /// Decode code on demand instead of up front.
+    pub lazy: bool,
+
+    /// Only dump the section with this name.
+    pub section: Option<String>,
+
+    /// Address to start dumping from.
+    pub start: Option<usize>,
+
+    /// Address to stop dumping at.
+    pub end: Option<usize>,
+
+    /// Only dump the function with this name.
+    pub symbol: Option<String>,
+
+    /// Only dump the blocks between two expressions, `<expr>..<expr>`.
+    pub range: Option<(String, String)>,
+
+    /// Generate signatures from the given archives and save them to a file.
+    pub signatures: Option<(PathBuf, Vec<PathBuf>)>,
+}
+
+/// Parses either a hexadecimal address with a leading '0x' or a decimal address.
+fn parse_addr(s: &str) -> Option<usize> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// Next argument as the value of `option`, which can't be missing or be another option.
+fn value_of(args: &mut impl Iterator<Item = String>, option: &str) -> Result<String, String> {
+    match args.next() {
+        Some(value) if !NAMES.contains(&&*value) && !ABBRV.contains(&&*value) => Ok(value),
+        _ => Err(format!("Option '{option}' requires a value.")),
+    }
 }
 
 impl Cli {
     pub fn parse() -> Self {
+        match Self::parse_from(std::env::args().skip(1)) {
+            Ok(cli) => cli,
+            Err(err) => exit!(1 => "{err}"),
+        }
+    }
+
+    fn parse_from(args: impl Iterator<Item = String>) -> Result<Self, String> {
         let mut cli = Cli::default();
-        let mut args = std::env::args().skip(1).peekable();
+        let mut args = args.peekable();
+
+        if args.peek().map(String::as_str) == Some("signatures") {
+            args.next();
+            let output = match args.next() {
+                Some(output) => PathBuf::from(output),
+                None => fail!("You must provide a path to save the signatures to."),
+            };
+            let inputs: Vec<PathBuf> = args.map(PathBuf::from).collect();
+            if inputs.is_empty() {
+                fail!("You must provide archives to generate signatures from.");
+            }
+            if let Some(input) = inputs.iter().find(|input| !input.exists()) {
+                fail!("Archive {input:?} does not exist.");
+            }
+            cli.signatures = Some((output, inputs));
+            return Ok(cli);
+        }
 
         while let Some(arg) = args.next() {
             match arg.as_str() {
                 "-H" | "--help" => exit!(0 => "{HELP}"),
                 "-D" | "--disassemble" => {
-                    if let Some(path) = args.next().as_deref() {
-                        if !NAMES.contains(&path) && !ABBRV.contains(&path) {
-                            if cli.path != Path::new("") {
-                                exit!(1 => "Path to object already given.");
-                            }
-                            cli.path = PathBuf::from(path);
-                        }
+                    let path = value_of(&mut args, &arg)?;
+                    if cli.path != Path::new("") {
+                        fail!("Path to object already given.");
                     }
+                    cli.path = PathBuf::from(path);
                 },
                 "-C" | "--config" => {
-                    if let Some(path) = args.next().as_deref() {
-                        if !NAMES.contains(&path) && !ABBRV.contains(&path) {
-                            if cli.config.is_some() {
-                                exit!(1 => "Path to config already given.");
-                            }
-                            cli.config = Some(PathBuf::from(path));
-                        }
+                    let path = value_of(&mut args, &arg)?;
+                    if cli.config.is_some() {
+                        fail!("Path to config already given.");
                     }
+                    cli.config = Some(PathBuf::from(path));
                 },
                 "-B" | "--debug" => {
                     if cli.debug {
-                        exit!(1 => "Debug flag already set.");
+                        fail!("Debug flag already set.");
                     }
                     cli.debug = true
                 }
+                "--dump" => {
+                    if cli.dump {
+                        fail!("Dump flag already set.");
+                    }
+                    cli.dump = true
+                }
+                "--json" => {
+                    if cli.json {
+                        fail!("JSON flag already set.");
+                    }
+                    cli.json = true
+                }
+                "--lazy" => {
+                    if cli.lazy {
+                        fail!("Lazy flag already set.");
+                    }
+                    cli.lazy = true
+                }
+                "--section" => {
+                    let name = value_of(&mut args, &arg)?;
+                    if cli.section.is_some() {
+                        fail!("Section already given.");
+                    }
+                    cli.section = Some(name);
+                }
+                "--start" => {
+                    let addr = value_of(&mut args, &arg)?;
+                    if cli.start.is_some() {
+                        fail!("Start address already given.");
+                    }
+                    match parse_addr(&addr) {
+                        Some(addr) => cli.start = Some(addr),
+                        None => fail!("Start address '{addr}' isn't a valid address."),
+                    }
+                }
+                "--end" => {
+                    let addr = value_of(&mut args, &arg)?;
+                    if cli.end.is_some() {
+                        fail!("End address already given.");
+                    }
+                    match parse_addr(&addr) {
+                        Some(addr) => cli.end = Some(addr),
+                        None => fail!("End address '{addr}' isn't a valid address."),
+                    }
+                }
+                "--symbol" => {
+                    let name = value_of(&mut args, &arg)?;
+                    if cli.symbol.is_some() {
+                        fail!("Symbol already given.");
+                    }
+                    cli.symbol = Some(name);
+                }
+                "--range" => {
+                    let range = value_of(&mut args, &arg)?;
+                    if cli.range.is_some() {
+                        fail!("Range already given.");
+                    }
+                    let (start, end) = match range.split_once("..") {
+                        Some(pair) => pair,
+                        None => fail!("Range '{range}' must be of the form <expr>..<expr>."),
+                    };
+                    for expr in [start, end] {
+                        if let Err(err) = CompleteExpr::parse(expr) {
+                            fail!("Invalid range expression '{expr}': {err}");
+                        }
+                    }
+                    cli.range = Some((start.to_string(), end.to_string()));
+                }
                 unknown => {
                     let mut distance = u32::MAX;
                     let mut best_guess = "";
@@ -84,31 +244,117 @@ impl Cli {
 
                     // A guess that's less than 3 `steps` away from a correct arg.
                     if distance < 4 {
-                        exit!(1 => "Unknown cmd arg '{unknown}' did you mean '{best_guess}'?")
+                        fail!("Unknown cmd arg '{unknown}' did you mean '{best_guess}'?")
                     } else {
-                        exit!(1 => "Unknown cmd arg '{unknown}' was entered.");
+                        fail!("Unknown cmd arg '{unknown}' was entered.");
                     }
                 }
             }
         }
 
-        cli.validate_args();
-        cli
+        cli.validate_args()?;
+        Ok(cli)
     }
 
-    fn validate_args(&mut self) {
+    fn validate_args(&mut self) -> Result<(), String> {
         if self.path == Path::new("") {
-            exit!(1 => "You must provide a path to disassemble.");
+            fail!("You must provide a path to disassemble.");
         }
 
         if !self.path.exists() {
-            exit!(1 => "Object {:?} does not exist.", self.path);
+            fail!("Object {:?} does not exist.", self.path);
         }
 
         if let Some(ref cfg) = self.config {
             if !cfg.exists() {
-                exit!(1 => "Config {cfg:?} does not exist.");
+                fail!("Config {cfg:?} does not exist.");
             }
         }
+
+        if self.symbol.is_some() && self.range.is_some() {
+            fail!("Options '--symbol' and '--range' can't be used together.");
+        }
+
+        let is_bounded = self.start.is_some() || self.end.is_some();
+        if is_bounded && (self.symbol.is_some() || self.range.is_some()) {
+            fail!("Options '--start' and '--end' can't be combined with '--symbol' or '--range'.");
+        }
+
+        // Selecting a function or range only makes sense without the GUI.
+        if (self.symbol.is_some() || self.range.is_some()) && !self.json {
+            self.dump = true;
+        }
+
+        if self.dump && self.json {
+            fail!("Options '--dump' and '--json' can't be used together.");
+        }
+
+        let dump_only = self.section.is_some() || self.start.is_some() || self.end.is_some();
+        if dump_only && !self.dump && !self.json {
+            fail!("Options '--section', '--start' and '--end' require '--dump' or '--json'.");
+        }
+
+        if let (Some(start), Some(end)) = (self.start, self.end) {
+            if start > end {
+                fail!("Start address {start:#x} is past end address {end:#x}.");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Cli, String> {
+        // any path that exists will do
+        let path = env!("CARGO_MANIFEST_DIR");
+        let args = args.replace("<path>", path);
+        Cli::parse_from(args.split_whitespace().map(str::to_string))
+    }
+
+    #[test]
+    fn dump_options() {
+        let cli = parse("-D <path> --dump --section .text --start 0x1000 --end 4096").unwrap();
+        assert_eq!(cli.path, Path::new(env!("CARGO_MANIFEST_DIR")));
+        assert!(cli.dump);
+        assert_eq!(cli.section.as_deref(), Some(".text"));
+        assert_eq!(cli.start, Some(0x1000));
+        assert_eq!(cli.end, Some(0x1000));
+
+        // selecting a function or range implies dumping
+        let cli = parse("-D <path> --symbol main").unwrap();
+        assert!(cli.dump);
+        assert_eq!(cli.symbol.as_deref(), Some("main"));
+
+        let cli = parse("-D <path> --json --range main..main+0x10").unwrap();
+        assert!(!cli.dump);
+        assert_eq!(cli.range, Some(("main".to_string(), "main+0x10".to_string())));
+    }
+
+    #[test]
+    fn missing_values() {
+        for option in ["--section", "--start", "--end", "--symbol", "--range"] {
+            let expected = Err(format!("Option '{option}' requires a value."));
+            assert_eq!(parse(&format!("-D <path> --dump {option}")).map(|_| ()), expected);
+            assert_eq!(parse(&format!("-D <path> {option} --dump")).map(|_| ()), expected);
+        }
+
+        assert!(parse("-D").is_err());
+        assert!(parse("-D <path> -C").is_err());
+    }
+
+    #[test]
+    fn invalid_options() {
+        assert!(parse("").is_err());
+        assert!(parse("-D <path> --dump --start main").is_err());
+        assert!(parse("-D <path> --dump --start 0x20 --end 0x10").is_err());
+        assert!(parse("-D <path> --section .text").is_err());
+        assert!(parse("-D <path> --symbol main --range 0..1").is_err());
+        assert!(parse("-D <path> --dump --json").is_err());
+        assert!(parse("-D <path> --range main").is_err());
+        assert!(parse("-D <path> --dum").is_err());
     }
 }
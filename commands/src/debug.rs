@@ -2,7 +2,7 @@
 //!
 //! ```text
 //! <input> = <ws> <expr> <ws>
-//! <expr>  = <compound> | <number> | <symbol>
+//! <expr>  = <compound> | <number> | <symbol> | <offset>
 //!
 //! <number> = ['-'] <integer> | ['-'] <hex>
 //!
@@ -11,6 +11,8 @@
 //!
 //! <symbol>  = {<characters>}+ # if known in database
 //!
+//! <offset>  = 'file:' <expr> # address where the file offset is loaded
+//!
 //! <compound> = <expr> <ws> <op> <ws> <expr> | '(' <ws> <expr> <ws> ')'
 //! <op> = '+' | '-' | '*' | '/' | '%'
 //! ```
@@ -298,6 +300,12 @@ impl<'src> Context<'src> {
     fn parse_primary(&mut self) -> Result<Expr, Error> {
         self.consume_whitespace();
 
+        if self.src().starts_with("file:") {
+            self.offset += "file:".len();
+            let offset = self.parse_primary()?;
+            return Ok(Expr::FileOffset(self.store(offset)));
+        }
+
         if let Ok(num) = self.number() {
             return Ok(Expr::Number(num));
         }
@@ -437,6 +445,8 @@ enum Expr {
         op: Operator,
         rhs: ExprRef,
     },
+    /// Offset in the binary's file.
+    FileOffset(ExprRef),
 }
 
 /// Storable [`Expr`] with needed [`ExprRef`]'s.
@@ -481,6 +491,20 @@ impl CompleteExpr {
                     Operator::Min => lhs.checked_sub(rhs).ok_or_else(err),
                 }
             }
+            Expr::FileOffset(offset) => {
+                let offset = self.eval_recursive(self.load(*offset), index)?;
+                let addr = usize::try_from(offset)
+                    .ok()
+                    .and_then(|offset| index.file_map.offset_to_addr(offset));
+
+                match addr {
+                    Some(addr) => Ok(addr as isize),
+                    None => Err(Error {
+                        offset: None,
+                        msg: format!("File offset {offset:#x} isn't loaded"),
+                    }),
+                }
+            }
         }
     }
 
@@ -509,6 +533,7 @@ impl CompleteExpr {
 
                 None
             }
+            Expr::FileOffset(offset) => self.find_matching_symbol(self.load(*offset), cursor),
             Expr::Number(_) => None,
         }
     }
@@ -637,6 +662,18 @@ mod tests {
         );
     }
 
+    #[test]
+    fn file_offset() {
+        let mut index = debugvault::Index::default();
+        index.file_map.insert(0x1000..0x2000, 0x401000);
+
+        assert_eq!(parse(&index, "file:0x1010"), Ok(0x401010));
+        assert_eq!(parse(&index, "file:(0x1000 + 0x10) + 4"), Ok(0x401014));
+        assert!(parse(&index, "file:0x10").is_err());
+        assert!(parse(&index, "file:-1").is_err());
+        ast_eq!("file:16", Expr::FileOffset(ExprRef(0)));
+    }
+
     #[test]
     fn operation_order() {
         eval_eq!("1 + 10 * 10", 101);
@@ -2,15 +2,34 @@ use std::fmt;
 use std::path::{Path, PathBuf};
 
 use crate::debug::CompleteExpr;
+use crate::search::{IntKind, Pattern};
+use serde::{Deserialize, Serialize};
 
 pub const HELP: &str = "\
 Available commands:
-    pwd                -- Display the current path
-    cd <path>          -- Change the current directory to the specified path
-    quit               -- Exit the program
-    goto <expr>        -- Jump to code/data at the specified expression
-    clear              -- Clear out terminal
-    help               -- Display this help message";
+    pwd                   -- Display the current path
+    cd <path>             -- Change the current directory to the specified path
+    quit                  -- Exit the program
+    goto <expr>           -- Jump to code/data at the specified expression, file
+                             offsets are written as 'file:<offset>'
+    xref <expr>           -- List references to the specified expression
+    rename <expr> <name>  -- Name the symbol at the specified expression
+    comment <expr> [text] -- Comment on the instruction at the specified expression
+    patch <expr> <bytes>  -- Replace the bytes at the specified expression, in hex
+    asm <expr> <instr>    -- Assemble an instruction at the specified expression
+    find <pattern>        -- Search for bytes like '48 8b ?? 4?', a \"string\", a
+                             u\"utf-16 string\" or an integer like 'u32 <expr>'
+    revert <expr>         -- Undo the patch at the specified expression
+    patches               -- List all patches
+    type <expr> [type]    -- Show the data at the specified expression as a u8, u16,
+                             u32, u64, ptr or cstr, no type removes it
+    bookmark <expr>       -- Bookmark the specified expression or remove its bookmark
+    bookmarks             -- List all bookmarks
+    export <path>         -- Write the patched binary to the specified path
+    open <path>           -- Open a project and the binary it belongs to
+    save [path]           -- Save the project, by default next to the binary
+    clear                 -- Clear out terminal
+    help                  -- Display this help message";
 
 #[derive(Debug, PartialEq)]
 pub enum Command {
@@ -19,10 +38,74 @@ pub enum Command {
     ChangeDir(PathBuf),
     Quit,
     Goto(usize),
+    Xref(usize),
+    Rename(usize, String),
+    Comment(usize, String),
+    Patch(usize, Vec<u8>),
+    Assemble(usize, String),
+    Find(Pattern),
+    Revert(usize),
+    ListPatches,
+    SetType(usize, Option<DataType>),
+    Bookmark(usize),
+    ListBookmarks,
+    Export(PathBuf),
+    Open(PathBuf),
+    Save(Option<PathBuf>),
     Clear,
     Help,
 }
 
+/// How the user wants data at an address to be shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataType {
+    U8,
+    U16,
+    U32,
+    U64,
+    Pointer,
+    CString,
+}
+
+impl DataType {
+    pub fn parse(s: &str) -> Option<Self> {
+        Some(match s {
+            "u8" => Self::U8,
+            "u16" => Self::U16,
+            "u32" => Self::U32,
+            "u64" => Self::U64,
+            "ptr" => Self::Pointer,
+            "cstr" => Self::CString,
+            _ => return None,
+        })
+    }
+
+    /// Width of an integer type in bytes, pointers and strings depend on the binary.
+    pub fn width(self) -> Option<usize> {
+        match self {
+            Self::U8 => Some(1),
+            Self::U16 => Some(2),
+            Self::U32 => Some(4),
+            Self::U64 => Some(8),
+            Self::Pointer | Self::CString => None,
+        }
+    }
+}
+
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::U8 => "u8",
+            Self::U16 => "u16",
+            Self::U32 => "u32",
+            Self::U64 => "u64",
+            Self::Pointer => "ptr",
+            Self::CString => "cstr",
+        })
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Error {
     Missing(&'static str),
@@ -31,6 +114,11 @@ pub enum Error {
     PathIsntFile(PathBuf),
     PathIsntDir(PathBuf),
     InvalidEnv,
+    InvalidBytes,
+    InvalidPattern,
+    InvalidType(String),
+    /// An integer that doesn't fit in the given type.
+    OutOfRange(String),
     Debugger(crate::debug::Error),
 }
 
@@ -52,6 +140,15 @@ impl fmt::Display for Error {
                 f.write_fmt(format_args!("Path {path:?} isn't a directory."))
             }
             Self::InvalidEnv => f.write_str("Invalid environmental variable pair."),
+            Self::InvalidBytes => f.write_str("Expected bytes in hex, like '90 90'."),
+            Self::InvalidPattern => f.write_str(
+                "Expected bytes in hex like '48 8b ?? 4?', a string like \"text\" or \
+                 u\"text\", or an integer like 'u32 0x10'."
+            ),
+            Self::InvalidType(tipe) => f.write_fmt(format_args!(
+                "Type '{tipe}' is unknown, expected u8, u16, u32, u64, ptr or cstr."
+            )),
+            Self::OutOfRange(kind) => f.write_fmt(format_args!("Value doesn't fit in '{kind}'.")),
             Self::Debugger(err) => err.fmt(f),
         }
     }
@@ -89,6 +186,20 @@ fn possible_command(unknown: &str) -> Option<&str> {
         "quit",
         "run",
         "goto",
+        "xref",
+        "rename",
+        "comment",
+        "patch",
+        "asm",
+        "find",
+        "revert",
+        "patches",
+        "type",
+        "bookmark",
+        "bookmarks",
+        "export",
+        "open",
+        "save",
         "set",
         "break",
         "delete",
@@ -213,6 +324,12 @@ impl<'src> Context<'src> {
         Err(Error::PathDoesntExist(path))
     }
 
+    /// Path to a file that doesn't have to exist yet, if any is given.
+    fn parse_new_file_path(&mut self) -> Option<PathBuf> {
+        let s = self.parse_arg("path").ok()?;
+        Some(expand_homedir(PathBuf::from(s)))
+    }
+
     fn parse_dir_path(&mut self) -> Result<PathBuf, Error> {
         let start = self.offset;
         let s = self.parse_arg("path").unwrap_or_default();
@@ -305,6 +422,64 @@ impl<'src> Context<'src> {
         }
     }
 
+    /// Bytes written in hex, optionally separated by spaces.
+    fn parse_bytes(&mut self) -> Result<Vec<u8>, Error> {
+        let s = self.parse_arg("bytes")?;
+        let mut bytes = Vec::new();
+
+        for word in s.split_whitespace() {
+            if word.len() % 2 != 0 || !word.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(Error::InvalidBytes);
+            }
+
+            for idx in (0..word.len()).step_by(2) {
+                let byte = u8::from_str_radix(&word[idx..idx + 2], 16);
+                bytes.push(byte.map_err(|_| Error::InvalidBytes)?);
+            }
+        }
+
+        Ok(bytes)
+    }
+
+    /// Pattern to search for, either bytes in hex with wildcards, a string or an integer.
+    fn parse_pattern(&mut self) -> Result<Pattern, Error> {
+        self.skip_whitespace();
+
+        let pattern = match self.src() {
+            s if s.starts_with("u\"") => {
+                let s = self.parse_arg("string")?;
+                Pattern::from_utf16(&parse_string(&s[1..])?)
+            }
+            s if s.starts_with('"') => {
+                let s = self.parse_arg("string")?;
+                Pattern::from_bytes(parse_string(s)?.into_bytes())
+            }
+            s => match s.split(' ').next().and_then(IntKind::parse) {
+                Some(kind) => {
+                    let name = self.parse_next("type")?;
+                    let negative = self.src().starts_with('-');
+                    if negative {
+                        self.offset += 1;
+                    }
+
+                    let value = self.parse_debug_expr()? as u64;
+                    let pattern = kind.encode(value, negative);
+                    pattern.ok_or_else(|| Error::OutOfRange(name.to_string()))?
+                }
+                None => {
+                    let s = self.parse_arg("pattern")?;
+                    Pattern::from_hex(s).ok_or(Error::InvalidPattern)?
+                }
+            },
+        };
+
+        if !pattern.is_specific() {
+            return Err(Error::InvalidPattern);
+        }
+
+        Ok(pattern)
+    }
+
     #[allow(dead_code)]
     fn parse_env(&mut self) -> Result<String, Error> {
         let s = self.parse_arg("environmental variable")?;
@@ -315,6 +490,23 @@ impl<'src> Context<'src> {
     fn parse_debug_expr(&mut self) -> Result<usize, Error> {
         let offset = self.offset;
         let s = self.parse_arg("expr")?;
+        self.eval_debug_expr(offset, s)
+    }
+
+    /// Parse an expression that's followed by other arguments, so it can't contain spaces.
+    fn parse_debug_expr_word(&mut self) -> Result<usize, Error> {
+        self.skip_whitespace();
+        let offset = self.offset;
+        let s = self.parse_till_whitespace();
+        if s.is_empty() {
+            return Err(Error::Missing("expr"));
+        }
+
+        self.skip_whitespace();
+        self.eval_debug_expr(offset, s)
+    }
+
+    fn eval_debug_expr(&mut self, offset: usize, s: &str) -> Result<usize, Error> {
         let expr = CompleteExpr::parse(s).map_err(Error::Debugger)?;
 
         let err = match expr.eval(self.index) {
@@ -347,6 +539,47 @@ impl<'src> Context<'src> {
             "cd" => Command::ChangeDir(self.parse_dir_path()?),
             "quit" | "q" => Command::Quit,
             "goto" | "g" => Command::Goto(self.parse_debug_expr()?),
+            "xref" | "x" => Command::Xref(self.parse_debug_expr()?),
+            "rename" => {
+                let addr = self.parse_debug_expr_word()?;
+                let name = self.parse_arg("name")?;
+                Command::Rename(addr, name.to_string())
+            }
+            "comment" => {
+                let addr = self.parse_debug_expr_word()?;
+                let text = self.parse_arg("text").unwrap_or_default();
+                Command::Comment(addr, text.to_string())
+            }
+            "patch" => {
+                let addr = self.parse_debug_expr_word()?;
+                Command::Patch(addr, self.parse_bytes()?)
+            }
+            "asm" => {
+                let addr = self.parse_debug_expr_word()?;
+                let instruction = self.parse_arg("instruction")?;
+                Command::Assemble(addr, instruction.to_string())
+            }
+            "find" | "f" => Command::Find(self.parse_pattern()?),
+            "revert" => Command::Revert(self.parse_debug_expr()?),
+            "patches" => Command::ListPatches,
+            "type" => {
+                let addr = self.parse_debug_expr_word()?;
+                match self.parse_arg("type") {
+                    Ok(tipe) => match DataType::parse(tipe) {
+                        Some(tipe) => Command::SetType(addr, Some(tipe)),
+                        None => return Err(Error::InvalidType(tipe.to_string())),
+                    },
+                    Err(_) => Command::SetType(addr, None),
+                }
+            }
+            "bookmark" => Command::Bookmark(self.parse_debug_expr()?),
+            "bookmarks" => Command::ListBookmarks,
+            "export" => match self.parse_new_file_path() {
+                Some(path) => Command::Export(path),
+                None => return Err(Error::Missing("path")),
+            },
+            "open" | "o" => Command::Open(self.parse_file_path()?),
+            "save" | "s" => Command::Save(self.parse_new_file_path()),
             "clear" => Command::Clear,
             "help" | "?" => Command::Help,
             name => return Err(Error::UnknownName(name.to_string())),
@@ -367,6 +600,36 @@ impl Command {
     }
 }
 
+impl Pattern {
+    /// Parse a pattern the same way as the `find` command does.
+    pub fn parse(index: &debugvault::Index, s: &str) -> Result<Self, Error> {
+        Context::new(index, s, 0).parse_pattern()
+    }
+}
+
+/// Contents of a quoted string, supporting the escapes `\\`, `\"`, `\n`, `\r`, `\t` and `\0`.
+fn parse_string(s: &str) -> Result<String, Error> {
+    let mut chars = s.strip_prefix('"').ok_or(Error::InvalidPattern)?.chars();
+    let mut string = String::new();
+
+    loop {
+        match chars.next() {
+            Some('"') if chars.as_str().is_empty() => return Ok(string),
+            Some('\\') => string.push(match chars.next() {
+                Some('\\') => '\\',
+                Some('"') => '"',
+                Some('n') => '\n',
+                Some('r') => '\r',
+                Some('t') => '\t',
+                Some('0') => '\0',
+                _ => return Err(Error::InvalidPattern),
+            }),
+            Some(chr) if chr != '"' => string.push(chr),
+            _ => return Err(Error::InvalidPattern),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -423,6 +686,145 @@ mod tests {
         );
     }
 
+    #[test]
+    fn xref() {
+        eval_eq!(["abc::f"; 0x1234], "xref abc::f", Command::Xref(0x1234));
+        eval_eq!("x 0x10 + 4", Command::Xref(0x14));
+    }
+
+    #[test]
+    fn rename() {
+        eval_eq!(
+            ["abc::f"; 0x1234],
+            "rename abc::f+4  loop ",
+            Command::Rename(0x1238, "loop".to_string())
+        );
+        eval_eq!(
+            "rename 0x10 main",
+            Command::Rename(0x10, "main".to_string())
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn rename_missing_name() {
+        eval_eq!("rename 0x10", Command::Rename(0x10, String::new()));
+    }
+
+    #[test]
+    fn comment() {
+        eval_eq!(
+            "comment 0x10 checks the  header ",
+            Command::Comment(0x10, "checks the  header".to_string())
+        );
+        eval_eq!("comment 0x10", Command::Comment(0x10, String::new()));
+    }
+
+    #[test]
+    fn patch() {
+        eval_eq!("patch 0x10 90 90", Command::Patch(0x10, vec![0x90, 0x90]));
+        eval_eq!(
+            ["abc::f"; 0x1234],
+            "patch abc::f c3cc0F ",
+            Command::Patch(0x1234, vec![0xc3, 0xcc, 0x0f])
+        );
+        eval_eq!("revert 0x10 + 1", Command::Revert(0x11));
+    }
+
+    #[test]
+    #[should_panic]
+    fn patch_invalid_bytes() {
+        eval_eq!("patch 0x10 9 0", Command::Patch(0x10, vec![0x90]));
+    }
+
+    #[test]
+    fn assemble() {
+        eval_eq!(
+            "asm 0x10 mov rax, qword ptr [rbx + 0x8] ",
+            Command::Assemble(0x10, "mov rax, qword ptr [rbx + 0x8]".to_string())
+        );
+        eval_eq!(
+            ["abc::f"; 0x1234],
+            "asm abc::f+2 call <abc::f>",
+            Command::Assemble(0x1236, "call <abc::f>".to_string())
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn assemble_missing_instruction() {
+        eval_eq!("asm 0x10", Command::Assemble(0x10, String::new()));
+    }
+
+    #[test]
+    fn find() {
+        eval_eq!(
+            "find 48 8b ?? 4?",
+            Command::Find(Pattern::from_hex("488b??4?").unwrap())
+        );
+        eval_eq!(
+            "f \"a \\\"b\\\"\\n\"",
+            Command::Find(Pattern::from_bytes(b"a \"b\"\n".to_vec()))
+        );
+        eval_eq!(
+            "find u\"hi\"",
+            Command::Find(Pattern::from_bytes(vec![b'h', 0, b'i', 0]))
+        );
+        eval_eq!(
+            ["abc::f"; 0x1234],
+            "find u32be abc::f + 1",
+            Command::Find(Pattern::from_bytes(vec![0, 0, 0x12, 0x35]))
+        );
+        eval_eq!(
+            "find i16 -2",
+            Command::Find(Pattern::from_bytes(vec![0xfe, 0xff]))
+        );
+    }
+
+    #[test]
+    fn find_invalid() {
+        let index = debugvault::Index::default();
+        let parse = |s| Command::parse(&index, s, 0).map_err(|(err, _)| err);
+
+        assert_eq!(parse("find"), Err(Error::Missing("pattern")));
+        assert_eq!(parse("find ?? ??"), Err(Error::InvalidPattern));
+        assert_eq!(parse("find \"abc"), Err(Error::InvalidPattern));
+        assert_eq!(parse("find 4"), Err(Error::InvalidPattern));
+        assert_eq!(parse("find u8 0x100"), Err(Error::OutOfRange("u8".to_string())));
+    }
+
+    #[test]
+    fn set_type() {
+        eval_eq!(
+            ["abc::f"; 0x1234],
+            "type abc::f+8 u32 ",
+            Command::SetType(0x123c, Some(DataType::U32))
+        );
+        eval_eq!("type 0x10 cstr", Command::SetType(0x10, Some(DataType::CString)));
+        eval_eq!("type 0x10", Command::SetType(0x10, None));
+
+        let index = debugvault::Index::default();
+        let parse = |s| Command::parse(&index, s, 0).map_err(|(err, _)| err);
+        assert_eq!(parse("type 0x10 i32"), Err(Error::InvalidType("i32".to_string())));
+        assert_eq!(parse("type"), Err(Error::Missing("expr")));
+    }
+
+    #[test]
+    fn bookmark() {
+        eval_eq!(["abc::f"; 0x1234], "bookmark abc::f + 2", Command::Bookmark(0x1236));
+        eval_eq!("bookmarks", Command::ListBookmarks);
+    }
+
+    #[test]
+    fn save() {
+        let home = expand_homedir(PathBuf::from("~"));
+        eval_eq!("save", Command::Save(None));
+        eval_eq!(
+            "save  ~/ls.bite ",
+            Command::Save(Some(home.join("ls.bite")))
+        );
+    }
+
     #[test]
     fn change_dir() {
         let home = expand_homedir(PathBuf::from("~"));
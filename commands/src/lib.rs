@@ -1,9 +1,10 @@
 mod cli;
-mod debug;
+pub mod debug;
 mod gui;
+pub mod search;
 
 pub use cli::Cli;
-pub use gui::{Command, Error as CommandError, HELP as CMD_HELP};
+pub use gui::{Command, DataType, Error as CommandError, HELP as CMD_HELP};
 use once_cell::sync::Lazy;
 
 pub static ARGS: Lazy<cli::Cli> = Lazy::new(cli::Cli::parse);
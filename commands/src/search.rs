@@ -0,0 +1,220 @@
+//! Byte patterns to search binaries for.
+
+use std::fmt;
+
+/// Bytes to search for, where each bit of `mask` that's set must match the respective bit in
+/// `bytes`. Wildcards are written as `?` for a nibble, e.g. `48 8b ?? 4?`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    bytes: Vec<u8>,
+    mask: Vec<u8>,
+}
+
+impl Pattern {
+    /// Pattern where all bytes must match.
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        let mask = vec![0xff; bytes.len()];
+        Self { bytes, mask }
+    }
+
+    /// Pattern in hex, e.g. `488b??4?` or `48 8b ?? 4?`. Returns `None` if any of the words
+    /// aren't made of whole bytes.
+    pub fn from_hex(s: &str) -> Option<Self> {
+        let mut pattern = Self::from_bytes(Vec::new());
+
+        for word in s.split_whitespace() {
+            if word.len() % 2 != 0 {
+                return None;
+            }
+
+            for pair in word.as_bytes().chunks(2) {
+                let (mut byte, mut mask) = (0, 0);
+                for &nibble in pair {
+                    byte <<= 4;
+                    mask <<= 4;
+                    if nibble != b'?' {
+                        byte |= (nibble as char).to_digit(16)? as u8;
+                        mask |= 0xf;
+                    }
+                }
+                pattern.bytes.push(byte);
+                pattern.mask.push(mask);
+            }
+        }
+
+        Some(pattern)
+    }
+
+    /// Pattern of a string encoded as UTF-16, in little-endian.
+    pub fn from_utf16(s: &str) -> Self {
+        Self::from_bytes(s.encode_utf16().flat_map(u16::to_le_bytes).collect())
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Whether any bits of the pattern must match, a pattern of only wildcards matches
+    /// everything.
+    pub fn is_specific(&self) -> bool {
+        self.mask.iter().any(|&mask| mask != 0)
+    }
+
+    /// Index and value of the first byte without wildcards, used to quickly skip ahead to
+    /// possible matches.
+    pub fn anchor(&self) -> Option<(usize, u8)> {
+        let idx = self.mask.iter().position(|&mask| mask == 0xff)?;
+        Some((idx, self.bytes[idx]))
+    }
+
+    /// Whether `bytes` start with the pattern.
+    pub fn matches(&self, bytes: &[u8]) -> bool {
+        bytes.len() >= self.len()
+            && self
+                .bytes
+                .iter()
+                .zip(self.mask.iter())
+                .zip(bytes)
+                .all(|((pat, mask), byte)| byte & mask == *pat)
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, (byte, mask)) in self.bytes.iter().zip(self.mask.iter()).enumerate() {
+            if idx != 0 {
+                f.write_str(" ")?;
+            }
+
+            for shift in [4, 0] {
+                match (mask >> shift) & 0xf {
+                    0 => f.write_str("?")?,
+                    _ => f.write_fmt(format_args!("{:x}", (byte >> shift) & 0xf))?,
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Width and signedness of an integer to search for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IntKind {
+    pub width: usize,
+    pub signed: bool,
+    pub big_endian: bool,
+}
+
+impl IntKind {
+    /// Kinds written like `u32`, `i16be` or `u64le`, little-endian is assumed.
+    pub fn parse(s: &str) -> Option<Self> {
+        let (s, big_endian) = match (s.strip_suffix("be"), s.strip_suffix("le")) {
+            (Some(s), _) => (s, true),
+            (_, Some(s)) => (s, false),
+            _ => (s, false),
+        };
+
+        let (signed, width) = match s.as_bytes().first()? {
+            b'u' => (false, &s[1..]),
+            b'i' => (true, &s[1..]),
+            _ => return None,
+        };
+
+        let width = match width {
+            "8" => 1,
+            "16" => 2,
+            "32" => 4,
+            "64" => 8,
+            _ => return None,
+        };
+
+        Some(Self {
+            width,
+            signed,
+            big_endian,
+        })
+    }
+
+    /// Encode `value`, returning `None` if it doesn't fit.
+    pub fn encode(self, value: u64, negative: bool) -> Option<Pattern> {
+        let bits = self.width as u32 * 8;
+        let fits = match (self.signed, negative) {
+            (false, true) => false,
+            (false, false) => bits == 64 || value >> bits == 0,
+            (true, false) => value < 1 << (bits - 1),
+            (true, true) => value <= 1 << (bits - 1),
+        };
+
+        if !fits {
+            return None;
+        }
+
+        let value = if negative {
+            value.wrapping_neg()
+        } else {
+            value
+        };
+        let bytes = match self.big_endian {
+            true => value.to_be_bytes()[8 - self.width..].to_vec(),
+            false => value.to_le_bytes()[..self.width].to_vec(),
+        };
+
+        Some(Pattern::from_bytes(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex() {
+        let pattern = Pattern::from_hex("488b ?? 4?").unwrap();
+        assert_eq!(pattern.to_string(), "48 8b ?? 4?");
+        assert_eq!(pattern.anchor(), Some((0, 0x48)));
+        assert!(pattern.matches(&[0x48, 0x8b, 0x12, 0x4f, 0x00]));
+        assert!(!pattern.matches(&[0x48, 0x8b, 0x12, 0x5f]));
+        assert!(!pattern.matches(&[0x48, 0x8b, 0x12]));
+
+        assert_eq!(Pattern::from_hex("?? ?1").unwrap().anchor(), None);
+        assert!(!Pattern::from_hex("??").unwrap().is_specific());
+        assert_eq!(Pattern::from_hex("488"), None);
+        assert_eq!(Pattern::from_hex("4g"), None);
+    }
+
+    #[test]
+    fn integers() {
+        let u32le = IntKind::parse("u32").unwrap();
+        assert_eq!(
+            u32le.encode(0x1234, false),
+            Some(Pattern::from_bytes(vec![0x34, 0x12, 0x00, 0x00]))
+        );
+        assert_eq!(u32le.encode(1 << 32, false), None);
+        assert_eq!(u32le.encode(1, true), None);
+
+        let i16be = IntKind::parse("i16be").unwrap();
+        assert_eq!(
+            i16be.encode(2, true),
+            Some(Pattern::from_bytes(vec![0xff, 0xfe]))
+        );
+        assert_eq!(
+            i16be.encode(0x8000, true),
+            Some(Pattern::from_bytes(vec![0x80, 0x00]))
+        );
+        assert_eq!(i16be.encode(0x8000, false), None);
+
+        assert_eq!(
+            IntKind::parse("u64").unwrap().encode(u64::MAX, false),
+            Some(Pattern::from_bytes(vec![0xff; 8]))
+        );
+        assert_eq!(IntKind::parse("u24"), None);
+        assert_eq!(IntKind::parse("f32"), None);
+    }
+}
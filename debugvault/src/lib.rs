@@ -1,7 +1,8 @@
 use binformat::RawSymbol;
 use demangler::TokenStream;
 use dwarf::Dwarf;
-use processor_shared::{AddressMap, Addressed};
+use processor_shared::{AddressMap, Addressed, FileMap};
+use std::collections::{BTreeMap, HashSet};
 use std::path::Path;
 use std::sync::Arc;
 use std::fmt;
@@ -38,6 +39,7 @@ pub struct Symbol {
     name_as_str: Arc<str>,
     module: Option<String>,
     is_intrinsics: bool,
+    is_generated: bool,
 }
 
 fn is_name_an_intrinsic(name: &str) -> bool {
@@ -67,11 +69,25 @@ impl Default for Symbol {
             name_as_str: Arc::from(""),
             module: None,
             is_intrinsics: false,
+            is_generated: false,
         }
     }
 }
 
 impl Symbol {
+    fn parse(name: &str, module: Option<String>, demangle: bool) -> Self {
+        let demangled = if demangle { demangler::parse(name) } else { TokenStream::simple(name) };
+        let name_as_str = String::from_iter(demangled.tokens().iter().map(|t| &t.text[..]));
+
+        Self {
+            name: demangled,
+            name_as_str: Arc::from(name_as_str),
+            module,
+            is_intrinsics: is_name_an_intrinsic(name),
+            is_generated: false,
+        }
+    }
+
     #[inline]
     pub fn name(&self) -> &[Token] {
         self.name.tokens()
@@ -95,6 +111,11 @@ impl Symbol {
     pub fn imported(&self) -> bool {
         self.module.is_some()
     }
+
+    /// Is the function synthesized by analysis, rather than named in the binary.
+    pub fn generated(&self) -> bool {
+        self.is_generated
+    }
 }
 
 impl fmt::Debug for Symbol {
@@ -110,7 +131,7 @@ impl PartialEq for Symbol {
     }
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct Index {
     /// Mapping from addresses starting at the header base to functions.
     /// The addresses are sorted.
@@ -123,6 +144,9 @@ pub struct Index {
     /// Efficient string match searcher.
     pub prefixes: prefix::PrefixMatcher,
 
+    /// Where the binary's file is loaded, used to resolve file offsets in expressions.
+    pub file_map: FileMap,
+
     /// Number of named compiler artifacts.
     named_len: usize,
 }
@@ -130,12 +154,78 @@ pub struct Index {
 impl Index {
     pub fn parse<'data>(
         obj: &object::File<'data>,
-        #[allow(unused_variables)]
         path: &Path,
-        mut syms: AddressMap<RawSymbol<'data>>,
+        syms: AddressMap<RawSymbol<'data>>,
     ) -> Result<Self, Error> {
         let mut this = Self::default();
+        let mut symbols = symbols(syms.mapping, true);
+        symbols.extend(this.parse_debug_info(obj, path));
+        this.index(symbols);
+
+        log::complex!(
+            w "[index::parse] found ",
+            g this.syms.len().to_string(),
+            w " functions."
+        );
+
+        Ok(this)
+    }
+
+    /// Index the symbols without demangling them or looking at the debug info, as that can
+    /// take a while for large binaries. [`Index::load_debug_info`] does the rest.
+    pub fn parse_symbols(syms: AddressMap<RawSymbol>) -> Self {
+        let mut this = Self::default();
+        this.index(symbols(syms.mapping, false));
+
+        log::complex!(
+            w "[index::parse_symbols] found ",
+            g this.syms.len().to_string(),
+            w " functions."
+        );
 
+        this
+    }
+
+    /// Demangle the symbols of an index made by [`Index::parse_symbols`] and add the source
+    /// files and symbols from the binary's debug info.
+    pub fn load_debug_info(&mut self, obj: &object::File, path: &Path) {
+        let syms = std::mem::take(&mut self.syms);
+
+        log::PROGRESS.set("Demangling symbols.", syms.len());
+        let mut symbols = Vec::with_capacity(syms.len());
+        parallel_compute(syms.mapping, &mut symbols, |Addressed { addr, item }| {
+            let symbol = if item.generated() {
+                Arc::clone(item)
+            } else {
+                Arc::new(Symbol::parse(item.as_str(), item.module.clone(), true))
+            };
+
+            log::PROGRESS.step();
+            Addressed { addr: *addr, item: symbol }
+        });
+
+        // the symbols were already validated, only the debug info's symbols are new
+        let debug_symbols = self.parse_debug_info(obj, path);
+        self.syms = AddressMap { mapping: symbols };
+        if !debug_symbols.is_empty() {
+            self.syms.extend(AddressMap { mapping: debug_symbols });
+            self.syms.retain(|func| func.addr != 0 && !func.item.as_str().is_empty());
+            self.syms.sort_unstable();
+        }
+
+        self.named_len = self.syms.iter().filter(|func| !func.item.intrinsic()).count();
+        self.file_attrs.sort_unstable();
+        self.prefixes = prefix::PrefixMatcher::default();
+        self.build_prefix_tree();
+    }
+
+    /// Add the source files from the DWARF or PDB of a binary, returning the PDB's symbols.
+    fn parse_debug_info(
+        &mut self,
+        obj: &object::File,
+        #[allow(unused_variables)]
+        path: &Path,
+    ) -> Vec<Addressed<Arc<Symbol>>> {
         let dwarf = match obj {
             #[cfg(target_os = "macos")]
             object::File::MachO32(_) | object::File::MachO64(_) => macho_dwarf(obj, path),
@@ -143,7 +233,7 @@ impl Index {
         };
 
         match dwarf {
-            Ok(dwarf) => this.file_attrs.extend(dwarf.file_attrs),
+            Ok(dwarf) => self.file_attrs.extend(dwarf.file_attrs),
             Err(err) => log::complex!(
                 w "[dwarf::parse] ",
                 y format!("Failed to parse dwarf: {err:?}"),
@@ -165,41 +255,20 @@ impl Index {
 
         // NOTE: This is a little scuffed. We have to take a `ref mut` here
         //       otherwise the PDB will be dropped and so will the symbols.
-        if let Some(ref mut pdb) = pdb {
-            this.file_attrs.extend(std::mem::take(&mut pdb.file_attrs));
-            syms.extend(std::mem::take(&mut pdb.syms));
-        }
-
-        log::PROGRESS.set("Parsing symbols.", syms.len());
-        parallel_compute(syms.mapping, &mut this.syms, |Addressed { addr, item }| {
-            let demangled = demangler::parse(item.name);
-            let is_intrinsics = is_name_an_intrinsic(item.name);
-            let name_as_str = String::from_iter(demangled.tokens().iter().map(|t| &t.text[..]));
-            let name_as_str = Arc::from(name_as_str);
-            let symbol = Symbol {
-                name_as_str,
-                name: demangled,
-                module: item.module.map(|x| x.to_string()),
-                is_intrinsics,
-            };
-
-            log::PROGRESS.step();
-            Addressed {
-                addr: *addr,
-                item: Arc::new(symbol),
+        match pdb {
+            Some(ref mut pdb) => {
+                self.file_attrs.extend(std::mem::take(&mut pdb.file_attrs));
+                symbols(std::mem::take(&mut pdb.syms).mapping, true)
             }
-        });
-
-        this.sort_and_validate();
-        this.build_prefix_tree();
-
-        log::complex!(
-            w "[index::parse] found ",
-            g this.syms.len().to_string(),
-            w " functions."
-        );
+            None => Vec::new(),
+        }
+    }
 
-        Ok(this)
+    /// Replace the symbols, sorting them and building the prefix tree.
+    fn index(&mut self, symbols: Vec<Addressed<Arc<Symbol>>>) {
+        self.syms = AddressMap { mapping: symbols };
+        self.sort_and_validate();
+        self.build_prefix_tree();
     }
 
     fn sort_and_validate(&mut self) {
@@ -264,10 +333,128 @@ impl Index {
         }
     }
 
+    /// Closest function starting at or before `addr`.
+    pub fn get_func_containing(&self, addr: usize) -> Option<&Addressed<Arc<Symbol>>> {
+        let idx = self.syms.partition_point(|func| func.addr <= addr);
+        self.syms[..idx].iter().rev().find(|func| !func.item.intrinsic())
+    }
+
     pub fn get_func_by_name(&self, name: &str) -> Option<usize> {
         self.syms.iter().find(|func| func.item.as_str() == name).map(|func| func.addr)
     }
 
+    /// Insert functions found by analysis as `sub_XXXX` symbols, skipping any addresses that
+    /// already have a symbol.
+    pub fn insert_generated(&mut self, addrs: &[usize]) {
+        let count = self.insert_synthesized(addrs, |addr| format!("sub_{addr:x}"), false);
+
+        log::complex!(
+            w "[index::insert_generated] discovered ",
+            g count.to_string(),
+            w " functions."
+        );
+
+        self.named_len += count;
+    }
+
+    /// Insert local labels for branch targets as `.LBB_XXXX` symbols, skipping any addresses
+    /// that already have a symbol. Like other `.L` symbols these are treated as intrinsics.
+    pub fn insert_labels(&mut self, addrs: &[usize]) {
+        let count = self.insert_synthesized(addrs, |addr| format!(".LBB_{addr:x}"), true);
+
+        log::complex!(
+            w "[index::insert_labels] created ",
+            g count.to_string(),
+            w " labels."
+        );
+    }
+
+    fn insert_synthesized(
+        &mut self,
+        addrs: &[usize],
+        name: impl Fn(usize) -> String,
+        is_intrinsics: bool,
+    ) -> usize {
+        let mut generated = Vec::new();
+        for &addr in addrs {
+            if self.syms.search(addr).is_ok() {
+                continue;
+            }
+
+            let name = name(addr);
+            generated.push(Addressed {
+                addr,
+                item: Arc::new(Symbol {
+                    name: TokenStream::simple(&name),
+                    name_as_str: Arc::from(name),
+                    module: None,
+                    is_intrinsics,
+                    is_generated: true,
+                }),
+            });
+        }
+
+        for Addressed { item: func, .. } in generated.iter() {
+            self.prefixes.insert(func);
+        }
+
+        self.prefixes.reorder();
+
+        let count = generated.len();
+        self.syms.extend(AddressMap { mapping: generated });
+        self.syms.sort_unstable();
+        count
+    }
+
+    /// Give the symbols at some addresses the names the user picked, replacing whatever they
+    /// were named before. Names are demangled like any other symbol.
+    pub fn rename<'a>(&mut self, names: impl IntoIterator<Item = (usize, &'a str)>) {
+        // only the last name given to an address is kept
+        let names: BTreeMap<usize, &str> = names.into_iter().collect();
+        let mut replaced = HashSet::new();
+        let mut renamed = Vec::new();
+        let mut inserted = Vec::new();
+
+        for (addr, name) in names {
+            let demangled = demangler::parse(name);
+            let name_as_str = String::from_iter(demangled.tokens().iter().map(|t| &t.text[..]));
+            let prev = self.syms.search(addr).ok().map(|idx| &mut self.syms[idx].item);
+            let symbol = Arc::new(Symbol {
+                name: demangled,
+                name_as_str: Arc::from(name_as_str),
+                // imports keep the module they're imported from
+                module: prev.as_ref().and_then(|prev| prev.module.clone()),
+                is_intrinsics: is_name_an_intrinsic(name),
+                is_generated: false,
+            });
+
+            renamed.push(Arc::clone(&symbol));
+            match prev {
+                Some(prev) => {
+                    replaced.insert(Arc::as_ptr(&std::mem::replace(prev, symbol)));
+                }
+                None => inserted.push(Addressed { addr, item: symbol }),
+            }
+        }
+
+        if renamed.is_empty() {
+            return;
+        }
+
+        self.prefixes.remove(|item| replaced.contains(&Arc::as_ptr(item)));
+        for symbol in renamed.iter() {
+            self.prefixes.insert(symbol);
+        }
+        self.prefixes.reorder();
+
+        if !inserted.is_empty() {
+            self.syms.extend(AddressMap { mapping: inserted });
+            self.syms.sort_unstable();
+        }
+
+        self.named_len = self.syms.iter().filter(|func| !func.item.intrinsic()).count();
+    }
+
     /// Only used for tests.
     #[doc(hidden)]
     pub fn insert_func(&mut self, addr: usize, name: &str) {
@@ -278,6 +465,7 @@ impl Index {
                 name_as_str: Arc::from(name),
                 module: None,
                 is_intrinsics: false,
+                is_generated: false,
             }),
         })
     }
@@ -330,6 +518,21 @@ pub fn macho_dwarf(obj: &object::File, path: &Path) -> Result<Dwarf, dwarf::Erro
     Ok(dwarf)
 }
 
+/// Symbols of the `syms` in a binary, demangled if `demangle` is set.
+fn symbols(syms: Vec<Addressed<RawSymbol>>, demangle: bool) -> Vec<Addressed<Arc<Symbol>>> {
+    log::PROGRESS.set("Parsing symbols.", syms.len());
+    let mut symbols = Vec::with_capacity(syms.len());
+    parallel_compute(syms, &mut symbols, |Addressed { addr, item }| {
+        let module = item.module.map(|x| x.to_string());
+        let symbol = Symbol::parse(item.name, module, demangle);
+
+        log::PROGRESS.step();
+        Addressed { addr: *addr, item: Arc::new(symbol) }
+    });
+
+    symbols
+}
+
 pub fn parallel_compute<In, Out, F>(items: Vec<In>, output: &mut Vec<Out>, transformer: F)
 where
     F: FnOnce(&In) -> Out,
@@ -16,7 +16,7 @@ fn cmp(a: &str, b: &str) -> Ordering {
 }
 
 /// Datastructure for efficient string match searching.
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct PrefixMatcher {
     items: Vec<Arc<Symbol>>,
 }
@@ -27,6 +27,11 @@ impl PrefixMatcher {
         self.items.push(s.clone());
     }
 
+    /// Remove the items that `remove` returns true for, this keeps the items sorted.
+    pub fn remove(&mut self, remove: impl Fn(&Arc<Symbol>) -> bool) {
+        self.items.retain(|item| !remove(item));
+    }
+
     /// Sorts elements to allow for searching.
     pub fn reorder(&mut self) {
         self.items.sort_unstable_by(|a, b| cmp(a.as_str(), b.as_str()));
@@ -83,7 +88,8 @@ mod test {
             name_as_str: Arc::from(s),
             name: TokenStream::simple(s),
             module: None,
-            is_intrinsics: false
+            is_intrinsics: false,
+            is_generated: false,
         })
     }
 
@@ -3,7 +3,7 @@
 
 use core::fmt::{self, Display, Formatter};
 
-use decoder::{Decoded, Decodable, Error, ErrorKind, Reader, ToTokens};
+use decoder::{Decoded, Decodable, Error, ErrorKind, Flow, Reader, ToTokens};
 use debugvault::Index;
 use tokenizing::TokenStream;
 use config::CONFIG;
@@ -1165,6 +1165,22 @@ pub enum Opcode {
     SMLSLD(bool),
 }
 
+impl Opcode {
+    /// loads from memory that have a literal (pc-relative) form.
+    fn is_load(&self) -> bool {
+        matches!(
+            self,
+            Opcode::LDR
+                | Opcode::LDRB
+                | Opcode::LDRH
+                | Opcode::LDRSB
+                | Opcode::LDRSH
+                | Opcode::LDRD
+                | Opcode::PLD
+        )
+    }
+}
+
 static DATA_PROCESSING_OPCODES: [Opcode; 16] = [
     Opcode::AND,
     Opcode::EOR,
@@ -1640,16 +1656,6 @@ pub enum Operand {
     Nothing,
 }
 
-impl Operand {
-    fn offset(&self) -> i64 {
-        match self {
-            Operand::BranchOffset(offs) => (*offs as i64) << 2,
-            Operand::BranchThumbOffset(offs) => (*offs as i64) << 1,
-            _ => unreachable!(),
-        }
-    }
-}
-
 /// a register bank for a register in `armv7` or below.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[allow(missing_docs)]
@@ -2070,17 +2076,43 @@ fn format_reg_imm_mem(
 impl Decoded for Instruction {
     #[inline]
     fn width(&self) -> usize {
-        4
+        if self.thumb && !self.wide {
+            2
+        } else {
+            4
+        }
     }
 
     fn update_rel_addrs(&mut self, addr: usize, _: Option<&Instruction>) {
         match self.opcode {
+            // the offset is relative to the word-aligned pc, in arm mode the pc is also given
+            // as an operand
             Opcode::ADR => {
-                let offs = self.operands[1].offset();
-                let addr = (addr as u64).saturating_add_signed(offs);
-                self.operands[1] = Operand::Imm64Special(addr);
+                let pc = (addr as u64 + if self.thumb { 4 } else { 8 }) & !0b11;
+                let imm = match self.operands {
+                    [_, Operand::Imm32(imm), ..] | [_, Operand::Reg(_), Operand::Imm32(imm), _] => {
+                        imm
+                    }
+                    _ => return,
+                };
+                self.operands[1] = Operand::Imm64Special(pc + imm as u64);
+                self.operands[2] = Operand::Nothing;
             },
             _ => {
+                // thumb branch offsets are relative to the pc, which is 4 bytes ahead in thumb
+                // and 8 bytes in arm mode. `cbz`, `cbnz` and narrow conditional branches are
+                // already adjusted by 2 bytes when decoded and `blx` to arm code is relative to
+                // the word-aligned pc
+                let thumb_pc = match (self.thumb, self.opcode) {
+                    (false, _) => addr + 8,
+                    (true, Opcode::CBZ | Opcode::CBNZ) => addr + 2,
+                    (true, Opcode::B) if !self.wide && self.condition != ConditionCode::AL => {
+                        addr + 2
+                    }
+                    (true, Opcode::BLX) => (addr + 4) & !0b11,
+                    (true, _) => addr + 4,
+                };
+
                 for operand in self.operands.iter_mut() {
                     match operand {
                         Operand::BranchOffset(offs) => {
@@ -2090,7 +2122,20 @@ impl Decoded for Instruction {
                         }
                         Operand::BranchThumbOffset(offs) => {
                             let offs = (*offs as i64) << 1;
-                            let addr = (addr as u64).saturating_add_signed(offs);
+                            let addr = (thumb_pc as u64).saturating_add_signed(offs);
+                            *operand = Operand::Imm64(addr);
+                        }
+                        // literal loads are relative to the word-aligned pc
+                        Operand::RegDerefPreindexOffset(reg, offs, add, false)
+                            if reg.number() == 15 && self.opcode.is_load() =>
+                        {
+                            let pc = addr as u64 + if self.thumb { 4 } else { 8 };
+                            let pc = pc & !0b11;
+                            let addr = if *add {
+                                pc.saturating_add(*offs as u64)
+                            } else {
+                                pc.saturating_sub(*offs as u64)
+                            };
                             *operand = Operand::Imm64(addr);
                         }
                         _ => {}
@@ -2099,6 +2144,71 @@ impl Decoded for Instruction {
             }
         }
     }
+
+    fn flow(&self) -> Flow {
+        // branch offsets are replaced by their absolute target
+        let target = self.operands.iter().find_map(|op| match op {
+            Operand::Imm64(addr) => Some(*addr as usize),
+            _ => None,
+        });
+
+        let pc = Reg::from_u8(15);
+        let lr = Reg::from_u8(14);
+
+        // anything else that's conditionally executed might just continue
+        if self.condition != ConditionCode::AL && self.opcode != Opcode::B {
+            return match self.opcode {
+                Opcode::BL | Opcode::BLX => target.map_or(Flow::IndirectCall, Flow::Call),
+                _ => Flow::Continue,
+            };
+        }
+
+        match self.opcode {
+            Opcode::B if self.condition != ConditionCode::AL => {
+                target.map_or(Flow::Continue, Flow::Branch)
+            }
+            Opcode::B => target.map_or(Flow::IndirectJump, Flow::Jump),
+            Opcode::CBZ | Opcode::CBNZ => target.map_or(Flow::Continue, Flow::Branch),
+            Opcode::BL | Opcode::BLX => target.map_or(Flow::IndirectCall, Flow::Call),
+            Opcode::BX | Opcode::BXJ if self.operands[0] == Operand::Reg(lr) => Flow::Return,
+            Opcode::BX | Opcode::BXJ | Opcode::TBB | Opcode::TBH => Flow::IndirectJump,
+            Opcode::POP | Opcode::LDM(..) => match self.operands {
+                [_, Operand::RegList(list), ..] | [Operand::RegList(list), ..]
+                    if list & (1 << 15) != 0 =>
+                {
+                    Flow::Return
+                }
+                _ => Flow::Continue,
+            },
+            Opcode::MOV if self.operands[..2] == [Operand::Reg(pc), Operand::Reg(lr)] => {
+                Flow::Return
+            }
+            Opcode::MOV | Opcode::LDR if self.operands[0] == Operand::Reg(pc) => {
+                Flow::IndirectJump
+            }
+            Opcode::ERET => Flow::Return,
+            Opcode::UDF | Opcode::BKPT => Flow::Halt,
+            _ => Flow::Continue,
+        }
+    }
+
+    fn mem_ref(&self) -> Option<usize> {
+        match self.opcode {
+            Opcode::ADR => match self.operands[1] {
+                Operand::Imm64Special(addr) => Some(addr as usize),
+                _ => None,
+            },
+            opcode if opcode.is_load() => self.operands.iter().find_map(|op| match op {
+                Operand::Imm64(addr) => Some(*addr as usize),
+                _ => None,
+            }),
+            _ => None,
+        }
+    }
+
+    fn instruction_set(&self) -> Option<&'static str> {
+        Some(if self.thumb { "thumb" } else { "arm" })
+    }
 }
 
 impl Display for Instruction {
@@ -2676,6 +2786,10 @@ pub struct Decoder {
     version: ARMVersion,
     should_is_must: bool,
     thumb: bool,
+    /// start of each region of code that's decoded in `thumb` mode (`true`) or `arm` mode
+    /// (`false`), sorted by address. anything before the first region is decoded in the mode
+    /// set through [`Decoder::set_thumb_mode`].
+    regions: Vec<(usize, bool)>,
 }
 
 impl Default for Decoder {
@@ -2685,6 +2799,7 @@ impl Default for Decoder {
             version: ARMVersion::Any,
             should_is_must: true,
             thumb: false,
+            regions: Vec::new(),
         }
     }
 }
@@ -2705,6 +2820,25 @@ impl Decoder {
         self
     }
 
+    /// switch between `thumb` and `arm` mode by address when decoding through
+    /// [`Decodable::decode_at`]. each region is given by the address it starts at and whether
+    /// it's `thumb` code, and lasts until the next region.
+    ///
+    /// (this consumes and returns the `Decoder` to support use in chained calls.)`
+    pub fn with_regions(mut self, mut regions: Vec<(usize, bool)>) -> Self {
+        regions.sort_unstable_by_key(|(addr, _)| *addr);
+        self.regions = regions;
+        self
+    }
+
+    /// whether an instruction at `addr` is decoded in `thumb` mode.
+    pub fn is_thumb_at(&self, addr: usize) -> bool {
+        match self.regions.partition_point(|(start, _)| *start <= addr) {
+            0 => self.thumb,
+            idx => self.regions[idx - 1].1,
+        }
+    }
+
     /// initialize a new `arm` `Decoder` with default ("everything") support, but in `thumb`
     /// mode.
     pub fn default_thumb() -> Self {
@@ -2718,6 +2852,7 @@ impl Decoder {
             version: ARMVersion::v4,
             should_is_must: true,
             thumb: false,
+            regions: Vec::new(),
         }
     }
 
@@ -2728,6 +2863,7 @@ impl Decoder {
             version: ARMVersion::v5,
             should_is_must: true,
             thumb: false,
+            regions: Vec::new(),
         }
     }
 
@@ -2738,6 +2874,7 @@ impl Decoder {
             version: ARMVersion::v6,
             should_is_must: true,
             thumb: false,
+            regions: Vec::new(),
         }
     }
 
@@ -2748,6 +2885,7 @@ impl Decoder {
             version: ARMVersion::v6t2,
             should_is_must: true,
             thumb: false,
+            regions: Vec::new(),
         }
     }
 
@@ -2758,6 +2896,7 @@ impl Decoder {
             version: ARMVersion::v6t2,
             should_is_must: true,
             thumb: true,
+            regions: Vec::new(),
         }
     }
 
@@ -2768,6 +2907,7 @@ impl Decoder {
             version: ARMVersion::v7,
             should_is_must: true,
             thumb: false,
+            regions: Vec::new(),
         }
     }
 
@@ -2778,6 +2918,7 @@ impl Decoder {
             version: ARMVersion::v7,
             should_is_must: true,
             thumb: true,
+            regions: Vec::new(),
         }
     }
 
@@ -2788,6 +2929,7 @@ impl Decoder {
             version: ARMVersion::v7ve,
             should_is_must: true,
             thumb: false,
+            regions: Vec::new(),
         }
     }
 
@@ -2798,6 +2940,7 @@ impl Decoder {
             version: ARMVersion::v7ve,
             should_is_must: true,
             thumb: true,
+            regions: Vec::new(),
         }
     }
 
@@ -2808,6 +2951,7 @@ impl Decoder {
             version: ARMVersion::v7vese,
             should_is_must: true,
             thumb: false,
+            regions: Vec::new(),
         }
     }
 
@@ -2824,9 +2968,15 @@ impl Decodable for Decoder {
     type Instruction = Instruction;
 
     fn decode(&self, reader: &mut decoder::Reader) -> Result<Self::Instruction, Error> {
-        let mut inst = Instruction::default();
-        read(self, reader, &mut inst).map_err(|err| Error::new(err, 4))?;
-        Ok(inst)
+        decode_in(self, reader, self.thumb)
+    }
+
+    fn decode_at(
+        &self,
+        reader: &mut decoder::Reader,
+        addr: usize,
+    ) -> Result<Self::Instruction, Error> {
+        decode_in(self, reader, self.is_thumb_at(addr))
     }
 
     fn max_width(&self) -> usize {
@@ -2834,11 +2984,24 @@ impl Decodable for Decoder {
     }
 }
 
+fn decode_in(decoder: &Decoder, reader: &mut Reader, thumb: bool) -> Result<Instruction, Error> {
+    let mut inst = Instruction::default();
+    // skip over a single halfword of invalid thumb code, as that's the smallest instruction
+    let size = if thumb { 2 } else { 4 };
+    read(decoder, reader, &mut inst, thumb).map_err(|err| Error::new(err, size))?;
+    Ok(inst)
+}
+
 #[inline(always)]
-fn read(decoder: &Decoder, words: &mut Reader, inst: &mut Instruction) -> Result<(), ErrorKind> {
+fn read(
+    decoder: &Decoder,
+    words: &mut Reader,
+    inst: &mut Instruction,
+    thumb: bool,
+) -> Result<(), ErrorKind> {
     inst.set_w(false);
     inst.set_wide(false);
-    if decoder.thumb {
+    if thumb {
         return thumb::read(decoder, words, inst);
     } else {
         inst.set_thumb(false);
@@ -4392,7 +4555,8 @@ fn read(decoder: &Decoder, words: &mut Reader, inst: &mut Instruction) -> Result
                     let imm = (imm as u32).rotate_right(2 * (rot >> 8));
                     ((word >> 16) as u8 & 0x0f, (word >> 12) as u8 & 0x0f, imm)
                 };
-                if (opcode == 0b0010 || opcode == 0b0100) && Rn == 0b1111 {
+                // `sub rd, pc, #imm` stays as is, since `ADR` doesn't carry a sign
+                if opcode == 0b0100 && Rn == 0b1111 {
                     inst.opcode = Opcode::ADR;
                 }
                 match opcode {
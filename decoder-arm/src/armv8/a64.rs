@@ -8,7 +8,7 @@
 use core::fmt::{self, Display, Formatter};
 
 use debugvault::Index;
-use decoder::{Decodable, Decoded, Error, ErrorKind, Reader, ToTokens};
+use decoder::{Decodable, Decoded, Error, ErrorKind, Flow, Reader, ToTokens};
 use tokenizing::TokenStream;
 use config::CONFIG;
 
@@ -247,21 +247,44 @@ impl Decoded for Instruction {
                 self.operands[1] = Operand::Imm64Special(addr);
             },
             Opcode::LDR => {
-                let mut a_imm = 0;
-                let mut a_reg = 0;
+                // literal load
+                if let Operand::PCOffset(offs) = self.operands[1] {
+                    let addr = (addr as u64).saturating_add_signed(offs);
+                    self.operands[1] = Operand::Imm64(addr);
+                    return;
+                }
+
+                // load relative to the page of a previous `adrp`
                 if let Some(Instruction { opcode: Opcode::ADRP | Opcode::ADR, operands }) = prev_inst {
-                    if let [Operand::Register(_, reg), Operand::Imm64Special(imm), ..] = operands {
-                        a_imm = *imm;
-                        a_reg = *reg;
-                    } else {
-                        return;
+                    if let [Operand::Register(_, a_reg), Operand::Imm64Special(a_imm), ..] = operands {
+                        if let Operand::RegPreIndex(l_reg, off, _) = self.operands[1] {
+                            if *a_reg == l_reg {
+                                let addr = a_imm.saturating_add_signed(off as i64);
+                                self.operands[1] = Operand::Imm64(addr);
+                            }
+                        }
                     }
                 }
+            }
+            Opcode::ADD => {
+                // address formed by an `adrp` followed by the page offset
+                let Some(Instruction {
+                    opcode: Opcode::ADRP,
+                    operands: [Operand::Register(_, a_reg), Operand::Imm64Special(a_imm), ..],
+                }) = prev_inst
+                else {
+                    return;
+                };
 
-                if let Operand::RegPreIndex(l_reg, off, _) = self.operands[1] {
-                    if a_reg == l_reg {
-                        let addr = a_imm.saturating_add_signed(off as i64);
-                        self.operands[1] = Operand::Imm64(addr);
+                if let [
+                    Operand::RegisterOrSP(SizeCode::X, _),
+                    Operand::RegisterOrSP(SizeCode::X, reg),
+                    Operand::Immediate(off),
+                    ..
+                ] = self.operands {
+                    if *a_reg == reg {
+                        let addr = a_imm.saturating_add(off as u64);
+                        self.operands[2] = Operand::Imm64(addr);
                     }
                 }
             }
@@ -275,6 +298,53 @@ impl Decoded for Instruction {
             }
         }
     }
+
+    fn flow(&self) -> Flow {
+        // pc-relative offsets are replaced by their absolute target
+        let target = self.operands.iter().find_map(|op| match op {
+            Operand::Imm64(addr) => Some(*addr as usize),
+            _ => None,
+        });
+
+        match self.opcode {
+            Opcode::B => target.map_or(Flow::IndirectJump, Flow::Jump),
+            Opcode::BL => target.map_or(Flow::IndirectCall, Flow::Call),
+            Opcode::Bcc(_) | Opcode::CBZ | Opcode::CBNZ | Opcode::TBZ | Opcode::TBNZ => {
+                target.map_or(Flow::Continue, Flow::Branch)
+            }
+            Opcode::BR | Opcode::BRAA | Opcode::BRAAZ | Opcode::BRAB | Opcode::BRABZ => {
+                Flow::IndirectJump
+            }
+            Opcode::BLR | Opcode::BLRAA | Opcode::BLRAAZ | Opcode::BLRAB | Opcode::BLRABZ => {
+                Flow::IndirectCall
+            }
+            Opcode::RET
+            | Opcode::RETAA
+            | Opcode::RETAB
+            | Opcode::ERET
+            | Opcode::ERETAA
+            | Opcode::ERETAB
+            | Opcode::DRPS => Flow::Return,
+            Opcode::BRK | Opcode::HLT => Flow::Halt,
+            _ => Flow::Continue,
+        }
+    }
+
+    fn mem_ref(&self) -> Option<usize> {
+        match self.opcode {
+            Opcode::ADR => match self.operands[1] {
+                Operand::Imm64Special(addr) => Some(addr as usize),
+                _ => None,
+            },
+            Opcode::LDR | Opcode::LDRSW | Opcode::PRFM | Opcode::ADD => {
+                self.operands.iter().find_map(|op| match op {
+                    Operand::Imm64(addr) => Some(*addr as usize),
+                    _ => None,
+                })
+            }
+            _ => None,
+        }
+    }
 }
 
 impl Display for Instruction {
@@ -1,7 +1,7 @@
 mod thumb;
 
 use arm::armv7::{ConditionCode, Instruction, Opcode, Operand, Reg, RegShift};
-use decoder::{ErrorKind, Decodable, Decoded, Reader};
+use decoder::{ErrorKind, Decodable, Decoded, Flow, Reader};
 
 type InstDecoder = arm::armv7::Decoder;
 
@@ -552,6 +552,51 @@ fn test_unconditional() {
     test_armv5([0x24, 0x78, 0x4f, 0xfe], "cdp2 p8, 4, c7, c15, c4, 1");
 }
 
+fn test_flow(data: [u8; 4], addr: usize, expected: Flow) {
+    let mut reader = Reader::new(&data[..]);
+    let mut instr = InstDecoder::default().decode(&mut reader).unwrap();
+    instr.update_rel_addrs(addr, None);
+    assert_eq!(instr.flow(), expected, "flow of {:02x?}", data);
+}
+
+#[test]
+fn test_branch_targets() {
+    // b .+8
+    test_flow([0x00, 0x00, 0x00, 0xea], 0x1000, Flow::Jump(0x1008));
+    // beq .+8
+    test_flow([0x00, 0x00, 0x00, 0x0a], 0x1000, Flow::Branch(0x1008));
+    // b .
+    test_flow([0xfe, 0xff, 0xff, 0xea], 0x1000, Flow::Jump(0x1000));
+    // bl .+0x100
+    test_flow([0x3e, 0x00, 0x00, 0xeb], 0x1000, Flow::Call(0x1100));
+    // blne .+0x100
+    test_flow([0x3e, 0x00, 0x00, 0x1b], 0x1000, Flow::Call(0x1100));
+    // blx .+0x100
+    test_flow([0x3e, 0x00, 0x00, 0xfa], 0x1000, Flow::Call(0x1100));
+}
+
+#[test]
+fn test_indirect_flow() {
+    // bx lr
+    test_flow([0x1e, 0xff, 0x2f, 0xe1], 0x1000, Flow::Return);
+    // bx r3
+    test_flow([0x13, 0xff, 0x2f, 0xe1], 0x1000, Flow::IndirectJump);
+    // blx r3
+    test_flow([0x33, 0xff, 0x2f, 0xe1], 0x1000, Flow::IndirectCall);
+    // pop {r4, pc}
+    test_flow([0x10, 0x80, 0xbd, 0xe8], 0x1000, Flow::Return);
+    // mov pc, lr
+    test_flow([0x0e, 0xf0, 0xa0, 0xe1], 0x1000, Flow::Return);
+    // moveq pc, lr
+    test_flow([0x0e, 0xf0, 0xa0, 0x01], 0x1000, Flow::Continue);
+    // ldr pc, [r1]
+    test_flow([0x00, 0xf0, 0x91, 0xe5], 0x1000, Flow::IndirectJump);
+    // bkpt #0
+    test_flow([0x70, 0x00, 0x20, 0xe1], 0x1000, Flow::Halt);
+    // add r0, r1, r2
+    test_flow([0x02, 0x00, 0x81, 0xe0], 0x1000, Flow::Continue);
+}
+
 #[test]
 fn test_saturating_addsub() {
     test_armv5([0x50, 0x10, 0x64, 0xe1], "qdsub r1, r0, r4");
@@ -1,5 +1,5 @@
 use arm::armv7::Instruction;
-use decoder::{Decodable, Reader};
+use decoder::{Decodable, Decoded, Flow, Reader};
 
 type InstDecoder = arm::armv7::Decoder;
 
@@ -4136,3 +4136,45 @@ fn test_decode_simd_32b_cases() {
         "vstmdb r3!, {s11, s12, s13, s14, s15, s16, s17, s18, s19, s20, s21, s22, s23, s24, s25, s26, s27, s28, s29, s30, s31}"
     );
 }
+
+fn test_flow(data: &[u8], addr: usize, expected: Flow) {
+    let mut reader = Reader::new(data);
+    let mut instr = match InstDecoder::default_thumb().decode(&mut reader) {
+        Err(e) => {
+            panic!("failed to decode {:#x?}: {:?}", data, e)
+        }
+        Ok(instr) => instr,
+    };
+    instr.update_rel_addrs(addr, None);
+    assert_eq!(instr.width(), data.len(), "width of {:#x?}", data);
+    assert_eq!(instr.flow(), expected, "flow of {:#x?}", data);
+}
+
+#[test]
+fn test_branch_targets() {
+    // b .
+    test_flow(&[0xfe, 0xe7], 0x1000, Flow::Jump(0x1000));
+    // beq .
+    test_flow(&[0xfe, 0xd0], 0x1000, Flow::Branch(0x1000));
+    // cbz r1, 0x1004
+    test_flow(&[0x01, 0xb1], 0x1000, Flow::Branch(0x1004));
+    // b.w 0x12198
+    test_flow(&[0x09, 0xf0, 0x4c, 0xbf], 0x82fc, Flow::Jump(0x12198));
+}
+
+#[test]
+fn test_decode_by_region() {
+    let decoder = InstDecoder::default().with_regions(vec![(0x2000, false), (0x1000, true)]);
+    // `movs r0, r0; b .` in thumb, but a single instruction in arm
+    let data = [0x00, 0x00, 0xfe, 0xe7];
+
+    let instr = decoder.decode_at(&mut Reader::new(&data), 0x1000).unwrap();
+    assert!(instr.thumb);
+    assert_eq!(instr.width(), 2);
+
+    for addr in [0x0, 0x2000] {
+        let instr = decoder.decode_at(&mut Reader::new(&data), addr).unwrap();
+        assert!(!instr.thumb);
+        assert_eq!(instr.width(), 4);
+    }
+}
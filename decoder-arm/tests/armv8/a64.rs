@@ -1,4 +1,4 @@
-use decoder::{ErrorKind, Decodable, Reader};
+use decoder::{ErrorKind, Decodable, Decoded, Flow, Reader};
 use arm::armv8::a64::{Instruction, Operand, Opcode, SizeCode, ShiftStyle};
 
 type InstDecoder = arm::armv8::a64::Decoder;
@@ -40,6 +40,52 @@ fn test_display(data: [u8; 4], expected: &'static str) {
     );
 }
 
+fn test_flow(data: [u8; 4], addr: usize, expected: Flow) {
+    let mut reader = Reader::new(&data[..]);
+    let mut instr = InstDecoder::default().decode(&mut reader).unwrap();
+    instr.update_rel_addrs(addr, None);
+    assert_eq!(instr.flow(), expected, "flow error for {:02x?}", data);
+}
+
+#[test]
+fn test_flow_targets() {
+    test_flow([0x04, 0x00, 0x00, 0x14], 0x1000, Flow::Jump(0x1010));
+    test_flow([0x04, 0x00, 0x00, 0x94], 0x1000, Flow::Call(0x1010));
+    test_flow([0x80, 0x00, 0x00, 0x54], 0x1000, Flow::Branch(0x1010));
+    test_flow([0x80, 0x00, 0x00, 0xb4], 0x1000, Flow::Branch(0x1010));
+    test_flow([0x00, 0x02, 0x1f, 0xd6], 0x1000, Flow::IndirectJump);
+    test_flow([0x00, 0x02, 0x3f, 0xd6], 0x1000, Flow::IndirectCall);
+    test_flow([0xc0, 0x03, 0x5f, 0xd6], 0x1000, Flow::Return);
+}
+
+fn test_mem_ref(data: [u8; 4], addr: usize, prev: Option<[u8; 4]>, expected: Option<usize>) {
+    let prev = prev.map(|prev| {
+        let mut reader = Reader::new(&prev[..]);
+        let mut prev = InstDecoder::default().decode(&mut reader).unwrap();
+        prev.update_rel_addrs(addr - 4, None);
+        prev
+    });
+
+    let mut reader = Reader::new(&data[..]);
+    let mut instr = InstDecoder::default().decode(&mut reader).unwrap();
+    instr.update_rel_addrs(addr, prev.as_ref());
+    assert_eq!(instr.mem_ref(), expected, "mem_ref error for {:02x?}", data);
+}
+
+#[test]
+fn test_mem_refs() {
+    // adrp x0, 0x2000; add x0, x0, #0x10
+    test_mem_ref([0x00, 0x40, 0x00, 0x91], 0x1004, Some([0x00, 0x00, 0x00, 0xb0]), Some(0x2010));
+    // adrp x1, 0x2000; add x0, x0, #0x10
+    test_mem_ref([0x00, 0x40, 0x00, 0x91], 0x1004, Some([0x01, 0x00, 0x00, 0xb0]), None);
+    // ldr x0, 0x1008
+    test_mem_ref([0x40, 0x00, 0x00, 0x58], 0x1000, None, Some(0x1008));
+    // ldr x0, [x0, #0x8] without an adrp
+    test_mem_ref([0x00, 0x04, 0x40, 0xf9], 0x1000, None, None);
+    // adr x0, 0x1010
+    test_mem_ref([0x80, 0x00, 0x00, 0x10], 0x1000, None, Some(0x1010));
+}
+
 #[test]
 fn test_neon() {
     test_display([0x00, 0x01, 0x27, 0x1e], "fmov s0, w8");
@@ -2,7 +2,7 @@
 
 mod tests;
 
-use decoder::{Error, ErrorKind};
+use decoder::{Error, ErrorKind, Flow};
 use debugvault::Index;
 use std::borrow::Cow;
 use tokenizing::{colors, TokenStream};
@@ -70,6 +70,10 @@ pub struct Instruction {
     mnemomic: &'static str,
     operands: [Cow<'static, str>; 3],
     operand_count: usize,
+    /// Encoded instruction.
+    word: u32,
+    /// Absolute branch target, known after [`decoder::Decoded::update_rel_addrs`].
+    target: Option<usize>,
 }
 
 impl decoder::Decoded for Instruction {
@@ -77,7 +81,55 @@ impl decoder::Decoded for Instruction {
         4
     }
 
-    fn update_rel_addrs(&mut self, _: usize, _: Option<&Instruction>) {}
+    fn update_rel_addrs(&mut self, addr: usize, _: Option<&Instruction>) {
+        let target = match self.word >> 26 {
+            // j and jal replace the lower 28 bits of the delay slot's address
+            2 | 3 => {
+                let region = (addr + 4) & !0x0fff_ffff;
+                region | ((self.word as usize & 0x03ff_ffff) << 2)
+            }
+            // branches are relative to the delay slot
+            1 | 4..=7 | 20..=23 => {
+                let offset = (self.word as u16 as i16 as isize) << 2;
+                (addr + 4).wrapping_add_signed(offset)
+            }
+            _ => return,
+        };
+
+        self.target = Some(target);
+        self.operands[self.operand_count - 1] = Cow::Owned(format!("{target:#x}"));
+    }
+
+    fn flow(&self) -> Flow {
+        let opcode = self.word >> 26;
+        let rs = self.word >> 21 & 0b11111;
+        let rt = self.word >> 16 & 0b11111;
+        let rd = self.word >> 11 & 0b11111;
+        let funct = self.word & 0b111111;
+
+        match (opcode, self.target) {
+            (2, Some(target)) => Flow::Jump(target),
+            (3, Some(target)) => Flow::Call(target),
+            // `beq zero, zero` and `bgez zero` are unconditional branches
+            (4 | 20, Some(target)) if rs == 0 && rt == 0 => Flow::Jump(target),
+            (1, Some(target)) if rs == 0 && rt == 0b00001 => Flow::Jump(target),
+            // bltzal, bgezal (`bal` when $rs is zero) and their branch-likely forms link
+            (1, Some(target)) if rt & 0b10000 != 0 => Flow::Call(target),
+            (1 | 4..=7 | 20..=23, Some(target)) => Flow::Branch(target),
+            // jr ra
+            (0, _) if funct == 0b001000 && rs == Register::Ra as u32 => Flow::Return,
+            (0, _) if funct == 0b001000 => Flow::IndirectJump,
+            // jalr without linking is an indirect jump
+            (0, _) if funct == 0b001001 && rd == 0 => Flow::IndirectJump,
+            (0, _) if funct == 0b001001 => Flow::IndirectCall,
+            _ if self.mnemomic == "break" => Flow::Halt,
+            _ => Flow::Continue,
+        }
+    }
+
+    fn mem_ref(&self) -> Option<usize> {
+        None
+    }
 }
 
 #[derive(Default)]
@@ -107,6 +159,8 @@ fn decode(reader: &mut decoder::Reader) -> Result<Instruction, ErrorKind> {
             mnemomic: "nop",
             operands,
             operand_count,
+            word: dword as u32,
+            target: None,
         });
     }
 
@@ -117,6 +171,8 @@ fn decode(reader: &mut decoder::Reader) -> Result<Instruction, ErrorKind> {
             mnemomic: "break",
             operands,
             operand_count,
+            word: dword as u32,
+            target: None,
         });
     }
 
@@ -133,6 +189,11 @@ fn decode(reader: &mut decoder::Reader) -> Result<Instruction, ErrorKind> {
             Format::J,
             J_TYPES.get(opcode).ok_or(ErrorKind::InvalidOpcode)?,
         ),
+        // REGIMM branches are selected by $rt
+        1 => (
+            Format::I,
+            REGIMM_TYPES.get(dword >> 16 & 0b11111).ok_or(ErrorKind::InvalidOpcode)?,
+        ),
         _ => (
             Format::I,
             I_TYPES.get(opcode).ok_or(ErrorKind::InvalidOpcode)?,
@@ -180,6 +241,8 @@ fn decode(reader: &mut decoder::Reader) -> Result<Instruction, ErrorKind> {
                 mnemomic: inst.mnemomic,
                 operands,
                 operand_count: inst.format.len(),
+                word: dword as u32,
+                target: None,
             })
         }
         Format::I => {
@@ -202,6 +265,8 @@ fn decode(reader: &mut decoder::Reader) -> Result<Instruction, ErrorKind> {
                     mnemomic: inst.mnemomic,
                     operands,
                     operand_count,
+                    word: dword as u32,
+                    target: None,
                 });
             }
 
@@ -229,6 +294,8 @@ fn decode(reader: &mut decoder::Reader) -> Result<Instruction, ErrorKind> {
                 mnemomic: inst.mnemomic,
                 operands,
                 operand_count: inst.format.len(),
+                word: dword as u32,
+                target: None,
             })
         }
         Format::J => {
@@ -239,13 +306,15 @@ fn decode(reader: &mut decoder::Reader) -> Result<Instruction, ErrorKind> {
                 mnemomic: inst.mnemomic,
                 operands,
                 operand_count,
+                word: dword as u32,
+                target: None,
             })
         }
     }
 }
 
 impl decoder::ToTokens for Instruction {
-    fn tokenize(&self, stream: &mut TokenStream, _: &Index) {
+    fn tokenize(&self, stream: &mut TokenStream, symbols: &Index) {
         stream.push(self.mnemomic, CONFIG.colors.asm.opcode);
 
         // there are operands
@@ -256,11 +325,27 @@ impl decoder::ToTokens for Instruction {
             for idx in 0..self.operand_count {
                 let operand = self.operands[idx].clone();
 
-                match operand {
-                    Cow::Owned(s) => stream.push_owned(s, CONFIG.colors.asm.immediate),
-                    Cow::Borrowed(s) => stream.push(s, CONFIG.colors.asm.register),
+                // the target of a branch is always the last operand
+                let symbol = match self.target {
+                    Some(target) if idx == self.operand_count - 1 => {
+                        symbols.get_sym_by_addr(target)
+                    }
+                    _ => None,
                 };
 
+                if let Some(symbol) = symbol {
+                    stream.push("<", CONFIG.colors.asm.immediate);
+                    for token in symbol.name() {
+                        stream.push_token(token.clone());
+                    }
+                    stream.push(">", CONFIG.colors.asm.immediate);
+                } else {
+                    match operand {
+                        Cow::Owned(s) => stream.push_owned(s, CONFIG.colors.asm.immediate),
+                        Cow::Borrowed(s) => stream.push(s, CONFIG.colors.asm.register),
+                    };
+                }
+
                 // separator
                 if idx != self.operand_count - 1 {
                     stream.push(", ", CONFIG.colors.asm.expr);
@@ -414,10 +499,10 @@ const I_TYPES: [TableInstruction; 44] = [
     mips!(),
     mips!(),
     mips!(),
-    mips!(),
-    mips!(),
-    mips!(),
-    mips!(),
+    mips!("beql" : "Branch to immediate if values of $rs and $rt are equal, executing the delay slot only if taken", rs, rt, imm),
+    mips!("bnel" : "Branch to immediate if values of $rs and $rt are not equal, executing the delay slot only if taken", rs, rt, imm),
+    mips!("blezl" : "Branch to immediate if value of $rs is less than or equal to zero, executing the delay slot only if taken", rs, imm),
+    mips!("bgtzl" : "Branch to immediate if value of $rs is greater than zero, executing the delay slot only if taken", rs, imm),
     mips!(),
     mips!(),
     mips!(),
@@ -444,7 +529,30 @@ const J_TYPES: [TableInstruction; 4] = [
     mips!(),
     mips!(),
     mips!("j" : "Jump to target address", imm),
-    mips!("jr" : "Call the target address and save return addr in $ra", imm),
+    mips!("jal" : "Call the target address and save return addr in $ra", imm),
+];
+
+const REGIMM_TYPES: [TableInstruction; 20] = [
+    mips!("bltz" : "Branch to immediate if value of $rs is less than zero", rs, imm),
+    mips!("bgez" : "Branch to immediate if value of $rs is greater than or equal to zero", rs, imm),
+    mips!("bltzl" : "Branch to immediate if value of $rs is less than zero, executing the delay slot only if taken", rs, imm),
+    mips!("bgezl" : "Branch to immediate if value of $rs is greater than or equal to zero, executing the delay slot only if taken", rs, imm),
+    mips!(),
+    mips!(),
+    mips!(),
+    mips!(),
+    mips!(),
+    mips!(),
+    mips!(),
+    mips!(),
+    mips!(),
+    mips!(),
+    mips!(),
+    mips!(),
+    mips!("bltzal" : "Call immediate if value of $rs is less than zero and save return addr in $ra", rs, imm),
+    mips!("bgezal" : "Call immediate if value of $rs is greater than or equal to zero and save return addr in $ra", rs, imm),
+    mips!("bltzall" : "Call immediate if value of $rs is less than zero and save return addr in $ra, executing the delay slot only if taken", rs, imm),
+    mips!("bgezall" : "Call immediate if value of $rs is greater than or equal to zero and save return addr in $ra, executing the delay slot only if taken", rs, imm),
 ];
 
 const R_TYPES: [TableInstruction; 44] = [
@@ -457,7 +565,7 @@ const R_TYPES: [TableInstruction; 44] = [
     mips!("srlv" : "Shift value in $rt `$rs` number of times to the right storing the result in $rd and zero extending the shifted bits", rd, rt, rs),
     mips!("srav" : "Shift value in $rt `$rs` number of times to the right storing the result in $rd and sign extending the shifted bits", rd, rt, rs),
     mips!("jr" : "Jump to address of $rs", rs),
-    mips!(),
+    mips!("jalr" : "Call the address in $rs and save return addr in $rd", rd, rs),
     mips!(),
     mips!("syscall" : "Trigger exception tranfering control from user space to kernel space where the call is handled"),
     mips!(),
@@ -1,6 +1,6 @@
 #![cfg(test)]
 
-use decoder::{ToTokens, Decodable};
+use decoder::{Decodable, Decoded, Flow, ToTokens};
 
 fn test_display(bytes: &[u8], str: &str) {
     let mut reader = decoder::Reader::new(bytes);
@@ -43,3 +43,68 @@ fn sllv() {
 fn lb() {
     test_display(&[0x81, 0x49, 0x0, 0x10], "lb t1, t2, 0x10");
 }
+
+#[test]
+fn bltz() {
+    test_display(&[0x5, 0x20, 0x0, 0x1], "bltz t1, 0x1");
+}
+
+#[test]
+fn jalr() {
+    test_display(&[0x3, 0x20, 0xf8, 0x9], "jalr ra, t9");
+}
+
+fn test_flow(bytes: &[u8], addr: usize, flow: Flow) {
+    let mut reader = decoder::Reader::new(bytes);
+    let mut inst = crate::Decoder.decode(&mut reader).unwrap();
+    inst.update_rel_addrs(addr, None);
+    assert_eq!(inst.flow(), flow);
+}
+
+#[test]
+fn branch_label() {
+    let mut reader = decoder::Reader::new(&[0x11, 0x2a, 0x00, 0x03]);
+    let mut inst = crate::Decoder.decode(&mut reader).unwrap();
+    inst.update_rel_addrs(0x400000, None);
+
+    let mut symbols = debugvault::Index::default();
+    symbols.insert_labels(&[0x400010]);
+
+    let mut line = tokenizing::TokenStream::new();
+    inst.tokenize(&mut line, &symbols);
+    assert_eq!(line.to_string(), "beq t1, t2, <.LBB_400010>");
+}
+
+#[test]
+fn flow() {
+    test_flow(&[0x8, 0x10, 0, 0x10], 0x400000, Flow::Jump(0x400040));
+    test_flow(&[0xc, 0x10, 0, 0x10], 0x400000, Flow::Call(0x400040));
+    test_flow(&[0x11, 0x2a, 0xff, 0xff], 0x400000, Flow::Branch(0x400000));
+    test_flow(&[0x10, 0x0, 0x0, 0x4], 0x400000, Flow::Jump(0x400014));
+    test_flow(&[0x3, 0xe0, 0x0, 0x8], 0x400000, Flow::Return);
+    test_flow(&[0x1, 0x20, 0x0, 0x8], 0x400000, Flow::IndirectJump);
+}
+
+#[test]
+fn regimm_flow() {
+    test_flow(&[0x5, 0x20, 0x0, 0x1], 0x400000, Flow::Branch(0x400008));
+    test_flow(&[0x5, 0x23, 0x0, 0x1], 0x400000, Flow::Branch(0x400008));
+    test_flow(&[0x5, 0x30, 0x0, 0x1], 0x400000, Flow::Call(0x400008));
+    // bal
+    test_flow(&[0x4, 0x11, 0x0, 0x1], 0x400000, Flow::Call(0x400008));
+    // bgez zero
+    test_flow(&[0x4, 0x1, 0x0, 0x1], 0x400000, Flow::Jump(0x400008));
+}
+
+#[test]
+fn likely_flow() {
+    test_flow(&[0x51, 0x2a, 0xff, 0xff], 0x400000, Flow::Branch(0x400000));
+    test_flow(&[0x50, 0x0, 0x0, 0x4], 0x400000, Flow::Jump(0x400014));
+    test_flow(&[0x59, 0x20, 0x0, 0x1], 0x400000, Flow::Branch(0x400008));
+}
+
+#[test]
+fn jalr_flow() {
+    test_flow(&[0x3, 0x20, 0xf8, 0x9], 0x400000, Flow::IndirectCall);
+    test_flow(&[0x3, 0x20, 0x0, 0x9], 0x400000, Flow::IndirectJump);
+}
@@ -2,7 +2,7 @@
 
 mod tests;
 
-use decoder::{Error, ErrorKind, ToTokens};
+use decoder::{Error, ErrorKind, Flow, ToTokens};
 use debugvault::Index;
 use once_cell::sync::Lazy;
 use tokenizing::{TokenStream, colors};
@@ -409,7 +409,7 @@ impl Opcode {
         matches!(
             self,
             Self::JAL |
-            Self::JALR |
+            Self::J |
             Self::BEQ |
             Self::BNE |
             Self::BLT |
@@ -423,10 +423,29 @@ impl Opcode {
             Self::BLTZ |
             Self::BGTZ |
             Self::C_JAL |
+            Self::C_J |
             Self::C_BEQZ |
             Self::C_BNEZ
         )
     }
+
+    /// Loads and stores that address memory relative to a base register.
+    fn is_mem(&self) -> bool {
+        matches!(
+            self,
+            Self::LB |
+            Self::LH |
+            Self::LW |
+            Self::LBU |
+            Self::LHU |
+            Self::LWU |
+            Self::LD |
+            Self::SB |
+            Self::SH |
+            Self::SW |
+            Self::SD
+        )
+    }
 }
 
 static OPCODE_NAMES: [&str; 284] = [
@@ -728,6 +747,8 @@ pub enum Operand {
     Nothing,
     Register(Register),
     Immediate(i32),
+    /// Absolute address resolved from a pc-relative immediate.
+    Address(u64),
 }
 
 impl ToTokens for Operand {
@@ -737,13 +758,27 @@ impl ToTokens for Operand {
             Self::Immediate(imm) => {
                 match symbols.get_sym_by_addr(*imm as usize) {
                     Some(symbol) => {
+                        stream.push("<", CONFIG.colors.asm.immediate);
                         for token in symbol.name() {
                             stream.push_token(token.clone());
                         }
+                        stream.push(">", CONFIG.colors.asm.immediate);
                     }
                     None => stream.push_owned(imm.to_string(), CONFIG.colors.asm.immediate),
                 }
             }
+            Self::Address(addr) => match symbols.get_sym_by_addr(*addr as usize) {
+                Some(symbol) => {
+                    stream.push("<", CONFIG.colors.asm.immediate);
+                    for token in symbol.name() {
+                        stream.push_token(token.clone());
+                    }
+                    stream.push(">", CONFIG.colors.asm.immediate);
+                }
+                None => {
+                    stream.push_owned(decoder::encode_uhex(*addr), CONFIG.colors.asm.immediate)
+                }
+            },
             Self::Nothing => unreachable!("empty operand encountered"),
         }
     }
@@ -762,18 +797,134 @@ impl decoder::Decoded for Instruction {
         self.len
     }
 
-    fn update_rel_addrs(&mut self, addr: usize, _: Option<&Instruction>) {
+    fn update_rel_addrs(&mut self, addr: usize, prev_inst: Option<&Instruction>) {
+        if self.opcode == Opcode::AUIPC {
+            if let Operand::Immediate(imm) = self.operands[1] {
+                // the upper 20 bits of a sign-extended offset from the instruction
+                let addr = (addr as u64).wrapping_add_signed((imm << 12) as i64);
+                self.operands[1] = Operand::Address(addr);
+            }
+            return;
+        }
+
+        // offset relative to the address formed by a previous `auipc`
+        if self.opcode == Opcode::ADDI || self.opcode.is_mem() {
+            if let Some(Instruction {
+                opcode: Opcode::AUIPC,
+                operands: [Operand::Register(reg), Operand::Address(base), ..],
+                ..
+            }) = prev_inst
+            {
+                let reg = Operand::Register(*reg);
+                let off = match self.operands {
+                    // `addi rd, rs, imm` where rs is the same as rd is shown as `addi rd, imm`
+                    [dst, Operand::Immediate(off), _] if self.operand_count == 2 && dst == reg => {
+                        Some((1, off))
+                    }
+                    [_, src, Operand::Immediate(off)] if self.operand_count == 3 && src == reg => {
+                        Some((2, off))
+                    }
+                    _ => None,
+                };
+
+                if let Some((idx, off)) = off {
+                    self.operands[idx] = Operand::Address(base.wrapping_add_signed(off as i64));
+                }
+            }
+            return;
+        }
+
+        // `call` and `tail` are an `auipc` followed by a `jalr` through the same register
+        if let Some(Instruction {
+            opcode: Opcode::AUIPC,
+            operands: [Operand::Register(reg), Operand::Address(base), ..],
+            ..
+        }) = prev_inst
+        {
+            let reg = Operand::Register(*reg);
+            let (rd, off) = match (self.opcode, self.operands, self.operand_count) {
+                (Opcode::JALR, [rd, rs, Operand::Immediate(off)], 3) if rs == reg => (rd, off),
+                // `jalr rs` and `jr rs` have their registers swapped
+                (Opcode::JALR | Opcode::JR, [rs, rd, Operand::Immediate(off)], 1) if rs == reg => {
+                    (rd, off)
+                }
+                _ => return,
+            };
+
+            self.opcode = match rd {
+                Operand::Register(Register::Ra) => Opcode::CALL,
+                Operand::Register(Register::Zero) => Opcode::TAIL,
+                _ => return,
+            };
+            self.operands[0] = Operand::Address(base.wrapping_add_signed(off as i64));
+            self.operand_count = 1;
+            return;
+        }
+
         if !self.opcode.is_relative() {
             return;
         }
 
         for operand in &mut self.operands[..self.operand_count] {
-            if let Operand::Immediate(imm) = operand {
-                // questionable at best.
-                // ensuring all the i64 conversion are correct is hard
-                *imm = imm.saturating_add_unsigned(addr as u32);
+            if let Operand::Immediate(imm) = *operand {
+                let addr = (addr as u64).wrapping_add_signed(imm as i64);
+                *operand = Operand::Address(addr);
+            }
+        }
+    }
+
+    fn flow(&self) -> Flow {
+        // pc-relative offsets are replaced by their absolute target
+        let target = self.operands[..self.operand_count].iter().find_map(|op| match op {
+            Operand::Address(addr) => Some(*addr as usize),
+            _ => None,
+        });
+
+        match self.opcode {
+            Opcode::J | Opcode::C_J => target.map_or(Flow::IndirectJump, Flow::Jump),
+            Opcode::JAL | Opcode::C_JAL => target.map_or(Flow::IndirectCall, Flow::Call),
+            Opcode::BEQ
+            | Opcode::BNE
+            | Opcode::BLT
+            | Opcode::BGE
+            | Opcode::BLTU
+            | Opcode::BGEU
+            | Opcode::BEQZ
+            | Opcode::BNEZ
+            | Opcode::BLEZ
+            | Opcode::BGEZ
+            | Opcode::BLTZ
+            | Opcode::BGTZ
+            | Opcode::BGT
+            | Opcode::BLE
+            | Opcode::BGTU
+            | Opcode::BLEU
+            | Opcode::C_BEQZ
+            | Opcode::C_BNEZ => target.map_or(Flow::Continue, Flow::Branch),
+            Opcode::TAIL => target.map_or(Flow::IndirectJump, Flow::Jump),
+            Opcode::CALL => target.map_or(Flow::IndirectCall, Flow::Call),
+            Opcode::JR | Opcode::C_JR => Flow::IndirectJump,
+            Opcode::JALR | Opcode::C_JALR
+                if self.operands[0] == Operand::Register(Register::Zero) =>
+            {
+                Flow::IndirectJump
             }
+            Opcode::JALR | Opcode::C_JALR => Flow::IndirectCall,
+            Opcode::RET => Flow::Return,
+            Opcode::EBREAK | Opcode::C_EBREAK => Flow::Halt,
+            _ => Flow::Continue,
+        }
+    }
+
+    fn mem_ref(&self) -> Option<usize> {
+        if self.opcode != Opcode::ADDI && !self.opcode.is_mem() {
+            return None;
         }
+
+        self.operands[..self.operand_count].iter().find_map(|op| match op {
+            Operand::Address(addr) => Some(*addr as usize),
+            _ => None,
+        })
     }
 }
 
@@ -1370,38 +1521,11 @@ static MAPPING: Lazy<[fn(&mut Instruction); 284]> = Lazy::new(|| unsafe {
             return;
         }
 
-        if inst.operands[0] == Operand::Register(Register::Ra)
-            && inst.operands[1] == Operand::Register(Register::Ra)
-            && inst.operands[2] == Operand::Immediate(0)
-        {
-            inst.opcode = Opcode::RET;
-            inst.operand_count = 0;
-            return;
-        }
-
         if inst.operands[0] == Operand::Register(Register::Ra)
             && inst.operands[2] == Operand::Immediate(0)
         {
             inst.operands.swap(0, 1);
             inst.operand_count = 1;
-            return;
-        }
-
-        if inst.operands[0] == Operand::Register(Register::Ra)
-            && inst.operands[1] == Operand::Register(Register::Ra)
-        {
-            inst.opcode = Opcode::CALL;
-            inst.operands.swap(0, 1);
-            inst.operand_count = 1;
-            return;
-        }
-
-        if inst.operands[0] == Operand::Register(Register::Zero)
-            && inst.operands[1] == Operand::Register(Register::T1)
-        {
-            inst.opcode = Opcode::TAIL;
-            inst.operands.swap(0, 1);
-            inst.operand_count = 1;
         }
     };
 
@@ -1419,80 +1543,19 @@ static MAPPING: Lazy<[fn(&mut Instruction); 284]> = Lazy::new(|| unsafe {
             && inst.operands[2] == Operand::Immediate(0)
         {
             inst.opcode = Opcode::JR;
-            inst.operands.swap(0, 2);
-            inst.operand_count = 1;
-            return;
-        }
-
-        if inst.operands[0] == Operand::Register(Register::Ra)
-            && inst.operands[1] == Operand::Register(Register::Ra)
-            && inst.operands[2] == Operand::Immediate(0)
-        {
-            inst.opcode = Opcode::RET;
-            inst.operand_count = 0;
-            return;
-        }
-
-        if inst.operands[0] == Operand::Register(Register::Ra)
-            && inst.operands[2] == Operand::Immediate(0)
-        {
             inst.operands.swap(0, 1);
             inst.operand_count = 1;
             return;
         }
 
         if inst.operands[0] == Operand::Register(Register::Ra)
-            && inst.operands[1] == Operand::Register(Register::Ra)
-        {
-            inst.opcode = Opcode::CALL;
-            inst.operands.swap(0, 1);
-            inst.operand_count = 1;
-            return;
-        }
-
-        if inst.operands[0] == Operand::Register(Register::Zero)
-            && inst.operands[1] == Operand::Register(Register::T1)
+            && inst.operands[2] == Operand::Immediate(0)
         {
-            inst.opcode = Opcode::TAIL;
             inst.operands.swap(0, 1);
             inst.operand_count = 1;
         }
     };
 
-    MAPPING[Opcode::AUIPC as usize] = |inst| {
-        if inst.operands[0] == Operand::Register(Register::Ra) {
-            inst.opcode = Opcode::CALL;
-
-            match (inst.operands[0], inst.operands[1]) {
-                (Operand::Register(reg), Operand::Immediate(mut imm)) => {
-                    // offset[31 : 12] + offset[11] where register is bit's [11:6]
-                    imm <<= 1;
-                    imm |= ((reg as u16 & 0b10000) >> 4) as i32;
-                    inst.operands[0] = Operand::Immediate(imm);
-                    inst.operand_count = 1;
-                }
-                _ => unreachable!(),
-            }
-
-            return;
-        }
-
-        if inst.operands[0] == Operand::Register(Register::T1) {
-            inst.opcode = Opcode::TAIL;
-
-            match (inst.operands[0], inst.operands[1]) {
-                (Operand::Register(reg), Operand::Immediate(mut imm)) => {
-                    // offset[31 : 12] + offset[11] where register is bit's [11:6]
-                    imm <<= 1;
-                    imm |= ((reg as u16 & 0b10000) >> 4) as i32;
-                    inst.operands[0] = Operand::Immediate(imm);
-                    inst.operand_count = 1;
-                }
-                _ => unreachable!(),
-            }
-        }
-    };
-
     MAPPING[Opcode::C_SRAI as usize] = |inst| {
         if inst.operands[0] == inst.operands[1] {
             inst.operands.swap(1, 2);
@@ -2049,11 +2112,13 @@ fn decode_jump(dword: u32) -> Result<Instruction, ErrorKind> {
     })
 }
 
-/// Decode's ret instruction.
+/// Decode's jalr instruction.
 fn decode_jumpr(bytes: u32) -> Result<Instruction, ErrorKind> {
     let imm = bytes as i32 >> 20;
     let rd = Register::get(bytes >> 7 & 0b11111)?;
-    let (operands, operand_count) = operands![Operand::Register(rd), Operand::Immediate(imm)];
+    let rs = Register::get(bytes >> 15 & 0b11111)?;
+    let (operands, operand_count) =
+        operands![Operand::Register(rd), Operand::Register(rs), Operand::Immediate(imm)];
 
     Ok(Instruction {
         opcode: Opcode::JALR,
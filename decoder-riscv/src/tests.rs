@@ -1,6 +1,6 @@
 #![cfg(test)]
 
-use decoder::{Decodable, ToTokens};
+use decoder::{Decodable, Decoded, Flow, ToTokens};
 use object::{Object, ObjectSection, SectionKind};
 
 macro_rules! decode_instructions {
@@ -88,6 +88,72 @@ macro_rules! decode_instructions {
     }};
 }
 
+fn test_flow(bytes: &[u8], addr: usize, flow: Flow) {
+    let mut reader = decoder::Reader::new(bytes);
+    let decoder = crate::Decoder { is_64: true };
+    let mut inst = decoder.decode(&mut reader).unwrap();
+    inst.update_rel_addrs(addr, None);
+    assert_eq!(inst.flow(), flow, "flow of {bytes:02x?}");
+}
+
+#[test]
+fn flow() {
+    // j 16
+    test_flow(&[0x6f, 0x00, 0x00, 0x01], 0x1000, Flow::Jump(0x1010));
+    // jal 16
+    test_flow(&[0xef, 0x00, 0x00, 0x01], 0x1000, Flow::Call(0x1010));
+    // beq a0, a1, -8
+    test_flow(&[0xe3, 0x0c, 0xb5, 0xfe], 0x1000, Flow::Branch(0xff8));
+    // bnez a0, 8
+    test_flow(&[0x63, 0x14, 0x05, 0x00], 0x1000, Flow::Branch(0x1008));
+    // c.j 16
+    test_flow(&[0x01, 0xa8], 0x1000, Flow::Jump(0x1010));
+    // c.beqz a0, -4
+    test_flow(&[0x75, 0xdd], 0x1000, Flow::Branch(0xffc));
+    // ret
+    test_flow(&[0x67, 0x80, 0x00, 0x00], 0x1000, Flow::Return);
+    // jr a0
+    test_flow(&[0x67, 0x00, 0x05, 0x00], 0x1000, Flow::IndirectJump);
+    // jalr a0
+    test_flow(&[0xe7, 0x00, 0x05, 0x00], 0x1000, Flow::IndirectCall);
+    // ebreak
+    test_flow(&[0x73, 0x00, 0x10, 0x00], 0x1000, Flow::Halt);
+    // addi a0, a0, 1
+    test_flow(&[0x13, 0x05, 0x15, 0x00], 0x1000, Flow::Continue);
+}
+
+#[test]
+fn flow_above_4gib() {
+    // j 16
+    test_flow(&[0x6f, 0x00, 0x00, 0x01], 0x1_0000_1000, Flow::Jump(0x1_0000_1010));
+    // beq a0, a1, -8
+    test_flow(&[0xe3, 0x0c, 0xb5, 0xfe], 0x1_0000_0000, Flow::Branch(0xffff_fff8));
+}
+
+#[test]
+fn auipc_pairs() {
+    let decoder = crate::Decoder { is_64: true };
+    let decode = |bytes: &[u8], addr: usize, prev: Option<&crate::Instruction>| {
+        let mut inst = decoder.decode(&mut decoder::Reader::new(bytes)).unwrap();
+        inst.update_rel_addrs(addr, prev);
+        inst
+    };
+
+    // auipc ra, 0x1; jalr ra, -0x800(ra)
+    let auipc = decode(&[0x97, 0x10, 0x00, 0x00], 0x10000, None);
+    let call = decode(&[0xe7, 0x80, 0x00, 0x80], 0x10004, Some(&auipc));
+    assert_eq!(call.flow(), Flow::Call(0x10800));
+
+    // auipc t1, 0x1; jr -0x7f8(t1)
+    let auipc = decode(&[0x17, 0x13, 0x00, 0x00], 0x10008, None);
+    let tail = decode(&[0x67, 0x00, 0x83, 0x80], 0x1000c, Some(&auipc));
+    assert_eq!(tail.flow(), Flow::Jump(0x10810));
+
+    // jalr through a different register than the one `auipc` wrote
+    let jalr = decode(&[0xe7, 0x00, 0x05, 0x00], 0x10004, Some(&auipc));
+    assert_eq!(jalr.flow(), Flow::IndirectCall);
+}
+
 #[test]
 fn deref() -> Result<(), Box<dyn std::error::Error>> {
     let decoded = decode_instructions!(
@@ -11,7 +11,7 @@ use std::hash::{Hash, Hasher};
 use crate::safer_unchecked::unreachable_kinda_unchecked as unreachable_unchecked;
 pub use crate::MemoryAccessSize;
 
-use decoder::{Decoded, Decodable, Error, ErrorKind, Reader, ToTokens};
+use decoder::{Decoded, Decodable, Error, ErrorKind, Flow, Reader, ToTokens};
 use debugvault::Index;
 use tokenizing::TokenStream;
 use config::CONFIG;
@@ -2640,36 +2640,24 @@ impl Decoded for Instruction {
     }
 
     fn update_rel_addrs(&mut self, addr: usize, _: Option<&Instruction>) {
+        let is_relative_branch = self.is_relative_branch();
+
         for idx in 0..self.operand_count as usize {
             let operand = Operand::from_spec(&self, self.operands[idx]);
             let addr = addr as u64;
             let addr = match operand {
-                Operand::ImmediateI8(imm) => {
-                    addr.saturating_add(self.length as u64).saturating_add_signed(imm as i64)
-                }
-                Operand::ImmediateU8(imm) => {
-                    addr.saturating_add(self.length as u64).saturating_add(imm as u64)
-                }
-                Operand::ImmediateI16(imm) => {
+                Operand::ImmediateI8(imm) if is_relative_branch => {
                     addr.saturating_add(self.length as u64).saturating_add_signed(imm as i64)
                 }
-                Operand::ImmediateU16(imm) => {
-                    addr.saturating_add(self.length as u64).saturating_add(imm as u64)
-                }
-                Operand::ImmediateI32(imm) => {
+                Operand::ImmediateI16(imm) if is_relative_branch => {
                     addr.saturating_add(self.length as u64).saturating_add_signed(imm as i64)
                 }
-                Operand::ImmediateU32(imm) => {
-                    addr.saturating_add(self.length as u64).saturating_add(imm as u64)
-                }
-                Operand::ImmediateI64(imm) => {
+                Operand::ImmediateI32(imm) if is_relative_branch => {
                     addr.saturating_add(self.length as u64).saturating_add_signed(imm as i64)
                 }
-                Operand::ImmediateU64(imm) => {
-                    addr.saturating_add(self.length as u64).saturating_add(imm as u64)
-                }
-                Operand::DisplacementU32(imm) => addr.saturating_add(imm as u64),
-                Operand::DisplacementU64(imm) => addr.saturating_add(imm),
+                // absolute addresses
+                Operand::DisplacementU32(imm) => imm as u64,
+                Operand::DisplacementU64(imm) => imm,
                 Operand::RegDisp(RegSpec::RIP, disp) => {
                     addr.saturating_add(self.length as u64).saturating_add_signed(disp as i64)
                 }
@@ -2737,6 +2725,44 @@ impl Decoded for Instruction {
             self.imm_override = true;
         }
     }
+
+    fn flow(&self) -> Flow {
+        // relative branches have their immediate replaced by the absolute target
+        let target = if self.imm_override && self.is_relative_branch() {
+            Some(self.imm as usize)
+        } else {
+            None
+        };
+
+        match self.opcode {
+            Opcode::JMP => target.map_or(Flow::IndirectJump, Flow::Jump),
+            Opcode::CALL => target.map_or(Flow::IndirectCall, Flow::Call),
+            Opcode::JMPF => Flow::IndirectJump,
+            Opcode::CALLF => Flow::IndirectCall,
+            Opcode::LOOP | Opcode::LOOPZ | Opcode::LOOPNZ | Opcode::JRCXZ => {
+                target.map_or(Flow::Continue, Flow::Branch)
+            }
+            opcode if opcode.is_jcc() => target.map_or(Flow::Continue, Flow::Branch),
+            Opcode::RETURN
+            | Opcode::RETF
+            | Opcode::IRET
+            | Opcode::IRETD
+            | Opcode::IRETQ
+            | Opcode::SYSRET
+            | Opcode::SYSEXIT => Flow::Return,
+            Opcode::HLT | Opcode::UD0 | Opcode::UD1 | Opcode::UD2 => Flow::Halt,
+            _ => Flow::Continue,
+        }
+    }
+
+    fn mem_ref(&self) -> Option<usize> {
+        // the resolved address of a rip-relative or absolute memory operand
+        if self.imm_override && !self.is_relative_branch() {
+            Some(self.imm as usize)
+        } else {
+            None
+        }
+    }
 }
 
 impl Decodable for Decoder {
@@ -4373,6 +4399,27 @@ impl Instruction {
         }
     }
 
+    /// relative jumps and calls, their immediate is an offset from the next instruction.
+    fn is_relative_branch(&self) -> bool {
+        let is_branch = self.opcode.is_jcc()
+            || matches!(
+                self.opcode,
+                Opcode::JMP
+                    | Opcode::CALL
+                    | Opcode::LOOP
+                    | Opcode::LOOPZ
+                    | Opcode::LOOPNZ
+                    | Opcode::JRCXZ
+                    | Opcode::XBEGIN
+            );
+
+        is_branch
+            && matches!(
+                Operand::from_spec(self, self.operands[0]),
+                Operand::ImmediateI8(_) | Operand::ImmediateI16(_) | Operand::ImmediateI32(_)
+            )
+    }
+
     /// build a new instruction representing nothing in particular. this is primarily useful as a
     /// default to pass to `decode_into`.
     pub fn invalid() -> Instruction {
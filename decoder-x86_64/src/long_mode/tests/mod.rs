@@ -1802,6 +1802,9 @@ fn bmi1() {
         &[0xc4, 0xc2, 0xe0, 0xf2, 0x01],
         "andn rax, rbx, qword [r9]",
     );
+    // VEX.R extends the destination, VEX.X doesn't
+    test_display_under(&bmi1, &[0xc4, 0x62, 0x78, 0xf2, 0xc1], "andn r8d, eax, ecx");
+    test_display_under(&bmi1, &[0xc4, 0xa2, 0x78, 0xf2, 0xc1], "andn eax, eax, ecx");
     test_display_under(
         &bmi1,
         &[0xc4, 0xc2, 0x78, 0xf3, 0x09],
@@ -1858,6 +1861,11 @@ fn bmi2() {
         &[0xc4, 0xc3, 0xfb, 0xf0, 0x01, 0x05],
         "rorx rax, qword [r9], 0x5",
     );
+    test_display_under(
+        &bmi2,
+        &[0xc4, 0x63, 0x7b, 0xf0, 0xc8, 0x03],
+        "rorx r9d, eax, 0x3",
+    );
 
     // f2 0f38 map
     test_display_under(
@@ -2367,6 +2375,14 @@ fn vex() {
         &[0xc4, 0b000_00011, 0b0_0111_101, 0x38, 0b11_001_010, 0x77],
         "vinserti128 ymm9, ymm8, xmm10, 0x77",
     );
+    test_avx2(
+        &[0xc4, 0b011_00011, 0b0_0111_101, 0x38, 0b11_001_010, 0x77],
+        "vinserti128 ymm9, ymm8, xmm2, 0x77",
+    );
+    test_avx2(
+        &[0xc4, 0b101_00011, 0b0_0111_101, 0x38, 0b11_001_010, 0x77],
+        "vinserti128 ymm1, ymm8, xmm2, 0x77",
+    );
     test_invalid(&[0xc4, 0b000_00011, 0b1_0111_101, 0x18, 0b11_001_010, 0x77]);
     test_avx2(
         &[0xc4, 0b000_00011, 0b0_1111_101, 0x39, 0b11_001_010, 0x77],
@@ -4296,6 +4312,15 @@ fn vex() {
         &[0xc4, 0b000_00001, 0b1_1111_001, 0x71, 0b11_010_010, 0x77],
         "vpsrlw xmm0, xmm10, 0x77",
     );
+    // VEX.B extends the register in modrm.rm, VEX.R doesn't
+    test_instr(
+        &[0xc4, 0b110_00001, 0b0_1111_001, 0x71, 0b11_010_010, 0x77],
+        "vpsrlw xmm0, xmm10, 0x77",
+    );
+    test_instr(
+        &[0xc4, 0b011_00001, 0b0_1111_001, 0x71, 0b11_010_010, 0x77],
+        "vpsrlw xmm0, xmm2, 0x77",
+    );
     test_instr(
         &[0xc4, 0b000_00001, 0b1_0111_001, 0x71, 0b11_010_010, 0x77],
         "vpsrlw xmm8, xmm10, 0x77",
@@ -6374,6 +6399,46 @@ fn sevsnp() {
     test_display(&[0xf2, 0x0f, 0x01, 0xfe], "rmpupdate");
 }
 
+fn test_flow(data: &[u8], addr: usize, expected: decoder::Flow) {
+    let mut reader = Reader::new(data);
+    let mut instr = Decoder::default().decode(&mut reader).unwrap();
+    instr.update_rel_addrs(addr, None);
+    assert_eq!(instr.flow(), expected, "flow error for {:02x?}", data);
+}
+
+#[test]
+fn flow() {
+    use decoder::Flow;
+
+    test_flow(&[0xe8, 0x14, 0x00, 0x00, 0x00], 0x1000, Flow::Call(0x1019));
+    test_flow(&[0xeb, 0xfe], 0x1000, Flow::Jump(0x1000));
+    test_flow(&[0x75, 0x10], 0x1000, Flow::Branch(0x1012));
+    test_flow(&[0xff, 0xe0], 0x1000, Flow::IndirectJump);
+    test_flow(&[0xff, 0xd0], 0x1000, Flow::IndirectCall);
+    test_flow(&[0xc3], 0x1000, Flow::Return);
+    test_flow(&[0x0f, 0x0b], 0x1000, Flow::Halt);
+    test_flow(&[0xb8, 0x3c, 0x00, 0x00, 0x00], 0x1000, Flow::Continue);
+}
+
+fn test_mem_ref(data: &[u8], addr: usize, expected: Option<usize>) {
+    let mut reader = Reader::new(data);
+    let mut instr = Decoder::default().decode(&mut reader).unwrap();
+    instr.update_rel_addrs(addr, None);
+    assert_eq!(instr.mem_ref(), expected, "mem_ref error for {:02x?}", data);
+}
+
+#[test]
+fn mem_refs() {
+    // lea rax, [rip + 0x10]
+    test_mem_ref(&[0x48, 0x8d, 0x05, 0x10, 0x00, 0x00, 0x00], 0x1000, Some(0x1017));
+    // mov rax, qword [0x2000]
+    test_mem_ref(&[0x48, 0xa1, 0x00, 0x20, 0, 0, 0, 0, 0, 0], 0x1000, Some(0x2000));
+    // immediates aren't relative to the instruction
+    test_mem_ref(&[0xb8, 0x3c, 0x00, 0x00, 0x00], 0x1000, None);
+    test_mem_ref(&[0x6a, 0x64], 0x1000, None);
+    test_mem_ref(&[0xe8, 0x14, 0x00, 0x00, 0x00], 0x1000, None);
+}
+
 // some test cases are best just lifted from llvm or gcc.
 #[test]
 fn from_llvm() {
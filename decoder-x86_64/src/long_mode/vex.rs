@@ -183,7 +183,7 @@ fn read_vex_operands(
             }
             instruction.regs[0] = RegSpec::from_parts(
                 modrm & 7,
-                instruction.prefixes.vex_unchecked().r(),
+                instruction.prefixes.vex_unchecked().b(),
                 RegisterBank::X,
             );
             instruction.regs[3].bank = RegisterBank::X;
@@ -209,7 +209,7 @@ fn read_vex_operands(
             };
             instruction.regs[0] = RegSpec::from_parts(
                 modrm & 7,
-                instruction.prefixes.vex_unchecked().r(),
+                instruction.prefixes.vex_unchecked().b(),
                 RegisterBank::Y,
             );
             instruction.regs[3].bank = RegisterBank::Y;
@@ -234,7 +234,7 @@ fn read_vex_operands(
             };
             instruction.regs[0] = RegSpec::from_parts(
                 modrm & 7,
-                instruction.prefixes.vex_unchecked().r(),
+                instruction.prefixes.vex_unchecked().b(),
                 RegisterBank::X,
             );
             instruction.regs[3].bank = RegisterBank::X;
@@ -259,7 +259,7 @@ fn read_vex_operands(
             };
             instruction.regs[0] = RegSpec::from_parts(
                 modrm & 7,
-                instruction.prefixes.vex_unchecked().r(),
+                instruction.prefixes.vex_unchecked().b(),
                 RegisterBank::Y,
             );
             instruction.regs[3].bank = RegisterBank::Y;
@@ -285,7 +285,7 @@ fn read_vex_operands(
             };
             instruction.regs[0] = RegSpec::from_parts(
                 modrm & 7,
-                instruction.prefixes.vex_unchecked().r(),
+                instruction.prefixes.vex_unchecked().b(),
                 RegisterBank::X,
             );
             instruction.regs[3].bank = RegisterBank::X;
@@ -312,7 +312,7 @@ fn read_vex_operands(
             };
             instruction.regs[0] = RegSpec::from_parts(
                 modrm & 7,
-                instruction.prefixes.vex_unchecked().r(),
+                instruction.prefixes.vex_unchecked().b(),
                 RegisterBank::Y,
             );
             instruction.regs[3].bank = RegisterBank::Y;
@@ -1281,7 +1281,7 @@ fn read_vex_operands(
             };
             instruction.regs[0] = RegSpec::from_parts(
                 (modrm >> 3) & 7,
-                instruction.prefixes.vex_unchecked().x(),
+                instruction.prefixes.vex_unchecked().r(),
                 bank,
             );
             instruction.regs[3].bank = bank;
@@ -1304,7 +1304,7 @@ fn read_vex_operands(
             };
             instruction.regs[0] = RegSpec::from_parts(
                 (modrm >> 3) & 7,
-                instruction.prefixes.vex_unchecked().x(),
+                instruction.prefixes.vex_unchecked().r(),
                 bank,
             );
             instruction.regs[3].bank = bank;
@@ -1327,7 +1327,7 @@ fn read_vex_operands(
             };
             instruction.regs[0] = RegSpec::from_parts(
                 (modrm >> 3) & 7,
-                instruction.prefixes.vex_unchecked().x(),
+                instruction.prefixes.vex_unchecked().r(),
                 bank,
             );
             let mem_oper = read_E(words, instruction, modrm, opwidth)?;
@@ -1439,7 +1439,7 @@ fn read_vex_operands(
             let modrm = read_modrm(words)?;
             instruction.regs[0] = RegSpec::from_parts(
                 (modrm >> 3) & 7,
-                instruction.prefixes.vex_unchecked().x(),
+                instruction.prefixes.vex_unchecked().r(),
                 RegisterBank::Y,
             );
             instruction.regs[3].bank = RegisterBank::Y;
@@ -1459,7 +1459,7 @@ fn read_vex_operands(
             let modrm = read_modrm(words)?;
             instruction.regs[0] = RegSpec::from_parts(
                 (modrm >> 3) & 7,
-                instruction.prefixes.vex_unchecked().x(),
+                instruction.prefixes.vex_unchecked().r(),
                 RegisterBank::X,
             );
             instruction.regs[3].bank = RegisterBank::X;
@@ -1479,7 +1479,7 @@ fn read_vex_operands(
             let modrm = read_modrm(words)?;
             instruction.regs[0] = RegSpec::from_parts(
                 (modrm >> 3) & 7,
-                instruction.prefixes.vex_unchecked().x(),
+                instruction.prefixes.vex_unchecked().r(),
                 RegisterBank::Y,
             );
             instruction.regs[3].bank = RegisterBank::Y;
@@ -1497,7 +1497,7 @@ fn read_vex_operands(
             let modrm = read_modrm(words)?;
             instruction.regs[0] = RegSpec::from_parts(
                 (modrm >> 3) & 7,
-                instruction.prefixes.vex_unchecked().x(),
+                instruction.prefixes.vex_unchecked().r(),
                 RegisterBank::X,
             );
             instruction.regs[3].bank = RegisterBank::X;
@@ -2432,6 +2432,9 @@ impl ToTokens for Instruction {
                 None
             };
 
+        // only the operand that was resolved should be shown as an address
+        let is_branch = self.is_relative_branch();
+
         if self.operand_count > 0 {
             stream.push(" ", colors::WHITE);
 
@@ -2449,7 +2452,7 @@ impl ToTokens for Instruction {
                 stream.push(":", CONFIG.colors.asm.expr);
             }
 
-            op.tokenize(stream, symbols, imm_override);
+            op.tokenize(stream, symbols, imm_override.filter(|_| op.is_memory() || is_branch));
 
             for idx in 1..self.operand_count {
                 if self.operands[idx as usize] == OperandSpec::Nothing {
@@ -2470,7 +2473,7 @@ impl ToTokens for Instruction {
                     stream.push(":", CONFIG.colors.asm.expr);
                 }
 
-                op.tokenize(stream, symbols, imm_override);
+                op.tokenize(stream, symbols, imm_override.filter(|_| op.is_memory() || is_branch));
 
                 if let Some(evex) = self.prefixes.evex() {
                     if evex.broadcast() && op.is_memory() {
@@ -56,6 +56,48 @@ pub enum ErrorKind {
     Unpredictable,
 }
 
+/// How an instruction passes on control flow.
+///
+/// Targets are absolute addresses, so they're only meaningful after
+/// [`Decoded::update_rel_addrs`] has been called on the instruction.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Flow {
+    /// Execution continues at the next instruction.
+    Continue,
+    /// Conditional branch to a known address, otherwise continues at the next instruction.
+    Branch(usize),
+    /// Unconditional jump to a known address.
+    Jump(usize),
+    /// Call to a known address, returning to the next instruction.
+    Call(usize),
+    /// Jump through a register or memory operand.
+    IndirectJump,
+    /// Call through a register or memory operand, returning to the next instruction.
+    IndirectCall,
+    /// Return from a function.
+    Return,
+    /// Execution doesn't continue past this instruction (traps, halts, etc).
+    Halt,
+}
+
+impl Flow {
+    /// Whether the instruction following this one can be reached.
+    pub fn falls_through(&self) -> bool {
+        matches!(
+            self,
+            Flow::Continue | Flow::Branch(_) | Flow::Call(_) | Flow::IndirectCall
+        )
+    }
+
+    /// The known address this instruction may transfer execution to.
+    pub fn target(&self) -> Option<usize> {
+        match self {
+            Flow::Branch(addr) | Flow::Jump(addr) | Flow::Call(addr) => Some(*addr),
+            _ => None,
+        }
+    }
+}
+
 pub trait ToTokens {
     fn tokenize(&self, stream: &mut TokenStream, symbols: &Index);
 }
@@ -68,12 +110,26 @@ pub trait Decoded: ToTokens {
         stream.inner
     }
     fn update_rel_addrs(&mut self, addr: usize, prev_inst: Option<&Self>);
+    fn flow(&self) -> Flow;
+    /// Absolute address of memory the instruction references other than through its
+    /// control flow, e.g. pc-relative loads.
+    fn mem_ref(&self) -> Option<usize>;
+    /// Name of the instruction set the instruction is encoded in, for architectures that
+    /// switch between more than one.
+    fn instruction_set(&self) -> Option<&'static str> {
+        None
+    }
 }
 
 pub trait Decodable {
     type Instruction: Decoded;
 
     fn decode(&self, reader: &mut Reader) -> Result<Self::Instruction, Error>;
+    /// Decode the instruction located at `addr`. Only decoders that switch between
+    /// instruction sets by address (e.g. Arm and Thumb) care about where it's located.
+    fn decode_at(&self, reader: &mut Reader, _addr: usize) -> Result<Self::Instruction, Error> {
+        self.decode(reader)
+    }
     fn max_width(&self) -> usize;
 }
 
@@ -10,7 +10,7 @@ impl super::UI {
     /// Runs a singular commands, returning if it should exit the process.
     fn process_cmd(&mut self, cmd: &str) -> bool {
         let empty_index = debugvault::Index::default();
-        let index = self.panels.processor().map(|proc| &proc.index).unwrap_or(&empty_index);
+        let index = self.panels.processor().map(|proc| &*proc.index).unwrap_or(&empty_index);
 
         match Command::parse(index, cmd, 0) {
             Ok(Command::Load(path)) => self.offload_binary_processing(path),
@@ -47,13 +47,244 @@ impl super::UI {
                 };
 
                 if listing.jump(addr) {
-                    tprint!(self.panels.terminal(), "Jumped to address {addr:#X}.");
+                    let offset = self.panels.processor().and_then(|proc| proc.va_to_offset(addr));
+                    match offset {
+                        Some(offset) => tprint!(
+                            self.panels.terminal(),
+                            "Jumped to address {addr:#X} (file offset {offset:#X})."
+                        ),
+                        None => tprint!(self.panels.terminal(), "Jumped to address {addr:#X}."),
+                    }
                 } else {
                     tprint!(self.panels.terminal(), "Address {addr:#X} is undefined.");
                 }
 
+                if let Some(graph) = self.panels.graph() {
+                    graph.jump(addr);
+                }
+
                 self.panels.load_src(addr);
             }
+            Ok(Command::Xref(addr)) => {
+                let processor = match self.panels.processor() {
+                    Some(processor) => processor.clone(),
+                    None => {
+                        tprint!(self.panels.terminal(), "No targets loaded.");
+                        return true;
+                    }
+                };
+
+                let xrefs = processor.xrefs_to(addr);
+                if xrefs.is_empty() {
+                    tprint!(self.panels.terminal(), "No references to {addr:#X}.");
+                } else {
+                    tprint!(self.panels.terminal(), "References to {addr:#X}:");
+                }
+
+                for xref in xrefs {
+                    let line: String = crate::panes::xref_tokens(&processor, xref)
+                        .iter()
+                        .map(|token| &*token.text)
+                        .collect();
+
+                    tprint!(self.panels.terminal(), "  {line}");
+                }
+
+                if let Some(pane) = self.panels.xrefs() {
+                    pane.set_addr(addr);
+                }
+            }
+            Ok(Command::Rename(addr, name)) => {
+                if self.panels.processor().is_none() {
+                    tprint!(self.panels.terminal(), "No targets loaded.");
+                    return true;
+                }
+
+                self.panels.edit_processor(|processor| processor.rename(addr, &name));
+                tprint!(self.panels.terminal(), "Renamed {addr:#X} to {name}.");
+            }
+            Ok(Command::Comment(addr, text)) => {
+                if self.panels.processor().is_none() {
+                    tprint!(self.panels.terminal(), "No targets loaded.");
+                    return true;
+                }
+
+                self.panels.edit_processor(|processor| processor.set_comment(addr, &text));
+                if text.is_empty() {
+                    tprint!(self.panels.terminal(), "Removed comment at {addr:#X}.");
+                } else {
+                    tprint!(self.panels.terminal(), "Commented on {addr:#X}.");
+                }
+            }
+            Ok(Command::Patch(addr, bytes)) => {
+                if self.panels.processor().is_none() {
+                    tprint!(self.panels.terminal(), "No targets loaded.");
+                    return true;
+                }
+
+                let mut result = Ok(());
+                self.panels.edit_processor(|processor| result = processor.patch(addr, &bytes));
+                match result {
+                    Ok(()) => tprint!(
+                        self.panels.terminal(),
+                        "Patched {} bytes at {addr:#X}.",
+                        bytes.len()
+                    ),
+                    Err(err) => tprint!(self.panels.terminal(), "Failed to patch: {err}."),
+                }
+            }
+            Ok(Command::Assemble(addr, instruction)) => {
+                if self.panels.processor().is_none() {
+                    tprint!(self.panels.terminal(), "No targets loaded.");
+                    return true;
+                }
+
+                let mut result = Ok(0);
+                self.panels
+                    .edit_processor(|processor| result = processor.assemble(addr, &instruction));
+                match result {
+                    Ok(len) => tprint!(
+                        self.panels.terminal(),
+                        "Assembled {len} bytes at {addr:#X}."
+                    ),
+                    Err(err) => tprint!(self.panels.terminal(), "Failed to assemble: {err}."),
+                }
+            }
+            Ok(Command::Find(pattern)) => {
+                if self.panels.search().is_none() {
+                    tprint!(self.panels.terminal(), "No targets loaded.");
+                    return true;
+                }
+
+                tprint!(self.panels.terminal(), "Searching for {pattern}.");
+                if let Some(search) = self.panels.search() {
+                    search.find(pattern);
+                }
+
+                self.panels.goto_window(crate::panes::SEARCH);
+            }
+            Ok(Command::Revert(addr)) => {
+                if self.panels.processor().is_none() {
+                    tprint!(self.panels.terminal(), "No targets loaded.");
+                    return true;
+                }
+
+                let mut result = Ok(());
+                self.panels.edit_processor(|processor| result = processor.revert(addr));
+                match result {
+                    Ok(()) => tprint!(self.panels.terminal(), "Reverted patch at {addr:#X}."),
+                    Err(err) => tprint!(self.panels.terminal(), "Failed to revert: {err}."),
+                }
+            }
+            Ok(Command::ListPatches) => {
+                let processor = match self.panels.processor() {
+                    Some(processor) => processor.clone(),
+                    None => {
+                        tprint!(self.panels.terminal(), "No targets loaded.");
+                        return true;
+                    }
+                };
+
+                if processor.patches().next().is_none() {
+                    tprint!(self.panels.terminal(), "No patches.");
+                }
+
+                let hex = |bytes: &[u8]| {
+                    processor_shared::encode_hex_bytes_truncated(bytes, usize::MAX, false)
+                };
+                for patch in processor.patches() {
+                    let (original, bytes) = (hex(patch.original), hex(patch.bytes));
+                    tprint!(
+                        self.panels.terminal(),
+                        "  {:#X}  {original} -> {bytes}",
+                        patch.addr
+                    );
+                }
+            }
+            Ok(Command::SetType(addr, tipe)) => {
+                if self.panels.processor().is_none() {
+                    tprint!(self.panels.terminal(), "No targets loaded.");
+                    return true;
+                }
+
+                let mut fits = false;
+                self.panels
+                    .edit_processor(|processor| fits = processor.set_data_type(addr, tipe));
+                match tipe {
+                    Some(tipe) if fits => {
+                        tprint!(self.panels.terminal(), "Showing {addr:#X} as {tipe}.")
+                    }
+                    Some(tipe) => tprint!(
+                        self.panels.terminal(),
+                        "Can't show {addr:#X} as {tipe}, it isn't in code or data or doesn't fit."
+                    ),
+                    None if fits => tprint!(self.panels.terminal(), "Removed type at {addr:#X}."),
+                    None => tprint!(self.panels.terminal(), "Address {addr:#X} has no type."),
+                }
+            }
+            Ok(Command::Bookmark(addr)) => {
+                if self.panels.processor().is_none() {
+                    tprint!(self.panels.terminal(), "No targets loaded.");
+                    return true;
+                }
+
+                let mut bookmarked = false;
+                self.panels
+                    .edit_processor(|processor| bookmarked = processor.toggle_bookmark(addr));
+                if bookmarked {
+                    tprint!(self.panels.terminal(), "Bookmarked {addr:#X}.");
+                } else {
+                    tprint!(self.panels.terminal(), "Removed bookmark at {addr:#X}.");
+                }
+            }
+            Ok(Command::ListBookmarks) => {
+                let processor = match self.panels.processor() {
+                    Some(processor) => processor.clone(),
+                    None => {
+                        tprint!(self.panels.terminal(), "No targets loaded.");
+                        return true;
+                    }
+                };
+
+                if processor.bookmarks().next().is_none() {
+                    tprint!(self.panels.terminal(), "No bookmarks.");
+                }
+
+                for addr in processor.bookmarks() {
+                    match processor.index.get_func_containing(addr) {
+                        Some(func) if func.addr == addr => {
+                            tprint!(self.panels.terminal(), "  {addr:#X}  <{}>", func.item.as_str())
+                        }
+                        Some(func) => tprint!(
+                            self.panels.terminal(),
+                            "  {addr:#X}  <{}+{:#x}>",
+                            func.item.as_str(),
+                            addr - func.addr
+                        ),
+                        None => tprint!(self.panels.terminal(), "  {addr:#X}"),
+                    }
+                }
+            }
+            Ok(Command::Export(path)) => {
+                let processor = match self.panels.processor() {
+                    Some(processor) => processor.clone(),
+                    None => {
+                        tprint!(self.panels.terminal(), "No targets loaded.");
+                        return true;
+                    }
+                };
+
+                match processor.export_patched(&path) {
+                    Ok(()) => tprint!(
+                        self.panels.terminal(),
+                        "Wrote patched binary to {}.",
+                        path.display()
+                    ),
+                    Err(err) => tprint!(self.panels.terminal(), "Failed to export: {err}."),
+                }
+            }
+            Ok(Command::Open(path)) => self.offload_binary_processing(path),
+            Ok(Command::Save(path)) => self.panels.save_project(path),
             Ok(Command::Quit) => return false,
             Ok(Command::Clear) => {
                 log::LOGGER.write().unwrap().clear();
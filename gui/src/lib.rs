@@ -16,6 +16,7 @@ use unix::Arch;
 #[cfg(target_family = "windows")]
 use windows::Arch;
 
+use processor::{Decoding, Project};
 use std::sync::Arc;
 use winit::event::{Event, WindowEvent};
 use winit::event_loop::EventLoop;
@@ -57,7 +58,13 @@ pub enum UIEvent {
     BinaryRequested(std::path::PathBuf),
     BinaryFailed(processor::Error),
     BinaryLoaded(processor::Processor),
+    /// More of a lazily decoded binary was analyzed in the background.
+    BinaryAnalyzed(processor::Processor),
     GotoAddr(usize),
+    /// Move the listing to an address without switching to it.
+    SyncAddr(usize),
+    /// Fill in the terminal's prompt with a command for the user to finish.
+    PromptCommand(String),
 }
 
 #[derive(Clone)]
@@ -145,10 +152,44 @@ impl UI {
         let ui_queue = self.ui_queue.clone();
 
         std::thread::spawn(move || {
-            match processor::Processor::parse(&path) {
-                Ok(diss) => ui_queue.push(UIEvent::BinaryLoaded(diss)),
-                Err(err) => ui_queue.push(UIEvent::BinaryFailed(err)),
+            // projects are opened by loading the binary they belong to, otherwise the project
+            // next to the binary is opened if there is one
+            let (path, project) = if Project::is_project(&path) {
+                match Project::open(&path) {
+                    Ok(project) => (project.binary, path),
+                    Err(err) => {
+                        return ui_queue.push(UIEvent::BinaryFailed(processor::Error::Project(err)))
+                    }
+                }
+            } else {
+                let project = Project::path_for(&path);
+                (path, project)
+            };
+
+            let decoding = if commands::ARGS.lazy {
+                Decoding::Lazy
+            } else {
+                Decoding::for_file(&path)
+            };
+
+            let mut processor = match processor::Processor::parse_with(&path, decoding) {
+                Ok(processor) => processor,
+                Err(err) => return ui_queue.push(UIEvent::BinaryFailed(err)),
             };
+
+            if project.exists() {
+                if let Err(err) = processor.open_project(&project) {
+                    log::warning!("Failed to open project {project:?}: {err}.");
+                }
+            }
+
+            ui_queue.push(UIEvent::BinaryLoaded(processor.clone()));
+
+            // lazily decoded binaries are shown right away and analyzed afterwards, what's
+            // analyzed so far is shown after each step
+            if decoding == Decoding::Lazy {
+                processor.analyze(|diss| ui_queue.push(UIEvent::BinaryAnalyzed(diss)));
+            }
         });
     }
 
@@ -157,6 +198,8 @@ impl UI {
         while let Ok(event) = self.arch.menu_channel.try_recv() {
             match event.id.0.as_str() {
                 "open" => self.panels.ask_for_binary(),
+                "open_project" => self.panels.ask_for_project(),
+                "save_project" => self.panels.save_project(None),
                 panes::SOURCE => {
                     self.panels.goto_window(panes::SOURCE);
                     self.arch.bar.set_checked(panes::SOURCE);
@@ -169,6 +212,18 @@ impl UI {
                     self.panels.goto_window(panes::FUNCTIONS);
                     self.arch.bar.set_checked(panes::FUNCTIONS);
                 }
+                panes::GRAPH => {
+                    self.panels.goto_window(panes::GRAPH);
+                    self.arch.bar.set_checked(panes::GRAPH);
+                }
+                panes::XREFS => {
+                    self.panels.goto_window(panes::XREFS);
+                    self.arch.bar.set_checked(panes::XREFS);
+                }
+                panes::SEARCH => {
+                    self.panels.goto_window(panes::SEARCH);
+                    self.arch.bar.set_checked(panes::SEARCH);
+                }
                 panes::LOGGING => {
                     self.panels.goto_window(panes::LOGGING);
                     self.arch.bar.set_checked(panes::LOGGING);
@@ -191,14 +246,38 @@ impl UI {
                     self.arch.bar.set_path(&disassembly.path);
 
                     self.panels.stop_loading();
+                    if let Some(ref layout) = disassembly.project().layout {
+                        self.panels.set_layout(layout);
+                    }
                     self.panels.load_binary(disassembly);
                 }
+                UIEvent::BinaryAnalyzed(mut disassembly) => {
+                    // another binary might've been opened in the meantime
+                    let current = match self.panels.processor() {
+                        Some(current) if current.path == disassembly.path => current,
+                        _ => continue,
+                    };
+
+                    // keep the changes made while the binary was being analyzed
+                    disassembly.apply_project(current.project().clone());
+                    self.panels.set_processor(disassembly);
+                }
                 UIEvent::GotoAddr(addr) => {
+                    if let Some(graph) = self.panels.graph() {
+                        graph.jump(addr);
+                    }
+
                     if let Some(listing) = self.panels.listing() {
                         listing.jump(addr);
                         self.panels.goto_window(panes::DISASSEMBLY);
                     }
                 }
+                UIEvent::SyncAddr(addr) => {
+                    if let Some(listing) = self.panels.listing() {
+                        listing.jump(addr);
+                    }
+                }
+                UIEvent::PromptCommand(cmd) => self.panels.terminal().set_command(&cmd),
             }
         }
     }
@@ -28,6 +28,16 @@ impl Functions {
             max_row: 0,
         }
     }
+
+    /// Show the functions of an edited version of the binary.
+    pub fn set_processor(&mut self, processor: Arc<Processor>) {
+        self.lines_count = processor.index.named_funcs_count();
+        self.processor = processor;
+
+        // the shown rows are tokenized again
+        self.min_row = 0;
+        self.max_row = 0;
+    }
 }
 
 fn tokenize_functions(index: &debugvault::Index, range: std::ops::Range<usize>) -> Vec<(usize, Vec<Token>)> {
@@ -49,8 +59,13 @@ fn tokenize_functions(index: &debugvault::Index, range: std::ops::Range<usize>)
             tokens.push(Token::from_str("!", CONFIG.colors.delimiter));
         }
 
-        for token in item.name() {
-            tokens.push(token.clone());
+        if item.generated() {
+            // make it obvious the name wasn't found in the binary
+            tokens.push(Token::from_string(item.as_str().to_string(), CONFIG.colors.comment));
+        } else {
+            for token in item.name() {
+                tokens.push(token.clone());
+            }
         }
 
         functions.push((*addr, tokens));
@@ -0,0 +1,383 @@
+use crate::common::*;
+use crate::{UIEvent, UiQueue};
+use config::CONFIG;
+use egui::{Color32, Pos2, Rect, Sense, Shape, Stroke, Vec2};
+use processor::{Cfg, EdgeKind, Processor};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokenizing::{colors, Token};
+
+/// Space between nodes and layers.
+const GAP: Vec2 = Vec2::new(40.0, 50.0);
+
+/// Space between a node's border and it's instructions.
+const PADDING: f32 = 8.0;
+
+const TAKEN: Color32 = colors::GREEN;
+const NOT_TAKEN: Color32 = Color32::from_rgb(0xe6, 0x4a, 0x4a);
+const UNCONDITIONAL: Color32 = Color32::from_rgb(0x3e, 0xbc, 0xe6);
+const FALLTHROUGH: Color32 = colors::GRAYAA;
+
+struct Node {
+    start: usize,
+    /// Address and tokens of each instruction.
+    lines: Vec<(usize, Vec<Token>)>,
+    /// Position relative to the top-center of the graph.
+    rect: Rect,
+}
+
+struct Edge {
+    from: usize,
+    to: usize,
+    color: Color32,
+    /// Horizontal offset from the center of the node the edge leaves.
+    offset: f32,
+}
+
+pub struct Graph {
+    processor: Arc<Processor>,
+    ui_queue: Arc<UiQueue>,
+    cfg: Option<Cfg>,
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+    /// Whether the nodes have been positioned, requires font metrics.
+    laid_out: bool,
+    /// Address of the last selected instruction.
+    selected: usize,
+    pan: Vec2,
+}
+
+impl Graph {
+    pub fn new(processor: Arc<Processor>, ui_queue: Arc<UiQueue>) -> Self {
+        let mut this = Self {
+            processor,
+            ui_queue,
+            cfg: None,
+            nodes: Vec::new(),
+            edges: Vec::new(),
+            laid_out: false,
+            selected: 0,
+            pan: Vec2::ZERO,
+        };
+
+        this.jump(this.processor.entrypoint);
+        this
+    }
+
+    /// Show the function containing `addr`.
+    pub fn jump(&mut self, addr: usize) -> bool {
+        self.selected = addr;
+
+        // the function is already shown
+        if let Some(ref cfg) = self.cfg {
+            if cfg.block_by_addr(addr).is_some() {
+                return true;
+            }
+        }
+
+        let cfg = match self.function_containing(addr).and_then(|f| self.processor.cfg_for(f)) {
+            Some(cfg) if cfg.block_by_addr(addr).is_some() => cfg,
+            _ => match self.processor.cfg_for(addr) {
+                Some(cfg) => cfg,
+                None => return false,
+            },
+        };
+
+        self.nodes = cfg
+            .blocks
+            .iter()
+            .map(|block| Node {
+                start: block.start,
+                lines: self.tokenize_block(block.start, block.end),
+                rect: Rect::NOTHING,
+            })
+            .collect();
+
+        let idx_by_addr: HashMap<usize, usize> =
+            cfg.blocks.iter().enumerate().map(|(idx, block)| (block.start, idx)).collect();
+
+        self.edges.clear();
+        for (from, block) in cfg.blocks.iter().enumerate() {
+            let is_conditional = block.edges.iter().any(|e| e.kind == EdgeKind::Conditional);
+            let first_edge = self.edges.len();
+
+            for edge in block.edges.iter() {
+                let color = match edge.kind {
+                    EdgeKind::Conditional => TAKEN,
+                    EdgeKind::Fallthrough if is_conditional => NOT_TAKEN,
+                    EdgeKind::Fallthrough => FALLTHROUGH,
+                    EdgeKind::Unconditional => UNCONDITIONAL,
+                    // these leave the function
+                    EdgeKind::Call | EdgeKind::Return | EdgeKind::Indirect => continue,
+                };
+
+                if let Some(&to) = edge.target.and_then(|target| idx_by_addr.get(&target)) {
+                    self.edges.push(Edge { from, to, color, offset: 0.0 });
+                }
+            }
+
+            // spread out edges leaving the same node
+            let leaving = &mut self.edges[first_edge..];
+            let count = leaving.len();
+            for (idx, edge) in leaving.iter_mut().enumerate() {
+                edge.offset = (idx as f32 - (count as f32 - 1.0) / 2.0) * 12.0;
+            }
+        }
+
+        self.cfg = Some(cfg);
+        self.laid_out = false;
+        self.pan = Vec2::ZERO;
+        true
+    }
+
+    /// Show the same function of an edited version of the binary, staying where the graph
+    /// was panned to.
+    pub fn set_processor(&mut self, processor: Arc<Processor>) {
+        let pan = self.pan;
+        self.processor = processor;
+        self.cfg = None;
+
+        if self.jump(self.selected) {
+            self.pan = pan;
+        }
+    }
+
+    /// Address of the closest function symbol at or before `addr`.
+    fn function_containing(&self, addr: usize) -> Option<usize> {
+        self.processor.index.get_func_containing(addr).map(|func| func.addr)
+    }
+
+    fn tokenize_block(&self, start: usize, end: usize) -> Vec<(usize, Vec<Token>)> {
+        let mut lines = Vec::new();
+        let mut addr = start;
+
+        while addr < end {
+            let inst = match self.processor.instruction_by_addr(addr) {
+                Some(inst) => inst,
+                None => break,
+            };
+
+            let mut tokens = Vec::new();
+            tokens.push(Token::from_string(format!("{addr:0>10X}  "), CONFIG.colors.address));
+            tokens.extend(self.processor.instruction_tokens(&inst, &self.processor.index));
+            lines.push((addr, tokens));
+
+            addr += self.processor.instruction_width(&inst);
+        }
+
+        lines
+    }
+
+    /// Assign each node a layer and position, edges that lead back to an earlier layer
+    /// (loops) are ignored in deciding the layers.
+    fn layout(&mut self, glyph_width: f32, row_height: f32) {
+        let entry = match self.cfg {
+            Some(ref cfg) => self.nodes.iter().position(|n| n.start == cfg.entry).unwrap_or(0),
+            None => return,
+        };
+
+        let mut successors = vec![Vec::new(); self.nodes.len()];
+        for edge in self.edges.iter() {
+            successors[edge.from].push(edge.to);
+        }
+
+        // depth first search for loops, marking nodes as either unvisited, on the stack or done
+        let mut state = vec![0u8; self.nodes.len()];
+        let mut forward = vec![Vec::new(); self.nodes.len()];
+        let mut stack = vec![(entry, 0)];
+        state[entry] = 1;
+
+        while let Some((node, child)) = stack.pop() {
+            match successors[node].get(child) {
+                Some(&next) => {
+                    stack.push((node, child + 1));
+                    match state[next] {
+                        0 => {
+                            forward[node].push(next);
+                            state[next] = 1;
+                            stack.push((next, 0));
+                        }
+                        2 => forward[node].push(next),
+                        _ => {}
+                    }
+                }
+                None => state[node] = 2,
+            }
+        }
+
+        // longest path from the entry decides the layer
+        let mut incoming = vec![0usize; self.nodes.len()];
+        for &next in forward.iter().flatten() {
+            incoming[next] += 1;
+        }
+
+        let mut layer = vec![0usize; self.nodes.len()];
+        let mut ready: Vec<usize> = (0..self.nodes.len()).filter(|&n| incoming[n] == 0).collect();
+        while let Some(node) = ready.pop() {
+            for &next in forward[node].iter() {
+                layer[next] = layer[next].max(layer[node] + 1);
+                incoming[next] -= 1;
+                if incoming[next] == 0 {
+                    ready.push(next);
+                }
+            }
+        }
+
+        let layer_count = layer.iter().max().map_or(0, |max| max + 1);
+        let mut layers = vec![Vec::new(); layer_count];
+        for (node, &layer) in layer.iter().enumerate() {
+            layers[layer].push(node);
+        }
+
+        // order nodes by the average position of their parents to reduce crossing edges
+        let mut order = vec![0.0f32; self.nodes.len()];
+        for nodes in layers.iter_mut() {
+            for node in nodes.iter() {
+                let parents: Vec<f32> = forward
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, children)| children.contains(node))
+                    .map(|(parent, _)| order[parent])
+                    .collect();
+
+                if !parents.is_empty() {
+                    order[*node] = parents.iter().sum::<f32>() / parents.len() as f32;
+                }
+            }
+
+            nodes.sort_by(|a, b| order[*a].total_cmp(&order[*b]));
+            for (pos, node) in nodes.iter().enumerate() {
+                order[*node] = pos as f32 - nodes.len() as f32 / 2.0;
+            }
+        }
+
+        let size = |node: &Node| {
+            let chars = node.lines.iter().map(|(_, tokens)| {
+                tokens.iter().map(|t| t.text.chars().count()).sum::<usize>()
+            });
+
+            Vec2::new(
+                chars.max().unwrap_or(0) as f32 * glyph_width + PADDING * 2.0,
+                node.lines.len() as f32 * row_height + PADDING * 2.0,
+            )
+        };
+
+        let mut y = 0.0;
+        for nodes in layers.iter() {
+            let sizes: Vec<Vec2> = nodes.iter().map(|&n| size(&self.nodes[n])).collect();
+            let width = sizes.iter().map(|s| s.x).sum::<f32>() + GAP.x * (nodes.len() - 1) as f32;
+            let height = sizes.iter().map(|s| s.y).fold(0.0, f32::max);
+
+            let mut x = -width / 2.0;
+            for (&node, size) in nodes.iter().zip(sizes) {
+                self.nodes[node].rect = Rect::from_min_size(Pos2::new(x, y), size);
+                x += size.x + GAP.x;
+            }
+
+            y += height + GAP.y;
+        }
+
+        self.laid_out = true;
+    }
+
+    fn draw_edge(&self, painter: &egui::Painter, origin: Vec2, edge: &Edge) {
+        let from = self.nodes[edge.from].rect.translate(origin);
+        let to = self.nodes[edge.to].rect.translate(origin);
+        let stroke = Stroke::new(1.5, edge.color);
+
+        let start = from.center_bottom() + Vec2::new(edge.offset, 0.0);
+        let end = to.center_top();
+
+        if to.top() > from.bottom() {
+            let bend = Vec2::new(0.0, (end.y - start.y) / 2.0);
+            painter.add(egui::epaint::CubicBezierShape::from_points_stroke(
+                [start, start + bend, end - bend, end],
+                false,
+                Color32::TRANSPARENT,
+                stroke,
+            ));
+        } else {
+            // loop back around the right side of both nodes
+            let right = from.right().max(to.right()) + GAP.x / 2.0;
+            let points = vec![
+                start,
+                Pos2::new(start.x, start.y + GAP.y / 3.0),
+                Pos2::new(right, start.y + GAP.y / 3.0),
+                Pos2::new(right, end.y - GAP.y / 3.0),
+                Pos2::new(end.x, end.y - GAP.y / 3.0),
+                end,
+            ];
+            painter.add(Shape::line(points, stroke));
+        }
+
+        // arrow head
+        painter.add(Shape::convex_polygon(
+            vec![end, end + Vec2::new(-4.0, -8.0), end + Vec2::new(4.0, -8.0)],
+            edge.color,
+            Stroke::NONE,
+        ));
+    }
+}
+
+impl Display for Graph {
+    fn show(&mut self, ui: &mut egui::Ui) {
+        if self.cfg.is_none() {
+            ui.label("No function to show.");
+            return;
+        }
+
+        let row_height = ui.fonts(|f| f.row_height(&FONT));
+        if !self.laid_out {
+            let glyph_width = ui.fonts(|f| f.glyph_width(&FONT, 'x'));
+            self.layout(glyph_width, row_height);
+        }
+
+        let (response, painter) = ui.allocate_painter(ui.available_size(), Sense::click_and_drag());
+
+        self.pan += response.drag_delta();
+        if response.hovered() {
+            self.pan += ui.input(|i| i.smooth_scroll_delta);
+        }
+
+        let origin = response.rect.center_top().to_vec2() + Vec2::new(0.0, GAP.y) + self.pan;
+
+        for edge in self.edges.iter() {
+            self.draw_edge(&painter, origin, edge);
+        }
+
+        for node in self.nodes.iter() {
+            let rect = node.rect.translate(origin);
+            if !painter.clip_rect().intersects(rect) {
+                continue;
+            }
+
+            let is_selected = node.lines.iter().any(|(addr, _)| *addr == self.selected);
+            let stroke = if is_selected {
+                Stroke::new(2.0, colors::WHITE)
+            } else {
+                Stroke::new(1.0, colors::GRAY60)
+            };
+
+            painter.rect(rect, 2.0, CONFIG.colors.bg_secondary, stroke);
+
+            for (idx, (_, tokens)) in node.lines.iter().enumerate() {
+                let pos = rect.min + Vec2::new(PADDING, PADDING + idx as f32 * row_height);
+                let galley = ui.fonts(|f| f.layout_job(tokens_to_layoutjob(tokens.clone())));
+                painter.galley(pos, galley, colors::WHITE);
+            }
+        }
+
+        if response.clicked() {
+            if let Some(pos) = response.interact_pointer_pos() {
+                let pos = pos - origin;
+                if let Some(node) = self.nodes.iter().find(|n| n.rect.contains(pos)) {
+                    let row = ((pos.y - node.rect.top() - PADDING) / row_height).max(0.0) as usize;
+                    if let Some((addr, _)) = node.lines.get(row).or(node.lines.last()) {
+                        self.selected = *addr;
+                        self.ui_queue.push(UIEvent::SyncAddr(*addr));
+                    }
+                }
+            }
+        }
+    }
+}
@@ -5,19 +5,26 @@ use egui::mutex::RwLock;
 use egui::Color32;
 use infinite_scroll::{Callback, InfiniteScroll};
 use processor::{Block, BlockContent, Processor};
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use tokenizing::{colors, Token, TokenStream};
 
 pub struct Listing {
-    processor: Arc<Processor>,
+    /// Shared with the threads loading blocks, so it can be replaced by an edited version.
+    processor: Arc<RwLock<Arc<Processor>>>,
     #[allow(dead_code)]
     ui_queue: Arc<UiQueue>,
     boundaries: Arc<RwLock<Vec<usize>>>,
+    /// Whether the boundaries were computed again after the processor was replaced.
+    boundaries_changed: Arc<AtomicBool>,
     scroll: InfiniteScroll<Block, usize>,
     reset_position: Arc<AtomicUsize>,
     current_addr: usize,
+    /// Address of the selected line.
+    cursor: usize,
     jump_list: Vec<usize>,
+    /// Whether the file offset of each line is shown next to its address.
+    show_offsets: bool,
 }
 
 impl Listing {
@@ -28,13 +35,19 @@ impl Listing {
             // Compute boundaries on a separate thread to prevent GUI from blocking.
             let processor = Arc::clone(&processor);
             let boundaries = Arc::clone(&boundaries);
+            let (locked, is_locked) = std::sync::mpsc::channel();
             std::thread::spawn(move || {
                 let mut locked_boundaries = boundaries.write();
+                let _ = locked.send(());
                 *locked_boundaries = processor.compute_block_boundaries();
             });
+
+            // jumps made before the boundaries are computed wait for them
+            let _ = is_locked.recv();
         };
 
         let reset_position = Arc::new(AtomicUsize::new(0));
+        let processor = Arc::new(RwLock::new(processor));
 
         let start_loader = {
             let reset_position = Arc::clone(&reset_position);
@@ -48,6 +61,7 @@ impl Listing {
                 let block_idx = cursor.unwrap_or_else(|| reset_position.load(Ordering::SeqCst));
 
                 std::thread::spawn(move || {
+                    let processor = Arc::clone(&processor.read());
                     let boundaries = boundaries.read();
                     let mut all_blocks = Vec::new();
 
@@ -96,6 +110,7 @@ impl Listing {
                 let block_idx = cursor.unwrap_or_else(|| reset_position.load(Ordering::SeqCst));
 
                 std::thread::spawn(move || {
+                    let processor = Arc::clone(&processor.read());
                     let boundaries = boundaries.read();
                     let mut all_blocks = Vec::new();
 
@@ -127,28 +142,73 @@ impl Listing {
         };
 
         let scroll = InfiniteScroll::new().start_loader(start_loader).end_loader(end_loader);
-        let current_addr = processor.sections().next().unwrap().start;
+        let current_addr = processor.read().sections().next().unwrap().start;
 
         Self {
             scroll,
             ui_queue,
             boundaries,
+            boundaries_changed: Arc::default(),
             processor,
             reset_position,
             current_addr,
+            cursor: current_addr,
             jump_list: Vec::new(),
+            show_offsets: false,
+        }
+    }
+
+    /// Index of the boundary of the block that `addr` is in.
+    fn boundary(&self, addr: usize) -> Option<usize> {
+        match self.boundaries.read().binary_search(&addr) {
+            Ok(boundary) => Some(boundary),
+            // code only has a boundary at the start of each region
+            Err(boundary) if boundary > 0 => {
+                let is_mapped = self.processor.read().section_by_addr(addr).is_some();
+                is_mapped.then_some(boundary - 1)
+            }
+            Err(..) => None,
         }
     }
 
     pub fn jump(&mut self, addr: usize) -> bool {
-        if let Ok(boundary) = self.boundaries.read().binary_search(&addr) {
-            self.jump_list.push(self.current_addr);
+        let boundary = match self.boundary(addr) {
+            Some(boundary) => boundary,
+            None => return false,
+        };
+
+        self.jump_list.push(self.current_addr);
+        self.cursor = addr;
+        self.reset_position.store(boundary, Ordering::SeqCst);
+        self.scroll.reset();
+        true
+    }
+
+    /// Show an edited version of the binary. The blocks that are shown stay until the
+    /// boundaries of the edited binary are computed, as edits can change where blocks start.
+    pub fn set_processor(&mut self, processor: Arc<Processor>) {
+        *self.processor.write() = Arc::clone(&processor);
+
+        let boundaries = Arc::clone(&self.boundaries);
+        let boundaries_changed = Arc::clone(&self.boundaries_changed);
+        std::thread::spawn(move || {
+            let computed = processor.compute_block_boundaries();
+            *boundaries.write() = computed;
+            boundaries_changed.store(true, Ordering::SeqCst);
+        });
+    }
+
+    /// Load the blocks again from where the listing is, after the boundaries changed.
+    fn reload(&mut self) {
+        if let Some(boundary) = self.boundary(self.current_addr) {
             self.reset_position.store(boundary, Ordering::SeqCst);
             self.scroll.reset();
-            return true;
         }
+    }
 
-        false
+    /// Address of the selected line, this is the last address jumped to or clicked on.
+    pub fn cursor(&self) -> usize {
+        self.cursor
     }
 
     pub fn record_input(&mut self, events: &mut Vec<egui::Event>) {
@@ -201,7 +261,54 @@ fn draw_horizontal_line(ui: &mut egui::Ui) {
     ui.painter().extend(dashed_line);
 }
 
-fn draw_instruction(ui: &mut egui::Ui, tokens: Vec<Token>, index: &Index, ui_queue: &UiQueue) {
+/// Menu for the user to rename, comment on or patch the line at `addr`, the command is
+/// finished in the terminal.
+fn edit_menu(ui: &mut egui::Ui, addr: usize, ui_queue: &UiQueue, show_offsets: &mut bool) {
+    if ui.button("Rename").clicked() {
+        ui_queue.push(UIEvent::PromptCommand(format!("rename {addr:#x} ")));
+        ui.close_menu();
+    }
+
+    if ui.button("Comment").clicked() {
+        ui_queue.push(UIEvent::PromptCommand(format!("comment {addr:#x} ")));
+        ui.close_menu();
+    }
+
+    patch_menu(ui, addr, ui_queue, show_offsets);
+}
+
+fn patch_menu(ui: &mut egui::Ui, addr: usize, ui_queue: &UiQueue, show_offsets: &mut bool) {
+    if ui.button("Patch").clicked() {
+        ui_queue.push(UIEvent::PromptCommand(format!("patch {addr:#x} ")));
+        ui.close_menu();
+    }
+
+    if ui.button("Assemble").clicked() {
+        ui_queue.push(UIEvent::PromptCommand(format!("asm {addr:#x} ")));
+        ui.close_menu();
+    }
+
+    if ui.button("Revert patch").clicked() {
+        ui_queue.push(UIEvent::PromptCommand(format!("revert {addr:#x}")));
+        ui.close_menu();
+    }
+
+    ui.separator();
+    if ui.checkbox(show_offsets, "Show file offsets").clicked() {
+        ui.close_menu();
+    }
+}
+
+/// Returns whether the address of the instruction was clicked.
+fn draw_instruction(
+    ui: &mut egui::Ui,
+    addr: usize,
+    tokens: Vec<Token>,
+    comment: Option<Token>,
+    index: &Index,
+    ui_queue: &UiQueue,
+    show_offsets: &mut bool,
+) -> bool {
     let (a, b, c) = split_instruction_by_label(tokens);
     let label = tokens_to_layoutjob(b);
     let label_text = label.text.clone();
@@ -209,7 +316,11 @@ fn draw_instruction(ui: &mut egui::Ui, tokens: Vec<Token>, index: &Index, ui_que
     ui.horizontal(|ui| {
         ui.style_mut().spacing.item_spacing.x = 0.0;
 
-        ui.label(tokens_to_layoutjob(a));
+        let selected = egui::Label::new(tokens_to_layoutjob(a)).sense(egui::Sense::click());
+        let selected = ui.add(selected);
+        selected.context_menu(|ui| edit_menu(ui, addr, ui_queue, show_offsets));
+        let selected = selected.clicked();
+
         if ui.link(label).clicked() {
             let label_without_arrows = &label_text[1..][..label_text.len() - 2];
             if let Some(addr) = index.get_func_by_name(label_without_arrows) {
@@ -217,11 +328,21 @@ fn draw_instruction(ui: &mut egui::Ui, tokens: Vec<Token>, index: &Index, ui_que
             }
         }
         ui.label(tokens_to_layoutjob(c));
-    });
+        if let Some(comment) = comment {
+            ui.label(tokens_to_layoutjob(vec![comment]));
+        }
+        selected
+    })
+    .inner
 }
 
 impl Display for Listing {
     fn show(&mut self, ui: &mut egui::Ui) {
+        if self.boundaries_changed.swap(false, Ordering::SeqCst) {
+            self.reload();
+        }
+
+        let processor = Arc::clone(&self.processor.read());
         let area = egui::ScrollArea::vertical()
             .drag_to_scroll(false)
             .scroll_bar_visibility(egui::scroll_area::ScrollBarVisibility::AlwaysHidden)
@@ -233,6 +354,7 @@ impl Display for Listing {
             ui.set_width(ui.available_width());
 
             let mut idx = 0;
+            let file_map = self.show_offsets.then_some(&processor.index.file_map);
             self.scroll.ui(ui, 10, |ui, _, block| {
                 if idx == 0 {
                     self.current_addr = block.addr;
@@ -243,19 +365,41 @@ impl Display for Listing {
                 }
 
                 let mut stream = TokenStream::new();
-                block.tokenize(&mut stream);
+                block.tokenize(&mut stream, file_map);
 
                 match block.content {
-                    BlockContent::Instruction { .. } => {
-                        draw_instruction(ui, stream.inner, &self.processor.index, &self.ui_queue);
+                    BlockContent::Instruction { ref comment, .. } => {
+                        // the comment is drawn apart so it's never mistaken for a label
+                        let comment = comment.as_ref().and_then(|_| stream.inner.pop());
+                        let clicked = draw_instruction(
+                            ui,
+                            block.addr,
+                            stream.inner,
+                            comment,
+                            &processor.index,
+                            &self.ui_queue,
+                            &mut self.show_offsets,
+                        );
+
+                        if clicked {
+                            self.cursor = block.addr;
+                        }
                     }
                     BlockContent::Label { .. } => {
-                        if ui.link(tokens_to_layoutjob(stream.inner)).clicked() {
+                        let label = ui.link(tokens_to_layoutjob(stream.inner));
+                        label.context_menu(|ui| {
+                            edit_menu(ui, block.addr, &self.ui_queue, &mut self.show_offsets)
+                        });
+                        if label.clicked() {
                             self.ui_queue.push(UIEvent::GotoAddr(block.addr));
                         }
                     }
                     _ => {
-                        ui.label(tokens_to_layoutjob(stream.inner));
+                        let line = egui::Label::new(tokens_to_layoutjob(stream.inner));
+                        let line = ui.add(line.sense(egui::Sense::click()));
+                        line.context_menu(|ui| {
+                            patch_menu(ui, block.addr, &self.ui_queue, &mut self.show_offsets)
+                        });
                     }
                 }
 
@@ -269,7 +413,7 @@ impl Display for Listing {
         });
 
         // Overlay current section.
-        let text = self.processor.section_name(self.current_addr).unwrap();
+        let text = processor.section_name(self.current_addr).unwrap();
         let max_width = ui.available_width();
         let size = egui::vec2(9.0 * text.len() as f32, 25.0);
         let offset = egui::pos2(8.0, start_y + 6.0);
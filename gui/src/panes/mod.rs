@@ -1,28 +1,44 @@
 mod functions;
+mod graph;
 mod listing;
 mod source_code;
+mod search;
+mod xrefs;
 
 use crate::style::{EGUI, STYLE};
 use crate::widgets::{Donut, Terminal};
 use crate::{common::*, WinitQueue};
 use config::CONFIG;
-use egui_tiles::{Container, SimplificationOptions, Tile, TileId, Tiles, Tree, UiResponse};
-use processor::Processor;
+use egui_tiles::{
+    Container, LinearDir, SimplificationOptions, Tile, TileId, Tiles, Tree, UiResponse,
+};
+use processor::{project, Processor, Project};
 use tokenizing::colors;
 
 use std::collections::BTreeMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 
+pub use xrefs::xref_tokens;
+
 pub type Identifier = &'static str;
 
 pub const SOURCE: Identifier = crate::icon!(EMBED2, " Source");
 pub const DISASSEMBLY: Identifier = crate::icon!(PARAGRAPH_LEFT, " Disassembly");
 pub const FUNCTIONS: Identifier = crate::icon!(LIGATURE, " Functions");
+pub const GRAPH: Identifier = crate::icon!(TREE, " Graph");
+pub const XREFS: Identifier = crate::icon!(LINK, " Xrefs");
+pub const SEARCH: Identifier = crate::icon!(SEARCH, " Search");
 pub const LOGGING: Identifier = crate::icon!(TERMINAL, " Logs");
 
+const PANES: [Identifier; 7] = [SOURCE, DISASSEMBLY, FUNCTIONS, GRAPH, XREFS, SEARCH, LOGGING];
+
 enum PanelKind {
     Disassembly(listing::Listing),
     Functions(functions::Functions),
+    Graph(graph::Graph),
+    Xrefs(xrefs::Xrefs),
+    Search(search::Search),
     Source(source_code::Source),
     Logging,
 }
@@ -108,6 +124,9 @@ impl egui_tiles::Behavior<Identifier> for Tabs {
             match self.mapping.get_mut(pane) {
                 Some(PanelKind::Disassembly(disassembly)) => disassembly.show(ui),
                 Some(PanelKind::Functions(functions)) => functions.show(ui),
+                Some(PanelKind::Graph(graph)) => graph.show(ui),
+                Some(PanelKind::Xrefs(xrefs)) => xrefs.show(ui),
+                Some(PanelKind::Search(search)) => search.show(ui),
                 Some(PanelKind::Source(src)) => src.show(ui),
                 Some(PanelKind::Logging) => {
                     let area = egui::ScrollArea::vertical()
@@ -143,6 +162,9 @@ impl Panels {
         let tabs = vec![
             tiles.insert_pane(DISASSEMBLY),
             tiles.insert_pane(FUNCTIONS),
+            tiles.insert_pane(GRAPH),
+            tiles.insert_pane(XREFS),
+            tiles.insert_pane(SEARCH),
             tiles.insert_pane(LOGGING),
         ];
         let root: TileId = tiles.insert_tab_tile(tabs);
@@ -164,6 +186,27 @@ impl Panels {
         })
     }
 
+    pub fn graph(&mut self) -> Option<&mut graph::Graph> {
+        self.panes.mapping.get_mut(GRAPH).and_then(|kind| match kind {
+            PanelKind::Graph(graph) => Some(graph),
+            _ => None,
+        })
+    }
+
+    pub fn xrefs(&mut self) -> Option<&mut xrefs::Xrefs> {
+        self.panes.mapping.get_mut(XREFS).and_then(|kind| match kind {
+            PanelKind::Xrefs(xrefs) => Some(xrefs),
+            _ => None,
+        })
+    }
+
+    pub fn search(&mut self) -> Option<&mut search::Search> {
+        self.panes.mapping.get_mut(SEARCH).and_then(|kind| match kind {
+            PanelKind::Search(search) => Some(search),
+            _ => None,
+        })
+    }
+
     #[inline]
     pub fn processor(&mut self) -> Option<&Arc<Processor>> {
         self.panes.processor.as_ref()
@@ -220,6 +263,52 @@ impl Panels {
             )),
         );
 
+        self.panes.mapping.insert(
+            GRAPH,
+            PanelKind::Graph(graph::Graph::new(processor.clone(), self.ui_queue.clone())),
+        );
+
+        self.panes.mapping.insert(
+            XREFS,
+            PanelKind::Xrefs(xrefs::Xrefs::new(processor.clone(), self.ui_queue.clone())),
+        );
+
+        self.panes.mapping.insert(
+            SEARCH,
+            PanelKind::Search(search::Search::new(processor.clone(), self.ui_queue.clone())),
+        );
+
+        self.panes.processor = Some(processor);
+    }
+
+    /// Make changes to the loaded binary. The changes are made to a copy, as the panes and
+    /// anything running in the background (e.g. a search) might still be using it.
+    pub fn edit_processor(&mut self, edit: impl FnOnce(&mut Processor)) {
+        let mut processor = match self.panes.processor {
+            Some(ref processor) => Processor::clone(processor),
+            None => return,
+        };
+
+        edit(&mut processor);
+        self.set_processor(processor);
+    }
+
+    /// Replace the loaded binary with another version of it, such as an edited or analyzed
+    /// one. The panes show the new version without losing where they are.
+    pub fn set_processor(&mut self, processor: Processor) {
+        let processor = Arc::new(processor);
+
+        for pane in self.panes.mapping.values_mut() {
+            match pane {
+                PanelKind::Disassembly(listing) => listing.set_processor(processor.clone()),
+                PanelKind::Functions(functions) => functions.set_processor(processor.clone()),
+                PanelKind::Graph(graph) => graph.set_processor(processor.clone()),
+                PanelKind::Xrefs(xrefs) => xrefs.set_processor(processor.clone()),
+                PanelKind::Search(search) => search.set_processor(processor.clone()),
+                PanelKind::Source(_) | PanelKind::Logging => {}
+            }
+        }
+
         self.panes.processor = Some(processor);
     }
 
@@ -229,13 +318,50 @@ impl Panels {
         }
     }
 
+    pub fn ask_for_project(&self) {
+        let dialog = rfd::FileDialog::new().add_filter("Project", &[project::EXTENSION]);
+        if let Some(path) = dialog.pick_file() {
+            self.ui_queue.push(crate::UIEvent::BinaryRequested(path));
+        }
+    }
+
+    /// Save the project of the loaded binary to `path`, by default next to the binary.
+    pub fn save_project(&mut self, path: Option<PathBuf>) {
+        let processor = match self.processor() {
+            Some(processor) => Arc::clone(processor),
+            None => {
+                crate::tprint!(self.terminal(), "No targets loaded.");
+                return;
+            }
+        };
+
+        let path = path.unwrap_or_else(|| Project::path_for(&processor.path));
+        match processor.save_project(&path, self.layout()) {
+            Ok(()) => crate::tprint!(self.terminal(), "Saved project to {}.", path.display()),
+            Err(err) => crate::tprint!(self.terminal(), "Failed to save project: {err}."),
+        }
+    }
+
+    /// Layout of the tiles, as stored in a project.
+    fn layout(&self) -> Option<project::Tile> {
+        self.tree.root.and_then(|root| tile_layout(&self.tree.tiles, root))
+    }
+
+    /// Replace the tiles with a layout stored in a project.
+    pub fn set_layout(&mut self, layout: &project::Tile) {
+        let mut tiles = Tiles::default();
+        if let Some(root) = insert_layout(&mut tiles, layout) {
+            self.tree = Tree::new("tree", root, tiles);
+        }
+    }
+
     pub fn handle_events(&mut self, events: &mut Vec<egui::Event>) {
         if let Some(listing) = self.listing() {
             listing.record_input(events);
         }
 
         let empty_index = debugvault::Index::default();
-        let index = self.panes.processor.as_ref().map(|proc| &proc.index).unwrap_or(&empty_index);
+        let index = self.panes.processor.as_ref().map(|proc| &*proc.index).unwrap_or(&empty_index);
         self.panes.terminal.record_input(events, index);
     }
 
@@ -314,6 +440,16 @@ impl Panels {
                     ui.close_menu();
                 }
 
+                if ui.button(crate::icon!(FOLDER_OPEN, " Open project")).clicked() {
+                    self.ask_for_project();
+                    ui.close_menu();
+                }
+
+                if ui.button(crate::icon!(FLOPPY_DISK, " Save project")).clicked() {
+                    self.save_project(None);
+                    ui.close_menu();
+                }
+
                 if ui.button(crate::icon!(CROSS, " Exit")).clicked() {
                     self.winit_queue.push(crate::WinitEvent::CloseRequest);
                     ui.close_menu();
@@ -331,6 +467,21 @@ impl Panels {
                     ui.close_menu();
                 }
 
+                if ui.button(GRAPH).clicked() {
+                    self.goto_window(GRAPH);
+                    ui.close_menu();
+                }
+
+                if ui.button(XREFS).clicked() {
+                    self.goto_window(XREFS);
+                    ui.close_menu();
+                }
+
+                if ui.button(SEARCH).clicked() {
+                    self.goto_window(SEARCH);
+                    ui.close_menu();
+                }
+
                 if ui.button(SOURCE).clicked() {
                     self.goto_window(SOURCE);
                     ui.close_menu();
@@ -368,6 +519,10 @@ impl Panels {
             self.ask_for_binary();
         }
 
+        if ctx.input_mut(|i| i.consume_key(modifier, egui::Key::S)) {
+            self.save_project(None);
+        }
+
         // alt-tab'ing between tabs
         if ctx.input_mut(|i| i.consume_key(egui::Modifiers::CTRL, egui::Key::Tab)) {
             for id in self.tree.active_tiles() {
@@ -439,6 +594,13 @@ impl Panels {
 
         ctx.set_visuals(EGUI.visuals.clone());
 
+        // list the references to whatever is selected in the listing
+        if let Some(cursor) = self.listing().map(|listing| listing.cursor()) {
+            if let Some(xrefs) = self.xrefs() {
+                xrefs.follow(cursor);
+            }
+        }
+
         let frame = egui::Frame::default().inner_margin(egui::Margin::same(0.0));
         egui::CentralPanel::default().frame(frame).show(ctx, |ui| {
             if self.loading {
@@ -461,3 +623,142 @@ impl Panels {
         });
     }
 }
+
+/// Layout of a tile and its children, as stored in a project.
+fn tile_layout(tiles: &Tiles<Identifier>, id: TileId) -> Option<project::Tile> {
+    let children = |ids: &[TileId]| -> Vec<project::Tile> {
+        ids.iter().filter_map(|&child| tile_layout(tiles, child)).collect()
+    };
+
+    let layout = match tiles.get(id)? {
+        Tile::Pane(pane) => project::Tile::Pane(pane.to_string()),
+        Tile::Container(Container::Tabs(tabs)) => project::Tile::Tabs {
+            children: children(&tabs.children),
+            active: tabs.active.and_then(|id| tabs.children.iter().position(|&c| c == id)),
+        },
+        Tile::Container(Container::Linear(linear)) => {
+            let shares = linear.children.iter().map(|&child| linear.shares[child]).collect();
+            match linear.dir {
+                LinearDir::Horizontal => project::Tile::Horizontal {
+                    children: children(&linear.children),
+                    shares,
+                },
+                LinearDir::Vertical => project::Tile::Vertical {
+                    children: children(&linear.children),
+                    shares,
+                },
+            }
+        }
+        Tile::Container(Container::Grid(grid)) => {
+            let ids: Vec<TileId> = grid.children().copied().collect();
+            project::Tile::Grid {
+                children: children(&ids),
+            }
+        }
+    };
+
+    Some(layout)
+}
+
+/// Insert the tiles of a layout stored in a project, returning the outermost tile.
+fn insert_layout(tiles: &mut Tiles<Identifier>, layout: &project::Tile) -> Option<TileId> {
+    let id = match layout {
+        // panes that no longer exist are left out
+        project::Tile::Pane(title) => {
+            let pane = PANES.into_iter().find(|pane| pane == title)?;
+            tiles.insert_pane(pane)
+        }
+        project::Tile::Tabs { children, active } => {
+            // the active tab is found before children that are left out shift the indices
+            let mut ids = Vec::new();
+            let mut active_id = None;
+            for (idx, child) in children.iter().enumerate() {
+                if let Some(id) = insert_layout(tiles, child) {
+                    if *active == Some(idx) {
+                        active_id = Some(id);
+                    }
+                    ids.push(id);
+                }
+            }
+
+            let mut tabs = egui_tiles::Tabs::new(ids);
+            if let Some(active) = active_id {
+                tabs.set_active(active);
+            }
+            tiles.insert_container(tabs)
+        }
+        project::Tile::Horizontal { children, shares }
+        | project::Tile::Vertical { children, shares } => {
+            let dir = match layout {
+                project::Tile::Horizontal { .. } => LinearDir::Horizontal,
+                _ => LinearDir::Vertical,
+            };
+
+            // children that are left out don't get a share either
+            let mut linear = egui_tiles::Linear::new(dir, Vec::new());
+            for (child, &share) in children.iter().zip(shares) {
+                if let Some(id) = insert_layout(tiles, child) {
+                    linear.children.push(id);
+                    linear.shares.set_share(id, share);
+                }
+            }
+            tiles.insert_container(linear)
+        }
+        project::Tile::Grid { children } => {
+            let children = insert_layouts(tiles, children);
+            tiles.insert_grid_tile(children)
+        }
+    };
+
+    Some(id)
+}
+
+fn insert_layouts(tiles: &mut Tiles<Identifier>, layouts: &[project::Tile]) -> Vec<TileId> {
+    layouts.iter().filter_map(|layout| insert_layout(tiles, layout)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout_round_trip() {
+        let pane = |title: Identifier| project::Tile::Pane(title.to_string());
+        let layout = project::Tile::Horizontal {
+            children: vec![
+                project::Tile::Tabs {
+                    children: vec![pane(DISASSEMBLY), pane("Removed"), pane(FUNCTIONS)],
+                    active: Some(2),
+                },
+                pane("Removed"),
+                project::Tile::Vertical {
+                    children: vec![pane(SOURCE), pane(LOGGING)],
+                    shares: vec![2.0, 1.0],
+                },
+            ],
+            shares: vec![3.0, 1.0, 2.0],
+        };
+
+        let mut tiles = Tiles::default();
+        let root = insert_layout(&mut tiles, &layout).unwrap();
+
+        // the panes that no longer exist and their shares are left out, and the same tab is
+        // still active
+        assert_eq!(
+            tile_layout(&tiles, root),
+            Some(project::Tile::Horizontal {
+                children: vec![
+                    project::Tile::Tabs {
+                        children: vec![pane(DISASSEMBLY), pane(FUNCTIONS)],
+                        active: Some(1),
+                    },
+                    project::Tile::Vertical {
+                        children: vec![pane(SOURCE), pane(LOGGING)],
+                        shares: vec![2.0, 1.0],
+                    },
+                ],
+                shares: vec![3.0, 2.0],
+            })
+        );
+    }
+}
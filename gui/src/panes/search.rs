@@ -0,0 +1,169 @@
+use crate::common::*;
+use crate::{UiQueue, UIEvent};
+use commands::search::Pattern;
+use config::CONFIG;
+use processor::{Processor, MAX_MATCHES};
+use std::sync::mpsc;
+use std::sync::Arc;
+use tokenizing::{colors, Token};
+
+/// Number of bytes shown of each match.
+const PREVIEW_LEN: usize = 16;
+
+pub struct Search {
+    processor: Arc<Processor>,
+    ui_queue: Arc<UiQueue>,
+    /// Pattern that was searched for last.
+    pattern: Option<Pattern>,
+    /// Search running in the background.
+    pending: Option<mpsc::Receiver<Vec<usize>>>,
+    hits: Vec<usize>,
+}
+
+impl Search {
+    pub fn new(processor: Arc<Processor>, ui_queue: Arc<UiQueue>) -> Self {
+        Self {
+            processor,
+            ui_queue,
+            pattern: None,
+            pending: None,
+            hits: Vec::new(),
+        }
+    }
+
+    /// Search the binary for `pattern` in the background, replacing any previous search.
+    pub fn find(&mut self, pattern: Pattern) {
+        let (sender, receiver) = mpsc::channel();
+        let processor = self.processor.clone();
+        let search = pattern.clone();
+        std::thread::spawn(move || {
+            let _ = sender.send(processor.find(&search));
+        });
+
+        self.pattern = Some(pattern);
+        self.pending = Some(receiver);
+        self.hits.clear();
+    }
+
+    /// Show the matches in an edited version of the binary. A search that's still running
+    /// finishes searching the binary as it was when the search started.
+    pub fn set_processor(&mut self, processor: Arc<Processor>) {
+        self.processor = processor;
+    }
+
+    /// Take the matches of the background search, if it finished.
+    fn poll(&mut self) {
+        let hits = match self.pending.as_ref().map(mpsc::Receiver::try_recv) {
+            Some(Ok(hits)) => hits,
+            Some(Err(mpsc::TryRecvError::Empty)) | None => return,
+            Some(Err(mpsc::TryRecvError::Disconnected)) => Vec::new(),
+        };
+
+        log::complex!(
+            w "[search::poll] found ",
+            g hits.len().to_string(),
+            w " matches."
+        );
+
+        self.pending = None;
+        self.hits = hits;
+    }
+}
+
+/// Describe a match by its address, the section and symbol it's in and the bytes it starts
+/// with.
+fn hit_tokens(processor: &Processor, addr: usize) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    tokens.push(Token::from_string(format!("{addr:0>10X}"), CONFIG.colors.address));
+    tokens.push(Token::from_str(" | ", colors::WHITE));
+
+    let section = processor.section_by_addr(addr);
+    match section {
+        Some(section) => {
+            tokens.push(Token::from_string(section.name.clone(), CONFIG.colors.asm.section))
+        }
+        None => tokens.push(Token::from_str("?", colors::GRAY60)),
+    }
+
+    tokens.push(Token::from_str(" | ", colors::WHITE));
+
+    // symbols of other sections don't say anything about where the match is
+    let sym = processor.index.get_func_containing(addr);
+    match sym.filter(|sym| section.is_some_and(|section| sym.addr >= section.start)) {
+        Some(sym) => {
+            tokens.extend(sym.item.name().iter().cloned());
+            let offset = addr - sym.addr;
+            if offset != 0 {
+                tokens.push(Token::from_string(
+                    format!("+{offset:#x}"),
+                    CONFIG.colors.asm.immediate,
+                ));
+            }
+        }
+        None => tokens.push(Token::from_str("?", colors::GRAY60)),
+    }
+
+    if let Some(section) = section {
+        let bytes = section.bytes_by_addr(addr, PREVIEW_LEN);
+        let bytes: Vec<String> = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+        tokens.push(Token::from_str(" | ", colors::WHITE));
+        tokens.push(Token::from_string(bytes.join(" "), CONFIG.colors.comment));
+    }
+
+    tokens
+}
+
+impl Display for Search {
+    fn show(&mut self, ui: &mut egui::Ui) {
+        self.poll();
+
+        ui.horizontal(|ui| {
+            if ui.button("New search").clicked() {
+                self.ui_queue.push(UIEvent::PromptCommand("find ".to_string()));
+            }
+
+            if let Some(ref pattern) = self.pattern {
+                let title = match self.pending {
+                    Some(_) => format!("Searching for {pattern}"),
+                    None if self.hits.len() >= MAX_MATCHES => {
+                        format!("First {} matches of {pattern}", self.hits.len())
+                    }
+                    None => format!("{} matches of {pattern}", self.hits.len()),
+                };
+
+                ui.label(egui::RichText::new(title).font(FONT).color(colors::WHITE));
+            }
+        });
+
+        ui.separator();
+
+        if self.pending.is_some() {
+            log::PROGRESS.show(ui);
+            ui.ctx().request_repaint();
+            return;
+        }
+
+        if self.pattern.is_none() {
+            ui.label("Use `find <pattern>` to search the binary.");
+            return;
+        }
+
+        if self.hits.is_empty() {
+            ui.label(egui::RichText::new("No matches.").font(FONT).color(colors::GRAY60));
+            return;
+        }
+
+        let area = egui::ScrollArea::both().auto_shrink([false, false]).drag_to_scroll(false);
+
+        // matches are only described once they're visible, as there can be many of them
+        area.show_rows(ui, FONT.size, self.hits.len(), |ui, row_range| {
+            for &addr in self.hits[row_range].iter() {
+                let output = tokens_to_layoutjob(hit_tokens(&self.processor, addr));
+
+                if ui.link(output).clicked() {
+                    self.ui_queue.push(UIEvent::GotoAddr(addr));
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,135 @@
+use crate::common::*;
+use crate::{UiQueue, UIEvent};
+use config::CONFIG;
+use processor::{Processor, Xref};
+use std::sync::Arc;
+use tokenizing::{colors, Token};
+
+pub struct Xrefs {
+    processor: Arc<Processor>,
+    ui_queue: Arc<UiQueue>,
+    /// Address the references are listed for.
+    addr: Option<usize>,
+    /// Last address selected in the listing.
+    cursor: Option<usize>,
+    lines: Vec<(usize, Vec<Token>)>,
+}
+
+impl Xrefs {
+    pub fn new(processor: Arc<Processor>, ui_queue: Arc<UiQueue>) -> Self {
+        Self {
+            processor,
+            ui_queue,
+            addr: None,
+            cursor: None,
+            lines: Vec::new(),
+        }
+    }
+
+    /// List the references to `addr`.
+    pub fn set_addr(&mut self, addr: usize) {
+        if self.addr == Some(addr) {
+            return;
+        }
+
+        self.addr = Some(addr);
+        self.lines = self
+            .processor
+            .xrefs_to(addr)
+            .iter()
+            .map(|xref| (xref.from, xref_tokens(&self.processor, xref)))
+            .collect();
+    }
+
+    /// List the references of an edited version of the binary, to the same address.
+    pub fn set_processor(&mut self, processor: Arc<Processor>) {
+        self.processor = processor;
+        if let Some(addr) = self.addr.take() {
+            self.set_addr(addr);
+        }
+    }
+
+    /// Follow the cursor of the listing, only switching once the cursor moves so that an
+    /// address set through [`Self::set_addr`] stays.
+    pub fn follow(&mut self, cursor: usize) {
+        if self.cursor != Some(cursor) {
+            self.cursor = Some(cursor);
+            self.set_addr(cursor);
+        }
+    }
+}
+
+/// Describe a reference by its address, the function or section it's in, the instruction
+/// making the reference and the kind of reference.
+pub fn xref_tokens(processor: &Processor, xref: &Xref) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    tokens.push(Token::from_string(format!("{:0>10X}", xref.from), CONFIG.colors.address));
+    tokens.push(Token::from_str(" | ", colors::WHITE));
+
+    match processor.instruction_by_addr(xref.from) {
+        Some(inst) => {
+            match processor.index.get_func_containing(xref.from) {
+                Some(func) => {
+                    tokens.extend(func.item.name().iter().cloned());
+                    let offset = xref.from - func.addr;
+                    if offset != 0 {
+                        tokens.push(Token::from_string(
+                            format!("+{offset:#x}"),
+                            CONFIG.colors.asm.immediate,
+                        ));
+                    }
+                }
+                None => tokens.push(Token::from_str("?", colors::GRAY60)),
+            }
+
+            tokens.push(Token::from_str(" | ", colors::WHITE));
+            tokens.extend(processor.instruction_tokens(&inst, &processor.index));
+        }
+        // pointers aren't part of a function
+        None => {
+            let section = processor.section_name(xref.from).unwrap_or("?");
+            tokens.push(Token::from_string(section.to_string(), CONFIG.colors.asm.section));
+        }
+    }
+
+    tokens.push(Token::from_str(" | ", colors::WHITE));
+    tokens.push(Token::from_str(xref.kind.as_str(), CONFIG.colors.comment));
+    tokens
+}
+
+impl Display for Xrefs {
+    fn show(&mut self, ui: &mut egui::Ui) {
+        let addr = match self.addr {
+            Some(addr) => addr,
+            None => {
+                ui.label("Select an address in the listing or use `xref <expr>`.");
+                return;
+            }
+        };
+
+        let title = match self.processor.index.get_sym_by_addr(addr) {
+            Some(sym) => format!("References to {} ({addr:#X})", sym.as_str()),
+            None => format!("References to {addr:#X}"),
+        };
+
+        ui.label(egui::RichText::new(title).font(FONT).color(colors::WHITE));
+        ui.separator();
+
+        if self.lines.is_empty() {
+            ui.label(egui::RichText::new("No references.").font(FONT).color(colors::GRAY60));
+            return;
+        }
+
+        let area = egui::ScrollArea::both().auto_shrink([false, false]).drag_to_scroll(false);
+
+        area.show_rows(ui, FONT.size, self.lines.len(), |ui, row_range| {
+            for (addr, line) in self.lines[row_range].iter() {
+                let output = tokens_to_layoutjob(line.clone());
+
+                if ui.link(output).clicked() {
+                    self.ui_queue.push(UIEvent::GotoAddr(*addr));
+                }
+            }
+        });
+    }
+}
@@ -63,6 +63,8 @@ mod macos {
                     true,
                     Some(Accelerator::new(Some(Modifiers::SUPER), Code::KeyO)),
                 ),
+                &MenuItem::with_id("open_project", "Open Project...", true, None),
+                &MenuItem::with_id("save_project", "Save Project", true, None),
                 &PredefinedMenuItem::quit(None),
             ])?;
 
@@ -90,6 +92,27 @@ mod macos {
                 false,
                 None,
             ));
+            windows.push(CheckMenuItem::with_id(
+                panes::GRAPH,
+                "Graph",
+                true,
+                false,
+                None,
+            ));
+            windows.push(CheckMenuItem::with_id(
+                panes::XREFS,
+                "Xrefs",
+                true,
+                false,
+                None,
+            ));
+            windows.push(CheckMenuItem::with_id(
+                panes::SEARCH,
+                "Search",
+                true,
+                false,
+                None,
+            ));
             windows.push(CheckMenuItem::with_id(
                 panes::SOURCE,
                 "Source",
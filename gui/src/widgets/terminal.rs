@@ -160,6 +160,15 @@ impl Terminal {
         self.clear_line();
     }
 
+    /// Replace the line being typed with `cmd`, leaving the cursor at the end.
+    pub fn set_command(&mut self, cmd: &str) {
+        self.command_position = self.commands.len() - 1;
+        self.clear_line();
+        self.commands[self.command_position].push_str(cmd);
+        self.move_to_end();
+        self.reset_cursor = true;
+    }
+
     /// Search through newer commands, finding one that isn't empty.
     fn scroll_to_next_cmd(&mut self) {
         while self.command_position != self.commands.len() - 1 {
@@ -0,0 +1,13 @@
+.text
+.globl f
+f:
+    ldr x0, . + 0x10
+    ret
+    .p2align 3
+    .word 0x12345678
+    .word 0x01020304
+    .quad f
+    .quad 0x41414141
+.globl g
+g:
+    ret
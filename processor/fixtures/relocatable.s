@@ -0,0 +1,17 @@
+.text
+.globl f
+.type f, @function
+f:
+    .cfi_startproc
+    lea table(%rip), %rax
+    ret
+    .cfi_endproc
+table:
+    .quad 0xffffffffffffffff
+    .quad 0x0706050403020100
+# only known to be code through its unwind info
+g:
+    .cfi_startproc
+    mov $1, %eax
+    ret
+    .cfi_endproc
@@ -0,0 +1,123 @@
+//! Analysis of lazily decoded binaries after they're shown.
+//!
+//! Lazily decoded binaries are parsed without loading their debug info or analyzing their
+//! code, so they can be shown right away. Both happen afterwards on a copy of the processor,
+//! one code section at a time, so the results show up as they come in.
+
+use crate::{lazy, recursion, signatures, xrefs, Decoding, Processor, Xref, XrefKind, Xrefs};
+use object::read::File as ObjectFile;
+use processor_shared::{AddressMap, PhysAddr, Section, SectionKind};
+use std::sync::Arc;
+
+impl Processor {
+    /// Demangle the symbols and load the debug info of a lazily decoded binary.
+    pub fn load_debug_info(&mut self) {
+        // eagerly decoded binaries loaded their debug info while being parsed
+        if self.decoding == Decoding::Eager {
+            return;
+        }
+
+        let mmap = Arc::clone(&self._mmap);
+        let obj = match ObjectFile::parse(&mmap[..]) {
+            Ok(obj) => obj,
+            Err(..) => return,
+        };
+
+        Arc::make_mut(&mut self.index).load_debug_info(&obj, &self.path);
+    }
+
+    /// Analyze the code of a lazily decoded binary, reusing what was parsed. `step` is given
+    /// a copy of the processor once the debug info is loaded and after each code section is
+    /// analyzed, the last copy is fully analyzed.
+    ///
+    /// Sections are analyzed in order of their address. Control flow leading into later
+    /// sections is followed once those are analyzed, whereas control flow leading back into
+    /// sections that were already analyzed isn't.
+    pub fn analyze(&self, mut step: impl FnMut(Processor)) {
+        let mut this = self.clone();
+        this.load_debug_info();
+        step(this.clone());
+
+        let mmap = Arc::clone(&self._mmap);
+        let obj = match ObjectFile::parse(&mmap[..]) {
+            Ok(obj) => obj,
+            Err(..) => return,
+        };
+
+        let unwind_ranges = recursion::unwind_ranges(&obj, &this.sections);
+        let layout = this.layout.as_deref();
+        let mut entries = recursion::entries(&obj, layout, this.entrypoint, &unwind_ranges);
+        let code: Vec<Section> =
+            this.sections.iter().filter(|s| s.kind == SectionKind::Code).cloned().collect();
+
+        let mut known = lazy::Starts::default();
+        let mut errors = AddressMap::clone(&this.errors);
+        let mut xrefs = xrefs::from_pointers(&this.sections, this.endianness);
+
+        for (idx, section) in code.iter().enumerate() {
+            let target = lazy::Target {
+                arch: this.arch,
+                endianness: this.endianness,
+                sections: &this.sections,
+                data_ranges: &this.data_ranges,
+                unwind_ranges: &unwind_ranges,
+            };
+
+            let sweep = this.code.sweep();
+            let analysis = sweep.analyze(&target, std::slice::from_ref(section), &entries);
+
+            // control flow into the sections that are analyzed next leads to more code
+            entries.extend(
+                analysis
+                    .xrefs
+                    .iter()
+                    .filter(|xref| xref.kind != XrefKind::Data)
+                    .map(|xref| xref.to),
+            );
+            entries.sort_unstable();
+            entries.dedup();
+
+            known.insert(section, analysis.starts.into_iter());
+            errors.extend(analysis.errors);
+            errors.sort_unstable();
+            xrefs.extend(analysis.xrefs);
+
+            let index = Arc::make_mut(&mut this.index);
+            index.insert_generated(&analysis.functions);
+
+            // like when analyzing up front, functions are named by their signatures and labels
+            // are made once all functions are known
+            let is_done = idx + 1 == code.len();
+            if is_done {
+                signatures::apply(this.arch, &this.sections, index);
+            }
+
+            this.set_analysis(known.clone(), errors.clone(), xrefs.clone(), is_done);
+            step(this.clone());
+        }
+    }
+
+    /// Replace the results of analysis, decoding code according to where instructions start in
+    /// the sections that were analyzed.
+    fn set_analysis(
+        &mut self,
+        known: lazy::Starts,
+        errors: AddressMap<decoder::Error>,
+        xrefs: Vec<Xref>,
+        is_done: bool,
+    ) {
+        let xrefs = Xrefs::new(xrefs);
+        let index = Arc::make_mut(&mut self.index);
+        if is_done {
+            index.insert_labels(&xrefs.local_targets(index));
+        }
+
+        let symbols: Vec<PhysAddr> = index.syms.iter().map(|sym| sym.addr).collect();
+        let sweep = self.code.sweep();
+        let code = lazy::Lazy::new(sweep, &self.sections, &symbols, &self.data_ranges, Some(known));
+
+        self.code = Arc::new(code);
+        self.errors = Arc::new(errors);
+        self.xrefs = Arc::new(xrefs);
+    }
+}
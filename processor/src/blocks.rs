@@ -1,11 +1,11 @@
-use crate::Processor;
+use crate::{DataType, Processor};
 use binformat::elf::{Elf32Dyn, Elf32Sym, Elf64Dyn, Elf64Sym};
 use binformat::pe::ExceptionDirectoryEntry;
 use binformat::ToData;
 use config::CONFIG;
 use debugvault::Symbol;
 use object::Endian;
-use processor_shared::{encode_hex_bytes_truncated, Section, SectionKind};
+use processor_shared::{encode_hex_bytes_truncated, FileMap, Section, SectionKind};
 use std::mem::size_of;
 use std::sync::Arc;
 use tokenizing::{colors, Token, TokenStream};
@@ -23,9 +23,15 @@ pub enum BlockContent {
     Label {
         symbol: Arc<Symbol>,
     },
+    /// The instruction set code is decoded in from here on.
+    InstructionSet {
+        name: &'static str,
+    },
     Instruction {
         inst: Vec<Token>,
         bytes: String,
+        /// Comment the user left on the instruction.
+        comment: Option<String>,
     },
     Error {
         err: decoder::ErrorKind,
@@ -50,6 +56,11 @@ pub enum BlockContent {
     Bytes {
         bytes: Vec<u8>,
     },
+    /// Integer the user gave a type.
+    Integer {
+        tipe: DataType,
+        value: u64,
+    },
 }
 
 #[derive(Debug)]
@@ -65,6 +76,7 @@ impl Block {
             BlockContent::SectionStart { .. } => 2,
             BlockContent::SectionEnd { .. } => 2,
             BlockContent::Label { .. } => 2,
+            BlockContent::InstructionSet { .. } => 1,
             BlockContent::Instruction { .. } => 1,
             BlockContent::Error { .. } => 1,
             BlockContent::CString { bytes } => bytes.len() + 1,
@@ -72,16 +84,23 @@ impl Block {
             BlockContent::Got { .. } => 1,
             BlockContent::DataStructure { fields, .. } => 2 + fields.len(),
             BlockContent::Bytes { bytes } => (bytes.len() / 32) + 1,
+            BlockContent::Integer { .. } => 1,
         }
     }
 
-    pub fn tokenize(&self, stream: &mut TokenStream) {
+    /// Tokenize the block, with a column of file offsets after the addresses if `file_map`
+    /// is given.
+    pub fn tokenize(&self, stream: &mut TokenStream, file_map: Option<&FileMap>) {
         match &self.content {
             BlockContent::Label { symbol } => {
                 stream.push("\n<", CONFIG.colors.asm.label);
                 stream.inner.extend_from_slice(symbol.name());
                 stream.push(">", CONFIG.colors.asm.label);
             }
+            BlockContent::InstructionSet { name } => {
+                stream.push(".", CONFIG.colors.asm.annotation);
+                stream.push(name, CONFIG.colors.asm.annotation);
+            }
             BlockContent::SectionStart { section } => {
                 stream.push("section started", colors::WHITE);
                 stream.push_owned(format!(" {} ", section.name), CONFIG.colors.asm.section);
@@ -95,6 +114,21 @@ impl Block {
                 stream.push_owned(format!("{:x}", section.start), colors::GREEN);
                 stream.push("-", CONFIG.colors.delimiter);
                 stream.push_owned(format!("{:x}", section.end), colors::GREEN);
+                stream.push_owned(format!(" {}", section.permissions), colors::WHITE);
+                if let Some(offset) = section.file_offset {
+                    stream.push(" offset ", CONFIG.colors.delimiter);
+                    stream.push_owned(format!("{offset:#x}"), colors::GREEN);
+                }
+                if section.alignment > 1 {
+                    stream.push(" align ", CONFIG.colors.delimiter);
+                    stream.push_owned(format!("{:#x}", section.alignment), colors::GREEN);
+                }
+                let flags = section.flag_names();
+                if !flags.is_empty() {
+                    stream.push(" [", CONFIG.colors.brackets);
+                    stream.push_owned(flags.join(" | "), CONFIG.colors.asm.component);
+                    stream.push("]", CONFIG.colors.brackets);
+                }
             }
             BlockContent::SectionEnd { section } => {
                 stream.push("section ended", colors::WHITE);
@@ -110,26 +144,33 @@ impl Block {
                 stream.push("-", CONFIG.colors.delimiter);
                 stream.push_owned(format!("{:x}", section.end), colors::GREEN);
             }
-            BlockContent::Instruction { inst, bytes } => {
-                stream.push_owned(format!("{:0>10X}  ", self.addr), CONFIG.colors.address);
+            BlockContent::Instruction {
+                inst,
+                bytes,
+                comment,
+            } => {
+                push_addr(stream, self.addr, file_map);
                 stream.push_owned(bytes.clone(), CONFIG.colors.bytes);
                 stream.inner.extend_from_slice(&inst);
+                if let Some(comment) = comment {
+                    stream.push_owned(format!("  ; {comment}"), CONFIG.colors.comment);
+                }
             }
             BlockContent::Error { err, bytes } => {
-                stream.push_owned(format!("{:0>10X}  ", self.addr), CONFIG.colors.address);
+                push_addr(stream, self.addr, file_map);
                 stream.push_owned(bytes.clone(), CONFIG.colors.bytes);
                 stream.push("<", CONFIG.colors.brackets);
                 stream.push_owned(format!("{err:?}"), CONFIG.colors.asm.invalid);
                 stream.push(">", CONFIG.colors.brackets);
             }
             BlockContent::CString { bytes } => {
-                stream.push_owned(format!("{:0>10X}  ", self.addr), CONFIG.colors.address);
+                push_addr(stream, self.addr, file_map);
                 let lossy_string = String::from_utf8_lossy(&bytes);
                 let escaped = format!("\"{}\"", lossy_string.escape_debug());
                 stream.push_owned(escaped, CONFIG.colors.asm.string);
             }
             BlockContent::Got { symbol, .. } => {
-                stream.push_owned(format!("{:0>10X}  ", self.addr), CONFIG.colors.address);
+                push_addr(stream, self.addr, file_map);
                 stream.push("<", CONFIG.colors.asm.label);
                 let name = symbol.name();
                 if name.is_empty() {
@@ -146,12 +187,12 @@ impl Block {
                 // addr  }
                 let start_addr = fields[0].0;
                 let end_addr = fields[fields.len() - 1].0;
-                stream.push_owned(format!("{:0>10X}  ", start_addr), CONFIG.colors.address);
+                push_addr(stream, start_addr, file_map);
                 stream.push("struct ", CONFIG.colors.src.keyword);
                 stream.push(ident, CONFIG.colors.src.tipe);
                 stream.push(" {\n", CONFIG.colors.delimiter);
                 for (addr, name, tipe, value) in fields {
-                    stream.push_owned(format!("{:0>10X}  ", addr), CONFIG.colors.address);
+                    push_addr(stream, *addr, file_map);
                     stream.push("    ", colors::WHITE);
                     stream.push(name, CONFIG.colors.src.field);
                     stream.push(": ", colors::WHITE);
@@ -160,11 +201,11 @@ impl Block {
                     stream.push_owned(value.clone(), CONFIG.colors.src.constant);
                     stream.push("\n", colors::WHITE);
                 }
-                stream.push_owned(format!("{:0>10X}  ", end_addr), CONFIG.colors.address);
+                push_addr(stream, end_addr, file_map);
                 stream.push("}", CONFIG.colors.delimiter);
             }
             BlockContent::Pointer { value, symbol, .. } => {
-                stream.push_owned(format!("{:0>10X}  ", self.addr), CONFIG.colors.address);
+                push_addr(stream, self.addr, file_map);
                 stream.push_owned(format!("{:#x}", value), CONFIG.colors.bytes);
                 if let Some(symbol) = symbol {
                     stream.push(" <", CONFIG.colors.asm.label);
@@ -175,7 +216,7 @@ impl Block {
             BlockContent::Bytes { bytes } => {
                 let mut off = 0;
                 for chunk in bytes.chunks(32) {
-                    stream.push_owned(format!("{:0>10X}  ", self.addr + off), CONFIG.colors.address);
+                    push_addr(stream, self.addr + off, file_map);
                     let s = processor_shared::encode_hex_bytes_truncated(chunk, usize::MAX, false);
                     stream.push_owned(s, CONFIG.colors.bytes);
                     stream.push("\n", colors::WHITE);
@@ -184,6 +225,23 @@ impl Block {
                 // Pop last newline.
                 stream.inner.pop();
             }
+            BlockContent::Integer { tipe, value } => {
+                push_addr(stream, self.addr, file_map);
+                stream.push_owned(format!("{tipe} "), CONFIG.colors.src.tipe);
+                stream.push_owned(format!("{value:#x}"), CONFIG.colors.src.constant);
+            }
+        }
+    }
+}
+
+/// Push the address column of a line, followed by the file offset column if there's a
+/// `file_map`. Addresses that aren't in the file leave the column empty.
+fn push_addr(stream: &mut TokenStream, addr: usize, file_map: Option<&FileMap>) {
+    stream.push_owned(format!("{addr:0>10X}  "), CONFIG.colors.address);
+    if let Some(file_map) = file_map {
+        match file_map.addr_to_offset(addr) {
+            Some(offset) => stream.push_owned(format!("{offset:0>8X}  "), CONFIG.colors.comment),
+            None => stream.push("          ", colors::WHITE),
         }
     }
 }
@@ -260,7 +318,14 @@ impl Processor {
         }
 
         match section.kind {
-            SectionKind::Code => self.parse_code(addr, section, &mut blocks),
+            // code only has a boundary at the start of each region
+            SectionKind::Code => {
+                let bounds = self.code.bounds(addr).unwrap_or(addr..addr);
+                let mut addr = self.code.region(addr).map_or(addr, |region| region.start);
+                while addr < bounds.end {
+                    addr = self.parse_code(addr, section, &mut blocks);
+                }
+            }
             SectionKind::Ptr32 => self.parse_pointer(addr, section, 4, &mut blocks),
             SectionKind::Ptr64 => self.parse_pointer(addr, section, 8, &mut blocks),
             SectionKind::Got32 => self.parse_got(addr, 4, section, &mut blocks),
@@ -281,15 +346,18 @@ impl Processor {
             SectionKind::Elf64Dyn => {
                 self.parse_datastructure::<Elf64Dyn>(addr, section, &mut blocks);
             }
-            // For any other section kinds just assume they're made of bytes.
-            // As a note, we calculate the byte boundaries in blocks of [`BYTES_BLOCK_SIZE`],
-            // so this block can be up to [`BYTES_BLOCK_SIZE`] bytes.
+            // For any other section kinds just assume they're made of bytes, unless the user
+            // gave them a type. As a note, we calculate the byte boundaries in blocks of
+            // [`BYTES_BLOCK_SIZE`], so this block can be up to [`BYTES_BLOCK_SIZE`] bytes.
             _ => {
-                let bytes = section.bytes_by_addr(addr, BYTES_BLOCK_SIZE).to_vec();
-                blocks.push(Block {
-                    addr,
-                    content: BlockContent::Bytes { bytes },
-                });
+                if self.parse_typed_data(addr, section, &mut blocks).is_none() {
+                    let end = self.data_bytes_end(addr, section);
+                    let bytes = section.bytes_by_addr(addr, end - addr).to_vec();
+                    blocks.push(Block {
+                        addr,
+                        content: BlockContent::Bytes { bytes },
+                    });
+                }
             }
         }
 
@@ -349,31 +417,57 @@ impl Processor {
         });
     }
 
-    fn parse_code(&self, addr: usize, section: &Section, blocks: &mut Vec<Block>) {
+    /// Parse the instruction, error or data at `addr`, returning where it ends.
+    fn parse_code(&self, addr: usize, section: &Section, blocks: &mut Vec<Block>) -> usize {
         let opt_inst = self.instruction_by_addr(addr);
         let opt_err = self.error_by_addr(addr);
 
-        if opt_inst.is_some() || opt_err.is_some() {
-            if let Some(symbol) = self.get_symbol_by_addr(addr, section) {
-                blocks.push(Block {
-                    addr,
-                    content: BlockContent::Label { symbol },
-                })
-            }
+        if let Some(symbol) = self.get_symbol_by_addr(addr, section) {
+            blocks.push(Block {
+                addr,
+                content: BlockContent::Label { symbol },
+            })
+        }
+
+        if let Some(end) = self.parse_typed_data(addr, section, blocks) {
+            return end;
         }
 
         if let Some(inst) = opt_inst {
+            if let Some(name) = self.instruction_set(&inst) {
+                let prev = self
+                    .instruction_before(addr)
+                    .filter(|(prev_addr, _)| *prev_addr >= section.start);
+
+                // mark where the instruction set switches, including at the start of a section
+                if prev.is_none_or(|(_, prev)| self.instruction_set(&prev) != Some(name)) {
+                    blocks.push(Block {
+                        addr,
+                        content: BlockContent::InstructionSet { name },
+                    });
+                }
+            }
+
             let width = self.instruction_width(&inst);
             let inst = self.instruction_tokens(&inst, &self.index);
             let bytes = section.bytes_by_addr(addr, width);
             let bytes =
                 encode_hex_bytes_truncated(&bytes, self.max_instruction_width * 3 + 1, true);
 
+            let comment = self
+                .comment(addr)
+                .or_else(|| self.relocation_in(addr..addr + width))
+                .map(str::to_string);
+
             blocks.push(Block {
                 addr,
-                content: BlockContent::Instruction { inst, bytes },
+                content: BlockContent::Instruction {
+                    inst,
+                    bytes,
+                    comment,
+                },
             });
-            return;
+            return addr + width;
         }
 
         if let Some(err) = opt_err {
@@ -388,17 +482,161 @@ impl Processor {
                     bytes,
                 },
             });
-            return;
+            return addr + err.size();
+        }
+
+        if let Some(value) = self.data_pointer(addr, section) {
+            let symbol = self.index.get_sym_by_addr(value as usize);
+            blocks.push(Block {
+                addr,
+                content: BlockContent::Pointer { value, symbol },
+            });
+            return addr + self.arch.address_size().map_or(1, |size| size.bytes() as usize);
         }
 
         // If we don't find any code, find bytes at the boundary.
-        self.parse_bytes(addr, section, blocks);
+        self.parse_bytes(addr, section, blocks)
+    }
+
+    /// Value of an aligned pointer into the binary at `addr`, if `addr` is in data that's part
+    /// of a code section.
+    fn data_pointer(&self, addr: usize, section: &Section) -> Option<u64> {
+        let range = self.data_range(addr)?;
+        let width = self.arch.address_size()?.bytes() as usize;
+        if !addr.is_multiple_of(width) || addr + width > range.end {
+            return None;
+        }
+
+        let bytes = section.bytes_by_addr(addr, width);
+        let value = match width {
+            4 => self.endianness.read_u32_bytes(bytes.try_into().ok()?) as u64,
+            _ => self.endianness.read_u64_bytes(bytes.try_into().ok()?),
+        };
+
+        self.sections()
+            .any(|section| (section.start..section.end).contains(&(value as usize)))
+            .then_some(value)
+    }
+
+    /// Where the data the user gave a type at `addr` ends, if it has a type and it fits in
+    /// the section's data. Only code and plain data can be given a type.
+    pub(crate) fn typed_data_end(&self, addr: usize, section: &Section) -> Option<usize> {
+        let tipe = self.data_type(addr)?;
+        if !matches!(
+            section.kind,
+            SectionKind::Code
+                | SectionKind::Raw
+                | SectionKind::Raw4
+                | SectionKind::Raw8
+                | SectionKind::Raw16
+        ) {
+            return None;
+        }
+
+        let bytes = section.bytes_by_addr(addr, usize::MAX);
+        let width = match tipe {
+            DataType::Pointer => self.arch.address_size()?.bytes() as usize,
+            DataType::CString => {
+                let len = bytes.iter().position(|&b| b == b'\0');
+                len.map_or(bytes.len(), |len| len + 1)
+            }
+            tipe => tipe.width()?,
+        };
+
+        (width > 0 && width <= bytes.len()).then_some(addr + width)
+    }
+
+    /// Parse the data the user gave a type at `addr`, returning where it ends.
+    fn parse_typed_data(
+        &self,
+        addr: usize,
+        section: &Section,
+        blocks: &mut Vec<Block>,
+    ) -> Option<usize> {
+        let end = self.typed_data_end(addr, section)?;
+        let bytes = section.bytes_by_addr(addr, end - addr);
+        let value = match bytes.len() {
+            1 => bytes[0] as u64,
+            2 => self.endianness.read_u16_bytes(bytes.try_into().ok()?) as u64,
+            4 => self.endianness.read_u32_bytes(bytes.try_into().ok()?) as u64,
+            8 => self.endianness.read_u64_bytes(bytes.try_into().ok()?),
+            _ => 0,
+        };
+
+        let content = match self.data_type(addr)? {
+            DataType::CString => BlockContent::CString {
+                bytes: bytes.strip_suffix(b"\0").unwrap_or(bytes).to_vec(),
+            },
+            DataType::Pointer => BlockContent::Pointer {
+                value,
+                symbol: self.index.get_sym_by_addr(value as usize),
+            },
+            tipe => BlockContent::Integer { tipe, value },
+        };
+
+        blocks.push(Block { addr, content });
+        Some(end)
     }
 
-    fn parse_bytes(&self, addr: usize, section: &Section, blocks: &mut Vec<Block>) {
+    /// Where a block of plain data at `addr` ends. That is either at the end of the data
+    /// the user gave a type, at the next data with a type or after [`BYTES_BLOCK_SIZE`] bytes.
+    fn data_bytes_end(&self, addr: usize, section: &Section) -> usize {
+        if let Some(end) = self.typed_data_end(addr, section) {
+            return end;
+        }
+
+        let end = std::cmp::min(addr + BYTES_BLOCK_SIZE, section.end);
+        self.project
+            .data_types
+            .range(addr + 1..end)
+            .map(|(&addr, _)| addr)
+            .find(|&addr| self.typed_data_end(addr, section).is_some())
+            .unwrap_or(end)
+    }
+
+    fn parse_bytes(&self, addr: usize, section: &Section, blocks: &mut Vec<Block>) -> usize {
+        let end = self.code_bytes_end(addr, section);
+        if end > addr {
+            let bytes = section.bytes_by_addr(addr, end - addr).to_vec();
+            blocks.push(Block {
+                addr,
+                content: BlockContent::Bytes { bytes },
+            });
+        }
+
+        end
+    }
+
+    /// Where a run of bytes that wasn't decoded in a code section ends. That is either
+    /// at the next instruction, the next symbol, the start or end of data, the next pointer in
+    /// data, the next data with a type, the end of a region of code or after
+    /// [`BYTES_BLOCK_SIZE`] bytes.
+    fn code_bytes_end(&self, addr: usize, section: &Section) -> usize {
+        let mut end = std::cmp::min(addr + BYTES_BLOCK_SIZE, section.end);
+        if let Some(bounds) = self.code.bounds(addr) {
+            end = std::cmp::min(end, bounds.end);
+        }
+
+        let data = self.data_range(addr);
+        if let Some(range) = data {
+            end = std::cmp::min(end, range.end);
+
+            if self.data_pointer(addr, section).is_some() {
+                return addr + self.arch.address_size().map_or(1, |size| size.bytes() as usize);
+            }
+        }
+
         let mut baddr = addr;
         loop {
-            if baddr == section.end {
+            if baddr == end {
+                break;
+            }
+
+            if baddr != addr && self.data_range(baddr) != data {
+                break;
+            }
+
+            if baddr != addr && self.data_pointer(baddr, section).is_some() {
                 break;
             }
 
@@ -410,21 +648,18 @@ impl Processor {
                 break;
             }
 
-            if self.get_symbol_by_addr(addr, section).is_some() {
+            if baddr != addr && self.get_symbol_by_addr(baddr, section).is_some() {
+                break;
+            }
+
+            if baddr != addr && self.typed_data_end(baddr, section).is_some() {
                 break;
             }
 
             baddr += 1;
         }
 
-        let bytes_len = baddr - addr;
-        if bytes_len > 0 {
-            let bytes = section.bytes_by_addr(addr, bytes_len).to_vec();
-            blocks.push(Block {
-                addr,
-                content: BlockContent::Bytes { bytes },
-            });
-        }
+        baddr
     }
 
     /// Only need to compute the start's of blocks.
@@ -464,7 +699,7 @@ impl Processor {
 
         boundaries.push(section.start);
         match section.kind {
-            SectionKind::Code => self.compute_code_boundaries(section, &mut boundaries),
+            SectionKind::Code => boundaries.extend(self.code.boundaries(section)),
             SectionKind::CString => self.compute_cstring_boundaries(section, &mut boundaries),
             SectionKind::Ptr32 | SectionKind::Got32 => {
                 let mut addr = section.start;
@@ -515,13 +750,13 @@ impl Processor {
                     addr += size_of::<Elf64Dyn>();
                 }
             }
-            // For any other section kinds just assume they evenly
-            // split in blocks of [`BYTES_BLOCK_SIZE`].
+            // For any other section kinds just assume they evenly split in blocks of
+            // [`BYTES_BLOCK_SIZE`], apart from the data the user gave a type.
             _ => {
                 let mut addr = section.start;
                 while addr < section.end {
                     boundaries.push(addr);
-                    addr += BYTES_BLOCK_SIZE;
+                    addr = self.data_bytes_end(addr, section);
                 }
             }
         }
@@ -529,51 +764,6 @@ impl Processor {
         boundaries
     }
 
-    fn compute_code_boundaries(&self, section: &Section, boundaries: &mut Vec<usize>) {
-        let mut addr = section.start;
-
-        loop {
-            if addr == section.end {
-                break;
-            }
-
-            if let Some(inst) = self.instruction_by_addr(addr) {
-                boundaries.push(addr);
-                addr += self.instruction_width(inst);
-                continue;
-            }
-
-            if let Some(err) = self.error_by_addr(addr) {
-                boundaries.push(addr);
-                addr += err.size();
-                continue;
-            }
-
-            let mut baddr = addr;
-            loop {
-                if baddr == section.end {
-                    break;
-                }
-
-                if self.instruction_by_addr(baddr).is_some() {
-                    break;
-                }
-
-                if self.error_by_addr(baddr).is_some() {
-                    break;
-                }
-
-                baddr += 1;
-            }
-
-            let bytes_len = baddr - addr;
-            if bytes_len > 0 {
-                boundaries.push(addr);
-                addr = baddr;
-            }
-        }
-    }
-
     fn compute_cstring_boundaries(&self, section: &Section, boundaries: &mut Vec<usize>) {
         let mut start_off = 0;
         for (idx, &byte) in section.bytes().iter().enumerate() {
@@ -588,3 +778,43 @@ impl Processor {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::Processor;
+
+    /// Assembled from `data_in_code.s` with `llvm-mc -triple aarch64 -filetype=obj`, its code
+    /// section is placed at 0x10000 and has data from 0x10008 up to 0x10020.
+    fn data_in_code() -> Processor {
+        Processor::parse(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/data_in_code.o")).unwrap()
+    }
+
+    #[test]
+    fn data_pointer() {
+        let processor = data_in_code();
+        let text = processor.sections().find(|s| s.name == ".text").unwrap();
+
+        // `.quad f`
+        assert_eq!(processor.data_pointer(0x10010, text), Some(0x10000));
+
+        // words that don't point into the binary, aren't aligned or aren't in data
+        assert_eq!(processor.data_pointer(0x10008, text), None);
+        assert_eq!(processor.data_pointer(0x10018, text), None);
+        assert_eq!(processor.data_pointer(0x10014, text), None);
+        assert_eq!(processor.data_pointer(0x10000, text), None);
+    }
+
+    #[test]
+    fn code_bytes_end() {
+        let processor = data_in_code();
+        let text = processor.sections().find(|s| s.name == ".text").unwrap();
+
+        // bytes in data end at the next pointer, which is a block of its own
+        assert_eq!(processor.code_bytes_end(0x10008, text), 0x10010);
+        assert_eq!(processor.code_bytes_end(0x10010, text), 0x10018);
+
+        // and at the end of the data, where code starts again
+        assert_eq!(processor.code_bytes_end(0x10018, text), 0x10020);
+        assert!(processor.instruction_by_addr(0x10020).is_some());
+    }
+}
@@ -0,0 +1,260 @@
+//! Control-flow graphs of functions.
+
+use decoder::Flow;
+use processor_shared::{AddressMap, PhysAddr};
+use std::collections::{BTreeSet, HashSet};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EdgeKind {
+    /// Execution continues at the next block.
+    Fallthrough,
+    /// Conditional branch that's taken.
+    Conditional,
+    /// Unconditional jump, this includes tail calls.
+    Unconditional,
+    /// Call to a function, the block also has a fallthrough edge to the return address.
+    Call,
+    /// Return from the function.
+    Return,
+    /// Jump through a register or memory operand.
+    Indirect,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Edge {
+    pub kind: EdgeKind,
+    /// Where the edge leads to, `None` if the address isn't known statically.
+    pub target: Option<PhysAddr>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BasicBlock {
+    /// Address of the first instruction.
+    pub start: PhysAddr,
+    /// Address past the last instruction.
+    pub end: PhysAddr,
+    /// Outgoing edges, a block without any edges doesn't pass on execution (e.g. halts).
+    pub edges: Vec<Edge>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Cfg {
+    /// Address of the function.
+    pub entry: PhysAddr,
+    /// Blocks sorted by address.
+    pub blocks: Vec<BasicBlock>,
+}
+
+impl Cfg {
+    /// Block containing `addr`.
+    pub fn block_by_addr(&self, addr: PhysAddr) -> Option<&BasicBlock> {
+        let idx = self.blocks.partition_point(|block| block.start <= addr);
+        let block = self.blocks.get(idx.checked_sub(1)?)?;
+        (addr < block.end).then_some(block)
+    }
+
+    /// Blocks with an edge leading to the block at `start`.
+    pub fn predecessors(&self, start: PhysAddr) -> impl Iterator<Item = &BasicBlock> {
+        self.blocks.iter().filter(move |block| {
+            block.edges.iter().any(|edge| {
+                edge.kind != EdgeKind::Call && edge.target == Some(start)
+            })
+        })
+    }
+}
+
+/// Build the control-flow graph of the function at `entry`.
+///
+/// Calls don't get explored, neither do jumps to other functions as these are tail calls.
+pub(crate) fn build<T>(
+    entry: PhysAddr,
+    instructions: &AddressMap<T>,
+    flow: impl Fn(&T) -> Flow,
+    width: impl Fn(&T) -> usize,
+    is_function: impl Fn(PhysAddr) -> bool,
+    has_delay_slot: bool,
+) -> Option<Cfg> {
+    instructions.search(entry).ok()?;
+
+    let is_local = |addr: PhysAddr| addr == entry || !is_function(addr);
+    let mut leaders = BTreeSet::from([entry]);
+    let mut explored = HashSet::new();
+    let mut worklist = vec![entry];
+
+    // find where every block starts
+    while let Some(start) = worklist.pop() {
+        if !explored.insert(start) {
+            continue;
+        }
+
+        let mut idx = match instructions.search(start) {
+            Ok(idx) => idx,
+            Err(..) => continue,
+        };
+
+        loop {
+            let inst = &instructions[idx];
+            let flow = flow(&inst.item);
+            let mut next = inst.addr + width(&inst.item);
+
+            if flow != Flow::Continue {
+                // the instruction in the delay slot is part of the block
+                if has_delay_slot && flow != Flow::Halt {
+                    if let Some(slot) = instructions.get(idx + 1).filter(|i| i.addr == next) {
+                        next += width(&slot.item);
+                    }
+                }
+
+                if let Some(target) = flow.target() {
+                    if !matches!(flow, Flow::Call(_)) && is_local(target) {
+                        leaders.insert(target);
+                        worklist.push(target);
+                    }
+                }
+
+                if flow.falls_through() {
+                    leaders.insert(next);
+                    worklist.push(next);
+                }
+
+                break;
+            }
+
+            idx += 1;
+            match instructions.get(idx) {
+                Some(inst) if inst.addr == next => {}
+                _ => break,
+            }
+        }
+    }
+
+    // split the function at every block start
+    let mut blocks = Vec::new();
+    for &start in leaders.iter() {
+        let mut idx = match instructions.search(start) {
+            Ok(idx) => idx,
+            Err(..) => continue,
+        };
+
+        let mut edges = Vec::new();
+        let end = loop {
+            let inst = &instructions[idx];
+            let flow = flow(&inst.item);
+            let mut next = inst.addr + width(&inst.item);
+
+            if flow != Flow::Continue {
+                if has_delay_slot && flow != Flow::Halt {
+                    if let Some(slot) = instructions.get(idx + 1).filter(|i| i.addr == next) {
+                        next += width(&slot.item);
+                    }
+                }
+
+                let fallthrough = Edge { kind: EdgeKind::Fallthrough, target: Some(next) };
+                match flow {
+                    Flow::Continue | Flow::Halt => {}
+                    Flow::Branch(target) => {
+                        edges.push(Edge { kind: EdgeKind::Conditional, target: Some(target) });
+                        edges.push(fallthrough);
+                    }
+                    Flow::Jump(target) => {
+                        edges.push(Edge { kind: EdgeKind::Unconditional, target: Some(target) });
+                    }
+                    Flow::Call(target) => {
+                        edges.push(Edge { kind: EdgeKind::Call, target: Some(target) });
+                        edges.push(fallthrough);
+                    }
+                    Flow::IndirectCall => {
+                        edges.push(Edge { kind: EdgeKind::Call, target: None });
+                        edges.push(fallthrough);
+                    }
+                    Flow::IndirectJump => {
+                        edges.push(Edge { kind: EdgeKind::Indirect, target: None });
+                    }
+                    Flow::Return => {
+                        edges.push(Edge { kind: EdgeKind::Return, target: None });
+                    }
+                }
+
+                break next;
+            }
+
+            idx += 1;
+            match instructions.get(idx) {
+                Some(inst) if inst.addr == next => {
+                    if leaders.contains(&next) {
+                        edges.push(Edge { kind: EdgeKind::Fallthrough, target: Some(next) });
+                        break next;
+                    }
+                }
+                // ran into undecoded bytes
+                _ => break next,
+            }
+        };
+
+        blocks.push(BasicBlock { start, end, edges });
+    }
+
+    Some(Cfg { entry, blocks })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use processor_shared::Addressed;
+
+    fn instructions(flows: &[(PhysAddr, Flow)]) -> AddressMap<Flow> {
+        AddressMap {
+            mapping: flows.iter().map(|&(addr, item)| Addressed { addr, item }).collect(),
+        }
+    }
+
+    #[test]
+    fn diamond() {
+        let instructions = instructions(&[
+            (0x0, Flow::Continue),
+            (0x4, Flow::Branch(0x10)),
+            (0x8, Flow::Call(0x100)),
+            (0xc, Flow::Jump(0x14)),
+            (0x10, Flow::Continue),
+            (0x14, Flow::Return),
+        ]);
+
+        let cfg = build(0x0, &instructions, |f| *f, |_| 4, |_| false, false).unwrap();
+        let starts: Vec<_> = cfg.blocks.iter().map(|b| (b.start, b.end)).collect();
+        assert_eq!(starts, [(0x0, 0x8), (0x8, 0xc), (0xc, 0x10), (0x10, 0x14), (0x14, 0x18)]);
+
+        assert_eq!(cfg.blocks[0].edges, [
+            Edge { kind: EdgeKind::Conditional, target: Some(0x10) },
+            Edge { kind: EdgeKind::Fallthrough, target: Some(0x8) },
+        ]);
+        assert_eq!(cfg.blocks[1].edges, [
+            Edge { kind: EdgeKind::Call, target: Some(0x100) },
+            Edge { kind: EdgeKind::Fallthrough, target: Some(0xc) },
+        ]);
+        assert_eq!(cfg.blocks[3].edges, [
+            Edge { kind: EdgeKind::Fallthrough, target: Some(0x14) },
+        ]);
+        assert_eq!(cfg.predecessors(0x14).count(), 2);
+        assert_eq!(cfg.block_by_addr(0x4).map(|b| b.start), Some(0x0));
+    }
+
+    #[test]
+    fn tail_call_and_delay_slot() {
+        let instructions = instructions(&[
+            (0x0, Flow::Branch(0x10)),
+            (0x4, Flow::Continue),
+            (0x8, Flow::Jump(0x100)),
+            (0xc, Flow::Continue),
+            (0x10, Flow::Return),
+            (0x14, Flow::Continue),
+            (0x100, Flow::Return),
+        ]);
+
+        let cfg = build(0x0, &instructions, |f| *f, |_| 4, |a| a == 0x100, true).unwrap();
+        let starts: Vec<_> = cfg.blocks.iter().map(|b| (b.start, b.end)).collect();
+        assert_eq!(starts, [(0x0, 0x8), (0x8, 0x10), (0x10, 0x18)]);
+        assert_eq!(cfg.blocks[1].edges, [
+            Edge { kind: EdgeKind::Unconditional, target: Some(0x100) },
+        ]);
+    }
+}
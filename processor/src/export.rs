@@ -0,0 +1,291 @@
+//! Machine-readable export of everything a [`Processor`] knows about a binary.
+
+use crate::{Instruction, Processor};
+use processor_shared::{Addressed, Section, Segment};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::io::Write;
+use std::ops::RangeInclusive;
+
+const HEX_NUGGET: [u8; 16] = *b"0123456789abcdef";
+
+/// Lowercase hex encoding without any separators.
+fn encode_hex(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        s.push(HEX_NUGGET[(byte >> 4) as usize] as char);
+        s.push(HEX_NUGGET[(byte & 0b1111) as usize] as char);
+    }
+    s
+}
+
+struct ExportSection<'a>(&'a Section);
+
+impl Serialize for ExportSection<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let section = self.0;
+        let mut state = serializer.serialize_struct("Section", 9)?;
+        state.serialize_field("name", &section.name)?;
+        state.serialize_field("ident", section.ident)?;
+        state.serialize_field("kind", &format!("{:?}", section.kind))?;
+        state.serialize_field("start", &section.start)?;
+        state.serialize_field("end", &section.end)?;
+        state.serialize_field("permissions", &section.permissions.to_string())?;
+        state.serialize_field("file_offset", &section.file_offset)?;
+        state.serialize_field("alignment", &section.alignment)?;
+        state.serialize_field("flags", &section.flag_names())?;
+        state.end()
+    }
+}
+
+struct ExportSegment<'a>(&'a Segment);
+
+impl Serialize for ExportSegment<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let segment = self.0;
+        let mut state = serializer.serialize_struct("Segment", 3)?;
+        state.serialize_field("name", &segment.name)?;
+        state.serialize_field("start", &segment.start)?;
+        state.serialize_field("end", &segment.end)?;
+        state.end()
+    }
+}
+
+/// Lazily serialized list, so large binaries don't have to be collected up front.
+struct Listing<'a> {
+    processor: &'a Processor,
+    range: RangeInclusive<usize>,
+    kind: ListingKind,
+}
+
+#[derive(Clone, Copy)]
+enum ListingKind {
+    Symbols,
+    FileAttrs,
+    Errors,
+    Instructions,
+}
+
+impl Serialize for Listing<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let processor = self.processor;
+        let range = &self.range;
+
+        match self.kind {
+            ListingKind::Symbols => serializer.collect_seq(
+                processor.index.syms.iter().filter(|sym| range.contains(&sym.addr)).map(
+                    |Addressed { addr, item }| ExportSymbol {
+                        addr: *addr,
+                        name: item.as_str(),
+                        module: item.module(),
+                        intrinsic: item.intrinsic(),
+                        imported: item.imported(),
+                        generated: item.generated(),
+                    },
+                ),
+            ),
+            ListingKind::FileAttrs => serializer.collect_seq(
+                processor.index.file_attrs.iter().filter(|attr| range.contains(&attr.addr)).map(
+                    |Addressed { addr, item }| ExportFileAttr {
+                        addr: *addr,
+                        path: item.path.to_string_lossy().into_owned(),
+                        line: item.line,
+                        column_start: item.column_start,
+                        column_end: item.column_end,
+                    },
+                ),
+            ),
+            ListingKind::Errors => serializer.collect_seq(
+                processor.errors.iter().filter(|err| range.contains(&err.addr)).map(
+                    |Addressed { addr, item }| ExportError {
+                        addr: *addr,
+                        kind: format!("{:?}", item.kind),
+                        bytes: encode_hex(processor.bytes_by_addr(*addr, item.size())),
+                    },
+                ),
+            ),
+            ListingKind::Instructions => {
+                let export = |addr: usize, item: &Instruction| {
+                    let width = processor.instruction_width(item);
+                    let tokens = processor.instruction_tokens(item, &processor.index);
+                    ExportInstruction {
+                        addr,
+                        bytes: encode_hex(processor.bytes_by_addr(addr, width)),
+                        text: tokens.iter().map(|t| &t.text as &str).collect(),
+                    }
+                };
+
+                serializer.collect_seq(processor.code.regions(range.clone()).flat_map(|region| {
+                    region
+                        .instructions
+                        .iter()
+                        .filter(|inst| range.contains(&inst.addr))
+                        .map(|inst| export(inst.addr, &inst.item))
+                        .collect::<Vec<_>>()
+                }))
+            }
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct ExportSymbol<'a> {
+    addr: usize,
+    name: &'a str,
+    module: Option<&'a str>,
+    intrinsic: bool,
+    imported: bool,
+    generated: bool,
+}
+
+#[derive(serde::Serialize)]
+struct ExportFileAttr {
+    addr: usize,
+    path: String,
+    line: usize,
+    column_start: usize,
+    column_end: usize,
+}
+
+#[derive(serde::Serialize)]
+struct ExportError {
+    addr: usize,
+    kind: String,
+    bytes: String,
+}
+
+#[derive(serde::Serialize)]
+struct ExportInstruction {
+    addr: usize,
+    bytes: String,
+    text: String,
+}
+
+impl Serialize for Processor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.serialize_range(0..=usize::MAX, serializer)
+    }
+}
+
+impl Processor {
+    fn serialize_range<S: Serializer>(
+        &self,
+        range: RangeInclusive<usize>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let listing = |kind| Listing {
+            processor: self,
+            range: range.clone(),
+            kind,
+        };
+
+        let mut state = serializer.serialize_struct("Processor", 10)?;
+        state.serialize_field("path", &self.path)?;
+        state.serialize_field("arch", &format!("{:?}", self.arch))?;
+        state.serialize_field("endianness", &format!("{:?}", self.endianness))?;
+        state.serialize_field("entrypoint", &self.entrypoint)?;
+        state.serialize_field(
+            "sections",
+            &self.sections.iter().map(ExportSection).collect::<Vec<_>>(),
+        )?;
+        state.serialize_field(
+            "segments",
+            &self.segments.iter().map(ExportSegment).collect::<Vec<_>>(),
+        )?;
+        state.serialize_field("symbols", &listing(ListingKind::Symbols))?;
+        state.serialize_field("file_attrs", &listing(ListingKind::FileAttrs))?;
+        state.serialize_field("errors", &listing(ListingKind::Errors))?;
+        state.serialize_field("instructions", &listing(ListingKind::Instructions))?;
+        state.end()
+    }
+
+    /// Write the analysis as JSON. Anything that is keyed by an address (symbols, file
+    /// attributes, errors and instructions) is limited to the addresses within `range`.
+    pub fn export_json<W: Write>(
+        &self,
+        writer: W,
+        range: RangeInclusive<usize>,
+    ) -> Result<(), serde_json::Error> {
+        let mut serializer = serde_json::Serializer::new(writer);
+        self.serialize_range(range, &mut serializer)
+    }
+
+    /// Bytes at a given address in whatever section contains it.
+    fn bytes_by_addr(&self, addr: usize, len: usize) -> &[u8] {
+        match self.section_by_addr(addr) {
+            Some(section) => section.bytes_by_addr(addr, len),
+            None => &[],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Decoding, Processor};
+    use serde_json::{json, Value};
+
+    /// `relocatable.s` assembled for x86_64, its code section is placed at 0x10000.
+    const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/relocatable.o");
+
+    fn export(processor: &Processor, range: std::ops::RangeInclusive<usize>) -> Value {
+        let mut out = Vec::new();
+        processor.export_json(&mut out, range).unwrap();
+        serde_json::from_slice(&out).unwrap()
+    }
+
+    #[test]
+    fn sections_symbols_and_instructions() {
+        let processor = Processor::parse(FIXTURE).unwrap();
+        let export = export(&processor, 0..=usize::MAX);
+
+        assert_eq!(export["arch"], "X86_64");
+        assert_eq!(export["endianness"], "Little");
+
+        let sections = export["sections"].as_array().unwrap();
+        let text = sections.iter().find(|section| section["name"] == ".text").unwrap();
+        assert_eq!(*text, json!({
+            "name": ".text",
+            "ident": "PROGBITS",
+            "kind": "Code",
+            "start": 0x10000,
+            "end": 0x1001e,
+            "permissions": "r-x",
+            "file_offset": 0x40,
+            "alignment": 4,
+            "flags": ["ALLOC", "EXECINSTR"],
+        }));
+
+        let symbols = export["symbols"].as_array().unwrap();
+        let table = symbols.iter().find(|symbol| symbol["name"] == "table").unwrap();
+        assert_eq!(*table, json!({
+            "addr": 0x10008,
+            "name": "table",
+            "module": null,
+            "intrinsic": false,
+            "imported": false,
+            "generated": false,
+        }));
+
+        // the table between the functions isn't decoded
+        assert_eq!(export["instructions"], json!([
+            { "addr": 0x10000, "bytes": "488d0501000000", "text": "lea rax, qword [<table>]" },
+            { "addr": 0x10007, "bytes": "c3", "text": "ret" },
+            { "addr": 0x10018, "bytes": "b801000000", "text": "mov eax, 0x1" },
+            { "addr": 0x1001d, "bytes": "c3", "text": "ret" },
+        ]));
+    }
+
+    #[test]
+    fn lazy_range() {
+        let processor = Processor::parse_with(FIXTURE, Decoding::Lazy).unwrap();
+        let export = export(&processor, 0x10000..=0x10007);
+
+        // sections aren't keyed by an address, so they're all exported
+        assert!(export["sections"].as_array().unwrap().iter().any(|s| s["name"] == ".eh_frame"));
+        assert!(export["symbols"].as_array().unwrap().iter().all(|s| s["addr"] == 0x10000));
+        assert_eq!(export["errors"], json!([]));
+        assert_eq!(export["instructions"], json!([
+            { "addr": 0x10000, "bytes": "488d0501000000", "text": "lea rax, qword [<table>]" },
+            { "addr": 0x10007, "bytes": "c3", "text": "ret" },
+        ]));
+    }
+}
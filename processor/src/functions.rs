@@ -0,0 +1,181 @@
+//! Function discovery for binaries with few or no symbols.
+//!
+//! Function starts are collected from the known entries into code, the targets of direct calls,
+//! instructions matching a common prologue and the targets of tail calls.
+
+use decoder::{Decoded, Flow};
+use object::{Architecture, Endianness};
+use processor_shared::{AddressMap, PhysAddr, Section, SectionKind};
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Find the start of every function in the decoded `instructions`.
+///
+/// `instructions` must be sorted. `entries` are addresses known to be functions and
+/// `unwind_ranges` are the ranges from the exception/unwind tables.
+pub fn discover<I: Decoded>(
+    arch: Architecture,
+    endianness: Endianness,
+    sections: &[Section],
+    instructions: &AddressMap<I>,
+    entries: &[PhysAddr],
+    unwind_ranges: &[Range<PhysAddr>],
+) -> Vec<PhysAddr> {
+    let sections: Vec<&Section> =
+        sections.iter().filter(|s| s.kind == SectionKind::Code).collect();
+
+    let mut starts = entries.to_vec();
+
+    for inst in instructions.iter() {
+        if let Flow::Call(target) = inst.item.flow() {
+            starts.push(target);
+        }
+
+        let section = match sections.iter().find(|s| (s.start..s.end).contains(&inst.addr)) {
+            Some(section) => section,
+            None => continue,
+        };
+
+        if is_prologue(arch, endianness, section.bytes_by_addr(inst.addr, 8)) {
+            starts.push(inst.addr);
+        }
+    }
+
+    // only keep starts that were decoded as instructions
+    starts.retain(|&addr| instructions.search(addr).is_ok());
+    starts.sort_unstable();
+    starts.dedup();
+
+    let tail_calls = tail_calls(instructions, &starts, unwind_ranges);
+    starts.extend(tail_calls);
+    starts.sort_unstable();
+    starts.dedup();
+
+    starts
+}
+
+/// Targets of jumps that leave the function they're in.
+fn tail_calls<I: Decoded>(
+    instructions: &AddressMap<I>,
+    starts: &[PhysAddr],
+    unwind_ranges: &[Range<PhysAddr>],
+) -> Vec<PhysAddr> {
+    let ends: HashMap<PhysAddr, PhysAddr> =
+        unwind_ranges.iter().map(|range| (range.start, range.end)).collect();
+
+    let mut targets = Vec::new();
+    for inst in instructions.iter() {
+        let target = match inst.item.flow() {
+            Flow::Jump(target) => target,
+            _ => continue,
+        };
+
+        let idx = starts.partition_point(|&start| start <= inst.addr);
+
+        // the jump isn't part of any function
+        if idx == 0 {
+            continue;
+        }
+
+        let start = starts[idx - 1];
+        let end = match ends.get(&start) {
+            Some(&end) => end,
+            None => starts.get(idx).copied().unwrap_or(PhysAddr::MAX),
+        };
+
+        if !(start..end).contains(&target) && instructions.search(target).is_ok() {
+            targets.push(target);
+        }
+    }
+
+    targets
+}
+
+/// Whether `bytes` start with an instruction commonly found at the start of a function.
+fn is_prologue(arch: Architecture, endianness: Endianness, bytes: &[u8]) -> bool {
+    let word = match bytes.get(..4) {
+        Some(&[a, b, c, d]) => match endianness {
+            Endianness::Little => u32::from_le_bytes([a, b, c, d]),
+            Endianness::Big => u32::from_be_bytes([a, b, c, d]),
+        },
+        _ => 0,
+    };
+
+    match arch {
+        Architecture::X86_64 => {
+            // push rbp; mov rbp, rsp
+            bytes.starts_with(&[0x55, 0x48, 0x89, 0xe5])
+                || bytes.starts_with(&[0x55, 0x48, 0x8b, 0xec])
+                // endbr64
+                || bytes.starts_with(&[0xf3, 0x0f, 0x1e, 0xfa])
+        }
+        Architecture::X86_64_X32 | Architecture::I386 => {
+            // push ebp; mov ebp, esp
+            bytes.starts_with(&[0x55, 0x89, 0xe5])
+                || bytes.starts_with(&[0x55, 0x8b, 0xec])
+                // mov edi, edi; push ebp
+                || bytes.starts_with(&[0x8b, 0xff, 0x55])
+                // endbr32
+                || bytes.starts_with(&[0xf3, 0x0f, 0x1e, 0xfb])
+        }
+        Architecture::Aarch64 | Architecture::Aarch64_Ilp32 => {
+            // stp x29, x30, [sp, #-N]!
+            word & 0xffc0_7fff == 0xa980_7bfd
+                // paciasp
+                || word == 0xd503_233f
+                // bti c
+                || word == 0xd503_245f
+        }
+        Architecture::Arm => {
+            // push {.., lr}
+            word & 0xffff_4000 == 0xe92d_4000
+                // str lr, [sp, #-4]!
+                || word == 0xe52d_e004
+        }
+        Architecture::Mips | Architecture::Mips64 => {
+            // addiu sp, sp, -N / daddiu sp, sp, -N
+            word & 0xffff_8000 == 0x27bd_8000 || word & 0xffff_8000 == 0x67bd_8000
+        }
+        Architecture::Riscv32 | Architecture::Riscv64 => {
+            let half = match bytes.get(..2) {
+                Some(&[a, b]) => u16::from_le_bytes([a, b]),
+                _ => 0,
+            };
+
+            // addi sp, sp, -N
+            word & 0x800f_ffff == 0x8001_0113
+                // c.addi16sp sp, -N / c.addi sp, -N
+                || half & 0xff83 == 0x7101
+                || half & 0xff83 == 0x1101
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prologues() {
+        let le = Endianness::Little;
+        let be = Endianness::Big;
+
+        assert!(is_prologue(Architecture::X86_64, le, &[0x55, 0x48, 0x89, 0xe5, 0x48]));
+        assert!(is_prologue(Architecture::X86_64, le, &[0xf3, 0x0f, 0x1e, 0xfa]));
+        assert!(!is_prologue(Architecture::X86_64, le, &[0x55, 0x53]));
+        assert!(is_prologue(Architecture::I386, le, &[0x55, 0x89, 0xe5]));
+        // stp x29, x30, [sp, #-32]!
+        assert!(is_prologue(Architecture::Aarch64, le, &[0xfd, 0x7b, 0xbe, 0xa9]));
+        // push {r4, lr}
+        assert!(is_prologue(Architecture::Arm, le, &[0x10, 0x40, 0x2d, 0xe9]));
+        // addiu sp, sp, -32
+        assert!(is_prologue(Architecture::Mips, be, &[0x27, 0xbd, 0xff, 0xe0]));
+        // addiu sp, sp, 32
+        assert!(!is_prologue(Architecture::Mips, be, &[0x27, 0xbd, 0x00, 0x20]));
+        // addi sp, sp, -32
+        assert!(is_prologue(Architecture::Riscv64, le, &[0x13, 0x01, 0x01, 0xfe]));
+        // c.addi sp, -16
+        assert!(is_prologue(Architecture::Riscv64, le, &[0x41, 0x11]));
+    }
+}
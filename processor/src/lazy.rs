@@ -0,0 +1,669 @@
+//! On-demand decoding of code.
+//!
+//! Decoded instructions take up a lot more memory than the code they're decoded from, so
+//! they aren't kept around. Instead code sections are split into regions that are decoded
+//! the first time they're looked at and kept around in a least-recently-used cache. A region
+//! starts at every symbol, at the edges of data in code sections and at least every
+//! [`REGION_SIZE`] bytes.
+//!
+//! Analyzed code only keeps where instructions start, one bit per byte of code, so decoding
+//! a region again gives the same instructions as analysis did.
+
+use crate::{functions, recursion, xrefs, Instruction, Xref};
+use decoder::{Decodable, Decoded};
+use object::{Architecture, Endianness};
+use processor_shared::{AddressMap, Addressed, PhysAddr, Section, SectionKind};
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::ops::{Deref, Range, RangeInclusive};
+use std::sync::{Arc, Mutex};
+
+/// Upper bound on the number of bytes in a region.
+pub const REGION_SIZE: usize = 1024;
+
+/// Number of regions that are kept decoded.
+const CACHE_CAPACITY: usize = 512;
+
+/// Decoded instructions of a region.
+pub struct Region {
+    /// Address of the first instruction, this is past the end of the region's start if an
+    /// instruction of the previous region runs into it.
+    pub start: PhysAddr,
+    /// Address after the last instruction.
+    pub end: PhysAddr,
+    /// Sorted by address.
+    pub instructions: AddressMap<Instruction>,
+    /// Sorted by address.
+    pub errors: AddressMap<decoder::Error>,
+}
+
+/// An [`Instruction`] of a cached [`Region`].
+pub struct InstructionRef {
+    pub(crate) region: Arc<Region>,
+    pub(crate) idx: usize,
+}
+
+impl Deref for InstructionRef {
+    type Target = Instruction;
+
+    fn deref(&self) -> &Instruction {
+        &self.region.instructions[self.idx].item
+    }
+}
+
+/// Addresses in code sections where instructions start, stored as one bit per byte.
+#[derive(Default, Clone)]
+pub struct Starts {
+    /// Bitmap of each code section, sorted by the section's start.
+    sections: Vec<(Range<PhysAddr>, Vec<u64>)>,
+}
+
+impl Starts {
+    pub fn new(sections: &[Section], addrs: impl Iterator<Item = PhysAddr>) -> Self {
+        let mut sections: Vec<(Range<PhysAddr>, Vec<u64>)> = sections
+            .iter()
+            .filter(|s| s.kind == SectionKind::Code)
+            .map(|s| (s.start..s.end, vec![0; (s.end - s.start).div_ceil(64)]))
+            .collect();
+
+        sections.sort_unstable_by_key(|(range, _)| range.start);
+
+        let mut this = Self { sections };
+        for addr in addrs {
+            this.set(addr);
+        }
+
+        this
+    }
+
+    /// Add where instructions start in a code `section` that wasn't analyzed before.
+    pub fn insert(&mut self, section: &Section, addrs: impl Iterator<Item = PhysAddr>) {
+        let idx = self.sections.partition_point(|(range, _)| range.start < section.start);
+        let bits = vec![0; (section.end - section.start).div_ceil(64)];
+        self.sections.insert(idx, (section.start..section.end, bits));
+
+        for addr in addrs.filter(|addr| (section.start..section.end).contains(addr)) {
+            self.set(addr);
+        }
+    }
+
+    fn set(&mut self, addr: PhysAddr) {
+        if let Some((range, bits)) = self.section_mut(addr) {
+            let offset = addr - range.start;
+            bits[offset / 64] |= 1 << (offset % 64);
+        }
+    }
+
+    /// Whether `addr` is in a section that was analyzed.
+    pub fn covers(&self, addr: PhysAddr) -> bool {
+        let idx = self.sections.partition_point(|(range, _)| range.end <= addr);
+        self.sections.get(idx).is_some_and(|(range, _)| range.contains(&addr))
+    }
+
+    fn section_mut(&mut self, addr: PhysAddr) -> Option<(&Range<PhysAddr>, &mut Vec<u64>)> {
+        let idx = self.sections.partition_point(|(range, _)| range.end <= addr);
+        match self.sections.get_mut(idx) {
+            Some((range, bits)) if range.contains(&addr) => Some((range, bits)),
+            _ => None,
+        }
+    }
+
+    /// Replace where instructions start in `range` with `addrs`.
+    pub fn replace(&mut self, range: Range<PhysAddr>, addrs: impl Iterator<Item = PhysAddr>) {
+        let (section, bits) = match self.section_mut(range.start) {
+            Some((section, bits)) => (section.clone(), bits),
+            None => return,
+        };
+
+        let first = range.start - section.start;
+        let last = std::cmp::min(range.end, section.end) - section.start;
+        for offset in first..last {
+            bits[offset / 64] &= !(1 << (offset % 64));
+        }
+
+        for addr in addrs.filter(|addr| range.contains(addr) && section.contains(addr)) {
+            let offset = addr - section.start;
+            bits[offset / 64] |= 1 << (offset % 64);
+        }
+    }
+
+    pub fn contains(&self, addr: PhysAddr) -> bool {
+        let idx = self.sections.partition_point(|(range, _)| range.end <= addr);
+        match self.sections.get(idx) {
+            Some((range, bits)) if range.contains(&addr) => {
+                let offset = addr - range.start;
+                bits[offset / 64] & (1 << (offset % 64)) != 0
+            }
+            _ => false,
+        }
+    }
+}
+
+/// What analysis needs to know about a binary besides the code it analyzes.
+pub struct Target<'a> {
+    pub arch: Architecture,
+    pub endianness: Endianness,
+    /// All of the binary's sections.
+    pub sections: &'a [Section],
+    /// Parts of code sections that hold data, sorted by address.
+    pub data_ranges: &'a [Range<PhysAddr>],
+    /// Function ranges described by the exception/unwind tables, sorted by their start.
+    pub unwind_ranges: &'a [Range<PhysAddr>],
+}
+
+/// Results of analyzing code.
+pub struct Analysis {
+    /// Where instructions start, sorted.
+    pub starts: Vec<PhysAddr>,
+    /// Sorted by address.
+    pub errors: AddressMap<decoder::Error>,
+    /// Starts of functions, sorted.
+    pub functions: Vec<PhysAddr>,
+    /// References made by instructions.
+    pub xrefs: Vec<Xref>,
+}
+
+/// Linear sweep through code, type-erased as the decoder differs per architecture.
+pub trait Sweep: Send + Sync {
+    /// Decode the instructions in `range` one after another. If there are `known` starts of
+    /// instructions, only those are decoded. The last instruction may run past the end of
+    /// `range` unless it's `bounded`.
+    fn sweep(
+        &self,
+        section: &Section,
+        range: Range<PhysAddr>,
+        bounded: bool,
+        known: Option<&Starts>,
+    ) -> Region;
+
+    /// Recursively descend through the code sections in `code` from `entries`, finding the
+    /// functions and references in them.
+    fn analyze(&self, target: &Target, code: &[Section], entries: &[PhysAddr]) -> Analysis;
+}
+
+pub struct Sweeper<D: Decodable> {
+    pub decoder: D,
+}
+
+impl<D> Sweep for Sweeper<D>
+where
+    D: Decodable + Send + Sync,
+    D::Instruction: Send + Sync + 'static,
+{
+    fn sweep(
+        &self,
+        section: &Section,
+        range: Range<PhysAddr>,
+        bounded: bool,
+        known: Option<&Starts>,
+    ) -> Region {
+        let mut decoded: Vec<Addressed<D::Instruction>> = Vec::new();
+        let mut errors = AddressMap::default();
+        let mut ip = range.start;
+
+        // the instruction before the range is decoded again, as some instructions depend on
+        // the one before them (e.g. a load relative to the page of an aarch64 `adrp`)
+        let before = known.and_then(|known| {
+            let lookback = range.start.saturating_sub(self.decoder.max_width());
+            let lookback = std::cmp::max(lookback, section.start);
+            let addr = (lookback..range.start).rev().find(|&addr| known.contains(addr))?;
+            let mut reader = decoder::Reader::new(section.bytes_by_addr(addr, usize::MAX));
+            let mut instruction = self.decoder.decode_at(&mut reader, addr).ok()?;
+            instruction.update_rel_addrs(addr, None);
+            Some(instruction)
+        });
+
+        while ip < range.end {
+            if known.is_some_and(|known| !known.contains(ip)) {
+                ip += 1;
+                continue;
+            }
+
+            let mut reader = decoder::Reader::new(section.bytes_by_addr(ip, usize::MAX));
+            match self.decoder.decode_at(&mut reader, ip) {
+                Ok(mut instruction) => {
+                    let prev = decoded.last().map(|inst| &inst.item).or(before.as_ref());
+                    instruction.update_rel_addrs(ip, prev);
+
+                    let width = instruction.width();
+                    if bounded && ip + width > range.end {
+                        break;
+                    }
+
+                    decoded.push(Addressed { addr: ip, item: instruction });
+                    ip += width;
+                }
+                Err(error) => {
+                    if error.kind == decoder::ErrorKind::ExhaustedInput {
+                        break;
+                    }
+
+                    // analysis already knows about the errors in between known instructions
+                    if known.is_none() {
+                        errors.push(Addressed { addr: ip, item: error });
+                    }
+
+                    ip += error.size();
+                }
+            }
+        }
+
+        let instructions = decoded
+            .into_iter()
+            .map(|inst| Addressed { addr: inst.addr, item: Box::new(inst.item) as Instruction })
+            .collect();
+
+        Region {
+            start: range.start,
+            end: std::cmp::max(ip, range.start),
+            instructions: AddressMap { mapping: instructions },
+            errors,
+        }
+    }
+
+    fn analyze(&self, target: &Target, code: &[Section], entries: &[PhysAddr]) -> Analysis {
+        let has_delay_slot = matches!(target.arch, Architecture::Mips | Architecture::Mips64);
+        let (instructions, mut errors) =
+            recursion::recurse(&self.decoder, code, entries, target.data_ranges, has_delay_slot);
+
+        let functions = functions::discover(
+            target.arch,
+            target.endianness,
+            code,
+            &instructions,
+            entries,
+            target.unwind_ranges,
+        );
+
+        errors.sort_unstable();
+
+        Analysis {
+            xrefs: xrefs::from_instructions(&instructions, target.sections),
+            starts: instructions.iter().map(|inst| inst.addr).collect(),
+            errors,
+            functions,
+        }
+    }
+}
+
+/// Least-recently-used cache holding up to a fixed number of items.
+struct Lru<K, V> {
+    capacity: usize,
+    /// Items along with when they were last used.
+    items: HashMap<K, (V, u64)>,
+    /// Keys by when they were last used.
+    order: BTreeMap<u64, K>,
+    /// Incremented every time an item is used.
+    clock: u64,
+}
+
+impl<K: Hash + Eq + Copy, V: Clone> Lru<K, V> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            items: HashMap::with_capacity(capacity),
+            order: BTreeMap::new(),
+            clock: 0,
+        }
+    }
+
+    fn get(&mut self, key: K) -> Option<V> {
+        let (value, used) = self.items.get_mut(&key)?;
+        self.order.remove(used);
+        self.clock += 1;
+        *used = self.clock;
+        self.order.insert(self.clock, key);
+        Some(value.clone())
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        self.clock += 1;
+        match self.items.insert(key, (value, self.clock)) {
+            Some((_, used)) => {
+                self.order.remove(&used);
+            }
+            None if self.items.len() > self.capacity => {
+                if let Some((_, oldest)) = self.order.pop_first() {
+                    self.items.remove(&oldest);
+                }
+            }
+            None => {}
+        }
+
+        self.order.insert(self.clock, key);
+    }
+}
+
+/// Bounds of a region.
+struct Bounds {
+    range: Range<PhysAddr>,
+    /// Index of the code section the region is in.
+    section: usize,
+    /// Whether decoding has to start at the region's start, rather than continue from the
+    /// previous region. That's the case at symbols, sections and the edges of data.
+    anchored: bool,
+    /// Whether the region is data in a code section.
+    data: bool,
+}
+
+/// Code that's decoded on demand.
+pub struct Lazy {
+    sweep: Arc<dyn Sweep>,
+    /// Code sections, sorted by address.
+    sections: Vec<Section>,
+    /// Sorted by address, these don't change when code is patched.
+    regions: Arc<[Bounds]>,
+    /// Where instructions start according to analysis, in the code sections that were analyzed.
+    known: Option<Starts>,
+    cache: Mutex<Lru<usize, Arc<Region>>>,
+}
+
+/// Clones start out with an empty cache, they're only made to be patched.
+impl Clone for Lazy {
+    fn clone(&self) -> Self {
+        Self {
+            sweep: Arc::clone(&self.sweep),
+            sections: self.sections.clone(),
+            regions: Arc::clone(&self.regions),
+            known: self.known.clone(),
+            cache: Mutex::new(Lru::new(CACHE_CAPACITY)),
+        }
+    }
+}
+
+impl Lazy {
+    /// Split the code `sections` into regions. `symbols` and `data` must be sorted.
+    pub fn new(
+        sweep: Arc<dyn Sweep>,
+        sections: &[Section],
+        symbols: &[PhysAddr],
+        data: &[Range<PhysAddr>],
+        known: Option<Starts>,
+    ) -> Self {
+        let mut sections: Vec<Section> =
+            sections.iter().filter(|s| s.kind == SectionKind::Code).cloned().collect();
+        sections.sort_unstable_by_key(|s| s.start);
+
+        let mut regions = Vec::new();
+        for (idx, section) in sections.iter().enumerate() {
+            let mut anchors: Vec<PhysAddr> = vec![section.start];
+
+            let first = symbols.partition_point(|&addr| addr < section.start);
+            let last = symbols.partition_point(|&addr| addr < section.end);
+            anchors.extend_from_slice(&symbols[first..last]);
+
+            for range in data {
+                if range.start < section.end && range.end > section.start {
+                    anchors.push(std::cmp::max(range.start, section.start));
+                    anchors.push(std::cmp::min(range.end, section.end));
+                }
+            }
+
+            anchors.sort_unstable();
+            anchors.dedup();
+            anchors.retain(|&addr| addr < section.end);
+
+            for (jdx, &start) in anchors.iter().enumerate() {
+                let end = anchors.get(jdx + 1).copied().unwrap_or(section.end);
+                let is_data = data.iter().any(|range| range.contains(&start));
+
+                let mut addr = start;
+                while addr < end {
+                    let region_end = std::cmp::min(addr + REGION_SIZE, end);
+                    regions.push(Bounds {
+                        range: addr..region_end,
+                        section: idx,
+                        anchored: addr == start,
+                        data: is_data,
+                    });
+                    addr = region_end;
+                }
+            }
+        }
+
+        log::complex!(
+            w "[processor::lazy] split code into ",
+            g regions.len().to_string(),
+            w " regions.",
+        );
+
+        Self {
+            sweep,
+            sections,
+            regions: Arc::from(regions),
+            known,
+            cache: Mutex::new(Lru::new(CACHE_CAPACITY)),
+        }
+    }
+
+    /// Sweeper the code is decoded with.
+    pub fn sweep(&self) -> Arc<dyn Sweep> {
+        Arc::clone(&self.sweep)
+    }
+
+    /// Where instructions start in `section`, if it was analyzed.
+    fn known(&self, section: &Section) -> Option<&Starts> {
+        self.known.as_ref().filter(|known| known.covers(section.start))
+    }
+
+    fn region_idx(&self, addr: PhysAddr) -> Option<usize> {
+        let idx = self.regions.partition_point(|region| region.range.end <= addr);
+        self.regions.get(idx).filter(|region| region.range.contains(&addr)).map(|_| idx)
+    }
+
+    /// Bounds of the region `addr` is in.
+    pub fn bounds(&self, addr: PhysAddr) -> Option<Range<PhysAddr>> {
+        self.region_idx(addr).map(|idx| self.regions[idx].range.clone())
+    }
+
+    /// Starts of the regions in `section`.
+    pub fn boundaries(&self, section: &Section) -> impl Iterator<Item = PhysAddr> + '_ {
+        let first = self.regions.partition_point(|region| region.range.start < section.start);
+        let last = self.regions.partition_point(|region| region.range.start < section.end);
+        self.regions[first..last].iter().map(|region| region.range.start)
+    }
+
+    /// Decoded region that `addr` is in, this decodes the region if it isn't cached.
+    pub fn region(&self, addr: PhysAddr) -> Option<Arc<Region>> {
+        let idx = self.region_idx(addr)?;
+
+        if let Some(region) = self.cache.lock().unwrap().get(idx) {
+            return Some(region);
+        }
+
+        // decoding continues from the previous region so an instruction crossing into this
+        // region isn't decoded twice
+        let mut first = idx;
+        while !self.regions[first].anchored {
+            if self.cache.lock().unwrap().get(first - 1).is_some() {
+                break;
+            }
+
+            first -= 1;
+        }
+
+        let mut region = None;
+        for idx in first..=idx {
+            let bounds = &self.regions[idx];
+            let mut start = bounds.range.start;
+
+            if !bounds.anchored {
+                if let Some(prev) = self.cache.lock().unwrap().get(idx - 1) {
+                    start = std::cmp::max(start, prev.end);
+                }
+            }
+
+            let decoded = Arc::new(self.decode_region(idx, start));
+            self.cache.lock().unwrap().insert(idx, Arc::clone(&decoded));
+            region = Some(decoded);
+        }
+
+        region
+    }
+
+    fn decode_region(&self, idx: usize, start: PhysAddr) -> Region {
+        let bounds = &self.regions[idx];
+        let section = &self.sections[bounds.section];
+        let end = bounds.range.end;
+
+        if bounds.data || start >= end {
+            let empty = start..start;
+            return self.sweep.sweep(section, empty, true, None);
+        }
+
+        // instructions can't run into symbols, data or past the end of the section
+        let bounded = self.regions.get(idx + 1).is_none_or(|next| next.anchored);
+        self.sweep.sweep(section, start..end, bounded, self.known(section))
+    }
+
+    /// Decode code again after the bytes in `range` of `section` were patched. If code was
+    /// analyzed, this returns the range where instructions start differently than before and
+    /// the errors in it.
+    pub fn patch(
+        &mut self,
+        section: &Section,
+        range: Range<PhysAddr>,
+    ) -> Option<(Range<PhysAddr>, AddressMap<decoder::Error>)> {
+        // cached regions were decoded from the bytes from before the patch
+        *self.cache.get_mut().unwrap() = Lru::new(CACHE_CAPACITY);
+
+        let idx = self.sections.iter().position(|s| s.start == section.start)?;
+        self.sections[idx] = section.clone();
+
+        let known = self.known(section)?;
+        let region = &self.regions[self.region_idx(range.start)?];
+        if region.data {
+            return None;
+        }
+
+        // decoding starts at the last instruction that starts before the patch
+        let lookback = std::cmp::max(range.start.saturating_sub(REGION_SIZE), section.start);
+        let start = (lookback..=range.start)
+            .rev()
+            .find(|&addr| known.contains(addr))
+            .unwrap_or(range.start);
+
+        let mut starts = Vec::new();
+        let mut errors = AddressMap::default();
+        let mut from = start;
+        let mut to = range.end;
+        let end = loop {
+            let region = self.sweep.sweep(&self.sections[idx], from..to, false, None);
+            let end = region.end;
+            starts.extend(region.instructions.iter().map(|inst| inst.addr));
+            errors.extend(region.errors);
+
+            // decoding is back in sync once an instruction ends where one started before
+            let synced = end >= range.end && known.contains(end);
+            let in_data = self.region_idx(end).is_none_or(|idx| self.regions[idx].data);
+            if synced || in_data || end == from {
+                break end;
+            }
+
+            // keep decoding one instruction at a time
+            from = end;
+            to = end + 1;
+        };
+
+        if let Some(known) = self.known.as_mut() {
+            known.replace(start..end, starts.into_iter());
+        }
+
+        Some((start..end, errors))
+    }
+
+    /// Decoded regions that overlap `range`.
+    pub fn regions(&self, range: RangeInclusive<PhysAddr>) -> impl Iterator<Item = Arc<Region>> + '_ {
+        let first = self.regions.partition_point(|region| region.range.end <= *range.start());
+        let last = self.regions.partition_point(|region| region.range.start <= *range.end());
+        self.regions[first..last].iter().filter_map(|region| self.region(region.range.start))
+    }
+
+    /// Decode `range` without caching it, this is meant for ranges that span multiple
+    /// regions such as functions.
+    pub fn decode(&self, range: Range<PhysAddr>) -> Option<Region> {
+        let section = self.sections.iter().find(|s| (s.start..s.end).contains(&range.start))?;
+        let end = std::cmp::min(range.end, section.end);
+        Some(self.sweep.sweep(section, range.start..end, true, self.known(section)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lru() {
+        let mut lru = Lru::new(2);
+        lru.insert(1, "a");
+        lru.insert(2, "b");
+
+        // 1 is used more recently than 2, so 2 gets evicted
+        assert_eq!(lru.get(1), Some("a"));
+        lru.insert(3, "c");
+        assert_eq!(lru.get(2), None);
+        assert_eq!(lru.get(1), Some("a"));
+        assert_eq!(lru.get(3), Some("c"));
+
+        // replacing an item doesn't evict anything
+        lru.insert(3, "d");
+        assert_eq!(lru.get(1), Some("a"));
+        assert_eq!(lru.get(3), Some("d"));
+    }
+
+    #[test]
+    fn starts() {
+        static CODE: [u8; 0x100] = [0; 0x100];
+        let sections = [
+            Section::new(".text".into(), "", SectionKind::Code, &CODE, 0x1000, 0x1100),
+            Section::new(".data".into(), "", SectionKind::Raw, &CODE, 0x2000, 0x2100),
+        ];
+
+        let starts = Starts::new(&sections, [0x1000, 0x1041, 0x10ff, 0x2000].into_iter());
+        assert!(starts.contains(0x1000));
+        assert!(starts.contains(0x1041));
+        assert!(starts.contains(0x10ff));
+        assert!(!starts.contains(0x1040));
+        assert!(!starts.contains(0x2000));
+        assert!(!starts.contains(0x1100));
+    }
+
+    #[test]
+    fn insert_starts() {
+        static CODE: [u8; 0x100] = [0; 0x100];
+        let text = Section::new(".text".into(), "", SectionKind::Code, &CODE, 0x1000, 0x1100);
+        let init = Section::new(".init".into(), "", SectionKind::Code, &CODE, 0x800, 0x900);
+
+        let mut starts = Starts::default();
+        starts.insert(&text, [0x1000, 0x1010, 0x850].into_iter());
+        assert!(starts.covers(0x1000));
+        assert!(!starts.covers(0x850));
+        assert!(starts.contains(0x1010));
+        assert!(!starts.contains(0x850));
+
+        // sections can be added in any order
+        starts.insert(&init, [0x850].into_iter());
+        assert!(starts.covers(0x800));
+        assert!(starts.contains(0x850));
+        assert!(starts.contains(0x1010));
+        assert!(!starts.covers(0x900));
+    }
+
+    #[test]
+    fn replace_starts() {
+        static CODE: [u8; 0x100] = [0; 0x100];
+        let sections = [Section::new(".text".into(), "", SectionKind::Code, &CODE, 0x1000, 0x1100)];
+
+        let mut starts = Starts::new(&sections, [0x1000, 0x1004, 0x1008, 0x100c].into_iter());
+        starts.replace(0x1004..0x100c, [0x1002, 0x1005, 0x1006, 0x100c].into_iter());
+        assert!(starts.contains(0x1000));
+        assert!(!starts.contains(0x1004));
+        assert!(starts.contains(0x1005));
+        assert!(starts.contains(0x1006));
+        assert!(!starts.contains(0x1008));
+
+        // starts outside of the range are left alone
+        assert!(!starts.contains(0x1002));
+        assert!(starts.contains(0x100c));
+    }
+}
@@ -1,11 +1,25 @@
 mod fmt;
 mod blocks;
-
-use decoder::{Decodable, Decoded};
-use object::{Endianness, Object, ObjectSegment};
+mod export;
+mod recursion;
+mod functions;
+mod cfg;
+mod xrefs;
+mod lazy;
+mod analysis;
+pub mod project;
+pub mod patch;
+pub mod signatures;
+mod search;
+mod offsets;
+
+use decoder::{Decodable, Decoded, Flow};
+use object::{Endianness, Object, ObjectKind, ObjectSegment};
 use object::{Architecture, BinaryFormat};
 use object::read::File as ObjectFile;
-use processor_shared::{AddressMap, Addressed, PhysAddr, Section, SectionKind, Segment};
+use processor_shared::{
+    AddressMap, Addressed, Permissions, PhysAddr, Section, SectionKind, Segment,
+};
 use debugvault::Index;
 use tokenizing::Token;
 use binformat::{elf, macho, pe, RawSymbol};
@@ -17,9 +31,16 @@ use arm::armv7 as armv7;
 use arm::armv8::a64 as aarch64;
 
 use std::fs::File;
-use std::mem::ManuallyDrop;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
 
 pub use blocks::{BlockContent, Block};
+pub use cfg::{BasicBlock, Cfg, Edge, EdgeKind};
+pub use xrefs::{Xref, XrefKind, Xrefs};
+pub use lazy::InstructionRef;
+pub use project::{DataType, Project};
+pub use search::MAX_MATCHES;
 
 /// FIXME: This is way too large and way too broad.
 ///        Especially since these are being started for any address with a faulty decoding.
@@ -30,103 +51,82 @@ pub enum Error {
     NotAnExecutable,
     DecompressionFailed(object::Error),
     UnknownArchitecture(object::Architecture),
+    Project(project::Error),
 }
 
-pub union Instruction {
-    x86: ManuallyDrop<x86_64::protected_mode::Instruction>,
-    x64: ManuallyDrop<x86_64::long_mode::Instruction>,
-    riscv: ManuallyDrop<riscv::Instruction>,
-    mips: ManuallyDrop<mips::Instruction>,
-    armv7: ManuallyDrop<armv7::Instruction>,
-    aarch64: ManuallyDrop<aarch64::Instruction>,
+/// How a binary's code is decoded.
+///
+/// Either way instructions aren't kept around, they're decoded again when they're looked at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decoding {
+    /// Analyze all code up front, keeping only the results of the analyses and where
+    /// instructions start.
+    Eager,
+    /// Decode code when it's first looked at, without analyzing it or loading the debug info.
+    /// See [`Processor::analyze`] for analyzing it afterwards.
+    Lazy,
 }
 
-macro_rules! impl_recursion {
-    ($symbols:expr, $errors:expr, $instructions:expr, $sections:expr,
-     $max_instruction_width:expr, $decoder:expr, $arch:ident) => {{
-        $max_instruction_width = $decoder.max_width();
+impl Decoding {
+    /// Binaries larger than this are decoded lazily by default.
+    const LAZY_FILE_SIZE: u64 = 256 * 1024 * 1024;
 
-        let width_guess = if $max_instruction_width == 4 {
-            4
-        } else {
-            5
-        };
+    /// Decode lazily if the binary at `path` is too large to decode up front.
+    pub fn for_file<P: AsRef<Path>>(path: P) -> Self {
+        match std::fs::metadata(path) {
+            Ok(metadata) if metadata.len() > Self::LAZY_FILE_SIZE => Self::Lazy,
+            _ => Self::Eager,
+        }
+    }
+}
 
-        for section in $sections.iter().filter(|s| s.kind == SectionKind::Code) {
-            let mut prev_inst = None;
-            let mut reader = decoder::Reader::new(section.bytes());
-            let mut ip = section.start;
+/// An instruction of any architecture.
+pub type Instruction = Box<dyn AnyInstruction>;
 
-            log::complex!(
-                w "[processor::recurse] analyzing section ",
-                b &*section.name,
-                w " <",
-                g format!("{:x}", section.start),
-                w "..",
-                g format!("{:x}", section.end),
-                w ">.",
-            );
+/// Object-safe part of [`Decoded`], so instructions of every architecture can be stored
+/// the same way.
+pub trait AnyInstruction: Send + Sync {
+    /// Relatively slow tokenization of the instruction.
+    /// Xref's get resolved which requires some extra computation.
+    fn tokens(&self, symbols: &Index) -> Vec<Token>;
+    fn width(&self) -> usize;
+    fn flow(&self) -> Flow;
+    fn instruction_set(&self) -> Option<&'static str>;
+}
 
-            // guessing an average of 5 byte long instructions
-            log::PROGRESS.set("Decoding instructions", section.bytes().len() / width_guess);
-
-            loop {
-                // prefetch next cache line line
-                #[cfg(target_arch = "x86")]
-                unsafe {
-                    core::arch::x86::_mm_prefetch(
-                        reader.as_ptr() as *const i8,
-                        core::arch::x86::_MM_HINT_NTA
-                    );
-                }
-
-                #[cfg(target_arch = "x86_64")]
-                unsafe {
-                    core::arch::x86_64::_mm_prefetch(
-                        reader.as_ptr() as *const i8,
-                        core::arch::x86_64::_MM_HINT_NTA
-                    );
-                }
-
-                match $decoder.decode(&mut reader) {
-                    Ok(mut instruction) => {
-                        instruction.update_rel_addrs(ip, prev_inst);
-
-                        let width = instruction.width();
-                        $instructions.push(Addressed {
-                            addr: ip,
-                            item: Instruction {
-                                $arch: std::mem::ManuallyDrop::new(instruction)
-                            }
-                        });
-
-                        prev_inst = $instructions.last().map(|inst| {
-                            unsafe { &*inst.item.$arch }
-                        });
-                        ip += width;
-                    }
-                    Err(error) => {
-                        if error.kind == decoder::ErrorKind::ExhaustedInput {
-                            break;
-                        }
-
-                        let width = error.size();
-                        $errors.push(Addressed {
-                            addr: ip,
-                            item: error
-                        });
-                        prev_inst = None;
-                        ip += width;
-                    }
-                }
-
-                log::PROGRESS.step();
-            }
-        }
-    }};
+impl<T: Decoded + Send + Sync> AnyInstruction for T {
+    fn tokens(&self, symbols: &Index) -> Vec<Token> {
+        Decoded::tokens(self, symbols)
+    }
+
+    fn width(&self) -> usize {
+        Decoded::width(self)
+    }
+
+    fn flow(&self) -> Flow {
+        Decoded::flow(self)
+    }
+
+    fn instruction_set(&self) -> Option<&'static str> {
+        Decoded::instruction_set(self)
+    }
+}
+
+/// Sweeper decoding with `decoder`, along with how wide its instructions can be.
+fn sweeper<D>(decoder: D) -> (Arc<dyn lazy::Sweep>, usize)
+where
+    D: Decodable + Send + Sync + 'static,
+    D::Instruction: Send + Sync + 'static,
+{
+    let max_width = decoder.max_width();
+    (Arc::new(lazy::Sweeper { decoder }), max_width)
 }
 
 /// Architecture agnostic analysis of a module.
+///
+/// Cloning is cheap as the results of analysis are shared between clones. The user's edits
+/// only copy what they change, so a clone can be edited while the original is still in use.
+#[derive(Clone)]
 pub struct Processor {
     /// Where execution start. Might be zero in case of libraries.
     pub entrypoint: PhysAddr,
@@ -135,13 +135,16 @@ pub struct Processor {
     pub path: std::path::PathBuf,
 
     /// Symbol lookup by physical address.
-    pub index: Index,
+    pub index: Arc<Index>,
 
     /// File handle to binary,
-    _file: File,
+    _file: Arc<File>,
 
     /// A memory map of the binary.
-    _mmap: Mmap,
+    _mmap: Arc<Mmap>,
+
+    /// Hex encoded SHA-256 of the binary, computed when it's first needed.
+    hash: OnceLock<String>,
 
     /// Object's sections sorted by address.
     sections: Vec<Section>,
@@ -151,20 +154,31 @@ pub struct Processor {
 
     /// Errors occurred in decoding instructions.
     /// Sorted by address.
-    errors: AddressMap<decoder::Error>,
+    errors: Arc<AddressMap<decoder::Error>>,
 
-    /// Successfully decoded instructions.
-    /// Sorted by address.
-    instructions: AddressMap<Instruction>,
+    /// Code that's decoded on demand.
+    code: Arc<lazy::Lazy>,
+
+    /// How the binary's code was decoded.
+    decoding: Decoding,
+
+    /// References between addresses in the binary.
+    xrefs: Arc<Xrefs>,
+
+    /// Parts of code sections that hold data, sorted by address.
+    data_ranges: Vec<Range<PhysAddr>>,
+
+    /// Targets of relocations that aren't applied or don't have a name, sorted by address.
+    relocations: Arc<AddressMap<String>>,
+
+    /// Addresses given to the sections of a relocatable object.
+    layout: Option<Arc<elf::Layout>>,
 
     /// How many bytes an instruction given the architecture.
     max_instruction_width: usize,
 
-    /// Function pointer to an [`Instruction`]'s implementation of [`Decoded::tokens`].
-    instruction_tokens: fn(&Instruction, &Index) -> Vec<Token>,
-
-    /// Function pointer to an [`Instruction`]'s implementation of [`Decoded::width`].
-    instruction_width: fn(&Instruction) -> usize,
+    /// The user's work on the binary.
+    project: Project,
 
     /// Target's instruction set.
     arch: Architecture,
@@ -174,7 +188,11 @@ pub struct Processor {
 }
 
 impl Processor {
-    pub fn parse<P: AsRef<std::path::Path>>(path: P) -> Result<Self, Error> {
+    pub fn parse<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::parse_with(path, Decoding::Eager)
+    }
+
+    pub fn parse_with<P: AsRef<Path>>(path: P, decoding: Decoding) -> Result<Self, Error> {
         let file = std::fs::File::open(path.as_ref()).map_err(Error::IO)?;
         let mmap = unsafe { Mmap::map(&file).map_err(Error::IO)? };
         let binary: &'static [u8] = unsafe { std::mem::transmute(&mmap[..]) };
@@ -185,6 +203,11 @@ impl Processor {
 
         let mut syms = AddressMap::default();
         let mut sections = Vec::new();
+        let mut arm_modes = AddressMap::default();
+        let mut data_ranges = Vec::new();
+        let mut plt_syms = AddressMap::default();
+        let mut relocations = AddressMap::default();
+        let mut layout = None;
         match &obj {
             object::File::MachO32(macho) => {
                 let debug_info = macho::MachoDebugInfo::parse(macho)?;
@@ -200,11 +223,21 @@ impl Processor {
                 let debug_info = elf::ElfDebugInfo::parse(elf)?;
                 sections.extend(debug_info.sections);
                 syms.extend(debug_info.syms);
+                arm_modes.extend(debug_info.arm_modes);
+                data_ranges.extend(debug_info.data_ranges);
+                plt_syms.extend(debug_info.plt_syms);
+                relocations.extend(debug_info.relocations);
+                layout = debug_info.layout.map(Arc::new);
             }
             object::File::Elf64(elf) => {
                 let debug_info = elf::ElfDebugInfo::parse(elf)?;
                 sections.extend(debug_info.sections);
                 syms.extend(debug_info.syms);
+                arm_modes.extend(debug_info.arm_modes);
+                data_ranges.extend(debug_info.data_ranges);
+                plt_syms.extend(debug_info.plt_syms);
+                relocations.extend(debug_info.relocations);
+                layout = debug_info.layout.map(Arc::new);
             }
             object::File::Pe32(pe) => {
                 let debug_info = pe::PeDebugInfo::parse(pe)?;
@@ -226,7 +259,18 @@ impl Processor {
             });
         }
 
-        let index = Index::parse(&obj, &path, syms).map_err(Error::Debug)?;
+        for sym in plt_syms.iter() {
+            syms.push(Addressed {
+                addr: sym.addr,
+                item: RawSymbol { name: &sym.item, module: None }
+            });
+        }
+
+        // debug info is loaded along with the analysis of lazily decoded code
+        let mut index = match decoding {
+            Decoding::Eager => Index::parse(&obj, &path, syms).map_err(Error::Debug)?,
+            Decoding::Lazy => Index::parse_symbols(syms),
+        };
         let entrypoint = index.get_func_by_name("entry").unwrap_or(0);
 
         if entrypoint != 0 {
@@ -242,8 +286,10 @@ impl Processor {
             let name = segment.name()?.unwrap_or("unknown").to_string();
             let start = segment.address() as PhysAddr;
             let end = start + segment.size() as PhysAddr;
+            let (offset, size) = segment.file_range();
+            let file_range = offset as usize..(offset + size) as usize;
 
-            segments.push(Segment { name, start, end });
+            segments.push(Segment { name, start, end, file_range });
         }
 
         segments.sort_unstable_by_key(|s| s.start);
@@ -259,7 +305,7 @@ impl Processor {
             let rva = entrypoint - obj.relative_address_base() as PhysAddr;
             let start = obj.relative_address_base() as PhysAddr + rva;
             let end = start + binary.len() - rva;
-            let section = Section::new(
+            let mut section = Section::new(
                 "flat".to_string(),
                 "GENERATED",
                 SectionKind::Code,
@@ -267,140 +313,86 @@ impl Processor {
                 base + start,
                 end,
             );
+            section.permissions = Permissions {
+                read: true,
+                write: false,
+                execute: true,
+            };
+            section.file_offset = Some(rva);
 
             sections.push(section);
         }
 
-        if segments.is_empty() {
+        // relocatable objects aren't loaded as a whole, their sections are placed apart
+        if segments.is_empty() && obj.kind() != ObjectKind::Relocatable {
             let start = obj.relative_address_base() as PhysAddr;
             let end = start + binary.len();
             let segment = Segment {
                 name: "flat (generated)".to_string(),
                 start,
                 end,
+                file_range: 0..binary.len(),
             };
 
             segments.push(segment);
         }
 
+        index.file_map = offsets::file_map(&sections, &segments);
+
         let arch = obj.architecture();
-        let (instruction_tokens, instruction_width) = unsafe {
-            match arch {
-                Architecture::Riscv32 | Architecture::Riscv64 => (
-                    std::mem::transmute(<riscv::Instruction as Decoded>::tokens as usize),
-                    std::mem::transmute(<riscv::Instruction as Decoded>::width as usize),
-                ),
-                Architecture::Mips | Architecture::Mips64 => (
-                    std::mem::transmute(<mips::Instruction as Decoded>::tokens as usize),
-                    std::mem::transmute(<mips::Instruction as Decoded>::width as usize),
-                ),
-                Architecture::X86_64_X32 | Architecture::I386 => (
-                    std::mem::transmute(<x86::Instruction as Decoded>::tokens as usize),
-                    std::mem::transmute(<x86::Instruction as Decoded>::width as usize),
-                ),
-                Architecture::X86_64 => (
-                    std::mem::transmute(<x64::Instruction as Decoded>::tokens as usize),
-                    std::mem::transmute(<x64::Instruction as Decoded>::width as usize),
-                ),
-                Architecture::Arm => (
-                    std::mem::transmute(<armv7::Instruction as Decoded>::tokens as usize),
-                    std::mem::transmute(<armv7::Instruction as Decoded>::width as usize),
-                ),
-                Architecture::Aarch64 | Architecture::Aarch64_Ilp32 => (
-                    std::mem::transmute(<aarch64::Instruction as Decoded>::tokens as usize),
-                    std::mem::transmute(<aarch64::Instruction as Decoded>::width as usize),
-                ),
-                arch => return Err(Error::UnknownArchitecture(arch)),
+        let (sweep, max_instruction_width) = match arch {
+            Architecture::Riscv32 => sweeper(riscv::Decoder { is_64: false }),
+            Architecture::Riscv64 => sweeper(riscv::Decoder { is_64: true }),
+            Architecture::Mips | Architecture::Mips64 => sweeper(mips::Decoder::default()),
+            Architecture::X86_64_X32 | Architecture::I386 => sweeper(x86::Decoder::default()),
+            Architecture::X86_64 => sweeper(x64::Decoder::default()),
+            Architecture::Arm => sweeper(armv7::Decoder::default().with_regions(
+                arm_modes
+                    .iter()
+                    .map(|mode| (mode.addr, mode.item == elf::ArmMode::Thumb))
+                    .collect(),
+            )),
+            Architecture::Aarch64 | Architecture::Aarch64_Ilp32 => {
+                sweeper(aarch64::Decoder::default())
             }
+            arch => return Err(Error::UnknownArchitecture(arch)),
         };
 
-        let mut instructions = AddressMap::default();
         let mut errors = AddressMap::default();
-        let max_instruction_width;
-
-        match arch {
-            Architecture::Riscv32 => {
-                impl_recursion!(
-                    &index,
-                    &mut errors,
-                    &mut instructions,
-                    &mut sections,
-                    max_instruction_width,
-                    riscv::Decoder { is_64: false },
-                    riscv
-                )
-            }
-            Architecture::Riscv64 => {
-                impl_recursion!(
-                    &index,
-                    &mut errors,
-                    &mut instructions,
-                    &mut sections,
-                    max_instruction_width,
-                    riscv::Decoder { is_64: true },
-                    riscv
-                )
-            }
-            Architecture::Mips | Architecture::Mips64 => {
-                impl_recursion!(
-                    &index,
-                    &mut errors,
-                    &mut instructions,
-                    &mut sections,
-                    max_instruction_width,
-                    mips::Decoder::default(),
-                    mips
-                )
-            }
-            Architecture::X86_64_X32 | Architecture::I386 => {
-                impl_recursion!(
-                    &index,
-                    &mut errors,
-                    &mut instructions,
-                    &mut sections,
-                    max_instruction_width,
-                    x86::Decoder::default(),
-                    x86
-                )
-            }
-            Architecture::X86_64 => {
-                impl_recursion!(
-                    &index,
-                    &mut errors,
-                    &mut instructions,
-                    &mut sections,
-                    max_instruction_width,
-                    x64::Decoder::default(),
-                    x64
-                )
-            }
-            Architecture::Arm => {
-                impl_recursion!(
-                    &index,
-                    &mut errors,
-                    &mut instructions,
-                    &mut sections,
-                    max_instruction_width,
-                    armv7::Decoder::default(),
-                    armv7
-                )
-            },
-            Architecture::Aarch64 | Architecture::Aarch64_Ilp32 => {
-                impl_recursion!(
-                    &index,
-                    &mut errors,
-                    &mut instructions,
-                    &mut sections,
-                    max_instruction_width,
-                    aarch64::Decoder::default(),
-                    aarch64
-                )
-            }
-            _ => unreachable!(),
-        };
+        let mut functions = Vec::new();
+        let mut xrefs: Vec<Xref> = Vec::new();
+        let mut starts = None;
+
+        if decoding == Decoding::Eager {
+            let unwind_ranges = recursion::unwind_ranges(&obj, &sections);
+            let entries =
+                recursion::entries(&obj, layout.as_deref(), entrypoint, &unwind_ranges);
+            let target = lazy::Target {
+                arch,
+                endianness: obj.endianness(),
+                sections: &sections,
+                data_ranges: &data_ranges,
+                unwind_ranges: &unwind_ranges,
+            };
 
-        instructions.sort_unstable();
-        errors.sort_unstable();
+            let analysis = sweep.analyze(&target, &sections, &entries);
+            errors = analysis.errors;
+            functions = analysis.functions;
+            xrefs = analysis.xrefs;
+
+            // only keep where instructions start, they're decoded again when looked at
+            starts = Some(lazy::Starts::new(&sections, analysis.starts.into_iter()));
+        }
+
+        index.insert_generated(&functions);
+        signatures::apply(obj.architecture(), &sections, &mut index);
+
+        xrefs.extend(xrefs::from_pointers(&sections, obj.endianness()));
+        let xrefs = Xrefs::new(xrefs);
+        index.insert_labels(&xrefs.local_targets(&index));
+
+        let symbols: Vec<PhysAddr> = index.syms.iter().map(|sym| sym.addr).collect();
+        let code = lazy::Lazy::new(sweep, &sections, &symbols, &data_ranges, starts);
 
         log::complex!(
             w "[processor::parse] took ",
@@ -414,14 +406,19 @@ impl Processor {
             path,
             sections,
             segments,
-            errors,
-            instructions,
-            index,
-            _file: file,
-            _mmap: mmap,
+            errors: Arc::new(errors),
+            code: Arc::new(code),
+            decoding,
+            xrefs: Arc::new(xrefs),
+            data_ranges,
+            relocations: Arc::new(relocations),
+            layout,
+            index: Arc::new(index),
+            _file: Arc::new(file),
+            _mmap: Arc::new(mmap),
+            hash: OnceLock::new(),
             max_instruction_width,
-            instruction_tokens,
-            instruction_width,
+            project: Project::default(),
             arch,
             endianness: obj.endianness(),
         })
@@ -430,25 +427,236 @@ impl Processor {
     /// Relatively slow tokenization of an [`Instruction`].
     /// Xref's get resolved which requires some extra computation.
     pub fn instruction_tokens(&self, instruction: &Instruction, symbols: &Index) -> Vec<Token> {
-        (self.instruction_tokens)(instruction, symbols)
+        instruction.tokens(symbols)
     }
 
     pub fn instruction_width(&self, instruction: &Instruction) -> usize {
-        (self.instruction_width)(instruction)
+        instruction.width()
+    }
+
+    pub fn instruction_flow(&self, instruction: &Instruction) -> Flow {
+        instruction.flow()
+    }
+
+    /// Name of the instruction set an [`Instruction`] is encoded in, for architectures that
+    /// switch between more than one.
+    pub fn instruction_set(&self, instruction: &Instruction) -> Option<&'static str> {
+        instruction.instruction_set()
     }
 
-    pub fn error_by_addr(&self, addr: PhysAddr) -> Option<&decoder::Error> {
-        match self.errors.search(addr) {
-            Ok(idx) => Some(&self.errors[idx].item),
-            Err(..) => None,
+    /// Control-flow graph of the function starting at `addr`.
+    ///
+    /// Returns `None` if there's no instruction at `addr`.
+    pub fn cfg_for(&self, addr: PhysAddr) -> Option<Cfg> {
+        // code is decoded up to where the next function starts
+        let end = self
+            .index
+            .syms
+            .iter()
+            .skip_while(|sym| sym.addr <= addr)
+            .find(|sym| !sym.item.intrinsic())
+            .map_or(usize::MAX, |sym| sym.addr);
+
+        let region = self.code.decode(addr..end)?;
+
+        cfg::build(
+            addr,
+            &region.instructions,
+            |inst| self.instruction_flow(inst),
+            |inst| self.instruction_width(inst),
+            |addr| self.index.get_sym_by_addr(addr).is_some_and(|sym| !sym.intrinsic()),
+            matches!(self.arch, Architecture::Mips | Architecture::Mips64),
+        )
+    }
+
+    /// References to `addr` from instructions and pointers.
+    pub fn xrefs_to(&self, addr: PhysAddr) -> &[Xref] {
+        self.xrefs.to(addr)
+    }
+
+    /// References made by the instruction or pointer at `addr`.
+    pub fn xrefs_from(&self, addr: PhysAddr) -> &[Xref] {
+        self.xrefs.from(addr)
+    }
+
+    /// Part of a code section holding data that `addr` is in.
+    fn data_range(&self, addr: PhysAddr) -> Option<&Range<PhysAddr>> {
+        let idx = self.data_ranges.partition_point(|range| range.end <= addr);
+        self.data_ranges.get(idx).filter(|range| range.contains(&addr))
+    }
+
+    pub fn error_by_addr(&self, addr: PhysAddr) -> Option<decoder::Error> {
+        if let Ok(idx) = self.errors.search(addr) {
+            return Some(self.errors[idx].item);
+        }
+
+        let region = self.code.region(addr)?;
+        let idx = region.errors.search(addr).ok()?;
+        Some(region.errors[idx].item)
+    }
+
+    pub fn instruction_by_addr(&self, addr: PhysAddr) -> Option<InstructionRef> {
+        let region = self.code.region(addr)?;
+        let idx = region.instructions.search(addr).ok()?;
+        Some(InstructionRef { region, idx })
+    }
+
+    /// The instruction preceding the one at `addr`.
+    fn instruction_before(&self, addr: PhysAddr) -> Option<(PhysAddr, InstructionRef)> {
+        let region = self.code.region(addr)?;
+        let idx = match region.instructions.search(addr) {
+            Ok(idx) | Err(idx) => idx,
+        };
+
+        if idx > 0 {
+            let prev = region.instructions[idx - 1].addr;
+            return Some((prev, InstructionRef { region, idx: idx - 1 }));
+        }
+
+        // the first instruction of a region follows the last one in the previous region
+        let region = self.code.region(self.code.bounds(addr)?.start.checked_sub(1)?)?;
+        let prev = region.instructions.last()?.addr;
+        let idx = region.instructions.len() - 1;
+        Some((prev, InstructionRef { region, idx }))
+    }
+
+    /// Hex encoded SHA-256 of the binary.
+    pub fn hash(&self) -> &str {
+        use sha2::{Digest, Sha256};
+
+        self.hash.get_or_init(|| {
+            Sha256::digest(&self._mmap[..]).iter().map(|byte| format!("{byte:02x}")).collect()
+        })
+    }
+
+    /// The user's work on the binary.
+    pub fn project(&self) -> &Project {
+        &self.project
+    }
+
+    /// Apply the project at `path` on top of the binary, if it was made for this binary.
+    pub fn open_project(&mut self, path: &Path) -> Result<(), project::Error> {
+        let project = Project::open(path)?;
+        if project.hash != self.hash() {
+            return Err(project::Error::HashMismatch);
+        }
+
+        self.apply_project(project);
+
+        log::complex!(
+            w "[processor::open_project] opened ",
+            w format!("{path:?}.")
+        );
+
+        Ok(())
+    }
+
+    /// Apply a project on top of the binary without checking what binary it was made for.
+    pub fn apply_project(&mut self, project: Project) {
+        let names = project.names.iter().map(|(addr, name)| (*addr, name.as_str()));
+        Arc::make_mut(&mut self.index).rename(names);
+        self.project = project;
+
+        // patches are checked again as the project might've been edited by hand
+        for (addr, bytes) in std::mem::take(&mut self.project.patches) {
+            if let Err(err) = self.patch(addr, &bytes) {
+                log::complex!(
+                    w "[processor::apply_project] ",
+                    y format!("Failed to apply patch at {addr:#X}: {err}"),
+                    w ".",
+                );
+            }
         }
     }
 
-    pub fn instruction_by_addr(&self, addr: PhysAddr) -> Option<&Instruction> {
-        match self.instructions.search(addr) {
-            Ok(idx) => Some(&self.instructions[idx].item),
-            Err(..) => None,
+    /// Give the symbol at `addr` a new name, creating a symbol if there isn't one yet.
+    pub fn rename(&mut self, addr: PhysAddr, name: &str) {
+        Arc::make_mut(&mut self.index).rename([(addr, name)]);
+        self.project.names.insert(addr, name.to_string());
+    }
+
+    /// Target of a relocation applied to the bytes in `range`, for relocatable objects.
+    pub fn relocation_in(&self, range: Range<PhysAddr>) -> Option<&str> {
+        let idx = self.relocations.search(range.start).unwrap_or_else(|idx| idx);
+        self.relocations
+            .get(idx)
+            .filter(|reloc| reloc.addr < range.end)
+            .map(|reloc| &reloc.item as &str)
+    }
+
+    /// The user's comment on the instruction at `addr`.
+    pub fn comment(&self, addr: PhysAddr) -> Option<&str> {
+        self.project.comments.get(&addr).map(String::as_str)
+    }
+
+    /// Comment on the instruction at `addr`, an empty comment removes the existing one.
+    pub fn set_comment(&mut self, addr: PhysAddr, comment: &str) {
+        match comment.trim() {
+            "" => self.project.comments.remove(&addr),
+            comment => self.project.comments.insert(addr, comment.to_string()),
+        };
+    }
+
+    /// Addresses the user bookmarked, in order.
+    pub fn bookmarks(&self) -> impl Iterator<Item = PhysAddr> + '_ {
+        self.project.bookmarks.iter().copied()
+    }
+
+    /// Bookmark `addr` or remove its bookmark, returning whether it's bookmarked now.
+    pub fn toggle_bookmark(&mut self, addr: PhysAddr) -> bool {
+        if self.project.bookmarks.remove(&addr) {
+            return false;
         }
+
+        self.project.bookmarks.insert(addr)
+    }
+
+    /// Type the user gave the data at `addr`.
+    pub fn data_type(&self, addr: PhysAddr) -> Option<DataType> {
+        self.project.data_types.get(&addr).copied()
+    }
+
+    /// Show the data at `addr` as `tipe`, `None` removes the existing type. Returns `false`
+    /// if the data can't be shown as `tipe`, as it's not in code or plain data or runs past
+    /// the end of its section.
+    pub fn set_data_type(&mut self, addr: PhysAddr, tipe: Option<DataType>) -> bool {
+        let tipe = match tipe {
+            Some(tipe) => tipe,
+            None => return self.project.data_types.remove(&addr).is_some(),
+        };
+
+        let previous = self.project.data_types.insert(addr, tipe);
+        let fits = self
+            .section_by_addr(addr)
+            .is_some_and(|section| self.typed_data_end(addr, section).is_some());
+
+        if !fits {
+            match previous {
+                Some(previous) => self.project.data_types.insert(addr, previous),
+                None => self.project.data_types.remove(&addr),
+            };
+        }
+
+        fits
+    }
+
+    /// Save the user's work on the binary to `path`, along with the GUI's `layout`.
+    pub fn save_project(
+        &self,
+        path: &Path,
+        layout: Option<project::Tile>,
+    ) -> Result<(), project::Error> {
+        // the project might be opened from another working directory
+        let mut project = self.project.clone();
+        project.binary = std::fs::canonicalize(&self.path).unwrap_or_else(|_| self.path.clone());
+        project.hash = self.hash().to_string();
+        project.layout = layout;
+        project.save(path)
+    }
+
+    /// How the binary's code was decoded.
+    pub fn decoding(&self) -> Decoding {
+        self.decoding
     }
 
     pub fn segments(&self) -> impl DoubleEndedIterator<Item = &Segment> {
@@ -485,27 +693,6 @@ impl Processor {
     }
 }
 
-impl Drop for Processor {
-    /// Required `Drop` impl as [`Instruction`]'s a non-copy union.
-    fn drop(&mut self) {
-        for Addressed { item: inst, .. } in self.instructions.iter_mut() {
-            match self.arch {
-                Architecture::X86_64 => unsafe { ManuallyDrop::drop(&mut inst.x64) },
-                Architecture::X86_64_X32 | Architecture::I386 => unsafe {
-                    ManuallyDrop::drop(&mut inst.x86)
-                },
-                Architecture::Riscv64 | Architecture::Riscv32 => unsafe {
-                    ManuallyDrop::drop(&mut inst.riscv)
-                },
-                Architecture::Mips | Architecture::Mips64 => unsafe {
-                    ManuallyDrop::drop(&mut inst.mips)
-                },
-                _ => {}
-            }
-        }
-    }
-}
-
 impl From<object::Error> for Error {
     fn from(err: object::Error) -> Self {
         Error::Object(err)
@@ -0,0 +1,52 @@
+//! Translating between the addresses shown in the listing and offsets in the binary's file.
+
+use crate::Processor;
+use processor_shared::{FileMap, PhysAddr, Section, Segment};
+
+/// Build the [`FileMap`] of a binary. Segments are what's loaded, so they're preferred over
+/// sections, which only fill in what isn't in a segment (e.g. in object files).
+pub(crate) fn file_map(sections: &[Section], segments: &[Segment]) -> FileMap {
+    let mut map = FileMap::default();
+
+    for segment in segments {
+        map.insert(segment.file_range.clone(), segment.start);
+    }
+
+    // sections that aren't loaded (e.g. `.comment`) don't have an address
+    for section in sections.iter().filter(|section| section.permissions.read) {
+        if let Some(offset) = section.file_offset {
+            let len = std::cmp::min(section.original_bytes().len(), section.end - section.start);
+            map.insert(offset..offset + len, section.start);
+        }
+    }
+
+    map
+}
+
+impl Processor {
+    /// Where the data at `addr` is stored in the binary's file.
+    pub fn va_to_offset(&self, addr: PhysAddr) -> Option<usize> {
+        self.index.file_map.addr_to_offset(addr)
+    }
+
+    /// Address where the data at `offset` in the binary's file is loaded.
+    pub fn offset_to_va(&self, offset: usize) -> Option<PhysAddr> {
+        self.index.file_map.offset_to_addr(offset)
+    }
+
+    /// Segment that `addr` is in.
+    pub fn segment_by_addr(&self, addr: PhysAddr) -> Option<&Segment> {
+        self.segments().find(|segment| (segment.start..segment.end).contains(&addr))
+    }
+
+    /// Segment whose data in the file contains `offset`.
+    pub fn segment_by_offset(&self, offset: usize) -> Option<&Segment> {
+        self.segments().find(|segment| segment.file_range.contains(&offset))
+    }
+
+    /// Section whose data in the file contains `offset`.
+    pub fn section_by_offset(&self, offset: usize) -> Option<&Section> {
+        let addr = self.offset_to_va(offset)?;
+        self.section_by_addr(addr)
+    }
+}
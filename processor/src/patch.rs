@@ -0,0 +1,256 @@
+//! Patching the bytes of a binary.
+//!
+//! Patches are part of the project and are applied on top of copies of the sections they're
+//! in, so the memory map of the binary is never written to. Once patched, a binary can be
+//! written out to a new file.
+
+use crate::Processor;
+use object::Architecture;
+use processor_shared::{PhysAddr, Section, SectionKind};
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub enum Error {
+    IO(std::io::Error),
+    /// There are no bytes to patch with.
+    Empty,
+    /// The address isn't stored in the binary's file.
+    Unmapped(PhysAddr),
+    /// The patch runs past the end of the section it starts in.
+    CrossesSection(PhysAddr),
+    /// There's no patch at the address.
+    NotPatched(PhysAddr),
+    /// The patched binary would replace the binary it's patching.
+    SameFile,
+    /// The instruction couldn't be assembled.
+    Assemble(assembler::Error),
+    /// There's no assembler for the binary's architecture.
+    UnsupportedArchitecture(Architecture),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IO(err) => err.fmt(f),
+            Self::Empty => f.write_str("Nothing to patch with"),
+            Self::Unmapped(addr) => {
+                f.write_fmt(format_args!("Address {addr:#X} isn't stored in the binary"))
+            }
+            Self::CrossesSection(addr) => f.write_fmt(format_args!(
+                "Patch at {addr:#X} runs past the end of its section"
+            )),
+            Self::NotPatched(addr) => f.write_fmt(format_args!("Address {addr:#X} isn't patched")),
+            Self::SameFile => f.write_str("Can't overwrite the binary that's being patched"),
+            Self::Assemble(err) => err.fmt(f),
+            Self::UnsupportedArchitecture(arch) => f.write_fmt(format_args!(
+                "Assembling {arch:?} instructions isn't supported"
+            )),
+        }
+    }
+}
+
+/// Bytes the user patched.
+#[derive(Debug, PartialEq)]
+pub struct Patch<'a> {
+    pub addr: PhysAddr,
+    pub bytes: &'a [u8],
+    /// Bytes in the binary that the patch replaces.
+    pub original: &'a [u8],
+}
+
+impl Processor {
+    /// Whether the data of `section` is where the file map says its addresses are in the file.
+    /// Sections that aren't loaded (e.g. `.comment`) can share addresses with ones that are.
+    fn is_loaded(&self, section: &Section) -> bool {
+        let data = section.original_bytes().as_ptr() as usize;
+        let offset = data.wrapping_sub(self._mmap.as_ptr() as usize);
+        self.va_to_offset(section.start) == Some(offset)
+    }
+
+    /// Loaded section that `addr` is in.
+    fn loaded_section(&self, addr: PhysAddr) -> Option<&Section> {
+        self.sections
+            .iter()
+            .find(|s| (s.start..s.end).contains(&addr) && self.is_loaded(s))
+    }
+
+    /// Check that all of `range` can be patched.
+    fn check_patch(&self, range: &Range<PhysAddr>) -> Result<(), Error> {
+        let section = self.loaded_section(range.start).ok_or(Error::Unmapped(range.start))?;
+        if range.end > section.start + section.original_bytes().len() {
+            return Err(Error::CrossesSection(range.start));
+        }
+
+        // a patch is written to one place in the file, so it can't span multiple segments
+        let offset = self.va_to_offset(range.start).ok_or(Error::Unmapped(range.start))?;
+        match self.va_to_offset(range.end - 1) {
+            Some(last) if last == offset + range.len() - 1 => Ok(()),
+            _ => Err(Error::CrossesSection(range.start)),
+        }
+    }
+
+    /// Replace the bytes at `addr`, decoding the code around them again. Patches that
+    /// overlap are merged into one.
+    pub fn patch(&mut self, addr: PhysAddr, bytes: &[u8]) -> Result<(), Error> {
+        if bytes.is_empty() {
+            return Err(Error::Empty);
+        }
+
+        let range = addr..addr + bytes.len();
+        self.check_patch(&range)?;
+
+        let overlapping: Vec<PhysAddr> = self
+            .project
+            .patches
+            .range(..range.end)
+            .filter(|(&start, patch)| start + patch.len() > range.start)
+            .map(|(&start, _)| start)
+            .collect();
+
+        let overlapping: Vec<(PhysAddr, Vec<u8>)> = overlapping
+            .into_iter()
+            .filter_map(|start| self.project.patches.remove_entry(&start))
+            .collect();
+
+        let start = overlapping.iter().map(|(start, _)| *start).fold(range.start, std::cmp::min);
+        let end = overlapping
+            .iter()
+            .map(|(start, patch)| start + patch.len())
+            .fold(range.end, std::cmp::max);
+
+        let mut merged = vec![0; end - start];
+        for (addr, patch) in overlapping {
+            merged[addr - start..][..patch.len()].copy_from_slice(&patch);
+        }
+        merged[range.start - start..][..bytes.len()].copy_from_slice(bytes);
+
+        self.project.patches.insert(start, merged);
+        self.apply_patches(start..end);
+
+        Ok(())
+    }
+
+    /// Assemble `src` and patch it in at `addr`. The rest of the last instruction it
+    /// overwrites is filled with nops. Returns how many bytes were patched.
+    pub fn assemble(&mut self, addr: PhysAddr, src: &str) -> Result<usize, Error> {
+        let (mut bytes, nop): (Vec<u8>, &[u8]) = match self.arch {
+            Architecture::X86_64 => (
+                assembler::x86_64::assemble(src, addr, &self.index).map_err(Error::Assemble)?,
+                &[0x90],
+            ),
+            Architecture::Aarch64 | Architecture::Aarch64_Ilp32 => (
+                assembler::aarch64::assemble(src, addr, &self.index).map_err(Error::Assemble)?,
+                &assembler::aarch64::NOP,
+            ),
+            Architecture::Riscv64 => (
+                assembler::riscv::assemble(src, addr, &self.index).map_err(Error::Assemble)?,
+                &assembler::riscv::NOP,
+            ),
+            arch => return Err(Error::UnsupportedArchitecture(arch)),
+        };
+
+        let mut end = addr;
+        while end < addr + bytes.len() {
+            match self.instruction_by_addr(end) {
+                Some(instruction) => end += self.instruction_width(&instruction),
+                None => break,
+            }
+        }
+
+        while addr + bytes.len() + nop.len() <= end {
+            bytes.extend_from_slice(nop);
+        }
+
+        // compressed instructions leave half a nop
+        if self.arch == Architecture::Riscv64 && addr + bytes.len() < end {
+            bytes.extend_from_slice(&assembler::riscv::C_NOP);
+        }
+
+        self.patch(addr, &bytes)?;
+        Ok(bytes.len())
+    }
+
+    /// Undo the patch that `addr` is in.
+    pub fn revert(&mut self, addr: PhysAddr) -> Result<(), Error> {
+        let (&start, patch) = self
+            .project
+            .patches
+            .range(..=addr)
+            .next_back()
+            .filter(|(&start, patch)| addr < start + patch.len())
+            .ok_or(Error::NotPatched(addr))?;
+
+        let range = start..start + patch.len();
+        self.project.patches.remove(&start);
+        self.apply_patches(range);
+
+        Ok(())
+    }
+
+    /// Patches sorted by address.
+    pub fn patches(&self) -> impl Iterator<Item = Patch<'_>> {
+        self.project.patches.iter().filter_map(|(&addr, bytes)| {
+            let section = self.loaded_section(addr)?;
+            let original = section.original_bytes().get(addr - section.start..)?;
+
+            Some(Patch {
+                addr,
+                bytes,
+                original: &original[..bytes.len()],
+            })
+        })
+    }
+
+    /// Update the sections that `range` is in after patching it.
+    fn apply_patches(&mut self, range: Range<PhysAddr>) {
+        let patched: Vec<usize> = (0..self.sections.len())
+            .filter(|&idx| {
+                let section = &self.sections[idx];
+                section.start < range.end && section.end > range.start && self.is_loaded(section)
+            })
+            .collect();
+
+        for &idx in patched.iter() {
+            self.sections[idx].patch(&self.project.patches);
+        }
+
+        for &idx in patched.iter() {
+            let section = &self.sections[idx];
+            if section.kind != SectionKind::Code {
+                continue;
+            }
+
+            // analysis only knows about the errors from before the patch
+            let code = Arc::make_mut(&mut self.code);
+            if let Some((decoded, errors)) = code.patch(section, range.clone()) {
+                let known = Arc::make_mut(&mut self.errors);
+                known.retain(|err| !decoded.contains(&err.addr));
+                known.extend(errors);
+                known.sort_unstable();
+            }
+        }
+    }
+
+    /// Write the binary with its patches applied to `path`.
+    pub fn export_patched(&self, path: &Path) -> Result<(), Error> {
+        let original = std::fs::canonicalize(&self.path).map_err(Error::IO)?;
+        if std::fs::canonicalize(path).is_ok_and(|path| path == original) {
+            return Err(Error::SameFile);
+        }
+
+        let mut binary = self._mmap.to_vec();
+        for (&addr, bytes) in self.project.patches.iter() {
+            let offset = self.va_to_offset(addr).ok_or(Error::Unmapped(addr))?;
+            binary[offset..][..bytes.len()].copy_from_slice(bytes);
+        }
+
+        std::fs::write(path, binary).map_err(Error::IO)?;
+
+        // keep the patched binary executable
+        let permissions = self._file.metadata().map_err(Error::IO)?.permissions();
+        std::fs::set_permissions(path, permissions).map_err(Error::IO)
+    }
+}
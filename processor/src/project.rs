@@ -0,0 +1,137 @@
+//! Projects hold the work done on a binary, so it isn't lost when the binary is closed.
+//!
+//! A project is stored as JSON, by default next to the binary with a `.bite` extension. It
+//! only applies to the binary it was made for, which is checked using the binary's hash.
+
+pub use commands::DataType;
+use processor_shared::PhysAddr;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Extension of project files.
+pub const EXTENSION: &str = "bite";
+
+/// Version of the project format, this is bumped whenever the format changes.
+const VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum Error {
+    IO(std::io::Error),
+    Format(serde_json::Error),
+    /// The project was saved by a newer version of bite.
+    UnsupportedVersion(u32),
+    /// The project was made for a different binary.
+    HashMismatch,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IO(err) => err.fmt(f),
+            Self::Format(err) => f.write_fmt(format_args!("Invalid project: {err}")),
+            Self::UnsupportedVersion(version) => {
+                f.write_fmt(format_args!("Project version {version} isn't supported"))
+            }
+            Self::HashMismatch => f.write_str("Project was made for a different binary"),
+        }
+    }
+}
+
+/// Layout of the GUI's tiles.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tile {
+    /// A pane, identified by its title.
+    Pane(String),
+    Tabs {
+        children: Vec<Tile>,
+        /// Index of the tab that's shown.
+        active: Option<usize>,
+    },
+    /// Tiles next to each other, each taking up a share of the width.
+    Horizontal {
+        children: Vec<Tile>,
+        shares: Vec<f32>,
+    },
+    /// Tiles on top of each other, each taking up a share of the height.
+    Vertical {
+        children: Vec<Tile>,
+        shares: Vec<f32>,
+    },
+    Grid {
+        children: Vec<Tile>,
+    },
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Project {
+    version: u32,
+
+    /// Binary the project was made for.
+    pub binary: PathBuf,
+
+    /// Hex encoded SHA-256 of the binary.
+    pub hash: String,
+
+    /// Names the user gave symbols, these replace the binary's own names.
+    pub names: BTreeMap<PhysAddr, String>,
+
+    /// Comments on addresses.
+    pub comments: BTreeMap<PhysAddr, String>,
+
+    /// Addresses the user marked to come back to.
+    pub bookmarks: BTreeSet<PhysAddr>,
+
+    /// Bytes the user patched, by the address they start at.
+    pub patches: BTreeMap<PhysAddr, Vec<u8>>,
+
+    /// Types the user gave data at an address.
+    pub data_types: BTreeMap<PhysAddr, DataType>,
+
+    /// Layout of the GUI's tiles when the project was saved.
+    pub layout: Option<Tile>,
+}
+
+impl Project {
+    /// Where the project of the binary at `binary` is stored by default.
+    pub fn path_for(binary: &Path) -> PathBuf {
+        let mut path = binary.as_os_str().to_owned();
+        path.push(".");
+        path.push(EXTENSION);
+        PathBuf::from(path)
+    }
+
+    /// Whether `path` looks like a project rather than a binary.
+    pub fn is_project(path: &Path) -> bool {
+        path.extension().is_some_and(|ext| ext == EXTENSION)
+    }
+
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let file = std::fs::File::open(path).map_err(Error::IO)?;
+        let project: Self =
+            serde_json::from_reader(std::io::BufReader::new(file)).map_err(Error::Format)?;
+
+        if project.version > VERSION {
+            return Err(Error::UnsupportedVersion(project.version));
+        }
+
+        Ok(project)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let project = Self {
+            version: VERSION,
+            ..self.clone()
+        };
+        let json = serde_json::to_vec_pretty(&project).map_err(Error::Format)?;
+
+        // write to a temporary file first so a failed save doesn't lose the previous one
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        std::fs::write(&tmp, json).map_err(Error::IO)?;
+        std::fs::rename(&tmp, path).map_err(Error::IO)
+    }
+}
@@ -0,0 +1,941 @@
+//! Recursive descent through code sections.
+//!
+//! Decoding starts at addresses known to contain code and follows the control flow of each
+//! decoded instruction. Bytes that aren't reachable this way are left undecoded and show up as
+//! data, which stops padding, jump tables and inline data from turning into garbage
+//! instructions.
+
+use binformat::elf;
+use binformat::pe::ExceptionDirectoryEntry;
+use decoder::{Decodable, Decoded};
+use gimli::UnwindSection;
+use object::read::File as ObjectFile;
+use object::{Architecture, Object, ObjectSection, ObjectSymbol, SymbolKind};
+use processor_shared::{AddressMap, Addressed, PhysAddr, Section, SectionKind};
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Addresses known to be the start of code, these are the entrypoint, function symbols and
+/// entries in the exception/unwind tables. Symbols of relocatable objects are placed where
+/// `layout` put their sections.
+pub fn entries(
+    obj: &ObjectFile,
+    layout: Option<&elf::Layout>,
+    entrypoint: PhysAddr,
+    unwind_ranges: &[Range<PhysAddr>],
+) -> Vec<PhysAddr> {
+    let mut entries = Vec::new();
+
+    if entrypoint != 0 {
+        entries.push(entrypoint);
+    }
+
+    // bit 0 of arm function symbols is set for thumb code and isn't part of the address
+    let mask = if obj.architecture() == Architecture::Arm { !1 } else { !0 };
+
+    // symbols without a kind might just as well be labels for data
+    entries.extend(
+        obj.symbols()
+            .chain(obj.dynamic_symbols())
+            .filter(|sym| sym.kind() == SymbolKind::Text && sym.is_definition())
+            .filter_map(|sym| match layout {
+                Some(layout) => layout.symbol_addr(&sym),
+                None => Some(sym.address() as PhysAddr),
+            })
+            .map(|addr| addr & mask),
+    );
+    entries.extend(unwind_ranges.iter().map(|range| range.start));
+
+    entries.sort_unstable();
+    entries.dedup();
+
+    log::complex!(
+        w "[processor::recurse] found ",
+        g entries.len().to_string(),
+        w " entries into code.",
+    );
+
+    entries
+}
+
+/// Function ranges described by the exception/unwind tables, sorted by their start.
+pub fn unwind_ranges(obj: &ObjectFile, sections: &[Section]) -> Vec<Range<PhysAddr>> {
+    let mut ranges = exception_dir_ranges(obj);
+    ranges.extend(eh_frame_ranges(obj, sections));
+    ranges.sort_unstable_by_key(|range| range.start);
+    ranges.dedup_by_key(|range| range.start);
+    ranges
+}
+
+/// Function ranges in a PE's `.pdata` section.
+fn exception_dir_ranges(obj: &ObjectFile) -> Vec<Range<PhysAddr>> {
+    // `.pdata` entries have a different layout on anything other than x86_64
+    if obj.architecture() != Architecture::X86_64 {
+        return Vec::new();
+    }
+
+    let data = match obj.section_by_name(".pdata").and_then(|s| s.data().ok()) {
+        Some(data) => data,
+        None => return Vec::new(),
+    };
+
+    let base = obj.relative_address_base() as PhysAddr;
+    let (entries, _) = match object::slice_from_bytes::<ExceptionDirectoryEntry>(
+        data,
+        data.len() / std::mem::size_of::<ExceptionDirectoryEntry>(),
+    ) {
+        Ok(entries) => entries,
+        Err(..) => return Vec::new(),
+    };
+
+    entries
+        .iter()
+        .map(|entry| base + entry.begin_addr as PhysAddr..base + entry.end_addr as PhysAddr)
+        .collect()
+}
+
+/// Function ranges described by the FDE's in an ELF's or Mach-O's `.eh_frame` section.
+///
+/// The parsed `sections` are read rather than the object's, as they hold the addresses and
+/// relocated data of relocatable objects.
+fn eh_frame_ranges(obj: &ObjectFile, sections: &[Section]) -> Vec<Range<PhysAddr>> {
+    let by_name = |names: [&str; 2]| sections.iter().find(|s| names.contains(&s.name.as_str()));
+
+    let section = match by_name([".eh_frame", "__eh_frame"]) {
+        Some(section) => section,
+        None => return Vec::new(),
+    };
+
+    let data = section.bytes();
+
+    let endian = if obj.is_little_endian() {
+        gimli::RunTimeEndian::Little
+    } else {
+        gimli::RunTimeEndian::Big
+    };
+
+    let mut eh_frame = gimli::EhFrame::new(data, endian);
+    eh_frame.set_address_size(if obj.is_64() { 8 } else { 4 });
+
+    let mut bases = gimli::BaseAddresses::default().set_eh_frame(section.start as u64);
+    if let Some(text) = by_name([".text", "__text"]) {
+        bases = bases.set_text(text.start as u64);
+    }
+    if let Some(got) = by_name([".got", "__got"]) {
+        bases = bases.set_got(got.start as u64);
+    }
+
+    let mut ranges = Vec::new();
+    let mut cfi_entries = eh_frame.entries(&bases);
+    loop {
+        match cfi_entries.next() {
+            Ok(Some(gimli::CieOrFde::Fde(partial))) => {
+                let fde = partial.parse(|eh_frame, bases, offset| {
+                    eh_frame.cie_from_offset(bases, offset)
+                });
+
+                if let Ok(fde) = fde {
+                    let start = fde.initial_address() as PhysAddr;
+                    ranges.push(start..start + fde.len() as PhysAddr);
+                }
+            }
+            Ok(Some(gimli::CieOrFde::Cie(..))) => continue,
+            Ok(None) => break,
+            Err(err) => {
+                log::complex!(
+                    w "[processor::recurse] failed to parse ",
+                    b ".eh_frame",
+                    w ": ",
+                    y err.to_string(),
+                    y "."
+                );
+                break;
+            }
+        }
+    }
+
+    ranges
+}
+
+/// Sections bigger than this are split up into chunks that are decoded in parallel.
+const CHUNK_SIZE: usize = 0x40000;
+
+/// How far an instruction at the end of a chunk can run into the next chunk, no instruction on
+/// any of the supported architectures is longer than this.
+const MAX_OVERHANG: usize = 16;
+
+/// Decode all code sections by following control flow from `entries`, skipping over the
+/// `data` in them.
+///
+/// Gaps that weren't reached are linearly swept afterwards and are only kept if they decode
+/// without any errors, otherwise they're left as data. Code sections without any entries or
+/// data are linearly swept as a whole.
+///
+/// Sections, and chunks of large sections, are decoded in parallel. Control flow that leaves a
+/// chunk is handed to the chunk it leads to in the next round, until no chunk has any entries
+/// left to follow.
+pub fn recurse<D>(
+    decoder: &D,
+    sections: &[Section],
+    entries: &[PhysAddr],
+    data: &[Range<PhysAddr>],
+    has_delay_slot: bool,
+) -> (AddressMap<D::Instruction>, AddressMap<decoder::Error>)
+where
+    D: Decodable + Sync,
+    D::Instruction: Send,
+{
+    recurse_chunked(decoder, sections, entries, data, has_delay_slot, CHUNK_SIZE)
+}
+
+fn recurse_chunked<D>(
+    decoder: &D,
+    sections: &[Section],
+    entries: &[PhysAddr],
+    data: &[Range<PhysAddr>],
+    has_delay_slot: bool,
+    chunk_size: usize,
+) -> (AddressMap<D::Instruction>, AddressMap<decoder::Error>)
+where
+    D: Decodable + Sync,
+    D::Instruction: Send,
+{
+    let sections: Vec<&Section> =
+        sections.iter().filter(|s| s.kind == SectionKind::Code).collect();
+
+    // guessing an average of 5 byte long instructions
+    let width_guess = if decoder.max_width() == 4 { 4 } else { 5 };
+    let total_len: usize = sections.iter().map(|s| s.bytes().len()).sum();
+    log::PROGRESS.set("Decoding instructions", total_len / width_guess);
+
+    let mut entries = entries.to_vec();
+    entries.sort_unstable();
+
+    let mut chunks = Vec::new();
+    for section in sections.iter() {
+        split(section, &entries, chunk_size, &mut chunks);
+    }
+
+    // data is treated as already decoded so nothing decodes into it
+    for chunk in chunks.iter_mut() {
+        for range in data {
+            let start = range.start.max(chunk.range.start) - chunk.range.start;
+            let end = range.end.saturating_sub(chunk.range.start).min(chunk.visited.len());
+            if start < end {
+                chunk.visited[start..end].fill(true);
+            }
+        }
+    }
+
+    let mut recursion = Recursion { decoder, has_delay_slot, chunks };
+    recursion.route(entries);
+    recursion.descend();
+
+    for idx in 1..recursion.chunks.len() {
+        link(&mut recursion.chunks, idx);
+    }
+
+    let mut swept = Vec::new();
+    for section in sections.iter() {
+        let reached = recursion
+            .chunks
+            .iter()
+            .filter(|chunk| std::ptr::eq(chunk.section, *section))
+            .any(|chunk| chunk.visited.contains(&true));
+
+        if !reached {
+            log::complex!(
+                w "[processor::recurse] no entries found in ",
+                b &*section.name,
+                w ", falling back to a linear sweep.",
+            );
+
+            swept.push(*section);
+        }
+    }
+
+    for_each_chunk(&mut recursion.chunks, |chunk| {
+        if swept.iter().any(|section| std::ptr::eq(chunk.section, *section)) {
+            chunk.sweep(decoder);
+        } else {
+            chunk.sweep_gaps(decoder, has_delay_slot);
+        }
+    });
+
+    // newly found code might lead to code in other chunks
+    recursion.route_outbox();
+    recursion.descend();
+
+    // code found in other chunks might have narrowed down gaps enough for them to decode
+    loop {
+        for idx in 1..recursion.chunks.len() {
+            link(&mut recursion.chunks, idx);
+        }
+
+        let swept = AtomicBool::new(false);
+        for_each_chunk(&mut recursion.chunks, |chunk| {
+            if chunk.sweep_rejected_gaps(decoder, has_delay_slot) {
+                swept.store(true, Ordering::Relaxed);
+            }
+        });
+
+        recursion.route_outbox();
+        recursion.descend();
+
+        if !swept.into_inner() {
+            break;
+        }
+    }
+
+    recursion.merge()
+}
+
+/// Let a chunk know how far the previous chunk's last instruction runs into it and whether the
+/// previous chunk ends in a gap that didn't decode.
+fn link<I>(chunks: &mut [Chunk<I>], idx: usize) {
+    let (prev, next) = chunks.split_at_mut(idx);
+    let (prev, next) = (&prev[idx - 1], &mut next[0]);
+    if prev.range.end == next.range.start {
+        let overhang = &prev.visited[prev.range.len()..];
+        for (visited, &overhang) in next.visited.iter_mut().zip(overhang) {
+            *visited |= overhang;
+        }
+
+        next.is_blocked = prev.rejected.iter().any(|gap| gap.end == prev.range.len());
+    }
+}
+
+/// Split a section into chunks of roughly `chunk_size` bytes.
+fn split<'a, I: Decoded>(
+    section: &'a Section,
+    entries: &[PhysAddr],
+    chunk_size: usize,
+    chunks: &mut Vec<Chunk<'a, I>>,
+) {
+    let len = section.bytes().len();
+    if len == 0 {
+        return;
+    }
+
+    let count = len.div_ceil(chunk_size);
+    let mut start = section.start;
+    for idx in 1..count {
+        let mut edge = section.start + idx * len / count;
+        let next_edge = section.start + (idx + 1) * len / count;
+
+        // splitting at an entry makes it unlikely for an instruction to cross the edge
+        let entry = entries[entries.partition_point(|&entry| entry < edge)..].first();
+        if let Some(&entry) = entry.filter(|&&entry| entry < next_edge) {
+            edge = entry;
+        }
+
+        if edge > start {
+            chunks.push(Chunk::new(section, start..edge));
+            start = edge;
+        }
+    }
+
+    chunks.push(Chunk::new(section, start..section.start + len));
+}
+
+/// Run `f` on every chunk, spread over all available threads.
+fn for_each_chunk<I: Send, F>(chunks: &mut [Chunk<I>], f: F)
+where
+    F: Fn(&mut Chunk<I>) + Sync,
+{
+    let thread_count = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let thread_count = thread_count.min(chunks.len());
+
+    // for a single chunk, perform single-threaded
+    if thread_count <= 1 {
+        chunks.iter_mut().for_each(f);
+        return;
+    }
+
+    let queue = std::sync::Mutex::new(chunks.iter_mut());
+    std::thread::scope(|s| {
+        for _ in 0..thread_count {
+            s.spawn(|| loop {
+                let chunk = queue.lock().unwrap().next();
+                match chunk {
+                    Some(chunk) => f(chunk),
+                    None => break,
+                }
+            });
+        }
+    });
+}
+
+struct Recursion<'a, D: Decodable> {
+    decoder: &'a D,
+    has_delay_slot: bool,
+    /// Parts of code sections that are decoded independently, sorted by address.
+    chunks: Vec<Chunk<'a, D::Instruction>>,
+}
+
+impl<D> Recursion<'_, D>
+where
+    D: Decodable + Sync,
+    D::Instruction: Send,
+{
+    fn chunk_idx(&self, addr: PhysAddr) -> Option<usize> {
+        let idx = self.chunks.partition_point(|chunk| chunk.range.end <= addr);
+        self.chunks.get(idx).filter(|chunk| chunk.range.contains(&addr)).map(|_| idx)
+    }
+
+    /// Hand each entry to the chunk it's located in.
+    fn route(&mut self, entries: Vec<PhysAddr>) {
+        for entry in entries {
+            if let Some(idx) = self.chunk_idx(entry) {
+                self.chunks[idx].entries.push(entry);
+            }
+        }
+    }
+
+    /// Hand the entries that lead out of each chunk to the chunks they lead to.
+    fn route_outbox(&mut self) {
+        let outbox = self.chunks.iter_mut().flat_map(|c| std::mem::take(&mut c.outbox)).collect();
+        self.route(outbox);
+    }
+
+    /// Follow control flow in rounds, until no chunk is left with entries.
+    fn descend(&mut self) {
+        let (decoder, has_delay_slot) = (self.decoder, self.has_delay_slot);
+
+        while self.chunks.iter().any(|chunk| !chunk.entries.is_empty()) {
+            for_each_chunk(&mut self.chunks, |chunk| chunk.descend(decoder, has_delay_slot));
+            self.route_outbox();
+        }
+    }
+
+    /// Merge the instructions and errors of every chunk, resynchronising decoding wherever an
+    /// instruction crosses into the next chunk.
+    fn merge(self) -> (AddressMap<D::Instruction>, AddressMap<decoder::Error>) {
+        let mut instructions = AddressMap::default();
+        let mut errors = AddressMap::default();
+        let mut edges = Vec::new();
+        let mut prev_end = None;
+
+        for mut chunk in self.chunks {
+            chunk.instructions.sort_unstable();
+            chunk.errors.sort_unstable();
+
+            if prev_end == Some(chunk.range.start) {
+                edges.push((chunk.section, chunk.range.start));
+            }
+
+            prev_end = Some(chunk.range.end);
+
+            instructions.extend(chunk.instructions);
+            errors.extend(chunk.errors);
+        }
+
+        for (section, edge) in edges {
+            resync(self.decoder, section, edge, &mut instructions, &mut errors);
+        }
+
+        (instructions, errors)
+    }
+}
+
+/// Decode the bytes after `edge` again if the instruction before it runs past the edge and
+/// doesn't line up with what was decoded after it.
+fn resync<D: Decodable>(
+    decoder: &D,
+    section: &Section,
+    edge: PhysAddr,
+    instructions: &mut AddressMap<D::Instruction>,
+    errors: &mut AddressMap<decoder::Error>,
+) {
+    let inst_idx = instructions.partition_point(|inst| inst.addr < edge);
+    let err_idx = errors.partition_point(|err| err.addr < edge);
+
+    let inst_end = inst_idx.checked_sub(1).map(|idx| {
+        let inst = &instructions[idx];
+        inst.addr + inst.item.width()
+    });
+    let err_end = err_idx.checked_sub(1).map(|idx| errors[idx].addr + errors[idx].item.size());
+    let mut ip = inst_end.max(err_end).unwrap_or(edge);
+    if ip <= edge || ip > section.start + section.bytes().len() {
+        return;
+    }
+
+    // anything starting in the bytes the instruction ran into is out of sync
+    let mut covered = ip;
+    let next_inst = instructions.partition_point(|inst| inst.addr < ip);
+    let next_err = errors.partition_point(|err| err.addr < ip);
+    for inst in instructions.drain(inst_idx..next_inst) {
+        covered = covered.max(inst.addr + inst.item.width());
+    }
+    for err in errors.drain(err_idx..next_err) {
+        covered = covered.max(err.addr + err.item.size());
+    }
+
+    while ip < covered {
+        let inst_idx = instructions.partition_point(|inst| inst.addr < ip);
+        let err_idx = errors.partition_point(|err| err.addr < ip);
+        if instructions.get(inst_idx).is_some_and(|inst| inst.addr == ip)
+            || errors.get(err_idx).is_some_and(|err| err.addr == ip)
+        {
+            break;
+        }
+
+        let mut reader = decoder::Reader::new(&section.bytes()[ip - section.start..]);
+        let (width, item) = match decoder.decode_at(&mut reader, ip) {
+            Ok(mut instruction) => {
+                let prev = inst_idx.checked_sub(1).map(|idx| &instructions[idx].item);
+                instruction.update_rel_addrs(ip, prev);
+                (instruction.width(), Ok(instruction))
+            }
+            Err(error) if error.kind == decoder::ErrorKind::ExhaustedInput => break,
+            Err(error) => (error.size(), Err(error)),
+        };
+
+        // the instruction might in turn run into instructions that are out of sync
+        let next_inst = instructions.partition_point(|inst| inst.addr < ip + width);
+        let next_err = errors.partition_point(|err| err.addr < ip + width);
+        for inst in instructions.drain(inst_idx..next_inst) {
+            covered = covered.max(inst.addr + inst.item.width());
+        }
+        for err in errors.drain(err_idx..next_err) {
+            covered = covered.max(err.addr + err.item.size());
+        }
+
+        match item {
+            Ok(item) => instructions.insert(inst_idx, Addressed { addr: ip, item }),
+            Err(item) => errors.insert(err_idx, Addressed { addr: ip, item }),
+        }
+
+        log::PROGRESS.step();
+        ip += width;
+    }
+}
+
+/// Part of a code section that's decoded by a single thread.
+struct Chunk<'a, I> {
+    section: &'a Section,
+    /// Addresses instructions in this chunk can start at.
+    range: Range<PhysAddr>,
+    /// Which bytes in the chunk have been decoded. This includes the bytes after the chunk
+    /// that an instruction at the end of the chunk might run into.
+    visited: Vec<bool>,
+    /// Addresses to follow control flow from.
+    entries: Vec<PhysAddr>,
+    /// Addresses control flow lead to outside of the chunk.
+    outbox: Vec<PhysAddr>,
+    /// Gaps that didn't decode cleanly.
+    rejected: Vec<Range<usize>>,
+    /// Whether the chunk starts in the middle of a section, and the gap at its start hasn't
+    /// been swept yet.
+    is_leading: bool,
+    /// Whether the previous chunk ends in a gap that didn't decode.
+    is_blocked: bool,
+    instructions: AddressMap<I>,
+    errors: AddressMap<decoder::Error>,
+}
+
+impl<'a, I: Decoded> Chunk<'a, I> {
+    fn new(section: &'a Section, range: Range<PhysAddr>) -> Self {
+        let section_end = section.start + section.bytes().len();
+        let visited_len = std::cmp::min(range.end + MAX_OVERHANG, section_end) - range.start;
+        let is_leading = range.start != section.start;
+
+        Self {
+            section,
+            range,
+            visited: vec![false; visited_len],
+            entries: Vec::new(),
+            outbox: Vec::new(),
+            rejected: Vec::new(),
+            is_leading,
+            is_blocked: false,
+            instructions: AddressMap::default(),
+            errors: AddressMap::default(),
+        }
+    }
+
+    /// Follow control flow from each entry until every instruction reachable inside of the
+    /// chunk is decoded.
+    fn descend<D: Decodable<Instruction = I>>(&mut self, decoder: &D, has_delay_slot: bool) {
+        let section = self.section;
+        let bytes = &section.bytes()[self.range.start - section.start..];
+
+        while let Some(entry) = self.entries.pop() {
+            let visited = &mut self.visited;
+            let mut prev_inst = None;
+            let mut in_delay_slot = false;
+            let mut ip = entry;
+
+            loop {
+                let offset = ip - self.range.start;
+
+                // control flow left the chunk
+                if offset >= self.range.len() {
+                    self.outbox.push(ip);
+                    break;
+                }
+
+                // we've already been here
+                if visited[offset] {
+                    break;
+                }
+
+                let mut reader = decoder::Reader::new(&bytes[offset..]);
+                match decoder.decode_at(&mut reader, ip) {
+                    Ok(mut instruction) => {
+                        instruction.update_rel_addrs(
+                            ip,
+                            prev_inst.map(|idx: usize| &self.instructions[idx].item)
+                        );
+
+                        let width = instruction.width();
+                        let end = std::cmp::min(offset + width, visited.len());
+
+                        // the instruction overlaps an already decoded instruction
+                        if visited[offset..end].contains(&true) {
+                            break;
+                        }
+
+                        visited[offset..end].fill(true);
+
+                        let flow = instruction.flow();
+                        if let Some(target) = flow.target() {
+                            if self.range.contains(&target) {
+                                self.entries.push(target);
+                            } else {
+                                self.outbox.push(target);
+                            }
+                        }
+
+                        self.instructions.push(Addressed {
+                            addr: ip,
+                            item: instruction,
+                        });
+
+                        log::PROGRESS.step();
+                        prev_inst = Some(self.instructions.len() - 1);
+                        ip += width;
+
+                        if in_delay_slot {
+                            break;
+                        }
+
+                        if !flow.falls_through() {
+                            if !has_delay_slot {
+                                break;
+                            }
+
+                            in_delay_slot = true;
+                        }
+                    }
+                    Err(error) => {
+                        if error.kind != decoder::ErrorKind::ExhaustedInput {
+                            let end = std::cmp::min(offset + error.size(), visited.len());
+                            visited[offset..end].fill(true);
+                            self.errors.push(Addressed {
+                                addr: ip,
+                                item: error,
+                            });
+                        }
+
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// The first gap that starts in `offset..limit`, given as offsets into the chunk.
+    fn next_gap(&self, offset: usize, limit: usize) -> Option<Range<usize>> {
+        let len = self.range.len();
+        let start = offset + self.visited[offset..limit].iter().position(|&v| !v)?;
+        let end = match self.visited[start..len].iter().position(|&v| v) {
+            Some(end) => start + end,
+            None => len,
+        };
+
+        Some(start..end)
+    }
+
+    /// Linearly sweep the unexplored gaps in the chunk, keeping the ones that decode cleanly.
+    ///
+    /// A gap at the very start of the chunk depends on how far the previous chunk's last
+    /// instruction runs into it, so it's left for [`Chunk::sweep_rejected_gaps`].
+    fn sweep_gaps<D: Decodable<Instruction = I>>(&mut self, decoder: &D, has_delay_slot: bool) {
+        let mut offset = 0;
+
+        while let Some(gap) = self.next_gap(offset, self.range.len()) {
+            offset = gap.end;
+
+            let is_leading = gap.start == 0 && self.is_leading;
+            if is_leading || !self.sweep_gap(decoder, has_delay_slot, gap.clone()) {
+                self.rejected.push(gap);
+            }
+        }
+    }
+
+    /// Sweep the gaps that were rejected before, but have since been narrowed down by code found
+    /// elsewhere. Returns whether any gap was swept again.
+    fn sweep_rejected_gaps<D: Decodable<Instruction = I>>(
+        &mut self,
+        decoder: &D,
+        has_delay_slot: bool,
+    ) -> bool {
+        let mut swept = false;
+
+        for gap in std::mem::take(&mut self.rejected) {
+            // the gap at the start of the chunk is swept at least once, unless it's part of a gap
+            // in the previous chunk that didn't decode
+            let is_leading = gap.start == 0 && self.is_leading && !self.is_blocked;
+            if !is_leading && !self.visited[gap.clone()].contains(&true) {
+                self.rejected.push(gap);
+                continue;
+            }
+
+            if is_leading {
+                self.is_leading = false;
+            }
+
+            let mut offset = gap.start;
+            while offset < gap.end {
+                let gap = match self.next_gap(offset, gap.end) {
+                    Some(gap) => gap,
+                    None => break,
+                };
+
+                offset = gap.end;
+                let is_leading = gap.start == 0 && self.is_leading;
+                if is_leading || !self.sweep_gap(decoder, has_delay_slot, gap.clone()) {
+                    self.rejected.push(gap);
+                }
+            }
+
+            swept = true;
+        }
+
+        swept
+    }
+
+    /// Linearly sweep a gap given as offsets into the chunk, keeping it if it decodes cleanly.
+    fn sweep_gap<D: Decodable<Instruction = I>>(
+        &mut self,
+        decoder: &D,
+        has_delay_slot: bool,
+        gap: Range<usize>,
+    ) -> bool {
+        let section = self.section;
+        let bytes = &section.bytes()[self.range.start - section.start..];
+
+        // zero-filled padding decodes perfectly fine on some architectures
+        if bytes[gap.clone()].iter().all(|&b| b == 0) {
+            return false;
+        }
+
+        // a gap running up to the end of the chunk might continue in the next chunk
+        let is_open = gap.end == self.range.len();
+        let mut reader = decoder::Reader::new(&bytes[gap.start..]);
+        let mut decoded = Vec::new();
+        let mut ip = self.range.start + gap.start;
+        let gap_end = self.range.start + gap.end;
+
+        let is_code = loop {
+            if ip >= gap_end {
+                break ip == gap_end || is_open;
+            }
+
+            match decoder.decode_at(&mut reader, ip) {
+                Ok(mut instruction) => {
+                    instruction.update_rel_addrs(
+                        ip,
+                        decoded.last().map(|inst: &Addressed<I>| &inst.item)
+                    );
+
+                    let width = instruction.width();
+                    decoded.push(Addressed {
+                        addr: ip,
+                        item: instruction,
+                    });
+
+                    ip += width;
+                }
+                Err(..) => break false,
+            }
+        };
+
+        // the last instruction might run into the next reached instruction
+        let end = ip - self.range.start;
+        let overhang = self.visited.get(gap.end..end);
+        if !is_code || overhang.is_none_or(|overhang| overhang.contains(&true)) {
+            return false;
+        }
+
+        for inst in decoded.iter() {
+            if let Some(target) = inst.item.flow().target() {
+                if self.range.contains(&target) {
+                    self.entries.push(target);
+                } else {
+                    self.outbox.push(target);
+                }
+            }
+            log::PROGRESS.step();
+        }
+
+        // a gap that ran into the next chunk continues there
+        if ip > gap_end && decoded.last().is_some_and(|inst| inst.item.flow().falls_through()) {
+            self.outbox.push(ip);
+        }
+
+        self.visited[gap.start..end].fill(true);
+        self.instructions.extend(AddressMap { mapping: decoded });
+
+        // newly found code might lead to code in other gaps
+        self.descend(decoder, has_delay_slot);
+        true
+    }
+
+    /// Linearly sweep through the chunk, decoding every instruction in order.
+    fn sweep<D: Decodable<Instruction = I>>(&mut self, decoder: &D) {
+        let section = self.section;
+        let bytes = &section.bytes()[self.range.start - section.start..];
+        let mut prev_inst = None;
+        let mut reader = decoder::Reader::new(bytes);
+        let mut ip = self.range.start;
+
+        while ip < self.range.end {
+            // prefetch next cache line line
+            #[cfg(target_arch = "x86")]
+            unsafe {
+                core::arch::x86::_mm_prefetch(
+                    reader.as_ptr() as *const i8,
+                    core::arch::x86::_MM_HINT_NTA
+                );
+            }
+
+            #[cfg(target_arch = "x86_64")]
+            unsafe {
+                core::arch::x86_64::_mm_prefetch(
+                    reader.as_ptr() as *const i8,
+                    core::arch::x86_64::_MM_HINT_NTA
+                );
+            }
+
+            match decoder.decode_at(&mut reader, ip) {
+                Ok(mut instruction) => {
+                    instruction.update_rel_addrs(
+                        ip,
+                        prev_inst.map(|idx: usize| &self.instructions[idx].item)
+                    );
+
+                    let width = instruction.width();
+                    self.instructions.push(Addressed {
+                        addr: ip,
+                        item: instruction,
+                    });
+
+                    prev_inst = Some(self.instructions.len() - 1);
+                    ip += width;
+                }
+                Err(error) => {
+                    if error.kind == decoder::ErrorKind::ExhaustedInput {
+                        break;
+                    }
+
+                    let width = error.size();
+                    self.errors.push(Addressed {
+                        addr: ip,
+                        item: error,
+                    });
+                    prev_inst = None;
+                    ip += width;
+                }
+            }
+
+            log::PROGRESS.step();
+        }
+
+        self.visited.fill(true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Functions that each call the next one, followed by some padding.
+    fn calls(count: usize) -> &'static [u8] {
+        let mut bytes = Vec::new();
+        for idx in 0..count {
+            bytes.extend_from_slice(&[0x55, 0x48, 0x89, 0xe5]); // push rbp; mov rbp, rsp
+            bytes.push(0xb8); // mov eax, idx
+            bytes.extend_from_slice(&(idx as u32).to_le_bytes());
+            bytes.push(0xe8); // call next
+            bytes.extend_from_slice(&5u32.to_le_bytes());
+            bytes.extend_from_slice(&[0x5d, 0xc3, 0xcc, 0xcc, 0xcc]); // pop rbp; ret
+        }
+        Box::leak(bytes.into_boxed_slice())
+    }
+
+    fn decode(bytes: &'static [u8], entries: &[PhysAddr], chunk_size: usize) -> Vec<PhysAddr> {
+        let sections = [Section::new(
+            ".text".into(),
+            "",
+            SectionKind::Code,
+            bytes,
+            0x1000,
+            0x1000 + bytes.len(),
+        )];
+
+        let decoder = x86_64::long_mode::Decoder::default();
+        let (instructions, errors) =
+            recurse_chunked(&decoder, &sections, entries, &[], false, chunk_size);
+        let mut addrs: Vec<PhysAddr> = instructions.iter().map(|inst| inst.addr).collect();
+        addrs.extend(errors.iter().map(|err| err.addr));
+        addrs.sort_unstable();
+        addrs
+    }
+
+    #[test]
+    fn chunked_descent() {
+        let bytes = calls(64);
+        let expected = decode(bytes, &[0x1000], usize::MAX);
+        // six instructions per function and three int3's of padding
+        assert_eq!(expected.len(), 64 * 9);
+
+        for chunk_size in [16, 19, 37, 100] {
+            assert_eq!(decode(bytes, &[0x1000], chunk_size), expected, "{chunk_size}");
+        }
+    }
+
+    #[test]
+    fn chunked_sweep() {
+        let bytes = calls(64);
+        let expected = decode(bytes, &[], usize::MAX);
+
+        for chunk_size in [7, 16, 19, 100] {
+            assert_eq!(decode(bytes, &[], chunk_size), expected, "{chunk_size}");
+        }
+    }
+
+    #[test]
+    fn relocatable() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/relocatable.o");
+        let processor = crate::Processor::parse(path).unwrap();
+        let binary = std::fs::read(path).unwrap();
+        let obj = ObjectFile::parse(&binary[..]).unwrap();
+
+        // `.text` is placed at 0x10000 and its `.eh_frame` is relocated to match
+        let unwind_ranges = unwind_ranges(&obj, &processor.sections);
+        assert_eq!(unwind_ranges, [0x10000..0x10008, 0x10018..0x1001e]);
+
+        let layout = processor.layout.as_deref();
+        let entries = entries(&obj, layout, processor.entrypoint, &unwind_ranges);
+        assert_eq!(entries, [0x10000, 0x10018]);
+
+        // the table between the functions isn't decoded
+        let decoder = x86_64::long_mode::Decoder::default();
+        let (instructions, errors) = recurse(&decoder, &processor.sections, &entries, &[], false);
+        let addrs: Vec<PhysAddr> = instructions.iter().map(|inst| inst.addr).collect();
+        assert_eq!(addrs, [0x10000, 0x10007, 0x10018, 0x1001d]);
+        assert!(errors.is_empty());
+    }
+}
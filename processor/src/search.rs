@@ -0,0 +1,120 @@
+//! Searching the bytes of a binary for patterns.
+
+use crate::Processor;
+use commands::search::Pattern;
+use processor_shared::{PhysAddr, SectionKind};
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Number of bytes searched at once, sections larger than this are searched in parallel.
+const CHUNK_SIZE: usize = 0x100000;
+
+/// Most matches a search returns, patterns like `00` would otherwise match most of a binary.
+pub const MAX_MATCHES: usize = 100_000;
+
+impl Processor {
+    /// Addresses where the (patched) bytes of a loaded section match `pattern`, in order. Only
+    /// the first [`MAX_MATCHES`] are returned.
+    pub fn find(&self, pattern: &Pattern) -> Vec<PhysAddr> {
+        // sections that aren't loaded (e.g. debug info) don't have any meaningful addresses
+        let sections = self
+            .sections()
+            .filter(|section| section.kind != SectionKind::Unloaded && section.permissions.read);
+
+        let mut chunks = Vec::new();
+        for section in sections {
+            let bytes = section.bytes();
+            for start in (0..bytes.len()).step_by(CHUNK_SIZE) {
+                let end = std::cmp::min(start + CHUNK_SIZE, bytes.len());
+                chunks.push((section.start, bytes, start..end, Vec::new()));
+            }
+        }
+
+        log::PROGRESS.set("Searching", chunks.len());
+
+        let thread_count = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let thread_count = thread_count.min(chunks.len());
+        let queue = std::sync::Mutex::new(chunks.iter_mut());
+        let found = AtomicUsize::new(0);
+
+        // chunks are taken in order, so once enough matches are found, the chunks that are left
+        // only hold matches past the ones that are returned
+        std::thread::scope(|s| {
+            for _ in 0..thread_count {
+                s.spawn(|| loop {
+                    if found.load(Ordering::Relaxed) >= MAX_MATCHES {
+                        break;
+                    }
+
+                    let chunk = queue.lock().unwrap().next();
+                    match chunk {
+                        Some((base, bytes, range, matches)) => {
+                            let offsets = find_in(pattern, bytes, range.clone());
+                            matches.extend(offsets.take(MAX_MATCHES).map(|offset| *base + offset));
+                            found.fetch_add(matches.len(), Ordering::Relaxed);
+                            log::PROGRESS.step();
+                        }
+                        None => break,
+                    }
+                });
+            }
+        });
+
+        chunks.into_iter().flat_map(|(.., matches)| matches).take(MAX_MATCHES).collect()
+    }
+}
+
+/// Offsets in `range` where `bytes` match `pattern`, matches may extend past the range.
+fn find_in<'a>(
+    pattern: &'a Pattern,
+    bytes: &'a [u8],
+    range: Range<usize>,
+) -> impl Iterator<Item = usize> + 'a {
+    let end = std::cmp::min(range.end, (bytes.len() + 1).saturating_sub(pattern.len()));
+    let mut offset = range.start;
+
+    std::iter::from_fn(move || {
+        while offset < end {
+            // skip ahead to where the first byte without wildcards is found
+            if let Some((idx, byte)) = pattern.anchor() {
+                let haystack = &bytes[offset + idx..end + idx];
+                match haystack.iter().position(|&b| b == byte) {
+                    Some(skip) => offset += skip,
+                    None => {
+                        offset = end;
+                        break;
+                    }
+                }
+            }
+
+            let found = offset;
+            offset += 1;
+            if pattern.matches(&bytes[found..]) {
+                return Some(found);
+            }
+        }
+
+        None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_in_chunks() {
+        let bytes: [u8; 9] = [0x90, 0x48, 0x8b, 0x05, 0x41, 0x48, 0x8b, 0x00, 0x4f];
+        let pattern = Pattern::from_hex("48 8b ?? 4?").unwrap();
+        let found = |range: Range<usize>| find_in(&pattern, &bytes, range).collect::<Vec<_>>();
+
+        assert_eq!(found(0..bytes.len()), [1, 5]);
+        assert_eq!(found(0..2), [1]);
+        assert_eq!(found(2..6), [5]);
+        assert!(found(6..bytes.len()).is_empty());
+
+        let pattern = Pattern::from_hex("?? 8b").unwrap();
+        let found = find_in(&pattern, &bytes, 0..bytes.len()).collect::<Vec<_>>();
+        assert_eq!(found, [1, 5]);
+    }
+}
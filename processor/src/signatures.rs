@@ -0,0 +1,763 @@
+//! Signatures of library functions, to name the code that stripped binaries statically link.
+//!
+//! A signature is made from a function in an object file or binary that has symbols. It holds
+//! the function's bytes, with the bytes that depend on where the function was linked masked
+//! out, and a hash of the function's control-flow graph. Bytes are masked where the object has
+//! relocations and for instructions that call, jump out of the function or reference memory.
+//!
+//! Signatures are stored as JSON with a `.sig` extension. The ones in [`directory`] are
+//! matched against the functions that analysis finds whenever a binary is parsed.
+
+use crate::cfg::{self, EdgeKind};
+use debugvault::Index;
+use decoder::{Decodable, Decoded, Flow};
+use object::{Architecture, Object, ObjectSection, ObjectSymbol, SymbolKind};
+use processor_shared::{AddressMap, Addressed, PhysAddr, Section, SectionKind};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Extension of signature files.
+pub const EXTENSION: &str = "sig";
+
+/// Version of the signature format, this is bumped whenever the format changes.
+const VERSION: u32 = 2;
+
+/// Functions with fewer bytes that don't depend on where they're linked match too much
+/// unrelated code.
+const MIN_KNOWN_BYTES: usize = 16;
+
+/// Number of bytes signatures are looked up by.
+const PREFIX_LEN: usize = 4;
+
+#[derive(Debug)]
+pub enum Error {
+    IO(std::io::Error),
+    Object(object::Error),
+    Format(serde_json::Error),
+    /// The signatures were saved by a newer version of bite.
+    UnsupportedVersion(u32),
+    /// There's no decoder for the object's architecture.
+    UnknownArchitecture(Architecture),
+    /// Signatures of different architectures can't be stored together.
+    ArchitectureMismatch(String, String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IO(err) => err.fmt(f),
+            Self::Object(err) => err.fmt(f),
+            Self::Format(err) => f.write_fmt(format_args!("Invalid signatures: {err}")),
+            Self::UnsupportedVersion(version) => {
+                f.write_fmt(format_args!("Signature version {version} isn't supported"))
+            }
+            Self::UnknownArchitecture(arch) => {
+                f.write_fmt(format_args!("Architecture {arch:?} isn't supported"))
+            }
+            Self::ArchitectureMismatch(expected, found) => f.write_fmt(format_args!(
+                "Can't add {found} signatures to {expected} signatures"
+            )),
+        }
+    }
+}
+
+/// Bytes of a function, `None` being a byte that depends on where the function was linked.
+///
+/// Stored as hex with `??` for masked bytes, e.g. `554889e5e8????????`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Pattern(pub Vec<Option<u8>>);
+
+impl Pattern {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Whether `bytes` start with the pattern.
+    pub fn matches(&self, bytes: &[u8]) -> bool {
+        self.len() <= bytes.len()
+            && self.0.iter().zip(bytes).all(|(pat, byte)| pat.is_none_or(|pat| pat == *byte))
+    }
+
+    /// The bytes the pattern starts with, if none of them are masked.
+    fn prefix(&self) -> Option<[u8; PREFIX_LEN]> {
+        let mut prefix = [0; PREFIX_LEN];
+        for (byte, pat) in prefix.iter_mut().zip(self.0.get(..PREFIX_LEN)?) {
+            *byte = (*pat)?;
+        }
+        Some(prefix)
+    }
+
+    fn is_masked(&self, offset: usize) -> bool {
+        self.0.get(offset).is_none_or(Option::is_none)
+    }
+
+    fn mask(&mut self, range: Range<usize>) {
+        let end = std::cmp::min(range.end, self.len());
+        for byte in self.0[std::cmp::min(range.start, end)..end].iter_mut() {
+            *byte = None;
+        }
+    }
+
+    fn known_bytes(&self) -> usize {
+        self.0.iter().filter(|byte| byte.is_some()).count()
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0.iter() {
+            match byte {
+                Some(byte) => f.write_fmt(format_args!("{byte:02x}"))?,
+                None => f.write_str("??")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<Pattern> for String {
+    fn from(pattern: Pattern) -> Self {
+        pattern.to_string()
+    }
+}
+
+impl TryFrom<String> for Pattern {
+    type Error = String;
+
+    fn try_from(hex: String) -> Result<Self, Self::Error> {
+        if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+            return Err(format!("pattern '{hex}' isn't made of whole bytes"));
+        }
+
+        let mut bytes = Vec::with_capacity(hex.len() / 2);
+        for idx in (0..hex.len()).step_by(2) {
+            let byte = match &hex[idx..idx + 2] {
+                "??" => None,
+                byte => match u8::from_str_radix(byte, 16) {
+                    Ok(byte) => Some(byte),
+                    Err(_) => return Err(format!("pattern '{hex}' has an invalid byte '{byte}'")),
+                },
+            };
+            bytes.push(byte);
+        }
+
+        Ok(Self(bytes))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Signature {
+    /// Name of the function, as it's mangled.
+    pub name: String,
+
+    pub pattern: Pattern,
+
+    /// Hash of the function's control-flow graph.
+    pub cfg: u64,
+
+    /// Whether the function is Arm's Thumb code.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub thumb: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Signatures {
+    version: u32,
+
+    /// Architecture of the functions, as named by [`Architecture`].
+    pub arch: String,
+
+    pub signatures: Vec<Signature>,
+}
+
+impl Signatures {
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let file = std::fs::File::open(path).map_err(Error::IO)?;
+        let signatures: Self =
+            serde_json::from_reader(std::io::BufReader::new(file)).map_err(Error::Format)?;
+
+        if signatures.version > VERSION {
+            return Err(Error::UnsupportedVersion(signatures.version));
+        }
+
+        Ok(signatures)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let signatures = Self {
+            version: VERSION,
+            ..self.clone()
+        };
+        let json = serde_json::to_vec(&signatures).map_err(Error::Format)?;
+        std::fs::write(path, json).map_err(Error::IO)
+    }
+
+    /// Make signatures of the functions in an archive (e.g. `.a` or `.rlib`), an object file
+    /// or a binary that has symbols.
+    pub fn generate(data: &[u8]) -> Result<Self, Error> {
+        let mut signatures = Self::default();
+
+        let archive = match object::read::archive::ArchiveFile::parse(data) {
+            Ok(archive) => archive,
+            Err(_) => {
+                let obj = object::File::parse(data).map_err(Error::Object)?;
+                signatures.extend(Self::from_object(&obj)?)?;
+                return Ok(signatures);
+            }
+        };
+
+        for member in archive.members() {
+            let member = member.map_err(Error::Object)?;
+            let data = member.data(data).map_err(Error::Object)?;
+
+            // archives also hold members that aren't objects, e.g. the metadata of an `.rlib`
+            if let Ok(obj) = object::File::parse(data) {
+                signatures.extend(Self::from_object(&obj)?)?;
+            }
+        }
+
+        Ok(signatures)
+    }
+
+    /// Add `other`'s signatures, skipping the ones that are already known.
+    pub fn extend(&mut self, other: Self) -> Result<(), Error> {
+        if other.signatures.is_empty() {
+            return Ok(());
+        }
+
+        if self.signatures.is_empty() {
+            self.arch = other.arch;
+        } else if self.arch != other.arch {
+            return Err(Error::ArchitectureMismatch(self.arch.clone(), other.arch));
+        }
+
+        let mut known: HashSet<Signature> = self.signatures.iter().cloned().collect();
+        for signature in other.signatures {
+            if known.insert(signature.clone()) {
+                self.signatures.push(signature);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn from_object(obj: &object::File) -> Result<Self, Error> {
+        let arch = obj.architecture();
+        let mut signatures = Vec::new();
+
+        for section in obj.sections().filter(|s| s.kind() == object::SectionKind::Text) {
+            let data = section.uncompressed_data().map_err(Error::Object)?;
+
+            let mut symbols: Vec<_> = obj
+                .symbols()
+                .filter(|sym| sym.section_index() == Some(section.index()))
+                .filter(|sym| sym.kind() == SymbolKind::Text && sym.is_definition())
+                .filter_map(|sym| {
+                    // the lowest bit of arm's functions is set if they're thumb code
+                    let thumb = arch == Architecture::Arm && sym.address() & 1 == 1;
+                    let addr = if thumb { sym.address() & !1 } else { sym.address() };
+                    Some((addr, sym.size(), sym.is_global(), thumb, sym.name().ok()?))
+                })
+                // skip local labels and mapping symbols (e.g. arm's `$x`)
+                .filter(|(.., name)| !name.is_empty() && !name.starts_with(['$', '.']))
+                .collect();
+
+            // aliases of a function get the name of the global one
+            symbols.sort_by_key(|&(addr, _, is_global, ..)| (addr, !is_global));
+            symbols.dedup_by_key(|(addr, ..)| *addr);
+
+            let relocations: Vec<Range<usize>> = section
+                .relocations()
+                .map(|(offset, relocation)| {
+                    let offset = offset as usize;
+                    offset..offset + relocation_width(arch, relocation)
+                })
+                .collect();
+
+            for (idx, &(addr, size, _, thumb, name)) in symbols.iter().enumerate() {
+                let start = addr.wrapping_sub(section.address()) as usize;
+                let end = match size {
+                    // symbols without a size (e.g. in mach-o) end where the next one starts
+                    0 => symbols
+                        .get(idx + 1)
+                        .map_or(data.len() as u64, |next| next.0 - section.address())
+                        as usize,
+                    size => start + size as usize,
+                };
+
+                let bytes = match data.get(start..end) {
+                    Some(bytes) if !bytes.is_empty() => bytes,
+                    _ => continue,
+                };
+
+                let relocations = relocations
+                    .iter()
+                    .filter(|reloc| reloc.start < end && reloc.end > start)
+                    .map(|reloc| reloc.start.saturating_sub(start)..reloc.end - start);
+
+                if let Some(signature) = signature(arch, thumb, name, bytes, relocations)? {
+                    signatures.push(signature);
+                }
+            }
+        }
+
+        Ok(Self {
+            version: VERSION,
+            arch: format!("{arch:?}"),
+            signatures,
+        })
+    }
+}
+
+/// Number of bytes a relocation changes.
+fn relocation_width(arch: Architecture, relocation: object::Relocation) -> usize {
+    match (arch, relocation.kind()) {
+        // `call` is an `auipc` followed by a `jalr`, both of which are relocated
+        (
+            Architecture::Riscv32 | Architecture::Riscv64,
+            object::RelocationKind::Elf(object::elf::R_RISCV_CALL | object::elf::R_RISCV_CALL_PLT),
+        ) => 8,
+        _ => match relocation.size() {
+            // relocations of unknown size are assumed to patch an instruction
+            0 => 4,
+            bits => (bits as usize).div_ceil(8),
+        },
+    }
+}
+
+/// Signature of the function in `bytes`, masking the bytes changed by `relocations`. Returns
+/// `None` if the function is too short to be told apart from other code.
+fn signature(
+    arch: Architecture,
+    thumb: bool,
+    name: &str,
+    bytes: &[u8],
+    relocations: impl Iterator<Item = Range<usize>>,
+) -> Result<Option<Signature>, Error> {
+    let instructions = summarize(arch, thumb, bytes)?;
+    let mut pattern = Pattern(bytes.iter().copied().map(Some).collect());
+
+    for relocation in relocations {
+        pattern.mask(relocation);
+    }
+
+    for inst in instructions.iter() {
+        let summary = &inst.item;
+        let leaves = summary.flow.target().is_some_and(|target| target >= bytes.len());
+        if summary.mem_ref || leaves || matches!(summary.flow, Flow::Call(_)) {
+            pattern.mask(inst.addr..inst.addr + summary.width);
+        }
+    }
+
+    if pattern.known_bytes() < MIN_KNOWN_BYTES {
+        return Ok(None);
+    }
+
+    Ok(
+        cfg_hash(arch, &instructions, &pattern).map(|cfg| Signature {
+            name: name.to_string(),
+            pattern,
+            cfg,
+            thumb,
+        }),
+    )
+}
+
+/// What's used of an instruction to tell functions apart.
+struct Summary {
+    width: usize,
+    flow: Flow,
+    /// Whether the instruction references memory by its address.
+    mem_ref: bool,
+}
+
+/// Decode all of `bytes`, as if they were at address zero. Arm code is decoded as Thumb code
+/// if `thumb` is set.
+fn summarize(
+    arch: Architecture,
+    thumb: bool,
+    bytes: &[u8],
+) -> Result<AddressMap<Summary>, Error> {
+    fn sweep<D: Decodable>(decoder: D, bytes: &[u8]) -> AddressMap<Summary> {
+        let mut instructions = AddressMap::default();
+        let mut prev = None;
+        let mut ip = 0;
+
+        while ip < bytes.len() {
+            let mut reader = decoder::Reader::new(&bytes[ip..]);
+            match decoder.decode_at(&mut reader, ip) {
+                Ok(mut instruction) => {
+                    instruction.update_rel_addrs(ip, prev.as_ref());
+
+                    let width = instruction.width();
+                    instructions.push(Addressed {
+                        addr: ip,
+                        item: Summary {
+                            width,
+                            flow: instruction.flow(),
+                            mem_ref: instruction.mem_ref().is_some(),
+                        },
+                    });
+
+                    prev = Some(instruction);
+                    ip += width;
+                }
+                Err(error) => {
+                    if error.kind == decoder::ErrorKind::ExhaustedInput {
+                        break;
+                    }
+
+                    prev = None;
+                    ip += std::cmp::max(error.size(), 1);
+                }
+            }
+        }
+
+        instructions
+    }
+
+    Ok(match arch {
+        Architecture::Riscv32 => sweep(riscv::Decoder { is_64: false }, bytes),
+        Architecture::Riscv64 => sweep(riscv::Decoder { is_64: true }, bytes),
+        Architecture::Mips | Architecture::Mips64 => sweep(mips::Decoder, bytes),
+        Architecture::X86_64_X32 | Architecture::I386 => {
+            sweep(x86_64::protected_mode::Decoder::default(), bytes)
+        }
+        Architecture::X86_64 => sweep(x86_64::long_mode::Decoder::default(), bytes),
+        Architecture::Arm => {
+            sweep(arm::armv7::Decoder::default().with_thumb_mode(thumb), bytes)
+        }
+        Architecture::Aarch64 | Architecture::Aarch64_Ilp32 => {
+            sweep(arm::armv8::a64::Decoder::default(), bytes)
+        }
+        arch => return Err(Error::UnknownArchitecture(arch)),
+    })
+}
+
+/// Hash of the control-flow graph of the function at address zero. Where masked instructions
+/// transfer execution to isn't part of the hash, as that depends on where they were linked.
+fn cfg_hash(
+    arch: Architecture,
+    instructions: &AddressMap<Summary>,
+    pattern: &Pattern,
+) -> Option<u64> {
+    let len = pattern.len();
+
+    // where masked instructions lead to is replaced by an address outside of the function
+    let mapping = instructions
+        .iter()
+        .map(|inst| {
+            let flow = match inst.item.flow {
+                flow if !pattern.is_masked(inst.addr) => flow,
+                Flow::Branch(_) => Flow::Branch(usize::MAX),
+                Flow::Jump(_) => Flow::Jump(usize::MAX),
+                Flow::Call(_) => Flow::Call(usize::MAX),
+                flow => flow,
+            };
+
+            Addressed {
+                addr: inst.addr,
+                item: (flow, inst.item.width),
+            }
+        })
+        .collect();
+    let instructions = AddressMap { mapping };
+
+    let cfg = cfg::build(
+        0,
+        &instructions,
+        |&(flow, _)| flow,
+        |&(_, width)| width,
+        |addr| addr >= len,
+        matches!(arch, Architecture::Mips | Architecture::Mips64),
+    )?;
+
+    let mut hash = Fnv::default();
+    hash.write(cfg.blocks.len() as u64);
+    for block in cfg.blocks.iter() {
+        hash.write(block.start as u64);
+        hash.write(block.end as u64);
+
+        for edge in block.edges.iter() {
+            hash.write(edge.kind as u8 as u64);
+            let target = match edge.target {
+                Some(_) if edge.kind == EdgeKind::Call => u64::MAX,
+                Some(target) if target < len => target as u64,
+                Some(_) => u64::MAX,
+                None => u64::MAX - 1,
+            };
+            hash.write(target);
+        }
+    }
+
+    Some(hash.0)
+}
+
+/// 64-bit FNV-1a, unlike std's hashers its output doesn't change between versions of rust.
+struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv {
+    fn write(&mut self, value: u64) {
+        for byte in value.to_le_bytes() {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
+
+/// Where signatures are loaded from when parsing a binary.
+pub fn directory() -> Option<PathBuf> {
+    let mut dir = dirs::data_dir()?;
+    dir.push("bite");
+    dir.push("signatures");
+    Some(dir)
+}
+
+/// Name the functions found by analysis that match the signatures in [`directory`].
+pub(crate) fn apply(arch: Architecture, sections: &[Section], index: &mut Index) {
+    if !index.syms.iter().any(|sym| sym.item.generated()) {
+        return;
+    }
+
+    let entries = match directory().map(std::fs::read_dir) {
+        Some(Ok(entries)) => entries,
+        _ => return,
+    };
+
+    let arch_name = format!("{arch:?}");
+    let mut signatures = Vec::new();
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.extension().is_none_or(|ext| ext != EXTENSION) {
+            continue;
+        }
+
+        match Signatures::open(&path) {
+            Ok(file) if file.arch == arch_name => signatures.extend(file.signatures),
+            Ok(_) => {}
+            Err(err) => log::complex!(
+                w "[signatures::apply] ",
+                y format!("Failed to open {path:?}: {err}"),
+                w ".",
+            ),
+        }
+    }
+
+    if signatures.is_empty() {
+        return;
+    }
+
+    let names = find(arch, sections, index, &signatures);
+
+    log::complex!(
+        w "[signatures::apply] named ",
+        g names.len().to_string(),
+        w " functions."
+    );
+
+    let names: Vec<(PhysAddr, String)> =
+        names.into_iter().map(|(addr, name)| (addr, name.to_string())).collect();
+    index.rename(names.iter().map(|(addr, name)| (*addr, name.as_str())));
+}
+
+/// Functions found by analysis that match a signature, along with the signature's name.
+/// Functions that match signatures with different names are skipped.
+fn find<'a>(
+    arch: Architecture,
+    sections: &[Section],
+    index: &Index,
+    signatures: &'a [Signature],
+) -> Vec<(PhysAddr, &'a str)> {
+    // signatures starting with masked bytes are compared against every function
+    let mut by_prefix: HashMap<[u8; PREFIX_LEN], Vec<&Signature>> = HashMap::new();
+    let mut unprefixed = Vec::new();
+    for signature in signatures {
+        match signature.pattern.prefix() {
+            Some(prefix) => by_prefix.entry(prefix).or_default().push(signature),
+            None => unprefixed.push(signature),
+        }
+    }
+
+    let mut names = Vec::new();
+    for (idx, sym) in index.syms.iter().enumerate() {
+        if !sym.item.generated() || sym.item.intrinsic() {
+            continue;
+        }
+
+        let section = match sections
+            .iter()
+            .find(|s| s.kind == SectionKind::Code && (s.start..s.end).contains(&sym.addr))
+        {
+            Some(section) => section,
+            None => continue,
+        };
+
+        // functions end where the next symbol starts
+        let end = index.syms.get(idx + 1).map_or(section.end, |next| next.addr);
+        let bytes = section.bytes_by_addr(sym.addr, end - sym.addr);
+
+        let prefixed = bytes
+            .get(..PREFIX_LEN)
+            .and_then(|prefix| by_prefix.get(prefix))
+            .map_or(&[][..], |signatures| &signatures[..]);
+
+        let mut matched: Vec<&Signature> = prefixed
+            .iter()
+            .chain(unprefixed.iter())
+            .copied()
+            .filter(|signature| signature.pattern.matches(bytes))
+            .collect();
+
+        // longer signatures say more about the function
+        let longest = matched.iter().map(|signature| signature.pattern.len()).max();
+        matched.retain(|signature| Some(signature.pattern.len()) == longest);
+        matched.retain(|signature| {
+            let bytes = &bytes[..signature.pattern.len()];
+            summarize(arch, signature.thumb, bytes).is_ok_and(|instructions| {
+                cfg_hash(arch, &instructions, &signature.pattern) == Some(signature.cfg)
+            })
+        });
+
+        if let Some(first) = matched.first() {
+            if matched.iter().all(|signature| signature.name == first.name) {
+                names.push((sym.addr, first.name.as_str()));
+            }
+        }
+    }
+
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `if (x != 0) f(x);` with `f` at `call` relative to the call's end.
+    fn function(branch: u8, call: [u8; 4]) -> Vec<u8> {
+        let mut bytes = vec![
+            0x55, // push rbp
+            0x48, 0x89, 0xe5, // mov rbp, rsp
+            0x48, 0x83, 0xec, 0x10, // sub rsp, 0x10
+            0x89, 0x7d, 0xfc, // mov dword [rbp - 0x4], edi
+            0x83, 0xff, 0x00, // cmp edi, 0x0
+            0x74, branch, // je
+            0xe8, // call
+        ];
+        bytes.extend(call);
+        bytes.extend([
+            0x48, 0x83, 0xc4, 0x10, // add rsp, 0x10
+            0x5d, // pop rbp
+            0xc3, // ret
+        ]);
+        bytes
+    }
+
+    fn generate(bytes: &[u8]) -> Signature {
+        signature(Architecture::X86_64, false, "lib_func", bytes, std::iter::empty())
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn pattern() {
+        let pattern = Pattern::try_from("55e8????c3".to_string()).unwrap();
+        assert_eq!(pattern.0, [Some(0x55), Some(0xe8), None, None, Some(0xc3)]);
+        assert_eq!(pattern.to_string(), "55e8????c3");
+        assert!(pattern.matches(&[0x55, 0xe8, 0x12, 0x34, 0xc3, 0x90]));
+        assert!(!pattern.matches(&[0x55, 0xe9, 0x12, 0x34, 0xc3]));
+        assert!(!pattern.matches(&[0x55, 0xe8]));
+
+        assert!(Pattern::try_from("55e".to_string()).is_err());
+        assert!(Pattern::try_from("55zz".to_string()).is_err());
+    }
+
+    #[test]
+    fn masks_calls() {
+        let a = generate(&function(0x05, [0x10, 0x00, 0x00, 0x00]));
+        let b = generate(&function(0x05, [0xf0, 0xff, 0x10, 0x00]));
+        assert_eq!(a, b);
+        assert!(a.pattern.is_masked(16));
+        assert!(a.pattern.is_masked(20));
+        assert!(!a.pattern.is_masked(21));
+
+        let too_short = [0x55, 0xe8, 0x00, 0x00, 0x00, 0x00, 0x5d, 0xc3];
+        let too_short =
+            signature(Architecture::X86_64, false, "f", &too_short, std::iter::empty());
+        assert!(too_short.unwrap().is_none());
+    }
+
+    #[test]
+    fn hashes_branches() {
+        let a = generate(&function(0x05, [0x10, 0x00, 0x00, 0x00]));
+        let b = generate(&function(0x00, [0x10, 0x00, 0x00, 0x00]));
+        assert_ne!(a.cfg, b.cfg);
+    }
+
+    #[test]
+    fn find() {
+        let mut bytes = function(0x05, [0x44, 0x33, 0x22, 0x11]);
+        bytes.resize(0x100, 0xcc);
+        bytes.extend(function(0x00, [0x44, 0x33, 0x22, 0x11]));
+        let len = bytes.len();
+
+        let section = Section::new(
+            ".text".to_string(),
+            "TEXT",
+            SectionKind::Code,
+            bytes.leak(),
+            0x1000,
+            0x1000 + len,
+        );
+
+        let mut index = Index::default();
+        index.insert_generated(&[0x1000, 0x1100]);
+
+        let signatures = [generate(&function(0x05, [0x00; 4]))];
+        let names = super::find(Architecture::X86_64, &[section], &index, &signatures);
+        assert_eq!(names, [(0x1000, "lib_func")]);
+    }
+
+    #[test]
+    fn thumb() {
+        let bytes = [
+            0xb0, 0xb5, // push {r4, r5, r7, lr}
+            0x04, 0x46, // mov r4, r0
+            0x00, 0x25, // movs r5, #0
+            0x00, 0x28, // cmp r0, #0
+            0x04, 0xd0, // beq 0x14
+            0x2d, 0x19, // adds r5, r5, r4
+            0x64, 0x1e, // subs r4, r4, #1
+            0x6d, 0x00, // lsls r5, r5, #1
+            0x03, 0x35, // adds r5, #3
+            0x28, 0x00, // movs r0, r5
+            0xb0, 0xbd, // pop {r4, r5, r7, pc}
+        ];
+
+        let instructions = summarize(Architecture::Arm, true, &bytes).unwrap();
+        assert_eq!(instructions.len(), 11);
+        assert!(instructions.iter().all(|inst| inst.item.width == 2));
+        assert_eq!(instructions[4].item.flow, Flow::Branch(0x14));
+
+        let thumb = signature(Architecture::Arm, true, "f", &bytes, std::iter::empty());
+        let thumb = thumb.unwrap().unwrap();
+        assert!(thumb.thumb);
+
+        // decoded as arm code, the function doesn't make sense
+        let arm = signature(Architecture::Arm, false, "f", &bytes, std::iter::empty());
+        assert!(arm.unwrap().is_none_or(|arm| arm.cfg != thumb.cfg));
+    }
+}
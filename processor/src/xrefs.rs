@@ -0,0 +1,258 @@
+//! Cross-references between addresses.
+//!
+//! References are collected from the control flow of decoded instructions, the memory they
+//! address (e.g. pc-relative loads) and the pointers stored in pointer/GOT sections.
+
+use debugvault::Index;
+use decoder::{Decoded, Flow};
+use object::Endianness;
+use processor_shared::{AddressMap, PhysAddr, Section, SectionKind};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum XrefKind {
+    /// Direct call.
+    Call,
+    /// Unconditional jump.
+    Jump,
+    /// Conditional branch.
+    Branch,
+    /// Memory read or address taken by an instruction.
+    Data,
+    /// Pointer stored in a data section.
+    Pointer,
+}
+
+impl XrefKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Call => "call",
+            Self::Jump => "jump",
+            Self::Branch => "branch",
+            Self::Data => "data",
+            Self::Pointer => "pointer",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Xref {
+    /// Address of the instruction or pointer making the reference.
+    pub from: PhysAddr,
+    /// Address being referenced.
+    pub to: PhysAddr,
+    pub kind: XrefKind,
+}
+
+/// Cross-references, searchable by both ends.
+#[derive(Debug, Default, Clone)]
+pub struct Xrefs {
+    /// Sorted by target address.
+    by_to: Vec<Xref>,
+    /// Sorted by referencing address.
+    by_from: Vec<Xref>,
+}
+
+impl Xrefs {
+    pub(crate) fn new(mut xrefs: Vec<Xref>) -> Self {
+        xrefs.sort_unstable_by_key(|xref| (xref.to, xref.from));
+        xrefs.dedup();
+
+        let mut by_from = xrefs.clone();
+        by_from.sort_unstable_by_key(|xref| (xref.from, xref.to));
+
+        Self { by_to: xrefs, by_from }
+    }
+
+    /// References to `addr`, sorted by where they're made.
+    pub fn to(&self, addr: PhysAddr) -> &[Xref] {
+        let start = self.by_to.partition_point(|xref| xref.to < addr);
+        let end = self.by_to.partition_point(|xref| xref.to <= addr);
+        &self.by_to[start..end]
+    }
+
+    /// References made by the instruction or pointer at `addr`.
+    pub fn from(&self, addr: PhysAddr) -> &[Xref] {
+        let start = self.by_from.partition_point(|xref| xref.from < addr);
+        let end = self.by_from.partition_point(|xref| xref.from <= addr);
+        &self.by_from[start..end]
+    }
+
+    /// Targets of jumps and branches that stay inside the function they're made from and that
+    /// don't have a symbol yet.
+    pub(crate) fn local_targets(&self, index: &Index) -> Vec<PhysAddr> {
+        let function = |addr| index.get_func_containing(addr).map(|func| func.addr);
+
+        let mut targets: Vec<PhysAddr> = self
+            .by_to
+            .iter()
+            .filter(|xref| matches!(xref.kind, XrefKind::Jump | XrefKind::Branch))
+            .filter(|xref| index.get_sym_by_addr(xref.to).is_none())
+            .filter(|xref| function(xref.from).is_some_and(|func| function(xref.to) == Some(func)))
+            .map(|xref| xref.to)
+            .collect();
+
+        targets.dedup();
+        targets
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_to.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_to.is_empty()
+    }
+}
+
+fn in_section(sections: &[Section], addr: PhysAddr) -> bool {
+    sections
+        .iter()
+        .filter(|s| s.kind != SectionKind::Debug)
+        .any(|s| (s.start..s.end).contains(&addr))
+}
+
+/// References made by the decoded `instructions`.
+pub fn from_instructions<I: Decoded>(
+    instructions: &AddressMap<I>,
+    sections: &[Section],
+) -> Vec<Xref> {
+    let mut xrefs = Vec::new();
+
+    for inst in instructions.iter() {
+        let (to, kind) = match inst.item.flow() {
+            Flow::Call(target) => (target, XrefKind::Call),
+            Flow::Jump(target) => (target, XrefKind::Jump),
+            Flow::Branch(target) => (target, XrefKind::Branch),
+            _ => match inst.item.mem_ref() {
+                // constants that happen to be resolved as addresses are ignored
+                Some(addr) if in_section(sections, addr) => (addr, XrefKind::Data),
+                _ => continue,
+            },
+        };
+
+        xrefs.push(Xref { from: inst.addr, to, kind });
+    }
+
+    xrefs
+}
+
+/// Pointers stored in pointer and GOT sections that point into the binary.
+pub fn from_pointers(sections: &[Section], endianness: Endianness) -> Vec<Xref> {
+    let mut xrefs = Vec::new();
+
+    for section in sections {
+        let width = match section.kind {
+            SectionKind::Ptr32 | SectionKind::Got32 => 4,
+            SectionKind::Ptr64 | SectionKind::Got64 => 8,
+            _ => continue,
+        };
+
+        for (idx, bytes) in section.bytes().chunks_exact(width).enumerate() {
+            let to = match (width, endianness) {
+                (4, Endianness::Little) => u32::from_le_bytes(bytes.try_into().unwrap()) as u64,
+                (4, Endianness::Big) => u32::from_be_bytes(bytes.try_into().unwrap()) as u64,
+                (_, Endianness::Little) => u64::from_le_bytes(bytes.try_into().unwrap()),
+                (_, Endianness::Big) => u64::from_be_bytes(bytes.try_into().unwrap()),
+            } as PhysAddr;
+
+            if !in_section(sections, to) {
+                continue;
+            }
+
+            xrefs.push(Xref {
+                from: section.start + idx * width,
+                to,
+                kind: XrefKind::Pointer,
+            });
+        }
+    }
+
+    xrefs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookups() {
+        let xrefs = Xrefs::new(vec![
+            Xref { from: 0x10, to: 0x100, kind: XrefKind::Call },
+            Xref { from: 0x20, to: 0x100, kind: XrefKind::Jump },
+            Xref { from: 0x10, to: 0x200, kind: XrefKind::Data },
+            Xref { from: 0x20, to: 0x100, kind: XrefKind::Jump },
+        ]);
+
+        assert_eq!(xrefs.len(), 3);
+        assert_eq!(xrefs.to(0x100).iter().map(|x| x.from).collect::<Vec<_>>(), [0x10, 0x20]);
+        assert_eq!(xrefs.from(0x10).iter().map(|x| x.to).collect::<Vec<_>>(), [0x100, 0x200]);
+        assert!(xrefs.to(0x10).is_empty());
+    }
+
+    #[test]
+    fn local_targets() {
+        let mut index = Index::default();
+        index.insert_func(0x100, "f");
+        index.insert_func(0x200, "g");
+
+        let xrefs = Xrefs::new(vec![
+            Xref { from: 0x104, to: 0x110, kind: XrefKind::Branch },
+            Xref { from: 0x108, to: 0x110, kind: XrefKind::Jump },
+            Xref { from: 0x10c, to: 0x204, kind: XrefKind::Jump },
+            Xref { from: 0x110, to: 0x200, kind: XrefKind::Jump },
+            Xref { from: 0x114, to: 0x120, kind: XrefKind::Call },
+        ]);
+
+        assert_eq!(xrefs.local_targets(&index), [0x110]);
+    }
+
+    #[test]
+    fn pointers() {
+        static CODE: [u8; 16] = [0; 16];
+        static PTRS: [u8; 16] = [
+            0x04, 0x10, 0, 0, 0, 0, 0, 0, // into .text
+            0x41, 0x41, 0, 0, 0, 0, 0, 0, // nowhere
+        ];
+
+        let sections = [
+            Section::new(".text".into(), "", SectionKind::Code, &CODE, 0x1000, 0x1010),
+            Section::new(".data".into(), "", SectionKind::Ptr64, &PTRS, 0x2000, 0x2010),
+        ];
+
+        assert_eq!(from_pointers(&sections, Endianness::Little), [
+            Xref { from: 0x2000, to: 0x1004, kind: XrefKind::Pointer },
+        ]);
+    }
+
+    #[test]
+    fn riscv_auipc_pairs() {
+        #[rustfmt::skip]
+        static CODE: [u8; 32] = [
+            0x17, 0x15, 0x00, 0x00, // auipc a0, 0x1
+            0x13, 0x05, 0x05, 0x01, // addi a0, a0, 0x10
+            0x97, 0x15, 0x00, 0x00, // auipc a1, 0x1
+            0x03, 0xb6, 0x85, 0xff, // ld a2, -0x8(a1)
+            0x97, 0x16, 0x00, 0x00, // auipc a3, 0x1
+            0x13, 0x87, 0x86, 0x00, // addi a4, a3, 0x8
+            0x23, 0xb0, 0xc6, 0x00, // sd a2, 0x0(a3)
+            0x67, 0x80, 0x00, 0x00, // ret
+        ];
+        static DATA: [u8; 32] = [0; 32];
+
+        let sections = [
+            Section::new(".text".into(), "", SectionKind::Code, &CODE, 0x1000, 0x1020),
+            Section::new(".data".into(), "", SectionKind::Raw, &DATA, 0x2000, 0x2020),
+        ];
+
+        let decoder = riscv::Decoder { is_64: true };
+        let (instructions, _) =
+            crate::recursion::recurse(&decoder, &sections, &[0x1000], &[], false);
+
+        // only an `auipc` directly before the instruction is known to hold its base
+        assert_eq!(from_instructions(&instructions, &sections), [
+            Xref { from: 0x1004, to: 0x2010, kind: XrefKind::Data },
+            Xref { from: 0x100c, to: 0x2000, kind: XrefKind::Data },
+            Xref { from: 0x1014, to: 0x2018, kind: XrefKind::Data },
+        ]);
+    }
+}
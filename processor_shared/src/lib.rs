@@ -1,4 +1,8 @@
-use object::{Pod, ReadRef};
+use object::{Pod, ReadRef, SectionFlags};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
 
 /// Address in memory.
 pub type VirtAddr = usize;
@@ -44,6 +48,23 @@ pub enum SectionKind {
     Unloaded
 }
 
+/// Access rights of a section once it's loaded.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Permissions {
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+}
+
+impl fmt::Display for Permissions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flag = |set: bool, chr: &'static str| if set { chr } else { "-" };
+        f.write_str(flag(self.read, "r"))?;
+        f.write_str(flag(self.write, "w"))?;
+        f.write_str(flag(self.execute, "x"))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Section {
     /// Section name.
@@ -58,11 +79,31 @@ pub struct Section {
     /// Section data.
     bytes: &'static [u8],
 
+    /// Section data with the relocations of an object file applied, as it isn't linked yet.
+    relocated: Option<Arc<[u8]>>,
+
+    /// Section data with the user's patches applied, if any of them are in the section.
+    patched: Option<Arc<[u8]>>,
+
     /// Address where section starts.
     pub start: PhysAddr,
 
     /// Section start + size of uncompressed data.
     pub end: PhysAddr,
+
+    /// Access rights of the section once it's loaded.
+    pub permissions: Permissions,
+
+    /// Where the section's data is in the file, `None` if it isn't stored in the file
+    /// (e.g. the .bss).
+    pub file_offset: Option<usize>,
+
+    /// Alignment of the section's address.
+    pub alignment: usize,
+
+    /// Format-specific flags, like ELF's `SHF_*`, PE's `IMAGE_SCN_*` or Mach-O's section
+    /// attributes.
+    pub flags: SectionFlags,
 }
 
 impl Section {
@@ -79,30 +120,126 @@ impl Section {
             ident,
             kind,
             bytes,
+            relocated: None,
+            patched: None,
             start,
-            end
+            end,
+            permissions: Permissions::default(),
+            file_offset: None,
+            alignment: 1,
+            flags: SectionFlags::None,
         }
     }
 
+    /// Names of the format-specific flags that are set.
+    pub fn flag_names(&self) -> Vec<&'static str> {
+        let (flags, names): (u64, &[(u64, &str)]) = match self.flags {
+            SectionFlags::Elf { sh_flags } => (sh_flags, &ELF_FLAGS),
+            SectionFlags::Coff { characteristics } => (characteristics as u64, &PE_FLAGS),
+            SectionFlags::MachO { flags } => (flags as u64, &MACHO_FLAGS),
+            _ => return Vec::new(),
+        };
+
+        names.iter().filter(|(flag, _)| flags & flag != 0).map(|(_, name)| *name).collect()
+    }
+
+    /// Section data, including any relocations and patches.
     #[inline]
     pub fn bytes(&self) -> &[u8] {
+        match (&self.patched, &self.relocated) {
+            (Some(patched), _) => patched,
+            (None, Some(relocated)) => relocated,
+            (None, None) => self.bytes,
+        }
+    }
+
+    /// Section data as it's stored in the binary, without any relocations or patches.
+    #[inline]
+    pub fn original_bytes(&self) -> &[u8] {
         self.bytes
     }
 
+    /// Replace the section's data with `relocated`, which must be as long as the original
+    /// data. Patches are applied on top of it.
+    pub fn relocate(&mut self, relocated: Vec<u8>) {
+        debug_assert_eq!(relocated.len(), self.bytes.len());
+        self.relocated = Some(Arc::from(relocated));
+    }
+
+    /// Apply the `patches` that are in the section on top of the original data, replacing
+    /// previously applied patches. Patches must not run past the end of the section's data.
+    pub fn patch(&mut self, patches: &BTreeMap<PhysAddr, Vec<u8>>) {
+        let mut patched = None;
+        for (&addr, bytes) in patches.range(self.start..self.start + self.bytes.len()) {
+            let base = self.relocated.as_deref().unwrap_or(self.bytes);
+            let patched = patched.get_or_insert_with(|| base.to_vec());
+            let rva = addr - self.start;
+            patched[rva..][..bytes.len()].copy_from_slice(bytes);
+        }
+
+        self.patched = patched.map(Arc::from);
+    }
+
     pub fn bytes_by_addr(&self, addr: PhysAddr, len: usize) -> &[u8] {
         let rva = addr - self.start;
-        let bytes = &self.bytes.get(rva..).unwrap_or(&[]);
+        let bytes = &self.bytes().get(rva..).unwrap_or(&[]);
         &bytes[..std::cmp::min(bytes.len(), len)]
     }
 
     pub fn read_at<T: Pod>(&self, addr: PhysAddr) -> Result<&T, ()> {
         let rva = addr - self.start;
-        let bytes = &self.bytes.get(rva..).unwrap_or(&[]);
+        let bytes = &self.bytes().get(rva..).unwrap_or(&[]);
         bytes.read_at(rva as u64)
     }
 }
 
-#[derive(Debug)]
+const ELF_FLAGS: [(u64, &str); 11] = [
+    (object::elf::SHF_WRITE as u64, "WRITE"),
+    (object::elf::SHF_ALLOC as u64, "ALLOC"),
+    (object::elf::SHF_EXECINSTR as u64, "EXECINSTR"),
+    (object::elf::SHF_MERGE as u64, "MERGE"),
+    (object::elf::SHF_STRINGS as u64, "STRINGS"),
+    (object::elf::SHF_INFO_LINK as u64, "INFO_LINK"),
+    (object::elf::SHF_LINK_ORDER as u64, "LINK_ORDER"),
+    (object::elf::SHF_OS_NONCONFORMING as u64, "OS_NONCONFORMING"),
+    (object::elf::SHF_GROUP as u64, "GROUP"),
+    (object::elf::SHF_TLS as u64, "TLS"),
+    (object::elf::SHF_COMPRESSED as u64, "COMPRESSED"),
+];
+
+const PE_FLAGS: [(u64, &str); 15] = [
+    (object::pe::IMAGE_SCN_CNT_CODE as u64, "CNT_CODE"),
+    (object::pe::IMAGE_SCN_CNT_INITIALIZED_DATA as u64, "CNT_INITIALIZED_DATA"),
+    (object::pe::IMAGE_SCN_CNT_UNINITIALIZED_DATA as u64, "CNT_UNINITIALIZED_DATA"),
+    (object::pe::IMAGE_SCN_LNK_INFO as u64, "LNK_INFO"),
+    (object::pe::IMAGE_SCN_LNK_REMOVE as u64, "LNK_REMOVE"),
+    (object::pe::IMAGE_SCN_LNK_COMDAT as u64, "LNK_COMDAT"),
+    (object::pe::IMAGE_SCN_GPREL as u64, "GPREL"),
+    (object::pe::IMAGE_SCN_LNK_NRELOC_OVFL as u64, "LNK_NRELOC_OVFL"),
+    (object::pe::IMAGE_SCN_MEM_DISCARDABLE as u64, "MEM_DISCARDABLE"),
+    (object::pe::IMAGE_SCN_MEM_NOT_CACHED as u64, "MEM_NOT_CACHED"),
+    (object::pe::IMAGE_SCN_MEM_NOT_PAGED as u64, "MEM_NOT_PAGED"),
+    (object::pe::IMAGE_SCN_MEM_SHARED as u64, "MEM_SHARED"),
+    (object::pe::IMAGE_SCN_MEM_EXECUTE as u64, "MEM_EXECUTE"),
+    (object::pe::IMAGE_SCN_MEM_READ as u64, "MEM_READ"),
+    (object::pe::IMAGE_SCN_MEM_WRITE as u64, "MEM_WRITE"),
+];
+
+/// Mach-O section attributes, the section's type is its [`Section::ident`].
+const MACHO_FLAGS: [(u64, &str); 10] = [
+    (object::macho::S_ATTR_PURE_INSTRUCTIONS as u64, "PURE_INSTRUCTIONS"),
+    (object::macho::S_ATTR_NO_TOC as u64, "NO_TOC"),
+    (object::macho::S_ATTR_STRIP_STATIC_SYMS as u64, "STRIP_STATIC_SYMS"),
+    (object::macho::S_ATTR_NO_DEAD_STRIP as u64, "NO_DEAD_STRIP"),
+    (object::macho::S_ATTR_LIVE_SUPPORT as u64, "LIVE_SUPPORT"),
+    (object::macho::S_ATTR_SELF_MODIFYING_CODE as u64, "SELF_MODIFYING_CODE"),
+    (object::macho::S_ATTR_DEBUG as u64, "DEBUG"),
+    (object::macho::S_ATTR_SOME_INSTRUCTIONS as u64, "SOME_INSTRUCTIONS"),
+    (object::macho::S_ATTR_EXT_RELOC as u64, "EXT_RELOC"),
+    (object::macho::S_ATTR_LOC_RELOC as u64, "LOC_RELOC"),
+];
+
+#[derive(Debug, Clone)]
 pub struct Segment {
     /// Segment identifier.
     pub name: String,
@@ -112,8 +249,45 @@ pub struct Segment {
 
     /// Physical address + size.
     pub end: PhysAddr,
+
+    /// Where the segment's data is in the file, this might be smaller than the segment as
+    /// the rest is zeroed when loaded.
+    pub file_range: Range<usize>,
+}
+
+/// Where ranges of a binary's file are loaded in memory, for translating between addresses
+/// and file offsets.
+#[derive(Debug, Default, Clone)]
+pub struct FileMap {
+    /// Ranges of the file with the address they're loaded at, earlier ranges take precedence.
+    mapping: Vec<(Range<usize>, PhysAddr)>,
 }
 
+impl FileMap {
+    /// Map `file_range` to the addresses starting at `addr`. Addresses and offsets that are
+    /// already mapped keep their earlier mapping.
+    pub fn insert(&mut self, file_range: Range<usize>, addr: PhysAddr) {
+        if !file_range.is_empty() {
+            self.mapping.push((file_range, addr));
+        }
+    }
+
+    /// Where the data at `addr` is stored in the file.
+    pub fn addr_to_offset(&self, addr: PhysAddr) -> Option<usize> {
+        self.mapping.iter().find_map(|(file_range, start)| {
+            let offset = addr.checked_sub(*start)?;
+            (offset < file_range.len()).then_some(file_range.start + offset)
+        })
+    }
+
+    /// Address where the data at `offset` in the file is loaded.
+    pub fn offset_to_addr(&self, offset: usize) -> Option<PhysAddr> {
+        self.mapping
+            .iter()
+            .find(|(file_range, _)| file_range.contains(&offset))
+            .map(|(file_range, start)| start + (offset - file_range.start))
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct AddressMap<T> {
@@ -250,6 +424,47 @@ pub fn encode_hex_bytes_truncated(bytes: &[u8], max_width: usize, is_padded: boo
 
 #[cfg(test)]
 mod tests {
+    use super::{FileMap, Section, SectionKind};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn relocated_section() {
+        let mut section = Section::new(
+            ".text".to_string(),
+            "PROGBITS",
+            SectionKind::Code,
+            &[0xe8, 0, 0, 0, 0, 0xc3],
+            0x1000,
+            0x1006,
+        );
+
+        section.relocate(vec![0xe8, 0x10, 0, 0, 0, 0xc3]);
+        assert_eq!(section.bytes(), [0xe8, 0x10, 0, 0, 0, 0xc3]);
+
+        section.patch(&BTreeMap::from([(0x1005, vec![0x90])]));
+        assert_eq!(section.bytes(), [0xe8, 0x10, 0, 0, 0, 0x90]);
+        assert_eq!(section.original_bytes(), [0xe8, 0, 0, 0, 0, 0xc3]);
+    }
+
+    #[test]
+    fn file_map() {
+        let mut map = FileMap::default();
+        map.insert(0x0..0x800, 0x400000);
+        map.insert(0x1000..0x1200, 0x401000);
+        map.insert(0x1000..0x1100, 0x600000);
+        map.insert(0x2000..0x2000, 0x402000);
+
+        assert_eq!(map.addr_to_offset(0x400010), Some(0x10));
+        assert_eq!(map.addr_to_offset(0x400900), None);
+        assert_eq!(map.addr_to_offset(0x401100), Some(0x1100));
+        assert_eq!(map.addr_to_offset(0x600010), Some(0x1010));
+        assert_eq!(map.addr_to_offset(0x402000), None);
+
+        assert_eq!(map.offset_to_addr(0x1010), Some(0x401010));
+        assert_eq!(map.offset_to_addr(0x800), None);
+        assert_eq!(map.offset_to_addr(0x2000), None);
+    }
+
     #[test]
     fn encode_hex_bytes_truncted() {
         assert_eq!(
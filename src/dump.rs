@@ -0,0 +1,193 @@
+//! Headless mode that prints the listing as plain text or the analysis as JSON.
+
+use commands::debug::CompleteExpr;
+use commands::Cli;
+use debugvault::Index;
+use processor::{Decoding, Processor, Project};
+use tokenizing::TokenStream;
+use std::io::{BufWriter, Write};
+
+pub fn run(args: &Cli) {
+    // projects are dumped by dumping the binary they belong to
+    let (path, project) = if Project::is_project(&args.path) {
+        match Project::open(&args.path) {
+            Ok(project) => (project.binary, args.path.clone()),
+            Err(err) => exit!(1 => "Failed to open project {:?}: {err}.", args.path),
+        }
+    } else {
+        (args.path.clone(), Project::path_for(&args.path))
+    };
+
+    let decoding = if args.lazy { Decoding::Lazy } else { Decoding::Eager };
+    let mut processor = match Processor::parse_with(&path, decoding) {
+        Ok(processor) => processor,
+        Err(err) => exit!(1 => "{err:?}"),
+    };
+
+    // the listing shows the same names either way
+    processor.load_debug_info();
+
+    if project.exists() {
+        if let Err(err) = processor.open_project(&project) {
+            eprintln!("Failed to open project {project:?}: {err}.");
+        }
+    }
+
+    let (mut start, mut end) = (0, usize::MAX);
+    if let Some(ref name) = args.section {
+        match processor.sections().find(|s| s.name == *name) {
+            Some(section) => (start, end) = (section.start, section.end.saturating_sub(1)),
+            None => exit!(1 => "Section '{name}' doesn't exist."),
+        }
+    }
+
+    // Explicit addresses narrow down the section (if any was given).
+    start = args.start.map_or(start, |addr| addr.max(start));
+    end = args.end.map_or(end, |addr| addr.min(end));
+
+    if let Some(ref name) = args.symbol {
+        let addr = match processor.index.get_func_by_name(name) {
+            Some(addr) => addr,
+            None => exit!(1 => "Symbol '{name}' doesn't exist."),
+        };
+
+        let section_end = processor.section_by_addr(addr).map(|section| section.end);
+        start = addr.max(start);
+        end = function_end(&processor.index, addr, section_end).saturating_sub(1).min(end);
+    }
+
+    if let Some((ref lhs, ref rhs)) = args.range {
+        let lhs = eval(&processor, lhs);
+        let rhs = eval(&processor, rhs);
+        if lhs >= rhs {
+            exit!(1 => "Range {lhs:#x}..{rhs:#x} is empty.");
+        }
+
+        start = lhs.max(start);
+        end = (rhs - 1).min(end);
+    }
+
+    let stdout = std::io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let result = if args.json {
+        processor
+            .export_json(&mut out, start..=end)
+            .map_err(std::io::Error::from)
+            .and_then(|_| writeln!(out))
+    } else {
+        dump(&processor, start, end, &mut out)
+    };
+
+    if let Err(err) = result.and_then(|_| out.flush()) {
+        // A closed pipe (e.g. `bite --dump | head`) isn't an error worth reporting.
+        if err.kind() != std::io::ErrorKind::BrokenPipe {
+            exit!(1 => "Failed to write to stdout: {err}.");
+        }
+    }
+}
+
+/// Where the function at `addr` ends (exclusive), which is wherever the next function or its
+/// section starts. Labels inside the function (e.g. `.LBB_` loop heads) don't end it.
+fn function_end(index: &Index, addr: usize, section_end: Option<usize>) -> usize {
+    let syms = &index.syms[index.syms.partition_point(|sym| sym.addr <= addr)..];
+    let next_sym = syms.iter().find(|sym| !sym.item.intrinsic()).map(|sym| sym.addr);
+
+    match (next_sym, section_end) {
+        (Some(sym), Some(section_end)) => sym.min(section_end),
+        (Some(addr), None) | (None, Some(addr)) => addr,
+        (None, None) => usize::MAX,
+    }
+}
+
+/// Evaluate an expression given on the command line, exiting on failure.
+fn eval(processor: &Processor, expr: &str) -> usize {
+    let result = CompleteExpr::parse(expr).and_then(|expr| expr.eval(&processor.index));
+    match result {
+        Ok(addr) if addr >= 0 => addr as usize,
+        Ok(addr) => exit!(1 => "Expression '{expr}' evaluated to a negative address {addr}."),
+        Err(err) => exit!(1 => "Failed to evaluate '{expr}': {err}"),
+    }
+}
+
+/// Write every block between `start` and `end` (inclusive), without any colors.
+fn dump<W: Write>(processor: &Processor, start: usize, end: usize, out: &mut W) -> std::io::Result<()> {
+    let mut stream = TokenStream::new();
+
+    // boundaries can span many blocks (e.g. a lazily decoded region), so start at the one
+    // containing `start`
+    let boundaries = processor.compute_block_boundaries();
+    let first = boundaries.partition_point(|&addr| addr <= start).saturating_sub(1);
+
+    for &addr in &boundaries[first..] {
+        if addr > end {
+            break;
+        }
+
+        for block in processor.parse_blocks(addr) {
+            if block.addr < start {
+                continue;
+            }
+
+            if block.addr > end {
+                return Ok(());
+            }
+
+            stream.clear();
+            block.tokenize(&mut stream, None);
+            writeln!(out, "{}", stream.to_string())?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn function_with_loop() {
+        let mut index = Index::default();
+        index.insert_func(0x1000, "main");
+        index.insert_func(0x1080, "exit");
+        // the loop's head and the block after it
+        index.insert_labels(&[0x1010, 0x1040]);
+
+        assert_eq!(function_end(&index, 0x1000, None), 0x1080);
+        assert_eq!(function_end(&index, 0x1000, Some(0x1060)), 0x1060);
+        assert_eq!(function_end(&index, 0x1080, None), usize::MAX);
+        assert_eq!(function_end(&index, 0x1080, Some(0x10a0)), 0x10a0);
+    }
+
+    #[test]
+    fn range_inside_region() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/processor/fixtures/data_in_code.o");
+        let processor = Processor::parse_with(path, Decoding::Lazy).unwrap();
+
+        // the whole of `.text` is a single region, the range starts at its second instruction
+        let mut out = Vec::new();
+        dump(&processor, 0x10004, 0x10010, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let addrs: Vec<&str> = out.lines().map(|line| &line[..10]).collect();
+        assert_eq!(addrs, ["0000010004", "0000010008", "0000010010"]);
+    }
+
+    #[test]
+    fn relocated_call() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/binformat/fixtures/reloc/riscv64.o");
+        let processor = Processor::parse(path).unwrap();
+
+        // after the section's header, the target of `call` and `tail` is split over an `auipc`
+        // and a `jalr`
+        let mut out = Vec::new();
+        dump(&processor, 0x10000, 0x1000c, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().skip(1).map(str::trim_end).collect();
+        assert_eq!(lines, [
+            "0000010000  97 10 00 00  auipc ra, 0x11000  ; printf",
+            "0000010004  e7 80 00 80  call <printf>",
+            "0000010008  17 13 00 00  auipc t1, 0x11008  ; puts",
+            "000001000C  67 00 83 80  tail <puts>",
+        ]);
+    }
+}
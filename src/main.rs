@@ -6,10 +6,28 @@
 #[cfg(not(any(target_family = "windows", target_family = "unix")))]
 compile_error!("Bite can only be build for windows, macos and linux.");
 
+/// Print an error message and exit with the given code.
+macro_rules! exit {
+    ($code:expr => $($arg:tt)*) => {{
+        eprintln!($($arg)*);
+        std::process::exit($code);
+    }};
+}
+
+mod dump;
+mod signatures;
 mod wayland;
 use commands::ARGS;
 
 fn main() {
+    if let Some((ref output, ref inputs)) = ARGS.signatures {
+        return signatures::run(output, inputs);
+    }
+
+    if ARGS.dump || ARGS.json {
+        return dump::run(&ARGS);
+    }
+
     #[cfg(target_os = "linux")]
     if nix::unistd::getuid() == 0.into() {
         wayland::set_env();
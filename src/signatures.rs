@@ -0,0 +1,35 @@
+//! Generates signature files from static libraries.
+
+use processor::signatures::Signatures;
+use std::path::{Path, PathBuf};
+
+pub fn run(output: &Path, inputs: &[PathBuf]) {
+    let mut signatures = Signatures::default();
+
+    for input in inputs {
+        let data = match std::fs::read(input) {
+            Ok(data) => data,
+            Err(err) => exit!(1 => "Failed to read {input:?}: {err}."),
+        };
+
+        let generated = match Signatures::generate(&data) {
+            Ok(generated) => generated,
+            Err(err) => exit!(1 => "Failed to generate signatures for {input:?}: {err}."),
+        };
+
+        println!("{input:?}: {} signatures.", generated.signatures.len());
+        if let Err(err) = signatures.extend(generated) {
+            exit!(1 => "Failed to add signatures of {input:?}: {err}.");
+        }
+    }
+
+    if let Err(err) = signatures.save(output) {
+        exit!(1 => "Failed to save signatures to {output:?}: {err}.");
+    }
+
+    println!(
+        "Saved {} {} signatures to {output:?}.",
+        signatures.signatures.len(),
+        signatures.arch
+    );
+}